@@ -0,0 +1,154 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use thiserror::Error;
+
+// Long enough for NEAR account ids and checksummed chain addresses
+pub const MAX_ACCOUNT_ID_CHARS: usize = 64;
+
+// Why a string was refused as an account id
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountIdError {
+    #[error("Account id is empty.")]
+    Empty,
+    #[error("Account id is longer than {MAX_ACCOUNT_ID_CHARS} characters.")]
+    TooLong,
+    #[error("Account id starts or ends with whitespace.")]
+    Padded,
+    #[error("Account id contains control or invisible characters.")]
+    Invisible,
+}
+
+// Identity of a player, admin or token holder, checked once when it is built. Methods storing an identity
+// take one, lookups take &str so an AccountId derefs into them. Serialized as the plain string, and
+// checked again when read back.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct AccountId(String);
+
+impl AccountId {
+    pub fn new(id: impl Into<String>) -> Result<AccountId, AccountIdError> {
+        let id = id.into();
+        if id.is_empty() {
+            return Err(AccountIdError::Empty);
+        }
+        if id.chars().count() > MAX_ACCOUNT_ID_CHARS {
+            return Err(AccountIdError::TooLong);
+        }
+        if id.trim() != id {
+            return Err(AccountIdError::Padded);
+        }
+        if id.chars().any(|c| c.is_control() || ('\u{200B}'..='\u{200F}').contains(&c) || c == '\u{FEFF}') {
+            return Err(AccountIdError::Invisible);
+        }
+        Ok(AccountId(id))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for AccountId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for AccountId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<str> for AccountId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = AccountIdError;
+
+    fn from_str(id: &str) -> Result<AccountId, AccountIdError> {
+        AccountId::new(id)
+    }
+}
+
+impl TryFrom<String> for AccountId {
+    type Error = AccountIdError;
+
+    fn try_from(id: String) -> Result<AccountId, AccountIdError> {
+        AccountId::new(id)
+    }
+}
+
+impl TryFrom<&str> for AccountId {
+    type Error = AccountIdError;
+
+    fn try_from(id: &str) -> Result<AccountId, AccountIdError> {
+        AccountId::new(id)
+    }
+}
+
+impl From<AccountId> for String {
+    fn from(id: AccountId) -> String {
+        id.0
+    }
+}
+
+impl PartialEq<str> for AccountId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for AccountId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for AccountId {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+// Account ids known to be valid, for tests
+#[cfg(test)]
+pub fn account(id: &str) -> AccountId {
+    AccountId::new(id).unwrap()
+}
+
+#[test]
+fn test_account_ids_checked_once() {
+    assert_eq!(AccountId::new("alice.near").unwrap(), "alice.near");
+    assert_eq!("0x3A3a3a3A3a3a3A3a3a3a3A3a3a3A3a3a3A3a3a3a".parse::<AccountId>().unwrap().len(), 42);
+    assert_eq!(AccountId::new("Zoë Ann").unwrap().to_string(), "Zoë Ann");
+
+    assert_eq!(AccountId::new(""), Err(AccountIdError::Empty));
+    assert_eq!(AccountId::new("a".repeat(MAX_ACCOUNT_ID_CHARS + 1)), Err(AccountIdError::TooLong));
+    assert_eq!(AccountId::new(" Alice"), Err(AccountIdError::Padded));
+    assert_eq!(AccountId::new("Ali\u{200B}ce"), Err(AccountIdError::Invisible));
+    assert_eq!(AccountId::new("Alice\n"), Err(AccountIdError::Padded));
+
+    // Stored as the plain string, a tampered one is refused when read back
+    let json = serde_json::to_string(&account("Alice")).unwrap();
+    assert_eq!(json, "\"Alice\"");
+    assert_eq!(serde_json::from_str::<AccountId>(&json).unwrap(), account("Alice"));
+    assert!(serde_json::from_str::<AccountId>("\"\"").is_err());
+}
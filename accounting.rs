@@ -0,0 +1,153 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerEntry {
+    Handle(u64),  // Total amount wagered
+    Rake(u64),    // Fees kept by the house
+    Payout(u64),  // Paid back to players, refunds included
+    Burn(u64),
+    Bonus(u64),
+    BotFunding(u64), // Moved from the treasury to the house bot's stake
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeriodTotals {
+    pub handle: u64,
+    pub rake: u64,
+    pub payouts: u64,
+    pub burns: u64,
+    pub bonuses: u64,
+    #[serde(default)]
+    pub bot_funding: u64,
+}
+
+impl PeriodTotals {
+    fn add(&mut self, entry: LedgerEntry) {
+        let (total, amount) = match entry {
+            LedgerEntry::Handle(amount) => (&mut self.handle, amount),
+            LedgerEntry::Rake(amount) => (&mut self.rake, amount),
+            LedgerEntry::Payout(amount) => (&mut self.payouts, amount),
+            LedgerEntry::Burn(amount) => (&mut self.burns, amount),
+            LedgerEntry::Bonus(amount) => (&mut self.bonuses, amount),
+            LedgerEntry::BotFunding(amount) => (&mut self.bot_funding, amount),
+        };
+        // Reporting totals saturate instead of failing the operation that produced them
+        *total = total.saturating_add(amount);
+    }
+}
+
+// Frozen once stored, nothing can change a closed period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeriodReport {
+    pub period_id: u64,
+    pub totals: PeriodTotals,
+    pub opened_at: u64,
+    pub closed_at: u64,
+    pub late_entries: u64, // Entries for already closed periods that were counted here instead
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Accounting {
+    current_period: u64,
+    opened_at: u64,
+    open_totals: PeriodTotals,
+    late_entries: u64,
+    reports: BTreeMap<u64, PeriodReport>,
+}
+
+impl Accounting {
+    pub fn new(now: u64) -> Self {
+        Accounting {
+            opened_at: now,
+            ..Accounting::default()
+        }
+    }
+
+    pub fn current_period(&self) -> u64 {
+        self.current_period
+    }
+
+    pub fn open_totals(&self) -> &PeriodTotals {
+        &self.open_totals
+    }
+
+    // `period_id` is the period the entry belongs to (e.g. when its game was created).
+    // Closed periods are immutable, so late entries roll into the open one.
+    // Returns the period the entry was counted in.
+    pub fn record(&mut self, period_id: u64, entry: LedgerEntry) -> u64 {
+        if period_id < self.current_period {
+            self.late_entries += 1;
+        }
+        self.open_totals.add(entry);
+        self.current_period
+    }
+
+    pub fn close_period(&mut self, period_id: u64, now: u64) -> Result<PeriodReport, GameError> {
+        if self.reports.contains_key(&period_id) {
+            return Err(GameError::PeriodClosed);
+        }
+        if period_id != self.current_period {
+            return Err(GameError::PeriodNotOpen);
+        }
+
+        let report = PeriodReport {
+            period_id,
+            totals: self.open_totals,
+            opened_at: self.opened_at,
+            closed_at: now,
+            late_entries: self.late_entries,
+        };
+        self.reports.insert(period_id, report.clone());
+
+        self.current_period += 1;
+        self.opened_at = now;
+        self.open_totals = PeriodTotals::default();
+        self.late_entries = 0;
+
+        Ok(report)
+    }
+
+    pub fn report(&self, period_id: u64) -> Option<&PeriodReport> {
+        self.reports.get(&period_id)
+    }
+
+    pub fn reports(&self) -> impl Iterator<Item = &PeriodReport> {
+        self.reports.values()
+    }
+
+    // The open period without the closed ones, for a transaction to put back with restore
+    pub(crate) fn save(&self) -> Accounting {
+        Accounting { reports: BTreeMap::new(), ..*self }
+    }
+
+    // Reports of periods closed after the save are dropped with the period totals they took
+    pub(crate) fn restore(&mut self, saved: Accounting) {
+        let mut reports = std::mem::take(&mut self.reports);
+        reports.retain(|&period_id, _| period_id < saved.current_period);
+        *self = Accounting { reports, ..saved };
+    }
+}
+
+#[test]
+fn test_close_period_freezes_totals() {
+    let mut accounting = Accounting::new(0);
+    accounting.record(0, LedgerEntry::Handle(20));
+    accounting.record(0, LedgerEntry::Payout(20));
+
+    let report = accounting.close_period(0, 100).unwrap();
+    assert_eq!(report.totals.handle, 20);
+    assert!(accounting.close_period(0, 101).is_err());
+    assert!(accounting.close_period(5, 101).is_err());
+
+    // A game created in period 0 settling now lands in period 1
+    assert_eq!(accounting.record(0, LedgerEntry::Handle(30)), 1);
+    assert_eq!(accounting.report(0).unwrap().totals.handle, 20);
+
+    let report = accounting.close_period(1, 200).unwrap();
+    assert_eq!(report.totals.handle, 30);
+    assert_eq!(report.late_entries, 1);
+    assert_eq!(report.opened_at, 100);
+}
@@ -0,0 +1,61 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::history::GameRecord;
+use crate::tx::{restore_entries, save_entries};
+
+// What is kept of an account after its player closed it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClosedAccount {
+    pub player: String,
+    pub closed_at: u64,
+    pub swept: u64, // Balance paid out at closure
+    pub payout_tx: Option<String>, // None when there was nothing to pay out
+    pub history: Vec<GameRecord>, // Settled games of the player at closure
+    pub name_free_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClosedAccounts {
+    accounts: BTreeMap<String, Vec<ClosedAccount>>, // A name claimed again after its quarantine can be closed again
+}
+
+impl ClosedAccounts {
+    pub fn new() -> Self {
+        ClosedAccounts::default()
+    }
+
+    pub fn archive(&mut self, account: ClosedAccount) {
+        self.accounts.entry(account.player.clone()).or_default().push(account);
+    }
+
+    pub fn closures(&self, player: &str) -> &[ClosedAccount] {
+        self.accounts.get(player).map(|closures| closures.as_slice()).unwrap_or(&[])
+    }
+
+    // The closures of `players` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, players: &[String]) -> ClosedAccounts {
+        ClosedAccounts { accounts: save_entries(&self.accounts, players) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: ClosedAccounts, players: &[String]) {
+        restore_entries(&mut self.accounts, saved.accounts, players);
+    }
+}
+
+#[test]
+fn test_closures_kept_per_player() {
+    let mut accounts = ClosedAccounts::new();
+    let closed = |closed_at| ClosedAccount {
+        player: "Alice".to_string(),
+        closed_at,
+        swept: 0,
+        payout_tx: None,
+        history: Vec::new(),
+        name_free_at: closed_at + 10,
+    };
+    accounts.archive(closed(1));
+    accounts.archive(closed(20));
+    assert_eq!(accounts.closures("Alice").iter().map(|c| c.closed_at).collect::<Vec<_>>(), vec![1, 20]);
+    assert!(accounts.closures("Bob").is_empty());
+}
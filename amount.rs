@@ -0,0 +1,111 @@
+use serde::{Serialize, Deserialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::config::LimitError;
+
+// A number of tokens. Arithmetic only comes checked, so a sum that does not fit is a None for the caller
+// to turn into its own error instead of a wrap or a panic. Serialized as the plain number.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    pub const fn new(amount: u64) -> Amount {
+        Amount(amount)
+    }
+
+    // For bets, stakes and transfers, where moving nothing is a mistake of the caller
+    pub fn positive(amount: u64) -> Result<Amount, LimitError> {
+        if amount == 0 {
+            return Err(LimitError::ZeroAmount);
+        }
+        Ok(Amount(amount))
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: impl Into<Amount>) -> Option<Amount> {
+        self.0.checked_add(other.into().0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: impl Into<Amount>) -> Option<Amount> {
+        self.0.checked_sub(other.into().0).map(Amount)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Amount)
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(amount: u64) -> Amount {
+        Amount(amount)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.0
+    }
+}
+
+impl PartialEq<u64> for Amount {
+    fn eq(&self, other: &u64) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialOrd<u64> for Amount {
+    fn partial_cmp(&self, other: &u64) -> Option<Ordering> {
+        self.0.partial_cmp(other)
+    }
+}
+
+// Groups of three digits, 1234567 is written 1,234,567
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.0.to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        f.pad(&grouped)
+    }
+}
+
+#[test]
+fn test_amounts_only_move_checked() {
+    let hundred = Amount::new(100);
+    assert_eq!(hundred.checked_add(20), Some(Amount::new(120)));
+    assert_eq!(hundred.checked_sub(101), None);
+    assert_eq!(Amount::MAX.checked_add(1), None);
+    assert_eq!(Amount::MAX.checked_mul(2), None);
+    assert_eq!(hundred.checked_mul(3), Some(Amount::new(300)));
+    assert!(hundred > 99 && hundred == 100);
+
+    assert_eq!(Amount::positive(0), Err(LimitError::ZeroAmount));
+    assert_eq!(Amount::positive(5).map(Amount::get), Ok(5));
+
+    assert_eq!(Amount::new(0).to_string(), "0");
+    assert_eq!(Amount::new(999).to_string(), "999");
+    assert_eq!(Amount::new(1_000).to_string(), "1,000");
+    assert_eq!(Amount::new(1_234_567).to_string(), "1,234,567");
+    assert_eq!(Amount::MAX.to_string(), "18,446,744,073,709,551,615");
+
+    // Stored as the plain number, states saved before keep loading
+    assert_eq!(serde_json::to_string(&hundred).unwrap(), "100");
+    assert_eq!(serde_json::from_str::<Amount>("100").unwrap(), hundred);
+}
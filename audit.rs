@@ -0,0 +1,91 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::commitments;
+use crate::error::GameError;
+use crate::events::LoggedEvent;
+use crate::strict;
+
+// What the first event is chained to
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Hex encoded sha256 of the hash before an event and the event as JSON with sorted keys
+pub fn entry_hash(previous: &str, logged: &LoggedEvent) -> String {
+    let json = serde_json::to_value(logged).expect("Logged events serialize to JSON.");
+    commitments::to_hex(&Sha256::new().chain_update(previous).chain_update(strict::canonical_json(&json)).finalize())
+}
+
+// Hash chain over the event log, every accepted action is in it through the events it emitted. Each hash
+// covers its event and the hash before it, so changing, dropping or reordering a logged event breaks the
+// chain from there on. Saved with the state, the head can be published to pin the whole history down.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditLog {
+    hashes: Vec<String>, // One per logged event, in sequence order
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        AuditLog::default()
+    }
+
+    // Chain of `entries` as they are, for logs taken from a store or saved before there was a chain
+    pub fn rebuild(entries: &[LoggedEvent]) -> Self {
+        let mut audit = AuditLog::new();
+        for logged in entries {
+            audit.append(logged);
+        }
+        audit
+    }
+
+    pub fn append(&mut self, logged: &LoggedEvent) {
+        let hash = entry_hash(self.head(), logged);
+        self.hashes.push(hash);
+    }
+
+    // Drops the hashes of every event after the first `len`, for events taken back with them
+    pub fn truncate(&mut self, len: usize) {
+        self.hashes.truncate(len);
+    }
+
+    // Hash of the last event, GENESIS while nothing was logged
+    pub fn head(&self) -> &str {
+        self.hashes.last().map_or(GENESIS, String::as_str)
+    }
+
+    pub fn hashes(&self) -> &[String] {
+        &self.hashes
+    }
+
+    // Recomputes the chain over `entries`, AuditLogBroken names the first event that does not match
+    pub fn verify(&self, entries: &[LoggedEvent]) -> Result<(), GameError> {
+        let mut previous = GENESIS;
+        for (index, logged) in entries.iter().enumerate() {
+            match self.hashes.get(index) {
+                Some(hash) if logged.sequence == index as u64 && *hash == entry_hash(previous, logged) => previous = hash,
+                _ => return Err(GameError::AuditLogBroken(index as u64)),
+            }
+        }
+        if self.hashes.len() != entries.len() {
+            return Err(GameError::AuditLogBroken(entries.len() as u64));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_chain_breaks_where_the_log_was_changed() {
+    use crate::events::GameEvent;
+
+    let staked = |sequence, amount| LoggedEvent { sequence, timestamp: 1_000, event: GameEvent::Staked { user: "Alice".to_string(), amount } };
+    let mut entries = vec![staked(0, 100), staked(1, 50), staked(2, 25)];
+    let audit = AuditLog::rebuild(&entries);
+    assert_eq!(audit.verify(&entries), Ok(()));
+    assert_eq!(audit.head(), entry_hash(&entry_hash(&entry_hash(GENESIS, &entries[0]), &entries[1]), &entries[2]));
+
+    entries[1] = staked(1, 5_000);
+    assert_eq!(audit.verify(&entries), Err(GameError::AuditLogBroken(1)));
+    entries.remove(1);
+    assert_eq!(audit.verify(&entries), Err(GameError::AuditLogBroken(1)));
+    entries.truncate(1);
+    assert_eq!(audit.verify(&entries), Err(GameError::AuditLogBroken(1)));
+}
@@ -0,0 +1,81 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::account_id::AccountId;
+use crate::commitments::{hash_secret, to_hex};
+use crate::error::GameError;
+
+// Bearer credentials for transports facing untrusted clients. The operator issues one per account and the
+// transport takes the caller of a request from the credential it presents, never from the request itself.
+// Only hashes of the secrets are kept, the file does not let anyone act for an account.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Credentials {
+    accounts: HashMap<String, AccountId>, // By the hash of the secret
+}
+
+impl Credentials {
+    pub fn new() -> Self {
+        Credentials::default()
+    }
+
+    // Issues a new secret acting for `account`, only ever shown here
+    pub fn issue(&mut self, account: AccountId) -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = to_hex(&bytes);
+        self.accounts.insert(hash_secret(secret.as_bytes()), account);
+        secret
+    }
+
+    pub fn revoke(&mut self, secret: &str) -> bool {
+        self.accounts.remove(&hash_secret(secret.as_bytes())).is_some()
+    }
+
+    // The account `secret` was issued for
+    pub fn authenticate(&self, secret: &str) -> Result<&AccountId, GameError> {
+        self.accounts.get(&hash_secret(secret.as_bytes())).ok_or(GameError::Unauthenticated)
+    }
+
+    // Secret of an `Authorization: Bearer <secret>` header value
+    pub fn bearer(header: &str) -> Result<&str, GameError> {
+        header.strip_prefix("Bearer ").map(str::trim).filter(|secret| !secret.is_empty()).ok_or(GameError::Unauthenticated)
+    }
+
+    // No file means nobody was issued a credential yet
+    pub fn load(path: &Path) -> Result<Self, GameError> {
+        if !path.exists() {
+            return Ok(Credentials::new());
+        }
+        let json = fs::read_to_string(path).map_err(|e| GameError::Io { path: path.display().to_string(), message: e.to_string() })?;
+        serde_json::from_str(&json).map_err(|e| GameError::InvalidJson(e.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), GameError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| GameError::Serialize(e.to_string()))?;
+        fs::write(path, json).map_err(|e| GameError::Io { path: path.display().to_string(), message: e.to_string() })
+    }
+}
+
+#[test]
+fn test_credentials_name_their_account() {
+    use crate::account_id::account;
+
+    let mut credentials = Credentials::new();
+    let alice = credentials.issue(account("Alice"));
+    let bob = credentials.issue(account("Bob"));
+    assert_ne!(alice, bob);
+    assert_eq!(credentials.authenticate(&alice), Ok(&account("Alice")));
+    let header = format!("Bearer {}", bob);
+    assert_eq!(credentials.authenticate(Credentials::bearer(&header).unwrap()), Ok(&account("Bob")));
+    assert_eq!(credentials.authenticate("guess"), Err(GameError::Unauthenticated));
+    assert_eq!(Credentials::bearer(&format!("Basic {}", alice)), Err(GameError::Unauthenticated));
+
+    // Saved without the secrets
+    let json = serde_json::to_string(&credentials).unwrap();
+    assert!(!json.contains(&alice));
+    assert!(credentials.revoke(&alice));
+    assert_eq!(credentials.authenticate(&alice), Err(GameError::Unauthenticated));
+}
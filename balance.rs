@@ -0,0 +1,76 @@
+use serde::{Serialize, Deserialize};
+
+use crate::amount::Amount;
+use crate::error::GameError;
+
+// A player's tokens. `locked` is escrowed in games that are not settled yet, only `available`
+// can be withdrawn or put into a new bet.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub available: Amount,
+    pub locked: Amount,
+}
+
+impl Balance {
+    pub fn new(available: u64, locked: u64) -> Self {
+        Balance { available: Amount::new(available), locked: Amount::new(locked) }
+    }
+
+    pub fn total(&self) -> Amount {
+        self.available.checked_add(self.locked).unwrap_or(Amount::MAX)
+    }
+
+    // The whole balance has to stay countable, not only the available part
+    pub fn credit(&mut self, amount: u64) -> Result<(), GameError> {
+        self.available.checked_add(self.locked).and_then(|total| total.checked_add(amount)).ok_or(GameError::Overflow)?;
+        self.available = self.available.checked_add(amount).ok_or(GameError::Overflow)?;
+        Ok(())
+    }
+
+    pub fn debit(&mut self, amount: u64) -> Result<(), GameError> {
+        self.available = self.available.checked_sub(amount).ok_or(GameError::InsufficientStake)?;
+        Ok(())
+    }
+
+    // Escrows a bet
+    pub fn lock(&mut self, amount: u64) -> Result<(), GameError> {
+        let locked = self.locked.checked_add(amount).ok_or(GameError::Overflow)?;
+        self.debit(amount)?;
+        self.locked = locked;
+        Ok(())
+    }
+
+    // Gives an escrowed bet back, on a draw or when the game is called off
+    pub fn unlock(&mut self, amount: u64) -> Result<(), GameError> {
+        let locked = self.locked.checked_sub(amount).ok_or(GameError::LockedBalanceTooLow)?;
+        self.available = self.available.checked_add(amount).ok_or(GameError::Overflow)?;
+        self.locked = locked;
+        Ok(())
+    }
+
+    // Hands an escrowed bet over to the pot, the winner is credited separately
+    pub fn release(&mut self, amount: u64) -> Result<(), GameError> {
+        self.locked = self.locked.checked_sub(amount).ok_or(GameError::LockedBalanceTooLow)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_locked_funds_are_not_available() {
+    let mut balance = Balance::default();
+    balance.credit(100).unwrap();
+    balance.lock(30).unwrap();
+    assert_eq!(balance, Balance::new(70, 30));
+    assert!(balance.debit(71).is_err());
+    assert!(balance.lock(71).is_err());
+
+    balance.unlock(10).unwrap();
+    balance.release(20).unwrap();
+    assert_eq!(balance, Balance::new(80, 0));
+    assert!(balance.release(1).is_err());
+    assert_eq!(balance.total(), 80);
+
+    balance.lock(80).unwrap();
+    assert_eq!(balance.credit(u64::MAX - 79), Err(GameError::Overflow));
+    balance.credit(u64::MAX - 80).unwrap();
+}
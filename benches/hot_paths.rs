@@ -0,0 +1,97 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use assessment_rust::vault::VAULT;
+use assessment_rust::{AccountId, Context, ERC20Token, GameState};
+
+// Throughput of the paths every game goes through, run with `cargo bench`. The numbers are the baseline
+// for the multi-game and locking work, compare against them before changing how games are stored.
+
+fn player(name: &str) -> Context {
+    Context::new(AccountId::new(name).expect("Bench names are valid account ids."))
+}
+
+// Token every player holds `amount` of, with the vault approved to take all of it
+fn funded(players: &[Context], amount: u64) -> ERC20Token {
+    let owner = AccountId::new("Owner").expect("Bench names are valid account ids.");
+    let mut token = ERC20Token::new(owner, "Game Token", "GAME", 0, None);
+    token.adjust_price(&player("Owner"), 0).unwrap();
+    for ctx in players {
+        token.mint(ctx, amount, 0).unwrap();
+        token.approve(ctx, AccountId::new(VAULT).unwrap(), amount).unwrap();
+    }
+    token
+}
+
+// Every player staked enough for any number of games the benches play
+fn staked(players: &[Context]) -> GameState {
+    let mut game_state = GameState::new();
+    let mut token = funded(players, 1_000_000_000);
+    for ctx in players {
+        game_state.deposit(ctx, &mut token, 1_000_000_000).unwrap();
+    }
+    game_state
+}
+
+// Both players ask for every round until the game is settled
+fn play_out(game_state: &mut GameState, game_id: u64) {
+    let summary = game_state.game_summary(game_id).unwrap();
+    let players: Vec<Context> = std::iter::once(summary.creator).chain(summary.opponent).map(|name| player(&name)).collect();
+    while game_state.game_summary(game_id).is_some() {
+        for ctx in &players {
+            game_state.contribute_reveal(ctx, game_id).unwrap();
+        }
+    }
+}
+
+fn bench_stake(c: &mut Criterion) {
+    let alice = player("Alice");
+    let mut game_state = GameState::new();
+    let mut token = funded(std::slice::from_ref(&alice), u64::MAX / 2);
+    c.bench_function("deposit", |b| b.iter(|| game_state.deposit(&alice, &mut token, black_box(10)).unwrap()));
+}
+
+fn bench_game(c: &mut Criterion) {
+    let (alice, bob) = (player("Alice"), player("Bob"));
+    let mut game_state = staked(&[alice.clone(), bob.clone()]);
+    c.bench_function("start_join_reveal", |b| {
+        b.iter(|| {
+            let game_id = game_state.start_game(&alice, black_box(10)).unwrap();
+            game_state.join_game(&bob, game_id).unwrap();
+            play_out(&mut game_state, game_id);
+        })
+    });
+}
+
+// Settles `games` games that all run at once, each between its own two players
+fn bench_settlement(c: &mut Criterion) {
+    let mut group = c.benchmark_group("settle_concurrent_games");
+    group.sample_size(10);
+    for games in [1_000u64, 5_000] {
+        let players: Vec<Context> = (0..games * 2).map(|i| player(&format!("Player{}", i))).collect();
+        let mut running = staked(&players);
+        let mut game_ids = Vec::new();
+        for pair in players.chunks(2) {
+            let game_id = running.start_game(&pair[0], 10).unwrap();
+            running.join_game(&pair[1], game_id).unwrap();
+            game_ids.push(game_id);
+        }
+        group.throughput(Throughput::Elements(games));
+        group.bench_with_input(BenchmarkId::from_parameter(games), &game_ids, |b, game_ids| {
+            b.iter_batched(
+                || running.clone(),
+                |mut game_state| {
+                    for game_id in game_ids {
+                        play_out(&mut game_state, *game_id);
+                    }
+                    game_state
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_stake, bench_game, bench_settlement);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+use crate::account_id::AccountId;
+use crate::accounting::LedgerEntry;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::tx::Scope;
+use crate::GameState;
+
+// Account the house plays from. The treasury funds its bets and whatever it wins stays with it for the
+// next games, so the treasury only pays for what the bot lost.
+pub const HOUSE_BOT: &str = "house-bot";
+
+impl GameState {
+    // Seats the house bot in `game_id`, so a player nobody joins still gets a game. Only the creator can
+    // call it in, once the game was left open for `bot_wait_secs`. The treasury tops the bot's available
+    // stake up to the bet, then the bot joins and is settled like any other player and asks for every
+    // reveal as soon as another player does.
+    pub fn join_game_with_bot(&mut self, ctx: &Context, game_id: u64) -> Result<(), GameError> {
+        let _timer = self.start_operation("join_game_with_bot");
+        self.run_once(ctx, "join_game_with_bot", |state| {
+            state.use_nonce(ctx)?;
+            let game = state.games.get(&game_id).ok_or(GameError::NoGameToJoin)?;
+            if game.creator != ctx.caller().as_str() {
+                return Err(GameError::NotYourGame);
+            }
+            let wait_secs = state.config.bot_wait_secs;
+            if state.clock.now().saturating_sub(game.start_time) < wait_secs {
+                return Err(GameError::BotTooEarly { wait_secs });
+            }
+            let (bet, period_id) = (game.bet_amount.get(), game.period_id);
+            let bot = AccountId::new(HOUSE_BOT)?;
+            // Funded and seated together, a join that fails leaves the treasury as it was
+            state.with_tx(Scope::game(game_id).and_account(HOUSE_BOT), |state| {
+                let shortfall = bet.saturating_sub(state.stake_of(HOUSE_BOT).available.get());
+                if shortfall > 0 {
+                    state.treasury.withdraw(shortfall)?;
+                    state.credit(HOUSE_BOT, shortfall)?;
+                    state.accounting.record(period_id, LedgerEntry::BotFunding(shortfall));
+                    state.events.emit(state.clock.now(), GameEvent::BotFunded { bot: HOUSE_BOT.to_string(), amount: shortfall });
+                }
+                state.join_game(&Context::new(bot), game_id)
+            })
+        })
+    }
+}
+
+#[test]
+fn test_bot_plays_on_the_treasury() {
+    use crate::clock::{ManualClock, SharedClock};
+    use crate::context::caller;
+
+    let clock = ManualClock::new(1_000);
+    let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+    game_state.stake_tokens(&caller("Alice"), 10_000).unwrap();
+    game_state.stake_tokens(&caller("Bob"), 10_000).unwrap();
+    assert_eq!(game_state.stake_tokens(&caller(HOUSE_BOT), 10), Err(GameError::NameReserved));
+    let lonely = game_state.start_game(&caller("Alice"), 10).unwrap();
+    assert_eq!(game_state.join_game_with_bot(&caller("Alice"), lonely), Err(GameError::BotTooEarly { wait_secs: 60 }));
+    clock.advance(60);
+    assert_eq!(game_state.join_game_with_bot(&caller("Bob"), lonely), Err(GameError::NotYourGame));
+    assert_eq!(game_state.join_game_with_bot(&caller("Alice"), lonely), Err(GameError::InsufficientTreasury));
+    assert_eq!(game_state.stake_of(HOUSE_BOT).total(), 0);
+
+    // The fee of decided games fills the treasury
+    while game_state.treasury_balance() < 30 {
+        let game_id = game_state.start_game(&caller("Alice"), 100).unwrap();
+        game_state.join_game(&caller("Bob"), game_id).unwrap();
+        while game_state.game_summary(game_id).is_some() {
+            game_state.play_round(game_id).unwrap();
+        }
+    }
+
+    let later = game_state.start_game(&caller("Alice"), 10).unwrap();
+    clock.advance(60);
+    let mut funded = 0;
+    for game_id in [lonely, later] {
+        let (treasury, bot) = (game_state.treasury_balance(), game_state.stake_of(HOUSE_BOT).available.get());
+        game_state.join_game_with_bot(&caller("Alice"), game_id).unwrap();
+        funded += 10u64.saturating_sub(bot);
+        assert_eq!(game_state.treasury_balance(), treasury - 10u64.saturating_sub(bot));
+        assert_eq!(game_state.stake_of(HOUSE_BOT).locked, 10);
+        // Alice asks, the bot is ready too
+        while game_state.game_summary(game_id).is_some() {
+            game_state.contribute_reveal(&caller("Alice"), game_id).unwrap();
+        }
+        assert_eq!(game_state.history().by_id(game_id).unwrap().opponent, HOUSE_BOT);
+        assert_eq!(game_state.check_invariants(), Ok(()));
+    }
+    assert!(game_state.events().iter().any(|logged| matches!(&logged.event, GameEvent::BotFunded { amount: 10, .. })));
+    assert_eq!(game_state.accounting.open_totals().bot_funding, funded);
+}
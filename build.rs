@@ -0,0 +1,10 @@
+// Generates the gRPC service from proto/game.proto (grpc feature). The proto is parsed by protox, so
+// building does not need protoc installed.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/game.proto");
+        let descriptors = protox::compile(["proto/game.proto"], ["proto"]).expect("proto/game.proto does not compile");
+        tonic_build::configure().compile_fds(descriptors).expect("cannot generate the gRPC service");
+    }
+}
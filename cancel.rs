@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", feature = "near"))]
+use crate::near::Instant;
+#[cfg(all(target_arch = "wasm32", feature = "stylus", not(feature = "near")))]
+use crate::stylus::Instant;
+#[cfg(all(target_arch = "wasm32", not(any(feature = "near", feature = "stylus"))))]
+use web_time::Instant;
+
+use crate::error::GameError;
+
+// Cooperative cancellation: the owner of a request (e.g. a server connection) keeps a clone and cancels it
+// when the client goes away, long running operations check it between units of work and stop early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+// What a handler passes down to the engine: the caller's token plus the deadline of its endpoint
+#[derive(Debug, Clone)]
+pub struct OperationContext {
+    token: CancellationToken,
+    deadline: Option<Instant>,
+}
+
+impl OperationContext {
+    // For direct library calls that can never be cancelled
+    pub fn unbounded() -> Self {
+        OperationContext { token: CancellationToken::new(), deadline: None }
+    }
+
+    pub fn new(token: CancellationToken, timeout: Duration) -> Self {
+        OperationContext { token, deadline: Some(Instant::now() + timeout) }
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    // Called between units of work, an error means the operation has to stop and discard its partial result
+    pub fn checkpoint(&self) -> Result<(), GameError> {
+        if self.token.is_cancelled() {
+            return Err(GameError::Cancelled);
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(GameError::TimedOut);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_operation_context_cancel_and_timeout() {
+    let token = CancellationToken::new();
+    let context = OperationContext::new(token.clone(), Duration::from_secs(60));
+    assert!(context.checkpoint().is_ok());
+
+    // Cancelling from the owner's clone is seen by the running operation
+    token.cancel();
+    assert_eq!(context.checkpoint(), Err(GameError::Cancelled));
+
+    let expired = OperationContext::new(CancellationToken::new(), Duration::ZERO);
+    assert_eq!(expired.checkpoint(), Err(GameError::TimedOut));
+    assert!(OperationContext::unbounded().checkpoint().is_ok());
+}
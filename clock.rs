@@ -0,0 +1,81 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::get_current_timestamp;
+
+// Source of the current time in seconds. Everything time based in the game state reads it from here,
+// so tests and simulations can move time forward instead of waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        get_current_timestamp()
+    }
+}
+
+// Only moves when told to. Clones share the same time.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(now: u64) -> Self {
+        ManualClock { now: Arc::new(AtomicU64::new(now)) }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+// Clock held by the game state, the system clock unless replaced
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+
+    pub fn now(&self) -> u64 {
+        self.0.now()
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        SharedClock::new(SystemClock)
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedClock").field(&self.now()).finish()
+    }
+}
+
+#[test]
+fn test_manual_clock_shared_between_clones() {
+    let clock = ManualClock::new(100);
+    let shared = SharedClock::new(clock.clone());
+    clock.advance(30);
+    assert_eq!(shared.now(), 130);
+    clock.set(5);
+    assert_eq!(shared.clone().now(), 5);
+}
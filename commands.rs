@@ -0,0 +1,98 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::error::GameError;
+use crate::GameState;
+
+// Command ids remembered, the oldest is forgotten first. A retry comes seconds after the first attempt,
+// not thousands of actions later.
+pub const MAX_COMMANDS: usize = 10_000;
+
+// What an action run under a command id answered
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Processed {
+    caller: String,
+    operation: String,
+    result: Value,
+}
+
+// Results of the actions recently run under a command id. Only actions that went through are kept, a
+// refused one changed nothing and runs again when it is retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CommandLog {
+    processed: BTreeMap<Uuid, Processed>,
+    order: VecDeque<Uuid>, // Oldest first
+}
+
+impl CommandLog {
+    pub fn new() -> Self {
+        CommandLog::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    // The answer to `command_id` if it already ran, CommandIdReused if it ran as another action or caller
+    fn find(&self, command_id: Uuid, caller: &str, operation: &str) -> Result<Option<&Value>, GameError> {
+        match self.processed.get(&command_id) {
+            None => Ok(None),
+            Some(processed) if processed.caller == caller && processed.operation == operation => Ok(Some(&processed.result)),
+            Some(_) => Err(GameError::CommandIdReused(command_id.to_string())),
+        }
+    }
+
+    fn record(&mut self, command_id: Uuid, caller: &str, operation: &str, result: Value) {
+        if self.order.len() >= MAX_COMMANDS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.processed.remove(&oldest);
+            }
+        }
+        self.order.push_back(command_id);
+        self.processed.insert(command_id, Processed { caller: caller.to_string(), operation: operation.to_string(), result });
+    }
+}
+
+impl GameState {
+    // Runs `action` at most once per command id of `ctx`, a retry gets the first answer back. Checked before
+    // the nonce, since a retried signed call carries the nonce it already used.
+    pub(crate) fn run_once<T>(
+        &mut self,
+        ctx: &Context,
+        operation: &str,
+        action: impl FnOnce(&mut GameState) -> Result<T, GameError>,
+    ) -> Result<T, GameError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let Some(command_id) = ctx.command_id() else { return action(self) };
+        if let Some(result) = self.commands.find(command_id, ctx.caller(), operation)? {
+            return serde_json::from_value(result.clone()).map_err(|e| GameError::InvalidContent(e.to_string()));
+        }
+        let value = action(self)?;
+        let result = serde_json::to_value(&value).map_err(|e| GameError::InvalidContent(e.to_string()))?;
+        self.commands.record(command_id, ctx.caller(), operation, result);
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_oldest_commands_are_forgotten() {
+    let mut commands = CommandLog::new();
+    let ids: Vec<Uuid> = (0..=MAX_COMMANDS as u128).map(Uuid::from_u128).collect();
+    for id in &ids {
+        commands.record(*id, "Alice", "stake_tokens", Value::Null);
+    }
+    assert_eq!(commands.len(), MAX_COMMANDS);
+    assert_eq!(commands.find(ids[0], "Alice", "stake_tokens"), Ok(None));
+    assert_eq!(commands.find(ids[1], "Alice", "stake_tokens"), Ok(Some(&Value::Null)));
+    assert_eq!(commands.find(ids[1], "Bob", "stake_tokens"), Err(GameError::CommandIdReused(ids[1].to_string())));
+}
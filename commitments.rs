@@ -0,0 +1,150 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentKind {
+    GameSeed,
+    AirdropRoot,
+    JackpotDraw,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevealStatus {
+    Pending,
+    Revealed,
+    Mismatched, // The revealed value does not hash to the published commitment
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Commitment {
+    pub id: u64,
+    pub kind: CommitmentKind,
+    pub subject: String, // What the commitment is about, e.g. "game:3"
+    pub hash: String,    // Hex encoded sha256 of the secret
+    pub committed_at: u64,
+    pub status: RevealStatus,
+    pub revealed_at: Option<u64>,
+    pub revealed_value: Option<String>, // Hex encoded secret once revealed
+}
+
+// Every random value the protocol depends on is committed here before it is used and revealed afterwards,
+// so third parties can check that reveals happen and match what was published.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CommitmentRegistry {
+    commitments: Vec<Commitment>,
+}
+
+pub fn hash_secret(secret: &[u8]) -> String {
+    to_hex(&Sha256::digest(secret))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// None unless `hex` is an even number of hex digits
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+impl CommitmentRegistry {
+    pub fn new() -> Self {
+        CommitmentRegistry::default()
+    }
+
+    pub fn commit(&mut self, kind: CommitmentKind, subject: String, hash: String, timestamp: u64) -> u64 {
+        let id = self.commitments.len() as u64;
+        self.commitments.push(Commitment {
+            id,
+            kind,
+            subject,
+            hash,
+            committed_at: timestamp,
+            status: RevealStatus::Pending,
+            revealed_at: None,
+            revealed_value: None,
+        });
+        id
+    }
+
+    // Records the reveal whether it matches or not, a mismatch has to stay visible.
+    // Returns Ok(true) when the secret matches the published hash.
+    pub fn reveal(&mut self, id: u64, secret: &[u8], timestamp: u64) -> Result<bool, GameError> {
+        let commitment = self.commitments.get_mut(id as usize).ok_or(GameError::CommitmentNotFound)?;
+        if commitment.status != RevealStatus::Pending {
+            return Err(GameError::CommitmentRevealed);
+        }
+
+        let matches = hash_secret(secret) == commitment.hash;
+        commitment.status = if matches { RevealStatus::Revealed } else { RevealStatus::Mismatched };
+        commitment.revealed_at = Some(timestamp);
+        commitment.revealed_value = Some(to_hex(secret));
+
+        Ok(matches)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Commitment> {
+        self.commitments.get(id as usize)
+    }
+
+    pub fn all(&self) -> &[Commitment] {
+        &self.commitments
+    }
+
+    pub fn by_kind(&self, kind: CommitmentKind) -> Vec<&Commitment> {
+        self.commitments.iter().filter(|c| c.kind == kind).collect()
+    }
+
+    pub fn by_subject(&self, subject: &str) -> Vec<&Commitment> {
+        self.commitments.iter().filter(|c| c.subject == subject).collect()
+    }
+
+    // Commitments still waiting for their reveal more than `max_age` seconds after being published
+    pub fn overdue(&self, now: u64, max_age: u64) -> Vec<&Commitment> {
+        self.commitments
+            .iter()
+            .filter(|c| c.status == RevealStatus::Pending && now.saturating_sub(c.committed_at) > max_age)
+            .collect()
+    }
+
+    pub fn mismatched(&self) -> Vec<&Commitment> {
+        self.commitments.iter().filter(|c| c.status == RevealStatus::Mismatched).collect()
+    }
+
+    // The commitments `ids` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, ids: &[u64]) -> CommitmentRegistry {
+        CommitmentRegistry { commitments: ids.iter().filter_map(|&id| self.get(id)).cloned().collect() }
+    }
+
+    // Drops the commitments published after the first `count` and puts the saved ones back
+    pub(crate) fn restore(&mut self, saved: CommitmentRegistry, count: usize) {
+        self.commitments.truncate(count);
+        for commitment in saved.commitments {
+            if let Some(current) = self.commitments.get_mut(commitment.id as usize) {
+                *current = commitment;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_commitment_reveal_match_and_mismatch() {
+    let mut registry = CommitmentRegistry::new();
+
+    let honest = registry.commit(CommitmentKind::AirdropRoot, "airdrop:1".to_string(), hash_secret(b"root"), 10);
+    let dishonest = registry.commit(CommitmentKind::JackpotDraw, "jackpot:1".to_string(), hash_secret(b"draw"), 10);
+    assert_eq!(registry.overdue(100, 60).len(), 2);
+
+    assert_eq!(registry.reveal(honest, b"root", 20), Ok(true));
+    assert_eq!(registry.reveal(dishonest, b"other", 20), Ok(false));
+    assert!(registry.reveal(honest, b"root", 30).is_err());
+
+    assert_eq!(registry.get(honest).unwrap().status, RevealStatus::Revealed);
+    assert_eq!(registry.mismatched().len(), 1);
+    assert!(registry.overdue(100, 60).is_empty());
+}
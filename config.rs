@@ -0,0 +1,250 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::energy::EnergyConfig;
+use crate::error::GameError;
+use crate::handicap::PayoutOdds;
+use crate::jackpot::ProgressiveJackpot;
+use crate::rules::{BonusRound, DrawPolicy};
+use crate::strict::{self, ParseMode, Parsed};
+use crate::treasury::FeePolicy;
+use crate::watchdog::WatchdogConfig;
+
+// Where the currency symbol goes relative to the amount
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolPosition {
+    Prefix,
+    Suffix,
+}
+
+// Token branding used whenever an amount is shown to a human (CLI output, statements, webhook payloads).
+// Amounts are always stored as raw integer units, `decimals` only affects how they are rendered.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyDisplay {
+    pub symbol: String,
+    pub decimals: u8,
+    pub position: SymbolPosition,
+}
+
+impl Default for CurrencyDisplay {
+    fn default() -> Self {
+        CurrencyDisplay {
+            symbol: "tokens".to_string(),
+            decimals: 0,
+            position: SymbolPosition::Suffix,
+        }
+    }
+}
+
+impl CurrencyDisplay {
+    // Renders the numeric part only, e.g. 123456 with 2 decimals -> "1234.56"
+    pub fn format_value(&self, amount: u64) -> String {
+        if self.decimals == 0 {
+            return amount.to_string();
+        }
+
+        let raw = format!("{:0>width$}", amount, width = self.decimals as usize + 1);
+        let (whole, fraction) = raw.split_at(raw.len() - self.decimals as usize);
+        format!("{}.{}", whole, fraction)
+    }
+
+    pub fn format(&self, amount: u64) -> String {
+        let value = self.format_value(amount);
+        match self.position {
+            SymbolPosition::Prefix => format!("{}{}", self.symbol, value),
+            SymbolPosition::Suffix => format!("{} {}", value, self.symbol),
+        }
+    }
+}
+
+// Per-endpoint time budget for server handlers, in milliseconds. Endpoints not listed use the default.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HandlerTimeouts {
+    pub default_ms: u64,
+    pub per_endpoint: HashMap<String, u64>,
+}
+
+impl Default for HandlerTimeouts {
+    fn default() -> Self {
+        HandlerTimeouts {
+            default_ms: 30_000,
+            per_endpoint: HashMap::new(),
+        }
+    }
+}
+
+impl HandlerTimeouts {
+    pub fn timeout_for(&self, endpoint: &str) -> Duration {
+        let ms = self.per_endpoint.get(endpoint).copied().unwrap_or(self.default_ms);
+        Duration::from_millis(ms)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RatingConfig {
+    pub k_factor: u32,
+    pub initial_rating: i32,
+}
+
+impl Default for RatingConfig {
+    fn default() -> Self {
+        RatingConfig {
+            k_factor: 32,
+            initial_rating: 1200,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GameConfig {
+    pub currency: CurrencyDisplay,
+    pub handler_timeouts: HandlerTimeouts,
+    pub rating: RatingConfig,
+    pub ace_bonus: BonusRound, // Used by GameMode::CaptureTheAce
+    pub tournament_rake_bps: u32, // Taken from the prize pool of tournaments created from now on
+    pub rounds_to_win: u32, // 1 is a single draw, 2 best-of-3, 3 best-of-5
+    pub draw_policy: DrawPolicy,
+    #[serde(default = "default_max_seats")]
+    pub max_seats: u32, // Most players a game can be started for, 2 only allows heads-up games
+    #[serde(default)]
+    pub payout_odds: PayoutOdds, // Offered on games created from now on
+    pub game_timeout_secs: u64, // Default time to reveal after a game starts
+    pub claim_grace_secs: u64, // After expiry plus this, a player left waiting for the reveal can claim the pot
+    pub min_timeout_secs: u64,
+    pub max_timeout_secs: u64,
+    pub min_bet: u64, // Checked when a game is started or joined, zero bets are never accepted
+    pub max_bet: u64,
+    pub deposit_confirmations: u32, // External deposits are only credited to the stake at this depth
+    pub energy: EnergyConfig,
+    pub fee: FeePolicy, // House fee on the pot of decided games, collected into the treasury
+    #[serde(default)]
+    pub progressive_jackpot: Option<ProgressiveJackpot>, // None feeds the jackpot from funding only
+    #[serde(default)]
+    pub referral_bps: u32, // Part of the fee a referred player's game generated that goes to their referrer
+    pub suspension_secs: u64, // How long a player slashed for cheating cannot play
+    pub name_quarantine_secs: u64, // The name of a closed account cannot be claimed again for this long
+    pub watchdog: WatchdogConfig,
+    pub slow_operation_micros: u64, // Operations taking this long or longer are reported with a trace
+    #[serde(default = "default_bot_wait_secs")]
+    pub bot_wait_secs: u64, // How long a game stays open to players before its creator can call in the house bot
+}
+
+fn default_max_seats() -> u32 {
+    8
+}
+
+fn default_bot_wait_secs() -> u64 {
+    60
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            currency: CurrencyDisplay::default(),
+            handler_timeouts: HandlerTimeouts::default(),
+            rating: RatingConfig::default(),
+            ace_bonus: BonusRound::default(),
+            tournament_rake_bps: 500,
+            rounds_to_win: 1,
+            draw_policy: DrawPolicy::Refund,
+            max_seats: default_max_seats(),
+            payout_odds: PayoutOdds::default(),
+            game_timeout_secs: 600,
+            claim_grace_secs: 300,
+            min_timeout_secs: 60,
+            max_timeout_secs: 3600,
+            min_bet: 1,
+            max_bet: u64::MAX,
+            deposit_confirmations: 12,
+            energy: EnergyConfig::default(),
+            fee: FeePolicy::default(),
+            progressive_jackpot: None,
+            referral_bps: 0,
+            suspension_secs: 7 * 24 * 3600,
+            name_quarantine_secs: 30 * 24 * 3600,
+            watchdog: WatchdogConfig::default(),
+            slow_operation_micros: 100_000,
+            bot_wait_secs: default_bot_wait_secs(),
+        }
+    }
+}
+
+// Why an amount was refused by the bet limits
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("Amount must be positive.")]
+    ZeroAmount,
+    #[error("Bet of {bet} is below the minimum of {min_bet}.")]
+    BelowMinBet { bet: u64, min_bet: u64 },
+    #[error("Bet of {bet} is above the maximum of {max_bet}.")]
+    AboveMaxBet { bet: u64, max_bet: u64 },
+}
+
+impl GameConfig {
+    // Config files written by operators, a misspelled field fails the load or is reported depending on `mode`
+    pub fn from_json(json: &str, mode: ParseMode) -> Result<Parsed<GameConfig>, GameError> {
+        strict::parse_json(json, mode)
+    }
+
+    pub fn check_bet(&self, bet: u64) -> Result<(), LimitError> {
+        if bet == 0 {
+            return Err(LimitError::ZeroAmount);
+        }
+        if bet < self.min_bet {
+            return Err(LimitError::BelowMinBet { bet, min_bet: self.min_bet });
+        }
+        if bet > self.max_bet {
+            return Err(LimitError::AboveMaxBet { bet, max_bet: self.max_bet });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_currency_format_positions() {
+    let mut display = CurrencyDisplay {
+        symbol: "$".to_string(),
+        decimals: 2,
+        position: SymbolPosition::Prefix,
+    };
+    assert_eq!(display.format(123456), "$1234.56");
+    assert_eq!(display.format(5), "$0.05");
+
+    display.symbol = "GLD".to_string();
+    display.position = SymbolPosition::Suffix;
+    display.decimals = 0;
+    assert_eq!(display.format(42), "42 GLD");
+}
+
+#[test]
+fn test_handler_timeout_override() {
+    let mut timeouts = HandlerTimeouts::default();
+    timeouts.per_endpoint.insert("history".to_string(), 120_000);
+
+    assert_eq!(timeouts.timeout_for("history"), Duration::from_secs(120));
+    assert_eq!(timeouts.timeout_for("stake"), Duration::from_secs(30));
+}
+
+#[test]
+fn test_bet_limits() {
+    let config = GameConfig { min_bet: 5, max_bet: 50, ..GameConfig::default() };
+    assert_eq!(config.check_bet(0), Err(LimitError::ZeroAmount));
+    assert_eq!(config.check_bet(4), Err(LimitError::BelowMinBet { bet: 4, min_bet: 5 }));
+    assert_eq!(config.check_bet(51), Err(LimitError::AboveMaxBet { bet: 51, max_bet: 50 }));
+    assert!(config.check_bet(50).is_ok());
+    assert_eq!(LimitError::ZeroAmount.to_string(), "Amount must be positive.");
+}
+
+#[test]
+fn test_config_typos_are_caught() {
+    let mut value = serde_json::to_value(GameConfig::default()).unwrap();
+    value["energy"]["enabeld"] = true.into();
+    let json = value.to_string();
+
+    assert_eq!(GameConfig::from_json(&json, ParseMode::Strict).unwrap_err(), GameError::UnknownFields(vec!["energy.enabeld".to_string()]));
+    let parsed = GameConfig::from_json(&json, ParseMode::Lenient).unwrap();
+    assert_eq!(parsed.value, GameConfig::default());
+    assert_eq!(parsed.warnings.len(), 1);
+}
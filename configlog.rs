@@ -0,0 +1,82 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::commitments::to_hex;
+use crate::config::GameConfig;
+use crate::strict::canonical_json;
+
+// One applied configuration. Games keep the version they were created under.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub hash: String, // Hex encoded sha256 of the canonical JSON of the config
+    pub effective_at: u64,
+    pub applied_by: String,
+    pub config: GameConfig,
+}
+
+// Hash of the config as JSON with sorted keys, so maps inside the config hash the same every time
+pub fn config_hash(config: &GameConfig) -> String {
+    let value = serde_json::to_value(config).expect("GameConfig has string keys only");
+    to_hex(&Sha256::digest(canonical_json(&value)))
+}
+
+// Every config the state ever ran with, oldest first. Version numbers are the positions in the log.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigLog {
+    versions: Vec<ConfigVersion>,
+}
+
+impl ConfigLog {
+    pub fn new(genesis: GameConfig, now: u64) -> Self {
+        let mut log = ConfigLog { versions: Vec::new() };
+        log.apply(genesis, "genesis".to_string(), now);
+        log
+    }
+
+    pub fn apply(&mut self, config: GameConfig, applied_by: String, now: u64) -> u64 {
+        let version = self.versions.len() as u64;
+        self.versions.push(ConfigVersion {
+            version,
+            hash: config_hash(&config),
+            effective_at: now,
+            applied_by,
+            config,
+        });
+        version
+    }
+
+    pub fn current_version(&self) -> u64 {
+        self.versions.len() as u64 - 1
+    }
+
+    pub fn get(&self, version: u64) -> Option<&ConfigVersion> {
+        self.versions.get(version as usize)
+    }
+
+    pub fn config(&self, version: u64) -> Option<&GameConfig> {
+        self.get(version).map(|v| &v.config)
+    }
+
+    pub fn all(&self) -> &[ConfigVersion] {
+        &self.versions
+    }
+}
+
+#[test]
+fn test_config_versions_logged_in_order() {
+    let mut log = ConfigLog::new(GameConfig::default(), 10);
+    assert_eq!(log.current_version(), 0);
+
+    let mut config = GameConfig::default();
+    config.handler_timeouts.per_endpoint.insert("reveal".to_string(), 5);
+    config.handler_timeouts.per_endpoint.insert("join".to_string(), 7);
+    assert_eq!(log.apply(config.clone(), "Alice".to_string(), 20), 1);
+
+    let applied = log.get(1).unwrap();
+    assert_eq!((applied.effective_at, applied.applied_by.as_str()), (20, "Alice"));
+    assert_eq!(applied.hash, config_hash(&config));
+    assert_ne!(applied.hash, log.get(0).unwrap().hash);
+    assert_eq!(log.config(0), Some(&GameConfig::default()));
+    assert!(log.get(2).is_none());
+}
@@ -0,0 +1,55 @@
+use uuid::Uuid;
+
+use crate::account_id::AccountId;
+#[cfg(test)]
+use crate::account_id::account;
+
+// Who is calling. Transports build it from the account they authenticated, and every mutating method takes
+// the acting player or admin from it rather than from its arguments, so nobody can act for somebody else.
+// Signed actions also carry the nonce they were signed with, each nonce of an account is accepted once.
+// Clients that retry give each action a command id, a retried action is answered without running it again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    caller: AccountId,
+    nonce: Option<u64>,
+    command_id: Option<Uuid>,
+}
+
+impl Context {
+    pub fn new(caller: AccountId) -> Self {
+        Context { caller, nonce: None, command_id: None }
+    }
+
+    // A call signed by `caller` with `nonce`, refused unless the nonce is at least their next one
+    pub fn signed(caller: AccountId, nonce: u64) -> Self {
+        Context { caller, nonce: Some(nonce), command_id: None }
+    }
+
+    pub fn with_command_id(mut self, command_id: Option<Uuid>) -> Self {
+        self.command_id = command_id;
+        self
+    }
+
+    pub fn caller(&self) -> &AccountId {
+        &self.caller
+    }
+
+    pub fn nonce(&self) -> Option<u64> {
+        self.nonce
+    }
+
+    pub fn command_id(&self) -> Option<Uuid> {
+        self.command_id
+    }
+
+    // The same caller without the nonce and command id, for calls made on behalf of a call that already used them
+    pub fn unsigned(&self) -> Context {
+        Context::new(self.caller.clone())
+    }
+}
+
+// Contexts of account ids known to be valid, for tests
+#[cfg(test)]
+pub fn caller(id: &str) -> Context {
+    Context::new(account(id))
+}
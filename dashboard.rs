@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::Frame;
+
+use crate::config::CurrencyDisplay;
+use crate::events::{GameEvent, LoggedEvent};
+use crate::get_current_timestamp;
+
+const RECENT_SETTLEMENTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DashboardGame {
+    pub creator: String,
+    pub opponent: Option<String>, // The last to sit down in a game of more than two seats
+    pub bet: u64,
+    pub expires_at: u64,
+    pub seats: u32,
+    pub seated: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DashboardSettlement {
+    pub game_id: u64,
+    pub winner: Option<String>,
+    pub payout: u64,
+    pub settled_at: u64,
+}
+
+// Everything on screen is derived from the event stream, so the dashboard never touches GameState
+#[derive(Debug, Default)]
+pub struct DashboardModel {
+    pub lobby: BTreeMap<u64, DashboardGame>,
+    pub active: BTreeMap<u64, DashboardGame>,
+    pub recent: VecDeque<DashboardSettlement>,
+    pub volume: u64,
+    pub treasury: u64,
+}
+
+impl DashboardModel {
+    pub fn apply(&mut self, logged: &LoggedEvent) {
+        match &logged.event {
+            GameEvent::GameStarted { game_id, creator, bet, expires_at, seats, .. } => {
+                self.lobby.insert(*game_id, DashboardGame {
+                    creator: creator.clone(),
+                    opponent: None,
+                    bet: *bet,
+                    expires_at: *expires_at,
+                    seats: *seats,
+                    seated: 1,
+                });
+            }
+            GameEvent::GameJoined { game_id, opponent } => {
+                let Some(game) = self.lobby.get_mut(game_id) else { return };
+                game.opponent = Some(opponent.clone());
+                game.seated += 1;
+                if game.seated >= game.seats {
+                    if let Some(game) = self.lobby.remove(game_id) {
+                        self.active.insert(*game_id, game);
+                    }
+                }
+            }
+            GameEvent::Settled { game_id, winner, payout } => {
+                if let Some(game) = self.active.remove(game_id) {
+                    self.volume = self.volume.saturating_add(game.bet.saturating_mul(game.seats.into()));
+                }
+                self.recent.push_front(DashboardSettlement {
+                    game_id: *game_id,
+                    winner: winner.clone(),
+                    payout: *payout,
+                    settled_at: logged.timestamp,
+                });
+                self.recent.truncate(RECENT_SETTLEMENTS);
+            }
+            GameEvent::FeeCollected { amount, .. } => {
+                self.treasury = self.treasury.saturating_add(*amount);
+            }
+            GameEvent::TreasuryWithdrawn { amount, .. } | GameEvent::BotFunded { amount, .. } | GameEvent::RewardsFunded { amount, .. } | GameEvent::ReferralPaid { amount, .. } => {
+                self.treasury = self.treasury.saturating_sub(*amount);
+            }
+            GameEvent::Expired { game_id, .. } => {
+                self.lobby.remove(game_id);
+                self.active.remove(game_id);
+            }
+            // The game of a slashed player is called off
+            GameEvent::PlayerSlashed { game_id: Some(game_id), .. } => {
+                self.lobby.remove(game_id);
+                self.active.remove(game_id);
+            }
+            GameEvent::PlayerSlashed { game_id: None, .. } => {}
+            GameEvent::StateReset { .. } => {
+                self.lobby.clear();
+                self.active.clear();
+            }
+            GameEvent::Staked { .. }
+            | GameEvent::Withdrawn { .. }
+            | GameEvent::CardsRevealed { .. }
+            | GameEvent::SeatsRevealed { .. }
+            | GameEvent::PotShared { .. }
+            | GameEvent::TeamJoined { .. }
+            | GameEvent::PotCarried { .. }
+            | GameEvent::TimeoutClaimed { .. }
+            | GameEvent::SlowOperation { .. }
+            | GameEvent::AccountClosed { .. }
+            | GameEvent::TickStalled { .. }
+            | GameEvent::JackpotFunded { .. }
+            | GameEvent::BonusPaid { .. }
+            | GameEvent::JackpotContributed { .. }
+            | GameEvent::JackpotWon { .. }
+            | GameEvent::TournamentJoined { .. }
+            | GameEvent::TournamentWaitlisted { .. }
+            | GameEvent::TournamentPromoted { .. }
+            | GameEvent::TournamentLeft { .. }
+            | GameEvent::TournamentFinished { .. }
+            | GameEvent::MatchQueued { .. }
+            | GameEvent::SideBetPlaced { .. }
+            | GameEvent::SideBetPaid { .. }
+            | GameEvent::ConfigApplied { .. }
+            | GameEvent::DepositPending { .. }
+            | GameEvent::DepositCredited { .. }
+            | GameEvent::DepositReversed { .. }
+            | GameEvent::Token(_)
+            | GameEvent::AccountFrozen { .. }
+            | GameEvent::AccountUnfrozen { .. }
+            | GameEvent::RewardsClaimed { .. }
+            | GameEvent::ReferralRegistered { .. } => {}
+        }
+    }
+}
+
+fn countdown(game: &DashboardGame, now: u64) -> String {
+    let remaining = game.expires_at.saturating_sub(now);
+    format!("{:02}:{:02}", remaining / 60, remaining % 60)
+}
+
+fn draw(frame: &mut Frame, model: &DashboardModel, currency: &CurrencyDisplay) {
+    let now = get_current_timestamp();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Min(5)])
+        .split(frame.area());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    let summary = format!(
+        "Open games: {}   Active games: {}   Settled volume: {}   Treasury: {}   (q to quit)",
+        model.lobby.len(),
+        model.active.len(),
+        currency.format(model.volume),
+        currency.format(model.treasury),
+    );
+    frame.render_widget(Paragraph::new(summary).block(Block::default().borders(Borders::ALL).title("Overview")), rows[0]);
+
+    let lobby: Vec<ListItem> = model
+        .lobby
+        .iter()
+        .map(|(id, game)| {
+            ListItem::new(format!("#{} {} bets {} expires in {}", id, game.creator, currency.format(game.bet), countdown(game, now)))
+        })
+        .collect();
+    frame.render_widget(List::new(lobby).block(Block::default().borders(Borders::ALL).title("Lobby")), columns[0]);
+
+    let active: Vec<ListItem> = model
+        .active
+        .iter()
+        .map(|(id, game)| {
+            ListItem::new(format!(
+                "#{} {} vs {} for {} expires in {}",
+                id,
+                game.creator,
+                game.opponent.as_deref().unwrap_or("?"),
+                currency.format(game.bet),
+                countdown(game, now),
+            ))
+        })
+        .collect();
+    frame.render_widget(List::new(active).block(Block::default().borders(Borders::ALL).title("Active games")), columns[1]);
+
+    let recent: Vec<ListItem> = model
+        .recent
+        .iter()
+        .map(|settlement| {
+            let result = match &settlement.winner {
+                Some(winner) => format!("{} won {}", winner, currency.format(settlement.payout)),
+                None => format!("draw, {} refunded each", currency.format(settlement.payout)),
+            };
+            ListItem::new(format!("#{} {}", settlement.game_id, result))
+        })
+        .collect();
+    frame.render_widget(List::new(recent).block(Block::default().borders(Borders::ALL).title("Recent settlements")), rows[2]);
+}
+
+// Renders until the operator presses q. `history` seeds the view with what already happened,
+// `feed` is usually a channel filled by a GameState subscriber.
+pub fn run(history: &[LoggedEvent], feed: Receiver<LoggedEvent>, currency: CurrencyDisplay) -> io::Result<()> {
+    let mut model = DashboardModel::default();
+    for logged in history {
+        model.apply(logged);
+    }
+
+    let mut terminal = ratatui::init();
+    let result = loop {
+        while let Ok(logged) = feed.try_recv() {
+            model.apply(&logged);
+        }
+
+        if let Err(e) = terminal.draw(|frame| draw(frame, &model, &currency)) {
+            break Err(e);
+        }
+
+        match event::poll(Duration::from_millis(250)) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if key.code == KeyCode::Char('q') => break Ok(()),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+#[test]
+fn test_dashboard_model_follows_events() {
+    use crate::handicap::PayoutOdds;
+    use crate::seats::HEADS_UP;
+
+    let mut model = DashboardModel::default();
+    let events = [
+        GameEvent::GameStarted { game_id: 0, creator: "Alice".to_string(), bet: 10, expires_at: 700, odds: PayoutOdds::default(), seats: HEADS_UP, teams: false },
+        GameEvent::GameStarted { game_id: 1, creator: "Carol".to_string(), bet: 5, expires_at: 700, odds: PayoutOdds::default(), seats: HEADS_UP, teams: false },
+        GameEvent::GameJoined { game_id: 0, opponent: "Bob".to_string() },
+    ];
+    for (sequence, event) in events.into_iter().enumerate() {
+        model.apply(&LoggedEvent { sequence: sequence as u64, timestamp: 100, event });
+    }
+    assert_eq!(model.lobby.len(), 1);
+    assert_eq!(model.active[&0].opponent.as_deref(), Some("Bob"));
+    assert_eq!(countdown(&model.active[&0], 160), "09:00");
+
+    let settled = GameEvent::Settled { game_id: 0, winner: Some("Bob".to_string()), payout: 20 };
+    model.apply(&LoggedEvent { sequence: 3, timestamp: 200, event: settled });
+    assert!(model.active.is_empty());
+    assert_eq!(model.volume, 20);
+    assert_eq!(model.recent[0].winner.as_deref(), Some("Bob"));
+
+    let fee = GameEvent::FeeCollected { game_id: Some(0), amount: 3 };
+    model.apply(&LoggedEvent { sequence: 4, timestamp: 200, event: fee });
+    assert_eq!(model.treasury, 3);
+}
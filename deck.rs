@@ -0,0 +1,128 @@
+use serde::{Serialize, Deserialize};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+pub const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+
+// Rank 1 is the Ace, 11 to 13 are Jack, Queen and King. Games compare ranks only, suits never break ties.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Card {
+    pub rank: u8,
+    pub suit: Suit,
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rank = match self.rank {
+            1 => "A".to_string(),
+            11 => "J".to_string(),
+            12 => "Q".to_string(),
+            13 => "K".to_string(),
+            rank => rank.to_string(),
+        };
+        let suit = match self.suit {
+            Suit::Clubs => 'c',
+            Suit::Diamonds => 'd',
+            Suit::Hearts => 'h',
+            Suit::Spades => 's',
+        };
+        write!(f, "{}{}", rank, suit)
+    }
+}
+
+// Shape of a deck: every rank from 1 to `ranks` once per suit
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeckSpec {
+    pub ranks: u8,
+    pub suits: u8,
+}
+
+impl Default for DeckSpec {
+    // The 52 card deck every game is dealt from
+    fn default() -> Self {
+        DeckSpec { ranks: 13, suits: SUITS.len() as u8 }
+    }
+}
+
+impl DeckSpec {
+    pub fn size(&self) -> usize {
+        self.ranks as usize * self.suits as usize
+    }
+}
+
+// A game's own deck, cards that were drawn are gone until the next game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Deck {
+    cards: Vec<Card>, // The top of the deck is the end of the vector
+}
+
+impl Deck {
+    pub fn ordered() -> Self {
+        let cards = SUITS
+            .iter()
+            .flat_map(|&suit| (1..=13).map(move |rank| Card { rank, suit }))
+            .collect();
+        Deck { cards }
+    }
+
+    // Fisher–Yates over the ordered deck, driven by the game seed so the order is fixed by the commitment
+    pub fn shuffled(seed: &[u8; 32]) -> Self {
+        let mut deck = Deck::ordered();
+        let mut rng = StdRng::from_seed(*seed);
+        for i in (1..deck.cards.len()).rev() {
+            let j = rng.gen_range(0..=i);
+            deck.cards.swap(i, j);
+        }
+        deck
+    }
+
+    pub fn draw(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+
+    // Draws `count` cards at once, the deck is left untouched if it does not hold enough
+    pub fn deal(&mut self, count: usize) -> Result<Vec<Card>, GameError> {
+        if self.cards.len() < count {
+            return Err(GameError::DeckEmpty);
+        }
+        Ok((0..count).filter_map(|_| self.cards.pop()).collect())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.cards.len()
+    }
+}
+
+#[test]
+fn test_deck_draws_without_replacement() {
+    let mut deck = Deck::shuffled(&[7; 32]);
+    assert_eq!(deck.remaining(), 52);
+
+    let mut seen = std::collections::HashSet::new();
+    while let Some(card) = deck.draw() {
+        assert!((1..=13).contains(&card.rank));
+        assert!(seen.insert(card), "Card {} drawn twice", card);
+    }
+    assert_eq!(seen.len(), 52);
+    assert!(deck.draw().is_none());
+    assert!(deck.deal(1).is_err());
+}
+
+#[test]
+fn test_shuffle_is_fixed_by_seed() {
+    assert_eq!(Deck::shuffled(&[1; 32]), Deck::shuffled(&[1; 32]));
+    assert_ne!(Deck::shuffled(&[1; 32]), Deck::shuffled(&[2; 32]));
+    assert_ne!(Deck::shuffled(&[1; 32]), Deck::ordered());
+    assert_eq!(Card { rank: 1, suit: Suit::Spades }.to_string(), "As");
+}
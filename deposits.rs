@@ -0,0 +1,153 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositStatus {
+    Pending,
+    Credited,
+    Reversed,
+}
+
+// Deposit seen on the external chain, keyed by its transaction id
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Deposit {
+    pub tx_id: String,
+    pub user: String,
+    pub amount: u64,
+    pub confirmations: u32,
+    pub status: DepositStatus,
+    pub seen_at: u64,
+}
+
+// View of the external chain, implemented by the bridge. None means the transaction
+// is no longer part of the canonical chain (reorged out or dropped).
+pub trait ChainAdapter {
+    fn confirmations(&self, tx_id: &str) -> Option<u32>;
+}
+
+// Sends tokens out to the external chain, returns the id of the transaction
+pub trait PayoutAdapter {
+    fn pay(&self, user: &str, amount: u64) -> Result<String, GameError>;
+}
+
+// What the caller has to apply to the stakes after a deposit moved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepositUpdate {
+    Credited { user: String, amount: u64 },
+    Reversed { user: String, amount: u64, was_credited: bool },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Deposits {
+    deposits: BTreeMap<String, Deposit>,
+}
+
+impl Deposits {
+    pub fn new() -> Self {
+        Deposits::default()
+    }
+
+    pub fn record(&mut self, tx_id: String, user: String, amount: u64, now: u64) -> Result<(), GameError> {
+        if self.deposits.contains_key(&tx_id) {
+            return Err(GameError::DepositRecorded);
+        }
+        self.deposits.insert(tx_id.clone(), Deposit {
+            tx_id,
+            user,
+            amount,
+            confirmations: 0,
+            status: DepositStatus::Pending,
+            seen_at: now,
+        });
+        Ok(())
+    }
+
+    pub fn get(&self, tx_id: &str) -> Option<&Deposit> {
+        self.deposits.get(tx_id)
+    }
+
+    // Deposits that can still change, credited ones are watched too in case of a reorg deeper than the required depth
+    pub fn tracked(&self) -> Vec<String> {
+        self.deposits
+            .values()
+            .filter(|d| d.status != DepositStatus::Reversed)
+            .map(|d| d.tx_id.clone())
+            .collect()
+    }
+
+    pub fn pending_for(&self, user: &str) -> u64 {
+        self.deposits
+            .values()
+            .filter(|d| d.user == user && d.status == DepositStatus::Pending)
+            .map(|d| d.amount)
+            .sum()
+    }
+
+    // Applies what the adapter reports for one deposit. Credited once `required` confirmations are reached,
+    // reversed as soon as the transaction disappears from the chain.
+    pub fn advance(&mut self, tx_id: &str, confirmations: Option<u32>, required: u32) -> Result<Option<DepositUpdate>, GameError> {
+        let deposit = self.deposits.get_mut(tx_id).ok_or(GameError::DepositNotFound)?;
+        if deposit.status == DepositStatus::Reversed {
+            return Ok(None);
+        }
+
+        let confirmations = match confirmations {
+            Some(confirmations) => confirmations,
+            None => {
+                let was_credited = deposit.status == DepositStatus::Credited;
+                deposit.status = DepositStatus::Reversed;
+                deposit.confirmations = 0;
+                return Ok(Some(DepositUpdate::Reversed {
+                    user: deposit.user.clone(),
+                    amount: deposit.amount,
+                    was_credited,
+                }));
+            }
+        };
+
+        deposit.confirmations = confirmations;
+        if deposit.status == DepositStatus::Pending && confirmations >= required {
+            deposit.status = DepositStatus::Credited;
+            return Ok(Some(DepositUpdate::Credited { user: deposit.user.clone(), amount: deposit.amount }));
+        }
+        Ok(None)
+    }
+
+    // The deposits of `users` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, users: &[String]) -> Deposits {
+        let deposits = self.deposits.iter().filter(|(_, d)| users.contains(&d.user));
+        Deposits { deposits: deposits.map(|(tx_id, d)| (tx_id.clone(), d.clone())).collect() }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Deposits, users: &[String]) {
+        self.deposits.retain(|_, d| !users.contains(&d.user));
+        self.deposits.extend(saved.deposits);
+    }
+}
+
+#[test]
+fn test_deposit_credited_after_depth_and_reversed() {
+    let mut deposits = Deposits::new();
+    deposits.record("0xa".to_string(), "Alice".to_string(), 50, 1).unwrap();
+    deposits.record("0xb".to_string(), "Alice".to_string(), 20, 1).unwrap();
+    assert!(deposits.record("0xa".to_string(), "Bob".to_string(), 1, 2).is_err());
+    assert_eq!(deposits.pending_for("Alice"), 70);
+
+    assert_eq!(deposits.advance("0xa", Some(3), 6), Ok(None));
+    assert_eq!(
+        deposits.advance("0xa", Some(6), 6),
+        Ok(Some(DepositUpdate::Credited { user: "Alice".to_string(), amount: 50 }))
+    );
+    // Further confirmations do not credit twice
+    assert_eq!(deposits.advance("0xa", Some(7), 6), Ok(None));
+
+    assert_eq!(
+        deposits.advance("0xb", None, 6),
+        Ok(Some(DepositUpdate::Reversed { user: "Alice".to_string(), amount: 20, was_credited: false }))
+    );
+    assert_eq!(deposits.get("0xb").unwrap().status, DepositStatus::Reversed);
+    assert_eq!(deposits.tracked(), vec!["0xa".to_string()]);
+    assert_eq!(deposits.pending_for("Alice"), 0);
+}
@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::error::GameError;
+use crate::tx::{restore_entries, save_entries};
+
+// Pacing for play-money deployments. Every game costs energy, energy comes back one point per
+// `regen_secs` up to `max`. Disabled by default, real-money games are never paced.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnergyConfig {
+    pub enabled: bool,
+    pub max: u32,
+    pub cost_per_game: u32,
+    pub regen_secs: u64,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        EnergyConfig {
+            enabled: false,
+            max: 5,
+            cost_per_game: 1,
+            regen_secs: 1800,
+        }
+    }
+}
+
+// Energy at `updated_at`. Players without a meter are full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct EnergyMeter {
+    energy: u32,
+    updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Energy {
+    meters: HashMap<String, EnergyMeter>,
+}
+
+impl Energy {
+    pub fn new() -> Self {
+        Energy::default()
+    }
+
+    // Meter brought up to `now`, the time of a partly regenerated point is kept
+    fn current(&self, player: &str, config: &EnergyConfig, now: u64) -> EnergyMeter {
+        let meter = match self.meters.get(player) {
+            Some(meter) => *meter,
+            None => return EnergyMeter { energy: config.max, updated_at: now },
+        };
+        if meter.energy >= config.max || config.regen_secs == 0 {
+            return EnergyMeter { energy: config.max.max(meter.energy), updated_at: now };
+        }
+
+        let regenerated = now.saturating_sub(meter.updated_at) / config.regen_secs;
+        let energy = (meter.energy as u64 + regenerated).min(config.max as u64) as u32;
+        if energy >= config.max {
+            return EnergyMeter { energy, updated_at: now };
+        }
+        EnergyMeter { energy, updated_at: meter.updated_at + regenerated * config.regen_secs }
+    }
+
+    pub fn available(&self, player: &str, config: &EnergyConfig, now: u64) -> u32 {
+        self.current(player, config, now).energy
+    }
+
+    pub fn consume(&mut self, player: &str, config: &EnergyConfig, now: u64) -> Result<(), GameError> {
+        let mut meter = self.current(player, config, now);
+        if meter.energy < config.cost_per_game {
+            return Err(GameError::NotEnoughEnergy);
+        }
+        meter.energy -= config.cost_per_game;
+        self.meters.insert(player.to_string(), meter);
+        Ok(())
+    }
+
+    // Seconds until the player has enough energy for one more game, 0 if they already do
+    pub fn time_to_next_game(&self, player: &str, config: &EnergyConfig, now: u64) -> u64 {
+        let meter = self.current(player, config, now);
+        if meter.energy >= config.cost_per_game {
+            return 0;
+        }
+        let missing = (config.cost_per_game - meter.energy) as u64;
+        (meter.updated_at + missing * config.regen_secs).saturating_sub(now)
+    }
+
+    // The meters of `players` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, players: &[String]) -> Energy {
+        Energy { meters: save_entries(&self.meters, players) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Energy, players: &[String]) {
+        restore_entries(&mut self.meters, saved.meters, players);
+    }
+}
+
+#[test]
+fn test_energy_regenerates_over_time() {
+    let config = EnergyConfig { enabled: true, max: 2, cost_per_game: 1, regen_secs: 100 };
+    let mut energy = Energy::new();
+
+    energy.consume("Alice", &config, 0).unwrap();
+    energy.consume("Alice", &config, 10).unwrap();
+    assert!(energy.consume("Alice", &config, 20).is_err());
+    assert_eq!(energy.time_to_next_game("Alice", &config, 20), 80);
+
+    // One point back at 100, the second one keeps counting from there
+    assert_eq!(energy.available("Alice", &config, 150), 1);
+    energy.consume("Alice", &config, 150).unwrap();
+    assert_eq!(energy.time_to_next_game("Alice", &config, 150), 50);
+    assert_eq!(energy.available("Alice", &config, 1_000), 2);
+    assert_eq!(energy.available("Bob", &config, 0), 2);
+}
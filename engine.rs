@@ -0,0 +1,187 @@
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::account_id::AccountId;
+use crate::balance::Balance;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::lobby::{GameSummary, LobbyFilter};
+use crate::GameState;
+
+// Commands waiting for the engine, senders wait for room once it is full
+pub const COMMAND_QUEUE: usize = 256;
+
+type Reply<T> = oneshot::Sender<Result<T, GameError>>;
+
+// What the engine can be asked to do, each command carries the channel its answer goes back on. Stakes
+// move tokens, deposits and withdrawals run with the token through Run.
+pub enum Command {
+    StartGame { creator: AccountId, bet: u64, reply: Reply<u64> },
+    JoinGame { game_id: u64, opponent: AccountId, reply: Reply<()> },
+    ContributeReveal { game_id: u64, player: AccountId, reply: Reply<bool> },
+    StakeOf { user: String, reply: oneshot::Sender<Balance> },
+    GameSummary { game_id: u64, reply: oneshot::Sender<Option<GameSummary>> },
+    ListOpenGames { filter: LobbyFilter, after: Option<u64>, limit: usize, reply: oneshot::Sender<Vec<GameSummary>> },
+    // Any other call on the state, the closure sends its own answer
+    Run(Box<dyn FnOnce(&mut GameState) + Send>),
+}
+
+// Actor owning the state (actor feature): commands are handled one at a time in the order they arrive, so
+// handlers share a game without locks. An alternative to SharedGameState for async servers.
+pub struct GameEngine {
+    game_state: GameState,
+    commands: mpsc::Receiver<Command>,
+}
+
+impl GameEngine {
+    // Starts the engine on the current tokio runtime. It runs until every handle is dropped and then hands
+    // the state back through the join handle, e.g. to save it.
+    pub fn spawn(game_state: GameState) -> (EngineHandle, JoinHandle<GameState>) {
+        let (sender, commands) = mpsc::channel(COMMAND_QUEUE);
+        let engine = GameEngine { game_state, commands };
+        (EngineHandle { commands: sender }, tokio::spawn(engine.run()))
+    }
+
+    async fn run(mut self) -> GameState {
+        while let Some(command) = self.commands.recv().await {
+            self.handle(command);
+        }
+        self.game_state
+    }
+
+    // Answers are dropped when the caller stopped waiting for them
+    fn handle(&mut self, command: Command) {
+        let game_state = &mut self.game_state;
+        match command {
+            Command::StartGame { creator, bet, reply } => {
+                let _ = reply.send(game_state.start_game(&Context::new(creator), bet));
+            }
+            Command::JoinGame { game_id, opponent, reply } => {
+                let _ = reply.send(game_state.join_game(&Context::new(opponent), game_id));
+            }
+            Command::ContributeReveal { game_id, player, reply } => {
+                let _ = reply.send(game_state.contribute_reveal(&Context::new(player), game_id));
+            }
+            Command::StakeOf { user, reply } => {
+                let _ = reply.send(game_state.stake_of(&user));
+            }
+            Command::GameSummary { game_id, reply } => {
+                let _ = reply.send(game_state.game_summary(game_id));
+            }
+            Command::ListOpenGames { filter, after, limit, reply } => {
+                let _ = reply.send(game_state.list_open_games(&filter, after, limit));
+            }
+            Command::Run(action) => action(game_state),
+        }
+    }
+}
+
+// Sends commands to a running engine and waits for the answers. Clones talk to the same engine.
+#[derive(Clone)]
+pub struct EngineHandle {
+    commands: mpsc::Sender<Command>,
+}
+
+impl EngineHandle {
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Result<T, GameError> {
+        let (reply, answer) = oneshot::channel();
+        self.commands.send(command(reply)).await.map_err(|_| GameError::EngineStopped)?;
+        answer.await.map_err(|_| GameError::EngineStopped)
+    }
+
+    pub async fn start_game(&self, creator: AccountId, bet: u64) -> Result<u64, GameError> {
+        self.request(|reply| Command::StartGame { creator, bet, reply }).await?
+    }
+
+    pub async fn join_game(&self, game_id: u64, opponent: AccountId) -> Result<(), GameError> {
+        self.request(|reply| Command::JoinGame { game_id, opponent, reply }).await?
+    }
+
+    // True once every player asked and the round was revealed
+    pub async fn contribute_reveal(&self, game_id: u64, player: AccountId) -> Result<bool, GameError> {
+        self.request(|reply| Command::ContributeReveal { game_id, player, reply }).await?
+    }
+
+    pub async fn stake_of(&self, user: String) -> Result<Balance, GameError> {
+        self.request(|reply| Command::StakeOf { user, reply }).await
+    }
+
+    pub async fn game_summary(&self, game_id: u64) -> Result<Option<GameSummary>, GameError> {
+        self.request(|reply| Command::GameSummary { game_id, reply }).await
+    }
+
+    pub async fn list_open_games(&self, filter: LobbyFilter, after: Option<u64>, limit: usize) -> Result<Vec<GameSummary>, GameError> {
+        self.request(|reply| Command::ListOpenGames { filter, after, limit, reply }).await
+    }
+
+    // Runs `action` on the state between two commands, for calls without a command of their own
+    pub async fn run<T: Send + 'static>(&self, action: impl FnOnce(&mut GameState) -> T + Send + 'static) -> Result<T, GameError> {
+        self.request(|reply| {
+            Command::Run(Box::new(move |game_state| {
+                let _ = reply.send(action(game_state));
+            }))
+        })
+        .await
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handlers_share_the_engine() {
+    use std::sync::{Arc, Mutex};
+
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::token::ERC20Token;
+    use crate::vault::vault;
+
+    let mut token = ERC20Token::new(account("Owner"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("Owner"), 0).unwrap();
+    let token = Arc::new(Mutex::new(token));
+    let (engine, stopped) = GameEngine::spawn(GameState::new());
+    let handlers: Vec<JoinHandle<()>> = (0..8)
+        .map(|pair| {
+            let (engine, token) = (engine.clone(), token.clone());
+            tokio::spawn(async move {
+                let (creator, opponent) = (account(&format!("Creator{}", pair)), account(&format!("Opponent{}", pair)));
+                for player in [&creator, &opponent] {
+                    let (player, token) = (player.clone(), token.clone());
+                    let deposit = move |game_state: &mut GameState| {
+                        let mut token = token.lock().unwrap();
+                        token.mint(&Context::new(player.clone()), 50, 0)?;
+                        token.approve(&Context::new(player.clone()), vault(), 50)?;
+                        game_state.deposit(&Context::new(player), &mut token, 50)
+                    };
+                    engine.run(deposit).await.unwrap().unwrap();
+                }
+                let game_id = engine.start_game(creator.clone(), 50).await.unwrap();
+                assert_eq!(engine.join_game(game_id, creator.clone()).await, Err(GameError::OwnGame));
+                engine.join_game(game_id, opponent.clone()).await.unwrap();
+                while engine.game_summary(game_id).await.unwrap().is_some() {
+                    assert!(!engine.contribute_reveal(game_id, creator.clone()).await.unwrap());
+                    assert!(engine.contribute_reveal(game_id, opponent.clone()).await.unwrap());
+                }
+            })
+        })
+        .collect();
+    for handler in handlers {
+        handler.await.unwrap();
+    }
+    assert!(engine.list_open_games(LobbyFilter::default(), None, 10).await.unwrap().is_empty());
+    let fees = engine.run(|game_state| game_state.fees_collected() + game_state.jackpot_balance()).await.unwrap();
+
+    // The state comes back once the last handle is gone
+    drop(engine);
+    let game_state = stopped.await.unwrap();
+    let staked: u64 = (0..8)
+        .flat_map(|pair| [format!("Creator{}", pair), format!("Opponent{}", pair)])
+        .map(|player| game_state.stake_of(&player))
+        .map(|stake| stake.total().get())
+        .sum();
+    assert_eq!(staked + fees, 8 * 100);
+    game_state.check_vault(&token.lock().unwrap()).unwrap();
+
+    let (engine, running) = GameEngine::spawn(game_state);
+    running.abort();
+    let _ = running.await;
+    assert!(matches!(engine.stake_of("Creator0".to_string()).await, Err(GameError::EngineStopped)));
+}
@@ -0,0 +1,298 @@
+use thiserror::Error;
+
+use crate::account_id::AccountIdError;
+use crate::config::LimitError;
+use crate::roles::Role;
+use crate::token::TokenError;
+
+// Every way an operation can be refused. Callers match on the variant, the message is what players see.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    #[error(transparent)]
+    AccountId(#[from] AccountIdError),
+    #[error(transparent)]
+    Token(#[from] TokenError),
+    #[error("Overflow error.")]
+    Overflow,
+
+    // Stakes and balances
+    #[error("Insufficient stake.")]
+    InsufficientStake,
+    #[error("Insufficient funds.")]
+    InsufficientFunds,
+    #[error("Locked balance too low.")]
+    LockedBalanceTooLow,
+    #[error("User not found.")]
+    UserNotFound,
+    #[error("Insufficient treasury balance.")]
+    InsufficientTreasury,
+    #[error("Payout failed: {0}.")]
+    PayoutFailed(String),
+
+    // Games
+    #[error("Game not found.")]
+    GameNotFound,
+    #[error("No game to join.")]
+    NoGameToJoin,
+    #[error("No game to reveal.")]
+    NoGameToReveal,
+    #[error("No game to settle.")]
+    NoGameToSettle,
+    #[error("No game to archive.")]
+    NoGameToArchive,
+    #[error("Game already joined.")]
+    GameAlreadyStarted,
+    #[error("Cannot join your own game.")]
+    OwnGame,
+    #[error("Game expired.")]
+    Expired,
+    #[error("Game already settled.")]
+    AlreadySettled,
+    #[error("No opponent to reveal against.")]
+    NoOpponentToReveal,
+    #[error("No opponent to claim against.")]
+    NoOpponentToClaim,
+    #[error("Not a player of this game.")]
+    NotYourGame,
+    #[error("Grace period not over.")]
+    GracePeriodNotOver,
+    #[error("Caller did not ask for the reveal.")]
+    RevealNotRequested,
+    #[error("Deck is empty.")]
+    DeckEmpty,
+    #[error("Deck is too small for this mode.")]
+    DeckTooSmall,
+    #[error("Timeout out of bounds.")]
+    TimeoutOutOfBounds,
+    #[error("A game has between 2 and {max_seats} seats.")]
+    SeatsOutOfBounds { max_seats: u32 },
+    #[error("Already seated in this game.")]
+    AlreadySeated,
+    #[error("A team game has {seats} seats.")]
+    TeamSeats { seats: u32 },
+    #[error("Not a team game.")]
+    NotATeamGame,
+    #[error("This team is full.")]
+    TeamFull,
+    #[error("No bet given and no preferred bet.")]
+    NoBet,
+    #[error("Odds of {numerator}:{denominator} leave a bet of {bet} without a stake to back it.")]
+    InvalidOdds { numerator: u64, denominator: u64, bet: u64 },
+    #[error("Side bets are closed.")]
+    SideBetsClosed,
+    #[error("Side bet must be positive.")]
+    SideBetNotPositive,
+    #[error("Players cannot side bet on their own game.")]
+    OwnGameSideBet,
+
+    // Players
+    #[error("Player is suspended.")]
+    Suspended,
+    #[error("Account is frozen.")]
+    Frozen,
+    #[error("Nonce {got} was already used, the next one is {expected}.")]
+    StaleNonce { expected: u64, got: u64 },
+    #[error("Command {0} was already used for another action.")]
+    CommandIdReused(String),
+    #[error("Not enough energy.")]
+    NotEnoughEnergy,
+    #[error("Name is empty.")]
+    NameEmpty,
+    #[error("Name is too long.")]
+    NameTooLong,
+    #[error("Name contains invisible characters.")]
+    NameInvisible,
+    #[error("Name is taken by another player.")]
+    NameTaken,
+    #[error("Name is quarantined after an account closure.")]
+    NameQuarantined,
+    #[error("Name is reserved for the house.")]
+    NameReserved,
+    #[error("The house bot only joins games left open for {wait_secs} seconds.")]
+    BotTooEarly { wait_secs: u64 },
+    #[error("Player has open games.")]
+    HasOpenGames,
+    #[error("Player has pending deposits.")]
+    HasPendingDeposits,
+    #[error("Already queued.")]
+    AlreadyQueued,
+    #[error("Not queued.")]
+    NotQueued,
+
+    // Tournaments
+    #[error("Tournament not found.")]
+    TournamentNotFound,
+    #[error("Tournament already started.")]
+    TournamentAlreadyStarted,
+    #[error("Tournament not started.")]
+    TournamentNotStarted,
+    #[error("Tournament is not running.")]
+    TournamentNotRunning,
+    #[error("Tournament registration closed.")]
+    TournamentClosed,
+    #[error("Already registered.")]
+    AlreadyRegistered,
+    #[error("Not registered.")]
+    NotRegistered,
+    #[error("Not enough players.")]
+    NotEnoughPlayers,
+    #[error("Match not found.")]
+    MatchNotFound,
+    #[error("Match already decided.")]
+    MatchDecided,
+
+    // Tokens games can be played in
+    #[error("Token not registered.")]
+    TokenNotRegistered,
+    #[error("Token already registered.")]
+    TokenAlreadyRegistered,
+
+    // Referrals
+    #[error("Cannot refer yourself.")]
+    SelfReferral,
+    #[error("Already referred.")]
+    AlreadyReferred,
+    #[error("Only users who never played can be referred.")]
+    NotNewUser,
+
+    // Administration and bookkeeping
+    #[error("Admin already set.")]
+    AdminAlreadySet,
+    #[error("{account} does not have the {role:?} role.")]
+    MissingRole { account: String, role: Role },
+    #[error("Missing or unknown credential.")]
+    Unauthenticated,
+    #[error("Cannot revoke the last admin.")]
+    LastAdmin,
+    #[error("Deposit already recorded.")]
+    DepositRecorded,
+    #[error("Deposit not found.")]
+    DepositNotFound,
+    #[error("Commitment not found.")]
+    CommitmentNotFound,
+    #[error("Commitment already revealed.")]
+    CommitmentRevealed,
+    #[error("Period already closed.")]
+    PeriodClosed,
+    #[error("Period is not open.")]
+    PeriodNotOpen,
+
+    // Running operations
+    #[error("Reentrancy attack detected.")]
+    Reentrancy,
+    #[error("Operation cancelled.")]
+    Cancelled,
+    #[error("Operation timed out.")]
+    TimedOut,
+    #[error("Tick driver stalled, {backlog} games overdue.")]
+    TickStalled { backlog: usize },
+    #[error("Game engine stopped.")]
+    EngineStopped,
+    #[error("Too many requests, slow down.")]
+    RateLimited,
+    #[error("Invariants violated: {}.", .0.join("; "))]
+    InvariantsViolated(Vec<String>),
+    #[error("Game {0} was archived without its setup and cannot be replayed.")]
+    NotReplayable(u64),
+    #[error("Replay of game {game_id} does not match: {reason}.")]
+    ReplayMismatch { game_id: u64, reason: String },
+    #[error("Event {sequence} cannot be replayed: {reason}.")]
+    EventNotReplayable { sequence: u64, reason: String },
+    #[error("Audit log does not match the events from event {0} on.")]
+    AuditLogBroken(u64),
+
+    // Saved states and config files
+    #[error("Invalid JSON: {0}.")]
+    InvalidJson(String),
+    #[error("Invalid content: {0}.")]
+    InvalidContent(String),
+    #[error("Cannot serialize: {0}.")]
+    Serialize(String),
+    #[error("Unknown fields: {}.", .0.join(", "))]
+    UnknownFields(Vec<String>),
+    #[error("Cannot access {path}: {message}.")]
+    Io { path: String, message: String },
+    #[error("Unsupported state version {0}.")]
+    UnsupportedVersion(u32),
+    #[error("Saved state is corrupt.")]
+    CorruptState,
+    #[error("Storage error: {0}.")]
+    Storage(String),
+}
+
+// What kind of refusal an error is, transports turn it into their own codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    Forbidden,
+    Conflict, // Valid request, but not in the current state of the game or balance
+    Invalid,
+    Unavailable, // Try again later
+    Internal,
+}
+
+impl GameError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            GameError::Token(error) => error.kind(),
+            GameError::GameNotFound
+            | GameError::UserNotFound
+            | GameError::TournamentNotFound
+            | GameError::MatchNotFound
+            | GameError::DepositNotFound
+            | GameError::CommitmentNotFound
+            | GameError::TokenNotRegistered => ErrorKind::NotFound,
+            GameError::MissingRole { .. }
+            | GameError::Unauthenticated
+            | GameError::NotYourGame
+            | GameError::Suspended
+            | GameError::Frozen
+            | GameError::NameReserved => ErrorKind::Forbidden,
+            GameError::GameAlreadyStarted
+            | GameError::AlreadySettled
+            | GameError::Expired
+            | GameError::InsufficientStake
+            | GameError::InsufficientFunds
+            | GameError::NameTaken
+            | GameError::HasOpenGames
+            | GameError::HasPendingDeposits
+            | GameError::TokenAlreadyRegistered
+            | GameError::AlreadyReferred
+            | GameError::NotNewUser
+            | GameError::AlreadySeated
+            | GameError::TeamFull
+            | GameError::BotTooEarly { .. }
+            | GameError::StaleNonce { .. }
+            | GameError::CommandIdReused(_)
+            | GameError::NotReplayable(_)
+            | GameError::EventNotReplayable { .. } => ErrorKind::Conflict,
+            GameError::TickStalled { .. }
+            | GameError::EngineStopped
+            | GameError::RateLimited
+            | GameError::Cancelled
+            | GameError::TimedOut => ErrorKind::Unavailable,
+            GameError::Reentrancy
+            | GameError::InvariantsViolated(_)
+            | GameError::ReplayMismatch { .. }
+            | GameError::AuditLogBroken(_)
+            | GameError::Serialize(_)
+            | GameError::Io { .. }
+            | GameError::CorruptState
+            | GameError::Storage(_)
+            | GameError::UnsupportedVersion(_) => ErrorKind::Internal,
+            _ => ErrorKind::Invalid,
+        }
+    }
+}
+
+#[test]
+fn test_messages_shown_to_players() {
+    assert_eq!(GameError::Expired.to_string(), "Game expired.");
+    assert_eq!(GameError::from(LimitError::ZeroAmount).to_string(), "Amount must be positive.");
+    let missing = GameError::MissingRole { account: "Bob".to_string(), role: Role::Admin };
+    assert_eq!(missing.to_string(), "Bob does not have the Admin role.");
+    let unknown = GameError::UnknownFields(vec!["a".to_string(), "b.c".to_string()]);
+    assert_eq!(unknown.to_string(), "Unknown fields: a, b.c.");
+}
@@ -0,0 +1,71 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::error::GameError;
+use crate::tx::{restore_entries, save_entries};
+
+// Pots of the games that are not settled yet. Bets are deposited here when they are locked in a
+// balance and leave in one piece when the game is settled or called off.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Escrow {
+    pots: BTreeMap<u64, u64>,
+}
+
+impl Escrow {
+    pub fn new() -> Self {
+        Escrow::default()
+    }
+
+    pub fn deposit(&mut self, game_id: u64, amount: u64) -> Result<(), GameError> {
+        let pot = self.pots.entry(game_id).or_insert(0);
+        *pot = pot.checked_add(amount).ok_or(GameError::Overflow)?;
+        Ok(())
+    }
+
+    // Empties the pot of the game, returns what it held
+    pub fn release(&mut self, game_id: u64) -> u64 {
+        self.pots.remove(&game_id).unwrap_or(0)
+    }
+
+    // Moves a whole pot to another game, used when a drawn pot is carried into a rematch
+    pub fn carry(&mut self, from: u64, to: u64) -> Result<(), GameError> {
+        let amount = self.release(from);
+        self.deposit(to, amount)
+    }
+
+    pub fn pot(&self, game_id: u64) -> u64 {
+        self.pots.get(&game_id).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u128 {
+        self.pots.values().map(|&pot| pot as u128).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.pots.clear();
+    }
+
+    // The pots of `games` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, games: &[u64]) -> Escrow {
+        Escrow { pots: save_entries(&self.pots, games) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Escrow, games: &[u64]) {
+        restore_entries(&mut self.pots, saved.pots, games);
+    }
+}
+
+#[test]
+fn test_pots_move_as_a_whole() {
+    let mut escrow = Escrow::new();
+    escrow.deposit(1, 10).unwrap();
+    escrow.deposit(1, 10).unwrap();
+    escrow.deposit(2, u64::MAX).unwrap();
+    assert!(escrow.deposit(2, 1).is_err());
+    assert_eq!(escrow.total(), 20 + u64::MAX as u128);
+
+    escrow.carry(1, 3).unwrap();
+    assert_eq!((escrow.pot(1), escrow.pot(3)), (0, 20));
+    assert_eq!(escrow.release(3), 20);
+    assert_eq!(escrow.release(3), 0);
+}
@@ -0,0 +1,289 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::audit::AuditLog;
+use crate::deck::Card;
+use crate::handicap::PayoutOdds;
+use crate::seats;
+use crate::sidebets::Side;
+use crate::teams::{self, Team};
+
+// Everything that changes the state of the protocol is reported as one of these
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    Staked { user: String, amount: u64 },
+    GameStarted {
+        game_id: u64,
+        creator: String,
+        bet: u64,
+        expires_at: u64,
+        #[serde(default, skip_serializing_if = "PayoutOdds::is_even")]
+        odds: PayoutOdds, // Left out of even games, so their events read as before odds existed
+        #[serde(default = "seats::heads_up", skip_serializing_if = "seats::is_heads_up")]
+        seats: u32,
+        #[serde(default, skip_serializing_if = "teams::is_solo")]
+        teams: bool,
+    },
+    GameJoined { game_id: u64, opponent: String },
+    CardsRevealed { game_id: u64, creator_hand: Vec<Card>, opponent_hand: Vec<Card> },
+    Settled { game_id: u64, winner: Option<String>, payout: u64 }, // winner is None on a draw or a shared pot
+    TimeoutClaimed { game_id: u64, winner: String, staller: String },
+    PotCarried { game_id: u64, rematch_id: u64, pot: u64 }, // A drawn pot moved into a rematch
+    Withdrawn { user: String, amount: u64 },
+    Expired { game_id: u64, creator: String, opponent: Option<String> },
+    JackpotFunded { funder: String, amount: u64 },
+    BonusPaid { game_id: u64, player: String, amount: u64 },
+    JackpotContributed { game_id: u64, amount: u64 }, // Slice of the pot fed into the progressive jackpot
+    JackpotWon { game_id: u64, player: String, amount: u64 },
+    TournamentJoined { tournament_id: u64, player: String },
+    TournamentWaitlisted { tournament_id: u64, player: String },
+    TournamentPromoted { tournament_id: u64, player: String }, // Moved from the waitlist into a freed seat
+    TournamentLeft { tournament_id: u64, player: String, refund: u64 }, // Withdrawn, or still waitlisted at the start
+    TournamentFinished { tournament_id: u64, winner: String, prize: u64 },
+    FeeCollected { game_id: Option<u64>, amount: u64 }, // game_id is None for tournament rake
+    TreasuryWithdrawn { admin: String, to: String, amount: u64 },
+    BotFunded { bot: String, amount: u64 }, // Moved from the treasury to the house bot's stake
+    PlayerSlashed { player: String, game_id: Option<u64>, amount: u64, suspended_until: u64 },
+    ConfigApplied { version: u64, hash: String, applied_by: String },
+    DepositPending { tx_id: String, user: String, amount: u64 },
+    DepositCredited { tx_id: String, user: String, amount: u64 },
+    MatchQueued { player: String, bet: u64 },
+    SideBetPlaced { game_id: u64, backer: String, side: Side, amount: u64 },
+    SideBetPaid { game_id: u64, backer: String, amount: u64 }, // Winnings, or the refund on a draw
+    AccountClosed { player: String, swept: u64, name_free_at: u64 }, // swept is the balance paid out
+    TickStalled { last_success: Option<u64>, backlog: u64 }, // Critical, restarting the tick driver did not help
+    SlowOperation { operation: String, micros: u64, trace: String }, // Took at least the configured threshold
+    DepositReversed { tx_id: String, user: String, amount: u64, clawed_back: u64 }, // clawed_back is 0 if it was never credited
+    StateReset { wiped: u64 }, // initialize dropped every game and stake, wiped is what the players held
+    Token(TokenEvent), // Taken from the token and logged here, so one log shows both
+    AccountFrozen { account: String, by: String },
+    AccountUnfrozen { account: String, by: String },
+    RewardsFunded { admin: String, amount: u64, duration_secs: u64 }, // Moved from the treasury, paid out to idle stakes
+    RewardsClaimed { user: String, amount: u64 },
+    ReferralRegistered { user: String, referrer: String },
+    ReferralPaid { referrer: String, referee: String, game_id: u64, amount: u64 }, // Out of the fee of the referee's game
+    SeatsRevealed { game_id: u64, hands: Vec<(String, Vec<Card>)> }, // Every seat of a game of more than two, in seating order
+    PotShared { game_id: u64, winners: Vec<String>, payout: u64 }, // payout is each winner's share, before Settled
+    TeamJoined { game_id: u64, player: String, team: Team }, // After the GameStarted or GameJoined of the player
+}
+
+// What a token operation did, as emitted by the token itself. A burn is a transfer to nobody.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TokenEvent {
+    Transfer { from: String, to: Option<String>, amount: u64 },
+    Approval { owner: String, spender: String, amount: u64 }, // amount is what the spender may still move
+    Mint { to: String, amount: u64, wei_paid: u64 },
+    Frozen { account: String },
+    Unfrozen { account: String },
+}
+
+impl GameEvent {
+    // Whether players watching `game_id` need to see this event, a carried pot also concerns the rematch
+    pub fn is_about_game(&self, game_id: u64) -> bool {
+        match self {
+            GameEvent::PotCarried { game_id: id, rematch_id, .. } => *id == game_id || *rematch_id == game_id,
+            GameEvent::GameStarted { game_id: id, .. }
+            | GameEvent::GameJoined { game_id: id, .. }
+            | GameEvent::CardsRevealed { game_id: id, .. }
+            | GameEvent::SeatsRevealed { game_id: id, .. }
+            | GameEvent::PotShared { game_id: id, .. }
+            | GameEvent::TeamJoined { game_id: id, .. }
+            | GameEvent::Settled { game_id: id, .. }
+            | GameEvent::TimeoutClaimed { game_id: id, .. }
+            | GameEvent::Expired { game_id: id, .. }
+            | GameEvent::BonusPaid { game_id: id, .. }
+            | GameEvent::JackpotContributed { game_id: id, .. }
+            | GameEvent::JackpotWon { game_id: id, .. }
+            | GameEvent::SideBetPlaced { game_id: id, .. }
+            | GameEvent::SideBetPaid { game_id: id, .. } => *id == game_id,
+            GameEvent::FeeCollected { game_id: id, .. } | GameEvent::PlayerSlashed { game_id: id, .. } => *id == Some(game_id),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LoggedEvent {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub event: GameEvent,
+}
+
+impl LoggedEvent {
+    // The same event with its cards left out, for spectators of a game still running
+    pub fn redacted(&self) -> LoggedEvent {
+        let event = match &self.event {
+            GameEvent::CardsRevealed { game_id, .. } => {
+                GameEvent::CardsRevealed { game_id: *game_id, creator_hand: Vec::new(), opponent_hand: Vec::new() }
+            }
+            GameEvent::SeatsRevealed { game_id, hands } => {
+                GameEvent::SeatsRevealed { game_id: *game_id, hands: hands.iter().map(|(player, _)| (player.clone(), Vec::new())).collect() }
+            }
+            event => event.clone(),
+        };
+        LoggedEvent { event, ..self.clone() }
+    }
+}
+
+pub type SubscriberId = u64;
+
+type Subscriber = Box<dyn Fn(&LoggedEvent) + Send + Sync>;
+
+// Where a transaction started in the log, see EventLog::hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hold {
+    len: usize,
+    outermost: bool, // Only the outermost transaction passes its events on to the subscribers
+}
+
+// Append-only log of every emitted event, chained by the audit log. Subscribers are called synchronously
+// on emit and are not persisted, they have to register again after a state is loaded.
+#[derive(Serialize, Deserialize, Default)]
+pub struct EventLog {
+    entries: Vec<LoggedEvent>,
+    #[serde(default)]
+    audit: AuditLog,
+    #[serde(skip)]
+    subscribers: Vec<(SubscriberId, Subscriber)>,
+    #[serde(skip)]
+    next_subscriber: SubscriberId,
+    #[serde(skip)]
+    held: bool, // Events wait for the running transaction before subscribers see them, see hold
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn emit(&mut self, timestamp: u64, event: GameEvent) {
+        let logged = LoggedEvent {
+            sequence: self.entries.len() as u64,
+            timestamp,
+            event,
+        };
+        self.append(logged);
+    }
+
+    // Logs an event as it was logged before, its sequence has to be the next one. Used to replay a log.
+    pub fn append(&mut self, logged: LoggedEvent) {
+        debug_assert_eq!(logged.sequence, self.entries.len() as u64, "Events are appended in sequence.");
+        tracing::debug!(sequence = logged.sequence, timestamp = logged.timestamp, event = ?logged.event, "event logged");
+        if !self.held {
+            for (_, subscriber) in &self.subscribers {
+                subscriber(&logged);
+            }
+        }
+        self.audit.append(&logged);
+        self.entries.push(logged);
+    }
+
+    pub fn subscribe<F>(&mut self, callback: F) -> SubscriberId
+    where
+        F: Fn(&LoggedEvent) + Send + Sync + 'static,
+    {
+        let id = self.next_subscriber;
+        self.next_subscriber += 1;
+        self.subscribers.push((id, Box::new(callback)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|(subscriber_id, _)| *subscriber_id != id);
+        self.subscribers.len() != before
+    }
+
+    pub fn entries(&self) -> &[LoggedEvent] {
+        &self.entries
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    // Replaces the log with entries restored from a store, subscribers stay registered. The store keeps
+    // no hashes, the chain starts over from the restored entries.
+    pub fn restore(&mut self, entries: Vec<LoggedEvent>) {
+        self.audit = AuditLog::rebuild(&entries);
+        self.entries = entries;
+    }
+
+    // Starts a transaction, events emitted until it is released are logged but subscribers only see them
+    // once it is. Transactions nest, the events of an inner one wait for the outermost.
+    pub fn hold(&mut self) -> Hold {
+        let hold = Hold { len: self.entries.len(), outermost: !self.held };
+        self.held = true;
+        hold
+    }
+
+    // Commits the transaction of `hold`, the outermost one hands every held event to the subscribers
+    pub fn release(&mut self, hold: Hold) {
+        if hold.outermost {
+            self.held = false;
+            for logged in &self.entries[hold.len..] {
+                for (_, subscriber) in &self.subscribers {
+                    subscriber(logged);
+                }
+            }
+        }
+    }
+
+    // Rolls the transaction of `hold` back, its events are dropped from the log and nobody saw them
+    pub fn discard(&mut self, hold: Hold) {
+        self.entries.truncate(hold.len);
+        self.audit.truncate(hold.len);
+        if hold.outermost {
+            self.held = false;
+        }
+    }
+
+    // Events with a sequence number greater or equal than `sequence`, used to catch up after a restart
+    pub fn since(&self, sequence: u64) -> &[LoggedEvent] {
+        let start = (sequence as usize).min(self.entries.len());
+        &self.entries[start..]
+    }
+}
+
+// Subscribers belong to the running process, a cloned log starts without them
+impl Clone for EventLog {
+    fn clone(&self) -> Self {
+        EventLog {
+            entries: self.entries.clone(),
+            audit: self.audit.clone(),
+            subscribers: Vec::new(),
+            next_subscriber: 0,
+            held: false,
+        }
+    }
+}
+
+impl fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventLog")
+            .field("entries", &self.entries)
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
+
+#[test]
+fn test_event_log_subscribe_and_unsubscribe() {
+    use std::sync::{Arc, Mutex};
+
+    let mut log = EventLog::new();
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let sink = Arc::clone(&received);
+    let id = log.subscribe(move |logged| sink.lock().unwrap().push(logged.sequence));
+
+    log.emit(1, GameEvent::Staked { user: "Alice".to_string(), amount: 10 });
+    assert!(log.unsubscribe(id));
+    log.emit(2, GameEvent::Withdrawn { user: "Alice".to_string(), amount: 10 });
+
+    // The log keeps everything, the subscriber only saw what happened while registered
+    assert_eq!(log.entries().len(), 2);
+    assert_eq!(log.since(1)[0].timestamp, 2);
+    assert_eq!(*received.lock().unwrap(), vec![0]);
+    assert!(!log.unsubscribe(id));
+}
@@ -0,0 +1,253 @@
+use serde::{Serialize, Deserialize};
+
+use crate::amount::Amount;
+use crate::clock::{ManualClock, SharedClock};
+use crate::error::GameError;
+use crate::events::{GameEvent, LoggedEvent};
+use crate::rules::{GameMode, Round};
+use crate::seats::HEADS_UP;
+use crate::strict::ParseMode;
+use crate::{Game, GameState};
+
+// The whole state as of the first `sequence` events, as export_state writes it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub sequence: u64,
+    pub state: String,
+}
+
+// Takes a snapshot every `every` events, recovery replays the events logged after the latest one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshots {
+    every: u64,
+    latest: Option<Snapshot>,
+}
+
+impl Snapshots {
+    pub fn new(every: u64) -> Self {
+        Snapshots { every: every.max(1), latest: None }
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.latest.as_ref()
+    }
+
+    // Called after every action, snapshots the state once `every` events were logged since the last one.
+    // Returns whether it took one.
+    pub fn observe(&mut self, game_state: &GameState) -> Result<bool, GameError> {
+        let since = self.latest.as_ref().map_or(0, |snapshot| snapshot.sequence);
+        if (game_state.events().len() as u64) < since + self.every {
+            return Ok(false);
+        }
+        self.latest = Some(game_state.take_snapshot()?);
+        Ok(true)
+    }
+
+    // The state at the end of `events`, the whole log as it was saved
+    pub fn recover(&self, events: &[LoggedEvent]) -> Result<GameState, GameError> {
+        match &self.latest {
+            Some(snapshot) => GameState::recover(snapshot, events),
+            None => GameState::replay_from_events(events.iter().cloned()),
+        }
+    }
+}
+
+impl GameState {
+    pub fn take_snapshot(&self) -> Result<Snapshot, GameError> {
+        Ok(Snapshot { sequence: self.events.entries().len() as u64, state: self.export_state()? })
+    }
+
+    // Loads `snapshot` and replays the events of `events` it does not hold yet
+    pub fn recover(snapshot: &Snapshot, events: &[LoggedEvent]) -> Result<GameState, GameError> {
+        let game_state = GameState::import_state(&snapshot.state, ParseMode::Strict)?.value;
+        let after = events.iter().skip_while(|logged| logged.sequence < snapshot.sequence).cloned();
+        game_state.apply_events(after)
+    }
+
+    // Rebuilds a state from its event log alone, each event changes the state as the action that logged it
+    // did, at the time it was logged. The log tells who holds what: stakes, pots, seats, fees, the jackpot
+    // and frozen accounts come back as they were, and the log and its audit chain are the same. Seeds are
+    // secret until a game settles and are not logged, so a game still running gets a new one, and what is
+    // derived from settled games (history, stats, ratings, accounting) is not rebuilt. Events of actions
+    // the log does not describe fully, such as config changes, tournaments or deposits, are refused with
+    // EventNotReplayable: replay those from a snapshot taken after them with recover.
+    pub fn replay_from_events(events: impl IntoIterator<Item = LoggedEvent>) -> Result<GameState, GameError> {
+        GameState::new().apply_events(events)
+    }
+
+    fn apply_events(mut self, events: impl IntoIterator<Item = LoggedEvent>) -> Result<GameState, GameError> {
+        let clock = ManualClock::new(self.clock.now());
+        let live = std::mem::replace(&mut self.clock, SharedClock::new(clock.clone()));
+        for logged in events {
+            clock.set(logged.timestamp);
+            self.apply_event(&logged)?;
+            self.events.append(logged);
+        }
+        self.clock = live;
+        Ok(self)
+    }
+
+    fn apply_event(&mut self, logged: &LoggedEvent) -> Result<(), GameError> {
+        let refuse = |reason: &str| GameError::EventNotReplayable { sequence: logged.sequence, reason: reason.to_string() };
+        let expected = self.events.entries().len() as u64;
+        if logged.sequence != expected {
+            return Err(refuse(&format!("event {} is the next one", expected)));
+        }
+
+        match &logged.event {
+            GameEvent::Staked { user, amount } => {
+                self.names.claim(user, logged.timestamp)?;
+                self.credit(user, *amount)?;
+            }
+            GameEvent::Withdrawn { user, amount } => self.take_available(user, *amount)?,
+            GameEvent::JackpotFunded { funder, amount } => {
+                self.take_available(funder, *amount)?;
+                self.jackpot.fund(*amount)?;
+            }
+            GameEvent::GameStarted { game_id, creator, bet, expires_at, odds, seats, .. } => {
+                if *game_id != self.next_game_id {
+                    return Err(refuse("game ids are not consecutive"));
+                }
+                if *seats != HEADS_UP {
+                    return Err(refuse("games of more than two seats are not replayed"));
+                }
+                let mut game = self.new_game(creator.clone(), *bet, GameMode::default());
+                game.timeout_secs = expires_at.saturating_sub(logged.timestamp);
+                game.odds = *odds;
+                self.seat(&mut game, creator)?;
+                self.games.insert(game.id, game);
+            }
+            GameEvent::GameJoined { game_id, opponent } => {
+                let mut game = self.games.remove(game_id).ok_or_else(|| refuse("the game was not started"))?;
+                let config = self.config_log.config(game.config_version).unwrap_or(&self.config);
+                game.opponent_hand = game.deck.deal(game.mode.rules(config).hand_size())?;
+                game.opponent = Some(opponent.clone());
+                self.seat(&mut game, opponent)?;
+                self.games.insert(game.id, game);
+            }
+            GameEvent::CardsRevealed { game_id, creator_hand, opponent_hand } => {
+                let game = self.games.get_mut(game_id).ok_or_else(|| refuse("the game is not running"))?;
+                let config = self.config_log.config(game.config_version).unwrap_or(&self.config);
+                let outcome = game.mode.rules(config).winner(creator_hand, opponent_hand);
+                game.creator_hand = creator_hand.clone();
+                game.opponent_hand = opponent_hand.clone();
+                game.rounds.push(Round { creator_hand: creator_hand.clone(), opponent_hand: opponent_hand.clone(), outcome });
+                game.reveal_requests.clear();
+            }
+            GameEvent::TimeoutClaimed { game_id, .. } => {
+                self.games.get_mut(game_id).ok_or_else(|| refuse("the game is not running"))?.is_settled = true;
+            }
+            // Both bets leave the pot, the winner is paid what is left of it after the fee and the jackpot's
+            // share. A drawn pot carried into a rematch is locked again by the rematch's own events.
+            GameEvent::Settled { game_id, winner, payout } => {
+                let game = self.games.remove(game_id).ok_or_else(|| refuse("the game is not running"))?;
+                for player in std::iter::once(&game.creator).chain(&game.opponent) {
+                    let balance = self.balances.entry(player.clone()).or_default();
+                    match winner {
+                        Some(_) => balance.release(game.stake(player))?,
+                        None => balance.unlock(game.stake(player))?,
+                    }
+                    self.sync_rewards(player);
+                }
+                if let Some(winner) = winner {
+                    self.credit(winner, *payout)?;
+                }
+                self.escrow.release(*game_id);
+            }
+            // Also logged when a reveal comes too late, the game is then called off here already and the
+            // Expired of the tick that calls it off finds nothing left to do
+            GameEvent::Expired { game_id, .. } => {
+                if let Some(game) = self.games.remove(game_id) {
+                    for player in std::iter::once(&game.creator).chain(&game.opponent) {
+                        self.balances.entry(player.clone()).or_default().unlock(game.stake(player))?;
+                        self.sync_rewards(player);
+                    }
+                    self.escrow.release(*game_id);
+                }
+            }
+            GameEvent::FeeCollected { amount, .. } => self.treasury.collect(*amount),
+            GameEvent::JackpotContributed { amount, .. } => self.jackpot.fund(*amount)?,
+            GameEvent::BonusPaid { player, amount, .. } | GameEvent::JackpotWon { player, amount, .. } => {
+                if self.jackpot.pay(*amount) != *amount {
+                    return Err(refuse("the jackpot holds less than was paid"));
+                }
+                self.credit(player, *amount)?;
+            }
+            GameEvent::TreasuryWithdrawn { to, amount, .. } | GameEvent::BotFunded { bot: to, amount } => {
+                self.treasury.withdraw(*amount)?;
+                self.credit(to, *amount)?;
+            }
+            GameEvent::AccountFrozen { account, .. } => {
+                self.frozen.insert(account.clone());
+            }
+            GameEvent::AccountUnfrozen { account, .. } => {
+                self.frozen.remove(account);
+            }
+            GameEvent::PotCarried { .. } | GameEvent::SlowOperation { .. } | GameEvent::TickStalled { .. } | GameEvent::Token(_) => {}
+            _ => return Err(refuse("the log does not hold everything it changed, recover from a snapshot taken after it")),
+        }
+        Ok(())
+    }
+
+    // Locks the stake of `player` and puts it into the pot of `game`
+    fn seat(&mut self, game: &mut Game, player: &str) -> Result<(), GameError> {
+        let stake = game.stake(player);
+        self.balances.entry(player.to_string()).or_default().lock(stake)?;
+        self.sync_rewards(player);
+        self.escrow.deposit(game.id, stake)?;
+        game.pot = game.pot.checked_add(Amount::new(stake)).ok_or(GameError::Overflow)?;
+        Ok(())
+    }
+
+    fn take_available(&mut self, user: &str, amount: u64) -> Result<(), GameError> {
+        self.balances.get_mut(user).ok_or(GameError::UserNotFound)?.debit(amount)?;
+        self.sync_rewards(user);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_state_is_rebuilt_from_its_log() {
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    for player in ["Alice", "Bob", "Carol"] {
+        game_state.stake_tokens(&caller(player), 100).unwrap();
+    }
+    let played = game_state.start_game(&caller("Alice"), 30).unwrap();
+    game_state.join_game(&caller("Bob"), played).unwrap();
+    while game_state.game_summary(played).is_some() {
+        game_state.play_round(played).unwrap();
+    }
+    let mut snapshots = Snapshots::new(5);
+    assert!(snapshots.observe(&game_state).unwrap());
+    assert!(!snapshots.observe(&game_state).unwrap());
+
+    let running = game_state.start_game(&caller("Carol"), 20).unwrap();
+    game_state.join_game(&caller("Alice"), running).unwrap();
+    game_state.start_game(&caller("Bob"), 10).unwrap();
+    game_state.withdraw_stake(&caller("Carol"), 5).unwrap();
+
+    let same = |rebuilt: &GameState| {
+        for player in ["Alice", "Bob", "Carol"] {
+            assert_eq!(rebuilt.stake_of(player), game_state.stake_of(player));
+        }
+        assert_eq!(rebuilt.escrow.total(), game_state.escrow.total());
+        assert_eq!(rebuilt.treasury_balance(), game_state.treasury_balance());
+        assert_eq!(rebuilt.game_summary(running), game_state.game_summary(running));
+        assert_eq!(rebuilt.audit_head(), game_state.audit_head());
+        assert_eq!(rebuilt.check_invariants(), Ok(()));
+    };
+    same(&GameState::replay_from_events(game_state.events().iter().cloned()).unwrap());
+    let recovered = snapshots.recover(game_state.events()).unwrap();
+    same(&recovered);
+    assert_eq!(recovered.history().len(), 1);
+
+    // A gap in the log, or an event that only a snapshot can restore
+    let mut log = game_state.events().to_vec();
+    log.remove(2);
+    assert!(matches!(GameState::replay_from_events(log), Err(GameError::EventNotReplayable { sequence: 3, .. })));
+    game_state.init_admin(crate::account_id::account("House")).unwrap();
+    game_state.apply_config(&caller("House"), crate::config::GameConfig::default()).unwrap();
+    assert!(matches!(GameState::replay_from_events(game_state.events().to_vec()), Err(GameError::EventNotReplayable { .. })));
+}
@@ -0,0 +1,111 @@
+#![no_main]
+
+// Arbitrary bytes played as game and token operations: nothing may panic, and after every operation the
+// game's invariants hold, the vault backs every stake and the token holds exactly what was minted. Run with `cargo fuzz run state_machine`.
+
+use assessment_rust::clock::{ManualClock, SharedClock};
+use assessment_rust::invariants::{Action, ADMIN, PLAYERS};
+use assessment_rust::sidebets::Side;
+use assessment_rust::token::WEI_PER_ETH;
+use assessment_rust::vault::VAULT;
+use assessment_rust::{AccountId, Context, ERC20Token, GameState};
+use libfuzzer_sys::fuzz_target;
+
+// Reads operations off the input, running out of bytes reads zeros
+struct Input<'a>(&'a [u8]);
+
+impl Input<'_> {
+    fn byte(&mut self) -> u8 {
+        match self.0.split_first() {
+            Some((&byte, rest)) => {
+                self.0 = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    fn index(&mut self) -> usize {
+        self.byte() as usize
+    }
+
+    // Small amounts take one byte so games get played, 255 reads a whole u64 to reach the limits
+    fn amount(&mut self) -> u64 {
+        match self.byte() {
+            255 => u64::from_le_bytes(std::array::from_fn(|_| self.byte())),
+            byte => byte as u64,
+        }
+    }
+
+    fn side(&mut self) -> Side {
+        if self.byte() & 1 == 0 { Side::Creator } else { Side::Opponent }
+    }
+}
+
+#[derive(Debug)]
+enum Operation {
+    Game(Action),
+    Mint { player: usize, amount: u64, wei_paid: u64 },
+    Transfer { from: usize, to: usize, amount: u64 },
+}
+
+impl Operation {
+    fn decode(input: &mut Input) -> Operation {
+        match input.byte() % 15 {
+            0 => Operation::Game(Action::Stake { player: input.index(), amount: input.amount() }),
+            1 => Operation::Game(Action::Withdraw { player: input.index(), amount: input.amount() }),
+            2 => Operation::Game(Action::StartGame { player: input.index(), bet: input.amount() }),
+            3 => Operation::Game(Action::JoinGame { game: input.index(), player: input.index() }),
+            4 => Operation::Game(Action::Reveal { game: input.index() }),
+            5 => Operation::Game(Action::SideBet {
+                game: input.index(),
+                player: input.index(),
+                side: input.side(),
+                amount: input.amount(),
+            }),
+            6 => Operation::Game(Action::FundJackpot { player: input.index(), amount: input.amount() }),
+            7 => Operation::Game(Action::Slash { player: input.index() }),
+            8 => Operation::Game(Action::CloseAccount { player: input.index() }),
+            9 => Operation::Game(Action::AdvanceTime { secs: input.amount() }),
+            10 => Operation::Game(Action::Tick),
+            11 => Operation::Game(Action::FundRewards { amount: input.amount(), duration_secs: input.amount() }),
+            12 => Operation::Game(Action::ClaimRewards { player: input.index() }),
+            13 => Operation::Mint { player: input.index(), amount: input.amount(), wei_paid: input.byte() as u64 * WEI_PER_ETH / 100 },
+            _ => Operation::Transfer { from: input.index(), to: input.index(), amount: input.amount() },
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let clock = ManualClock::new(1_000);
+    let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+    let admin = AccountId::new(ADMIN).unwrap();
+    game_state.init_admin(admin.clone()).unwrap();
+    let mut token = ERC20Token::new(admin, "Game Token", "GAME", 0, None);
+    let mut minted: u128 = 0;
+
+    let player = |index: usize| AccountId::new(PLAYERS[index % PLAYERS.len()]).unwrap();
+    let mut input = Input(data);
+    while !input.0.is_empty() {
+        let operation = Operation::decode(&mut input);
+        match &operation {
+            Operation::Game(action) => {
+                let _ = action.apply(&mut game_state, &mut token, &clock);
+            }
+            Operation::Mint { player: p, amount, wei_paid } => {
+                if token.mint(&Context::new(player(*p)), *amount, *wei_paid).is_ok() {
+                    minted += *amount as u128;
+                }
+            }
+            Operation::Transfer { from, to, amount } => {
+                let _ = token.transfer(&Context::new(player(*from)), player(*to), *amount);
+            }
+        }
+
+        if let Err(error) = game_state.check_invariants().and_then(|()| game_state.check_vault(&token)) {
+            panic!("after {:?}: {}", operation, error);
+        }
+        let supply: u128 = PLAYERS.iter().chain([&VAULT]).map(|name| token.get_balance(name).get() as u128).sum();
+        assert_eq!((supply, token.total_supply().get() as u128), (minted, minted), "after {:?}", operation);
+    }
+});
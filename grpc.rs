@@ -0,0 +1,286 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tonic::{Code, Request, Response, Status};
+
+use crate::account_id::{AccountId, AccountIdError};
+use crate::auth::Credentials;
+use crate::context::Context;
+use crate::error::{ErrorKind, GameError};
+use crate::lobby::{GameSummary, LobbyFilter};
+use crate::token::{ERC20Token, TokenError};
+use crate::GameState;
+
+// Generated by build.rs from proto/game.proto
+pub mod proto {
+    tonic::include_proto!("game.v1");
+}
+
+use proto::game_service_server::{GameService, GameServiceServer};
+use proto::token_service_server::{TokenService, TokenServiceServer};
+
+fn code(kind: ErrorKind) -> Code {
+    match kind {
+        ErrorKind::NotFound => Code::NotFound,
+        ErrorKind::Forbidden => Code::PermissionDenied,
+        ErrorKind::Conflict => Code::FailedPrecondition,
+        ErrorKind::Invalid => Code::InvalidArgument,
+        ErrorKind::Unavailable => Code::Unavailable,
+        ErrorKind::Internal => Code::Internal,
+    }
+}
+
+fn game_status(error: GameError) -> Status {
+    match error {
+        GameError::Unauthenticated => Status::unauthenticated(error.to_string()),
+        error => Status::new(code(error.kind()), error.to_string()),
+    }
+}
+
+fn token_status(error: TokenError) -> Status {
+    Status::new(code(error.kind()), error.to_string())
+}
+
+// Messages carry plain strings, checked here before they reach the game or the token
+fn invalid_account(error: AccountIdError) -> Status {
+    game_status(error.into())
+}
+
+impl From<GameSummary> for proto::GameSummary {
+    fn from(summary: GameSummary) -> Self {
+        proto::GameSummary {
+            game_id: summary.game_id,
+            creator: summary.creator,
+            opponent: summary.opponent,
+            bet: summary.bet,
+            pot: summary.pot,
+            created_at: summary.created_at,
+            expires_at: summary.expires_at,
+        }
+    }
+}
+
+// gRPC front of the engine (grpc feature), serving both services over the same state as the HTTP server.
+// Mutations are saved to the files given, if any. Actions are taken for the account of the bearer
+// credential of the call, nobody can act without one.
+#[derive(Clone)]
+pub struct Grpc {
+    pub game_state: Arc<RwLock<GameState>>,
+    pub token: Arc<RwLock<ERC20Token>>,
+    credentials: Arc<Credentials>,
+    save_game_to: Option<PathBuf>,
+    save_token_to: Option<PathBuf>,
+}
+
+impl Grpc {
+    pub fn new(game_state: Arc<RwLock<GameState>>, token: Arc<RwLock<ERC20Token>>) -> Self {
+        Grpc { game_state, token, credentials: Arc::new(Credentials::new()), save_game_to: None, save_token_to: None }
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    // The account of the `authorization` metadata of the call
+    fn caller<T>(&self, request: &Request<T>) -> Result<AccountId, GameError> {
+        let header = request.metadata().get("authorization").and_then(|value| value.to_str().ok()).unwrap_or_default();
+        Ok(self.credentials.authenticate(Credentials::bearer(header)?)?.clone())
+    }
+
+    pub fn saving_to(mut self, game_state: Option<PathBuf>, token: Option<PathBuf>) -> Self {
+        self.save_game_to = game_state;
+        self.save_token_to = token;
+        self
+    }
+
+    async fn mutate<T>(&self, action: impl FnOnce(&mut GameState) -> Result<T, GameError>) -> Result<T, Status> {
+        let mut game_state = self.game_state.write().await;
+        let result = action(&mut game_state).map_err(game_status)?;
+        if let Some(path) = &self.save_game_to {
+            game_state.save(path).map_err(game_status)?;
+        }
+        Ok(result)
+    }
+
+    // Stakes move tokens in and out of the vault, the game and the token are changed and saved together
+    async fn mutate_staked<T>(&self, action: impl FnOnce(&mut GameState, &mut ERC20Token) -> Result<T, GameError>) -> Result<T, Status> {
+        let mut game_state = self.game_state.write().await;
+        let mut token = self.token.write().await;
+        let result = action(&mut game_state, &mut token);
+        game_state.log_token_events(token.take_events());
+        let result = result.map_err(game_status)?;
+        if let Some(path) = &self.save_token_to {
+            token.export(path).map_err(token_status)?;
+        }
+        if let Some(path) = &self.save_game_to {
+            game_state.save(path).map_err(game_status)?;
+        }
+        Ok(result)
+    }
+
+    // What the token emitted goes to the game's log once the token is saved, subscribers of the game see it
+    async fn mutate_token(&self, action: impl FnOnce(&mut ERC20Token) -> Result<(), TokenError>) -> Result<(), Status> {
+        let mut token = self.token.write().await;
+        action(&mut token).map_err(token_status)?;
+        if let Some(path) = &self.save_token_to {
+            token.export(path).map_err(token_status)?;
+        }
+        let events = token.take_events();
+        drop(token);
+        self.mutate(|game_state| {
+            game_state.log_token_events(events);
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub async fn serve(addr: &str, grpc: Grpc) -> Result<(), GameError> {
+    let invalid = |message: String| GameError::Io { path: addr.to_string(), message };
+    let socket = addr.parse().map_err(|e: std::net::AddrParseError| invalid(e.to_string()))?;
+    tracing::info!(addr, "Serving gRPC.");
+    tonic::transport::Server::builder()
+        .add_service(GameServiceServer::new(grpc.clone()))
+        .add_service(TokenServiceServer::new(grpc))
+        .serve(socket)
+        .await
+        .map_err(|e| invalid(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl GameService for Grpc {
+    async fn stake(&self, request: Request<proto::Amount>) -> Result<Response<proto::Empty>, Status> {
+        let ctx = Context::new(self.caller(&request).map_err(game_status)?);
+        let request = request.into_inner();
+        self.mutate_staked(|game_state, token| game_state.deposit(&ctx, token, request.amount)).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn withdraw(&self, request: Request<proto::Amount>) -> Result<Response<proto::Empty>, Status> {
+        let ctx = Context::new(self.caller(&request).map_err(game_status)?);
+        let request = request.into_inner();
+        self.mutate_staked(|game_state, token| game_state.withdraw(&ctx, token, request.amount)).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn start_game(&self, request: Request<proto::StartGameRequest>) -> Result<Response<proto::GameId>, Status> {
+        let creator = self.caller(&request).map_err(game_status)?;
+        let bet = request.into_inner().bet;
+        let game_id = self.mutate(|game_state| game_state.start_game(&Context::new(creator), bet)).await?;
+        Ok(Response::new(proto::GameId { game_id }))
+    }
+
+    async fn join_game(&self, request: Request<proto::JoinGameRequest>) -> Result<Response<proto::Empty>, Status> {
+        let opponent = self.caller(&request).map_err(game_status)?;
+        let game_id = request.into_inner().game_id;
+        self.mutate(|game_state| game_state.join_game(&Context::new(opponent), game_id)).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn reveal_cards(&self, request: Request<proto::GameId>) -> Result<Response<proto::RevealReply>, Status> {
+        let player = self.caller(&request).map_err(game_status)?;
+        let game_id = request.into_inner().game_id;
+        let (revealed, summary) = self
+            .mutate(|game_state| {
+                let revealed = game_state.contribute_reveal(&Context::new(player), game_id)?;
+                Ok((revealed, game_state.game_summary(game_id)))
+            })
+            .await?;
+        Ok(Response::new(proto::RevealReply { settled: summary.is_none(), game: summary.map(Into::into), revealed }))
+    }
+
+    async fn stake_of(&self, request: Request<proto::User>) -> Result<Response<proto::Stake>, Status> {
+        let stake = self.game_state.read().await.stake_of(&request.into_inner().user);
+        Ok(Response::new(proto::Stake { available: stake.available.get(), locked: stake.locked.get() }))
+    }
+
+    async fn get_game(&self, request: Request<proto::GameId>) -> Result<Response<proto::GameSummary>, Status> {
+        let game_id = request.into_inner().game_id;
+        let summary = self.game_state.read().await.game_summary(game_id).ok_or(GameError::GameNotFound).map_err(game_status)?;
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn list_open_games(&self, request: Request<proto::OpenGamesRequest>) -> Result<Response<proto::GameList>, Status> {
+        let request = request.into_inner();
+        let filter = LobbyFilter { min_bet: request.min_bet, max_bet: request.max_bet };
+        let limit = request.limit.map_or(50, |limit| limit as usize);
+        let games = self.game_state.read().await.list_open_games(&filter, request.after, limit);
+        Ok(Response::new(proto::GameList { games: games.into_iter().map(Into::into).collect() }))
+    }
+}
+
+#[tonic::async_trait]
+impl TokenService for Grpc {
+    async fn transfer(&self, request: Request<proto::TransferRequest>) -> Result<Response<proto::Empty>, Status> {
+        let from = self.caller(&request).map_err(game_status)?;
+        let request = request.into_inner();
+        let to = AccountId::new(request.to).map_err(invalid_account)?;
+        self.mutate_token(|token| token.transfer(&Context::new(from), to, request.amount)).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn approve(&self, request: Request<proto::ApproveRequest>) -> Result<Response<proto::Empty>, Status> {
+        let owner = self.caller(&request).map_err(game_status)?;
+        let request = request.into_inner();
+        let spender = AccountId::new(request.spender).map_err(invalid_account)?;
+        self.mutate_token(|token| token.approve(&Context::new(owner), spender, request.amount)).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn balance_of(&self, request: Request<proto::User>) -> Result<Response<proto::TokenBalance>, Status> {
+        let balance = self.token.read().await.get_balance(&request.into_inner().user);
+        Ok(Response::new(proto::TokenBalance { balance: balance.get() }))
+    }
+
+    async fn mint_price(&self, _request: Request<proto::Empty>) -> Result<Response<proto::Price>, Status> {
+        Ok(Response::new(proto::Price { wei_per_token: self.token.read().await.mint_price() }))
+    }
+}
+
+#[tokio::test]
+async fn test_services_map_errors_to_codes() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::events::{GameEvent, TokenEvent};
+    use crate::vault::VAULT;
+
+    let mut credentials = Credentials::new();
+    let (alice, bob) = (credentials.issue(account("Alice")), credentials.issue(account("Bob")));
+    let mut token = ERC20Token::new(account("Owner"), "Game Token", "GAME", 0, None);
+    token.mint(&caller("Alice"), 110, 110 * token.mint_price()).unwrap();
+    let game_state = Arc::new(RwLock::new(GameState::new()));
+    let grpc = Grpc::new(game_state, Arc::new(RwLock::new(token))).with_credentials(credentials);
+    fn signed<T>(secret: &str, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", format!("Bearer {}", secret).parse().unwrap());
+        request
+    }
+    let anonymous = GameService::stake(&grpc, Request::new(proto::Amount { amount: 100 })).await.unwrap_err();
+    assert_eq!((anonymous.code(), anonymous.message()), (Code::Unauthenticated, "Missing or unknown credential."));
+    let unapproved = GameService::stake(&grpc, signed(&alice, proto::Amount { amount: 100 })).await.unwrap_err();
+    assert_eq!((unapproved.code(), unapproved.message()), (Code::FailedPrecondition, "Insufficient allowance."));
+    grpc.approve(signed(&alice, proto::ApproveRequest { spender: VAULT.to_string(), amount: 100 })).await.unwrap();
+    GameService::stake(&grpc, signed(&alice, proto::Amount { amount: 100 })).await.unwrap();
+    let blank = grpc.approve(signed(&alice, proto::ApproveRequest { spender: " ".to_string(), amount: 1 })).await.unwrap_err();
+    assert_eq!((blank.code(), blank.message()), (Code::InvalidArgument, "Account id starts or ends with whitespace."));
+    let started = grpc.start_game(signed(&alice, proto::StartGameRequest { bet: 30 })).await.unwrap();
+    let game_id = started.into_inner().game_id;
+
+    let own = grpc.join_game(signed(&alice, proto::JoinGameRequest { game_id })).await.unwrap_err();
+    assert_eq!((own.code(), own.message()), (Code::InvalidArgument, "Cannot join your own game."));
+    let outsider = grpc.reveal_cards(signed(&bob, proto::GameId { game_id })).await.unwrap_err();
+    assert_eq!(outsider.code(), Code::PermissionDenied);
+    let missing = grpc.get_game(Request::new(proto::GameId { game_id: 9 })).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+    let open = grpc.list_open_games(Request::new(proto::OpenGamesRequest::default())).await.unwrap().into_inner();
+    assert_eq!(open.games[0].bet, 30);
+
+    let transfer = proto::TransferRequest { to: "Alice".to_string(), amount: 1 };
+    assert_eq!(grpc.transfer(signed(&bob, transfer)).await.unwrap_err().code(), Code::NotFound);
+    let balance = grpc.balance_of(Request::new(proto::User { user: "Alice".to_string() })).await.unwrap();
+    assert_eq!(balance.into_inner().balance, 10);
+    grpc.transfer(signed(&alice, proto::TransferRequest { to: "Bob".to_string(), amount: 1 })).await.unwrap();
+    let sent = grpc.game_state.read().await.events().last().unwrap().event.clone();
+    assert_eq!(sent, GameEvent::Token(TokenEvent::Transfer { from: "Alice".to_string(), to: Some("Bob".to_string()), amount: 1 }));
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::GameError;
+
+// Boolean lock placed around call sites that hand control to external code (payouts, transfers).
+// Entering returns a lock that is released when dropped, so every early return unlocks too.
+// The flag lives behind an Arc so holding the lock does not keep `self` borrowed.
+#[derive(Debug, Default)]
+pub struct ReentrancyGuard {
+    locked: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+pub struct ReentrancyLock {
+    locked: Arc<AtomicBool>,
+}
+
+impl ReentrancyGuard {
+    pub fn new() -> Self {
+        ReentrancyGuard::default()
+    }
+
+    pub fn enter(&self) -> Result<ReentrancyLock, GameError> {
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return Err(GameError::Reentrancy);
+        }
+
+        Ok(ReentrancyLock { locked: Arc::clone(&self.locked) })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Acquire)
+    }
+}
+
+// A cloned state must not share the lock of the original
+impl Clone for ReentrancyGuard {
+    fn clone(&self) -> Self {
+        ReentrancyGuard::new()
+    }
+}
+
+impl Drop for ReentrancyLock {
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+#[test]
+fn test_guard_rejects_nested_enter() {
+    let guard = ReentrancyGuard::new();
+
+    let lock = guard.enter();
+    assert!(lock.is_ok());
+    assert!(guard.is_locked());
+
+    // A second entry while the first lock is alive is the reentrant call
+    let nested = guard.enter();
+    assert!(nested.is_err(), "Nested call was not rejected");
+
+    drop(lock);
+    assert!(!guard.is_locked());
+    assert!(guard.enter().is_ok());
+}
+
+#[test]
+fn test_guard_simulated_recursive_callback() {
+    // Simulates a malicious receiver calling back into the protected function
+    fn protected(guard: &ReentrancyGuard, depth: u32, calls: &mut u32) -> Result<(), GameError> {
+        let _lock = guard.enter()?;
+        *calls += 1;
+        if depth > 0 {
+            protected(guard, depth - 1, calls)?;
+        }
+        Ok(())
+    }
+
+    let guard = ReentrancyGuard::new();
+    let mut calls = 0;
+    let result = protected(&guard, 3, &mut calls);
+
+    assert_eq!(result, Err(GameError::Reentrancy));
+    assert_eq!(calls, 1);
+    // The failed call unwound and released the lock
+    assert!(!guard.is_locked());
+}
+
+#[test]
+fn test_guard_released_on_early_return() {
+    fn failing(guard: &ReentrancyGuard) -> Result<(), GameError> {
+        let _lock = guard.enter()?;
+        Err(GameError::InsufficientFunds)
+    }
+
+    let guard = ReentrancyGuard::new();
+    assert!(failing(&guard).is_err());
+    assert!(!guard.is_locked());
+
+    let cloned = guard.clone();
+    let _lock = guard.enter().unwrap();
+    assert!(!cloned.is_locked());
+}
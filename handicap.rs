@@ -0,0 +1,95 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::GameError;
+use crate::rules::Outcome;
+
+// Odds the creator of a game offers. The opponent bets the game's bet and wins `numerator` for every
+// `denominator` of it, the creator backs that with a stake of bet * numerator / denominator (rounded
+// down) and only wins the opponent's bet. 1:1 is an even game. With `creator_wins_ties` a drawn game
+// goes to the creator instead of the draw policy, the handicap of the opponent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayoutOdds {
+    pub numerator: u64,
+    pub denominator: u64,
+    #[serde(default)]
+    pub creator_wins_ties: bool,
+}
+
+impl Default for PayoutOdds {
+    fn default() -> Self {
+        PayoutOdds { numerator: 1, denominator: 1, creator_wins_ties: false }
+    }
+}
+
+impl PayoutOdds {
+    pub fn is_even(&self) -> bool {
+        *self == PayoutOdds::default()
+    }
+
+    // What the creator locks against an opponent's `bet`, refused if nothing would be at stake
+    pub fn creator_stake(&self, bet: u64) -> Result<u64, GameError> {
+        let invalid = GameError::InvalidOdds { numerator: self.numerator, denominator: self.denominator, bet };
+        if self.denominator == 0 {
+            return Err(invalid);
+        }
+        match u64::try_from(bet as u128 * self.numerator as u128 / self.denominator as u128) {
+            Ok(0) => Err(invalid),
+            Ok(stake) => Ok(stake),
+            Err(_) => Err(GameError::Overflow),
+        }
+    }
+
+    // Who the pot goes to once the series ended with `outcome`
+    pub fn decide(&self, outcome: Outcome) -> Outcome {
+        match outcome {
+            Outcome::Draw if self.creator_wins_ties => Outcome::CreatorWins,
+            outcome => outcome,
+        }
+    }
+}
+
+// What each player ends up with of a settled pot, before the house fee. The winner takes both stakes,
+// a draw gives each their own back.
+pub fn split(creator_stake: u64, bet: u64, outcome: Outcome) -> (u64, u64) {
+    match outcome {
+        Outcome::CreatorWins => (creator_stake + bet, 0),
+        Outcome::OpponentWins => (0, creator_stake + bet),
+        Outcome::Draw => (creator_stake, bet),
+    }
+}
+
+#[test]
+fn test_settlement_math_for_every_odds() {
+    for numerator in 1..=12u64 {
+        for denominator in 1..=12u64 {
+            for creator_wins_ties in [false, true] {
+                let odds = PayoutOdds { numerator, denominator, creator_wins_ties };
+                for bet in 1..=60u64 {
+                    let Ok(stake) = odds.creator_stake(bet) else {
+                        assert!(bet * numerator < denominator);
+                        continue;
+                    };
+                    assert_eq!(stake, bet * numerator / denominator);
+                    for outcome in [Outcome::CreatorWins, Outcome::OpponentWins, Outcome::Draw] {
+                        let (creator, opponent) = split(stake, bet, odds.decide(outcome));
+                        assert_eq!(creator + opponent, stake + bet);
+                        let net = (creator as i64 - stake as i64, opponent as i64 - bet as i64);
+                        let expected = match (outcome, creator_wins_ties) {
+                            (Outcome::CreatorWins, _) | (Outcome::Draw, true) => (bet as i64, -(bet as i64)),
+                            (Outcome::OpponentWins, _) => (-(stake as i64), stake as i64),
+                            (Outcome::Draw, false) => (0, 0),
+                        };
+                        assert_eq!(net, expected, "{}:{} on a bet of {}, {:?}", numerator, denominator, bet, outcome);
+                    }
+                }
+            }
+        }
+    }
+
+    // Even odds are a plain game, 3:2 pays the opponent half again their bet
+    assert_eq!(PayoutOdds::default().creator_stake(30), Ok(30));
+    let three_to_two = PayoutOdds { numerator: 3, denominator: 2, creator_wins_ties: false };
+    assert_eq!(split(three_to_two.creator_stake(30).unwrap(), 30, Outcome::OpponentWins), (0, 75));
+    assert!(matches!(PayoutOdds { denominator: 0, ..three_to_two }.creator_stake(30), Err(GameError::InvalidOdds { .. })));
+    assert_eq!(PayoutOdds { numerator: u64::MAX, ..three_to_two }.creator_stake(u64::MAX), Err(GameError::Overflow));
+}
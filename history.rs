@@ -0,0 +1,134 @@
+use serde::{Serialize, Deserialize};
+
+use crate::cancel::OperationContext;
+use crate::deck::Card;
+use crate::error::GameError;
+use crate::rules::{GameMode, Round};
+use crate::seats::Seat;
+
+// How a game was dealt, enough with its seed to deal it again
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameSetup {
+    pub mode: GameMode,
+    pub rounds_to_win: u32,
+    pub config_version: u64,
+}
+
+// Everything needed to show or audit a finished game once it left the open games
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub game_id: u64,
+    pub creator: String,
+    pub opponent: String,
+    pub creator_hand: Vec<Card>, // Hands of the last round
+    pub opponent_hand: Vec<Card>,
+    pub rounds: Vec<Round>,
+    pub winner: Option<String>, // None on a draw
+    pub pot: u64,
+    pub started_at: u64,
+    pub settled_at: u64,
+    pub seed: String, // Hex encoded, matches the revealed game seed commitment
+    #[serde(default)]
+    pub setup: Option<GameSetup>, // Missing from games archived before replays, those cannot be replayed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seats: Vec<Seat>, // Every seat and its hand of a game of more than two, creator and opponent are the first two
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shared_by: Vec<String>, // Winners of a shared pot, winner is None then
+}
+
+impl GameRecord {
+    pub fn involves(&self, player: &str) -> bool {
+        self.creator == player || self.opponent == player || self.seats.iter().any(|seat| seat.player == player)
+    }
+}
+
+// Archive of settled games, ordered by settlement
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct History {
+    records: Vec<GameRecord>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    pub fn record(&mut self, record: GameRecord) {
+        self.records.push(record);
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn all(&self) -> &[GameRecord] {
+        &self.records
+    }
+
+    pub fn by_id(&self, game_id: u64) -> Option<&GameRecord> {
+        self.records.iter().find(|r| r.game_id == game_id)
+    }
+
+    pub fn by_player(&self, player: &str) -> Vec<&GameRecord> {
+        self.records.iter().filter(|r| r.involves(player)).collect()
+    }
+
+    // Same as by_player but checks the context while scanning, so an abandoned request stops early
+    pub fn query_player(&self, player: &str, context: &OperationContext) -> Result<Vec<GameRecord>, GameError> {
+        let mut found = Vec::new();
+        for (index, record) in self.records.iter().enumerate() {
+            if index % 1024 == 0 {
+                context.checkpoint()?;
+            }
+            if record.involves(player) {
+                found.push(record.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    // Drops the records added after the first `len`, when the transaction that added them fails
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.records.truncate(len);
+    }
+}
+
+#[test]
+fn test_history_queries() {
+    use crate::cancel::CancellationToken;
+    use std::time::Duration;
+
+    let mut history = History::new();
+    for (game_id, opponent) in [(0, "Bob"), (1, "Carol")] {
+        history.record(GameRecord {
+            game_id,
+            creator: "Alice".to_string(),
+            opponent: opponent.to_string(),
+            creator_hand: Vec::new(),
+            opponent_hand: Vec::new(),
+            rounds: Vec::new(),
+            winner: Some("Alice".to_string()),
+            pot: 20,
+            started_at: 1,
+            settled_at: 2,
+            seed: String::new(),
+            setup: None,
+            seats: Vec::new(),
+            shared_by: Vec::new(),
+        });
+    }
+
+    assert_eq!(history.by_player("Alice").len(), 2);
+    assert_eq!(history.by_player("Carol")[0].game_id, 1);
+    assert!(history.by_id(7).is_none());
+
+    let token = CancellationToken::new();
+    let context = OperationContext::new(token.clone(), Duration::from_secs(60));
+    assert_eq!(history.query_player("Bob", &context).unwrap().len(), 1);
+    token.cancel();
+    assert!(history.query_player("Bob", &context).is_err());
+}
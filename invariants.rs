@@ -0,0 +1,283 @@
+use crate::account_id::AccountId;
+use crate::clock::ManualClock;
+use crate::context::Context;
+use crate::deposits::PayoutAdapter;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::sidebets::Side;
+use crate::token::ERC20Token;
+use crate::tournament::TournamentStatus;
+use crate::vault::vault;
+use crate::GameState;
+
+impl GameState {
+    // Everything held for players and the house: stakes, fees, rewards, jackpot, insurance, open side bets and the
+    // buy-ins of tournaments not finished yet
+    pub fn held_funds(&self) -> u128 {
+        let stakes: u128 = self.balances.values().map(|balance| balance.available.get() as u128 + balance.locked.get() as u128).sum();
+        let tournaments: u128 = self
+            .tournaments
+            .values()
+            .filter(|tournament| tournament.status != TournamentStatus::Finished)
+            .map(|tournament| tournament.prize_pool as u128 + tournament.held as u128)
+            .sum();
+        stakes
+            + tournaments
+            + self.side_bets.total()
+            + self.treasury.balance() as u128
+            + self.jackpot.balance() as u128
+            + self.slashing.insurance_pool() as u128
+            + self.rewards.pool() as u128
+    }
+
+    // Funds that came in less the funds that left, per the event log: stakes and credited deposits in,
+    // withdrawals, swept accounts, clawed back deposits and resets out
+    fn net_inflow(&self) -> i128 {
+        self.events().iter().fold(0, |net, logged| match &logged.event {
+            GameEvent::Staked { amount, .. } | GameEvent::DepositCredited { amount, .. } => net + *amount as i128,
+            GameEvent::Withdrawn { amount, .. } => net - *amount as i128,
+            GameEvent::AccountClosed { swept, .. } => net - *swept as i128,
+            GameEvent::DepositReversed { clawed_back, .. } => net - *clawed_back as i128,
+            GameEvent::StateReset { wiped } => net - *wiped as i128,
+            _ => net,
+        })
+    }
+
+    // Checks what has to hold between any two operations, every violation found is listed in the error:
+    // no funds created or lost, escrow matching the pots and locked stakes, and settled games archived
+    // with a winner among their players or as a draw
+    pub fn check_invariants(&self) -> Result<(), GameError> {
+        let mut violations = Vec::new();
+
+        let (held, net) = (self.held_funds(), self.net_inflow());
+        if net < 0 {
+            violations.push(format!("{} more paid out than paid in", -net));
+        } else if held != net as u128 {
+            violations.push(format!("{} held for {} paid in", held, net));
+        }
+
+        let pots: u128 = self.games.values().filter(|game| !game.is_settled).map(|game| game.pot.get() as u128).sum();
+        let locked: u128 = self.balances.values().map(|balance| balance.locked.get() as u128).sum();
+        if self.escrow.total() != pots || self.escrow.total() != locked {
+            violations.push(format!("escrow of {} for pots of {} and {} locked", self.escrow.total(), pots, locked));
+        }
+        let idle: u128 = self.balances.values().map(|balance| balance.available.get() as u128).sum();
+        if self.rewards.total_idle() != idle {
+            violations.push(format!("rewards count {} idle of {} available", self.rewards.total_idle(), idle));
+        }
+        for (player, balance) in &self.balances {
+            if balance.available.checked_add(balance.locked).is_none() {
+                violations.push(format!("balance of {} overflows", player));
+            }
+            if self.rewards.idle(player) != balance.available.get() {
+                violations.push(format!("rewards count {} idle for {} holding {}", self.rewards.idle(player), player, balance.available));
+            }
+            let seated = self.games.values().any(|game| game.creator == *player || game.opponent.as_ref() == Some(player));
+            if balance.locked > 0 && !seated {
+                violations.push(format!("{} has {} locked outside any game", player, balance.locked));
+            }
+        }
+        for game in self.games.values() {
+            if game.is_settled {
+                violations.push(format!("game {} is settled but not archived", game.id));
+            } else if game.pot != self.escrow.pot(game.id) {
+                violations.push(format!("escrow of game {} does not match its pot", game.id));
+            }
+        }
+        for game_id in self.side_bets.games().filter(|game_id| !self.games.contains_key(game_id)) {
+            violations.push(format!("side bets of game {} outlived it", game_id));
+        }
+        for record in self.history.all() {
+            if let Some(winner) = &record.winner {
+                if !record.involves(winner) {
+                    violations.push(format!("game {} was won by {}, who did not play it", record.game_id, winner));
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(GameError::InvariantsViolated(violations))
+        }
+    }
+}
+
+// Names the random action sequences below play with, ADMIN holds the Admin role
+pub const PLAYERS: [&str; 4] = ["Alice", "Bob", "Carol", "Mallory"];
+pub const ADMIN: &str = "House";
+
+// One step of a random sequence. Players are indices into PLAYERS and games indices into the unsettled
+// games, both wrapped around, so any generated step names something that exists. Refusals are part of
+// the sequence: a refused step has to leave the state as consistent as an accepted one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Stake { player: usize, amount: u64 },
+    Withdraw { player: usize, amount: u64 },
+    StartGame { player: usize, bet: u64 },
+    JoinGame { game: usize, player: usize },
+    Reveal { game: usize },
+    SideBet { game: usize, player: usize, side: Side, amount: u64 },
+    FundJackpot { player: usize, amount: u64 },
+    Slash { player: usize },
+    CloseAccount { player: usize },
+    FundRewards { amount: u64, duration_secs: u64 },
+    ClaimRewards { player: usize },
+    AdvanceTime { secs: u64 },
+    Tick,
+}
+
+struct Paid;
+
+impl PayoutAdapter for Paid {
+    fn pay(&self, _user: &str, _amount: u64) -> Result<String, GameError> {
+        Ok("0xpaid".to_string())
+    }
+}
+
+impl Action {
+    // Plays the step on a state running on `clock`, the refusal if any is returned. Stakes are deposited into
+    // the vault of `token` and withdrawals and swept accounts paid out of it.
+    pub fn apply(&self, game_state: &mut GameState, token: &mut ERC20Token, clock: &ManualClock) -> Result<(), GameError> {
+        let player = |index: &usize| AccountId::new(PLAYERS[index % PLAYERS.len()]);
+        let game = |index: &usize| {
+            let open: Vec<u64> = game_state.games.keys().copied().collect();
+            if open.is_empty() { None } else { Some(open[index % open.len()]) }
+        };
+        match self {
+            Action::Stake { player: p, amount } => {
+                token.approve(&Context::new(player(p)?), vault(), *amount)?;
+                game_state.deposit(&Context::new(player(p)?), token, *amount)
+            }
+            Action::Withdraw { player: p, amount } => game_state.withdraw(&Context::new(player(p)?), token, *amount),
+            Action::StartGame { player: p, bet } => game_state.start_game(&Context::new(player(p)?), *bet).map(|_| ()),
+            Action::JoinGame { game: g, player: p } => match game(g) {
+                Some(game_id) => game_state.join_game(&Context::new(player(p)?), game_id),
+                None => Err(GameError::NoGameToJoin),
+            },
+            Action::Reveal { game: g } => match game(g) {
+                Some(game_id) => game_state.play_round(game_id),
+                None => Err(GameError::NoGameToReveal),
+            },
+            Action::SideBet { game: g, player: p, side, amount } => match game(g) {
+                Some(game_id) => game_state.place_side_bet(&Context::new(player(p)?), game_id, *side, *amount),
+                None => Err(GameError::GameNotFound),
+            },
+            Action::FundJackpot { player: p, amount } => game_state.fund_jackpot(&Context::new(player(p)?), *amount),
+            Action::Slash { player: p } => game_state.slash_for_cheating(&Context::new(AccountId::new(ADMIN)?), player(p)?, "Random verdict".to_string()).map(|_| ()),
+            Action::CloseAccount { player: p } => {
+                let swept = game_state.close_account(&Context::new(player(p)?), &Paid)?;
+                Ok(token.transfer(&Context::new(vault()), player(p)?, swept)?)
+            }
+            Action::FundRewards { amount, duration_secs } => game_state.fund_rewards(&Context::new(AccountId::new(ADMIN)?), *amount, *duration_secs),
+            Action::ClaimRewards { player: p } => game_state.claim_rewards(&Context::new(player(p)?)).map(|_| ()),
+            Action::AdvanceTime { secs } => {
+                clock.advance(*secs);
+                Ok(())
+            }
+            Action::Tick => game_state.tick().map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod strategies {
+    use proptest::prelude::*;
+
+    use super::Action;
+    use crate::sidebets::Side;
+
+    pub fn side() -> impl Strategy<Value = Side> {
+        prop_oneof![Just(Side::Creator), Just(Side::Opponent)]
+    }
+
+    // Amounts mostly within what players stake, sometimes zero or far beyond
+    pub fn amount() -> impl Strategy<Value = u64> {
+        prop_oneof![8 => 1..200u64, 1 => Just(0u64), 1 => 1_000..u64::MAX]
+    }
+
+    // Bets a few stakes can cover, so games get started and joined
+    pub fn bet() -> impl Strategy<Value = u64> {
+        prop_oneof![9 => 1..50u64, 1 => amount()]
+    }
+
+    // Games are started and played more often than the rest, so sequences reach settlements
+    pub fn action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            8 => (any::<usize>(), amount()).prop_map(|(player, amount)| Action::Stake { player, amount }),
+            2 => (any::<usize>(), amount()).prop_map(|(player, amount)| Action::Withdraw { player, amount }),
+            6 => (any::<usize>(), bet()).prop_map(|(player, bet)| Action::StartGame { player, bet }),
+            8 => (any::<usize>(), any::<usize>()).prop_map(|(game, player)| Action::JoinGame { game, player }),
+            16 => any::<usize>().prop_map(|game| Action::Reveal { game }),
+            2 => (any::<usize>(), any::<usize>(), side(), amount())
+                .prop_map(|(game, player, side, amount)| Action::SideBet { game, player, side, amount }),
+            1 => (any::<usize>(), amount()).prop_map(|(player, amount)| Action::FundJackpot { player, amount }),
+            1 => any::<usize>().prop_map(|player| Action::Slash { player }),
+            1 => any::<usize>().prop_map(|player| Action::CloseAccount { player }),
+            1 => (0..20u64, 0..100_000u64).prop_map(|(amount, duration_secs)| Action::FundRewards { amount, duration_secs }),
+            1 => any::<usize>().prop_map(|player| Action::ClaimRewards { player }),
+            2 => (0..100_000u64).prop_map(|secs| Action::AdvanceTime { secs }),
+            1 => Just(Action::Tick),
+        ]
+    }
+
+    pub fn actions(max_len: usize) -> impl Strategy<Value = Vec<Action>> {
+        proptest::collection::vec(action(), 1..max_len)
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_invariants_hold_after_every_action(actions in strategies::actions(120)) {
+        use crate::account_id::account;
+        use crate::clock::SharedClock;
+        use crate::context::caller;
+
+        let clock = ManualClock::new(1_000);
+        let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+        game_state.init_admin(account(ADMIN)).unwrap();
+        let mut token = ERC20Token::new(account(ADMIN), "Game Token", "GAME", 0, None);
+        token.adjust_price(&caller(ADMIN), 0).unwrap();
+        for name in PLAYERS {
+            token.mint(&caller(name), 10_000, 0).unwrap();
+        }
+        for (step, action) in actions.iter().enumerate() {
+            let _ = action.apply(&mut game_state, &mut token, &clock);
+            if let Err(error) = game_state.check_invariants().and_then(|()| game_state.check_vault(&token)) {
+                proptest::prop_assert!(false, "after step {} ({:?}): {}", step, action, error);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_violations_are_listed() {
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    game_state.stake_tokens(&caller("Alice"), 100).unwrap();
+    game_state.start_game(&caller("Alice"), 40).unwrap();
+    game_state.check_invariants().unwrap();
+
+    // A reset is paid out of the books like a withdrawal
+    let mut reset = game_state.clone();
+    reset.init_admin(crate::account_id::account(ADMIN)).unwrap();
+    reset.initialize(&caller(ADMIN)).unwrap();
+    reset.check_invariants().unwrap();
+    assert!(matches!(reset.events().last().unwrap().event, GameEvent::StateReset { wiped: 100 }));
+
+    // A stake nobody paid for and a bet locked outside any game
+    game_state.balances.get_mut("Alice").unwrap().credit(5).unwrap();
+    game_state.sync_rewards("Alice");
+    game_state.games.clear();
+    let violations = match game_state.check_invariants() {
+        Err(GameError::InvariantsViolated(violations)) => violations,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(violations, vec![
+        "105 held for 100 paid in".to_string(),
+        "escrow of 40 for pots of 0 and 40 locked".to_string(),
+        "Alice has 40 locked outside any game".to_string(),
+    ]);
+}
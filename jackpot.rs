@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+
+use crate::deck::Card;
+use crate::error::GameError;
+use crate::rules::ACE;
+
+// Pool bonuses are paid from. It can only pay what it holds.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Jackpot {
+    balance: u64,
+}
+
+impl Jackpot {
+    pub fn new() -> Self {
+        Jackpot::default()
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.balance
+    }
+
+    pub fn fund(&mut self, amount: u64) -> Result<(), GameError> {
+        self.balance = self.balance.checked_add(amount).ok_or(GameError::Overflow)?;
+        Ok(())
+    }
+
+    // Pays up to `amount`, returns what was actually paid
+    pub fn pay(&mut self, amount: u64) -> u64 {
+        let paid = amount.min(self.balance);
+        self.balance -= paid;
+        paid
+    }
+}
+
+// Slice of every decided pot fed into the jackpot. The winner of a game whose deciding round they won
+// holding `winning_rank` against `losing_rank` takes the whole jackpot.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressiveJackpot {
+    pub contribution_bps: u32, // Basis points of the pot
+    pub winning_rank: u8,
+    pub losing_rank: u8,
+}
+
+impl Default for ProgressiveJackpot {
+    fn default() -> Self {
+        ProgressiveJackpot { contribution_bps: 100, winning_rank: 13, losing_rank: ACE }
+    }
+}
+
+impl ProgressiveJackpot {
+    pub fn contribution(&self, pot: u64) -> u64 {
+        (pot as u128 * self.contribution_bps.min(10_000) as u128 / 10_000) as u64
+    }
+
+    pub fn is_won(&self, winning_hand: &[Card], losing_hand: &[Card]) -> bool {
+        winning_hand.iter().any(|card| card.rank == self.winning_rank) && losing_hand.iter().any(|card| card.rank == self.losing_rank)
+    }
+}
+
+#[test]
+fn test_jackpot_pays_what_it_holds() {
+    let mut jackpot = Jackpot::new();
+    jackpot.fund(30).unwrap();
+
+    assert_eq!(jackpot.pay(20), 20);
+    assert_eq!(jackpot.pay(20), 10);
+    assert_eq!(jackpot.balance(), 0);
+    assert!(jackpot.fund(u64::MAX).is_ok());
+    assert!(jackpot.fund(1).is_err());
+}
@@ -0,0 +1,368 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::account_id::AccountId;
+use crate::cancel::OperationContext;
+use crate::context::Context;
+use crate::error::{ErrorKind, GameError};
+use crate::lobby::{GameFilter, LobbyFilter};
+use crate::preferences::GameOptions;
+use crate::ratelimit::RateLimiter;
+use crate::teams::Team;
+use crate::token::ERC20Token;
+use crate::vault::vault;
+use crate::GameState;
+
+// Codes defined by JSON-RPC 2.0
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+// Refusals of the game, one code per ErrorKind. The message is the one players see.
+pub const NOT_FOUND: i64 = 1000;
+pub const FORBIDDEN: i64 = 1001;
+pub const CONFLICT: i64 = 1002;
+pub const INVALID: i64 = 1003;
+pub const UNAVAILABLE: i64 = 1004;
+
+pub fn error_code(error: &GameError) -> i64 {
+    match error.kind() {
+        ErrorKind::NotFound => NOT_FOUND,
+        ErrorKind::Forbidden => FORBIDDEN,
+        ErrorKind::Conflict => CONFLICT,
+        ErrorKind::Invalid => INVALID,
+        ErrorKind::Unavailable => UNAVAILABLE,
+        ErrorKind::Internal => INTERNAL_ERROR,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip)]
+    pub kind: Option<ErrorKind>, // Set for refusals of the game, counted in the metrics
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into(), kind: None }
+    }
+}
+
+impl From<GameError> for RpcError {
+    fn from(error: GameError) -> Self {
+        RpcError { kind: Some(error.kind()), ..RpcError::new(error_code(&error), error.to_string()) }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>, // Missing on notifications, which get no response
+}
+
+// What handle produced: the response body, if any, and whether the state changed and has to be saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub body: Option<String>,
+    pub changed: bool,
+}
+
+// Params can be given by name or by position, in the order of the fields. None of them names the caller,
+// that comes from the credential the transport checked.
+#[derive(Deserialize)]
+struct Amount {
+    amount: u64,
+    #[serde(default)]
+    command_id: Option<Uuid>, // Calls retried with the same id get the first answer
+}
+
+#[derive(Deserialize)]
+struct User {
+    user: String,
+}
+
+#[derive(Deserialize)]
+struct StartGame {
+    bet: u64,
+    #[serde(default)]
+    seats: Option<u32>, // Two when left out
+    #[serde(default)]
+    teams: Option<bool>,
+    #[serde(default)]
+    command_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct JoinGame {
+    game_id: u64,
+    #[serde(default)]
+    team: Option<Team>, // Only for team games, the team with fewer players when left out
+    #[serde(default)]
+    command_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct GameCommand {
+    game_id: u64,
+    #[serde(default)]
+    command_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct GameId {
+    game_id: u64,
+}
+
+#[derive(Deserialize, Default)]
+struct OpenGames {
+    min_bet: Option<u64>,
+    max_bet: Option<u64>,
+    after: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+struct ListGames {
+    #[serde(default, flatten)]
+    filter: GameFilter,
+    cursor: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+struct Overview {
+    top: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+struct ListBalances {
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+// Answers one request or a batch of them, as JSON text, for the authenticated `caller`. Without one only
+// the queries are answered. Every call of a batch counts against the limiter, calls left once `operation`
+// is cancelled or out of time are refused. Deposits and withdrawals move tokens of `token` in and out of
+// the vault.
+pub fn handle(
+    game_state: &mut GameState,
+    token: &mut ERC20Token,
+    caller: Option<&AccountId>,
+    body: &str,
+    limiter: Option<&RateLimiter>,
+    operation: &OperationContext,
+) -> Reply {
+    let mut changed = false;
+    let response = match serde_json::from_str::<Value>(body) {
+        Err(e) => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+        Ok(Value::Array(batch)) if batch.is_empty() => Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "Empty batch."))),
+        Ok(Value::Array(batch)) => {
+            let responses: Vec<Value> = batch.into_iter().filter_map(|request| handle_one(game_state, token, caller, request, limiter, operation, &mut changed)).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        Ok(request) => handle_one(game_state, token, caller, request, limiter, operation, &mut changed),
+    };
+    Reply { body: response.map(|response| response.to_string()), changed }
+}
+
+fn handle_one(
+    game_state: &mut GameState,
+    token: &mut ERC20Token,
+    caller: Option<&AccountId>,
+    request: Value,
+    limiter: Option<&RateLimiter>,
+    operation: &OperationContext,
+    changed: &mut bool,
+) -> Option<Value> {
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string()))),
+    };
+    if request.jsonrpc != "2.0" {
+        return Some(error_response(request.id.unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported.")));
+    }
+    let outcome = match operation.checkpoint() {
+        Ok(()) => call(game_state, token, caller, &request.method, request.params, limiter, changed),
+        Err(error) => Err(error.into()),
+    };
+    if let Err(RpcError { kind: Some(kind), .. }) = &outcome {
+        game_state.record_error(*kind);
+    }
+    let id = request.id?;
+    Some(match outcome {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "error": error, "id": id })
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn call(
+    game_state: &mut GameState,
+    token: &mut ERC20Token,
+    caller: Option<&AccountId>,
+    method: &str,
+    raw: Value,
+    limiter: Option<&RateLimiter>,
+    changed: &mut bool,
+) -> Result<Value, RpcError> {
+    // The caller of an action, counted against the limiter
+    let act = || -> Result<AccountId, GameError> {
+        let caller = caller.ok_or(GameError::Unauthenticated)?;
+        limiter.map_or(Ok(()), |limiter| limiter.check(caller))?;
+        Ok(caller.clone())
+    };
+    let result = match method {
+        "approve" => {
+            let p: Amount = params(raw)?;
+            token.approve(&Context::new(act()?), vault(), p.amount).map_err(GameError::from)?;
+            Value::Null
+        }
+        "deposit" => {
+            let p: Amount = params(raw)?;
+            game_state.deposit(&Context::new(act()?).with_command_id(p.command_id), token, p.amount)?;
+            Value::Null
+        }
+        "withdraw" => {
+            let p: Amount = params(raw)?;
+            game_state.withdraw(&Context::new(act()?).with_command_id(p.command_id), token, p.amount)?;
+            Value::Null
+        }
+        "start_game" => {
+            let p: StartGame = params(raw)?;
+            let options = GameOptions { seats: p.seats, teams: p.teams, ..GameOptions::with_bet(p.bet) };
+            json!(game_state.start_game_with(&Context::new(act()?).with_command_id(p.command_id), options)?)
+        }
+        "join_game" => {
+            let p: JoinGame = params(raw)?;
+            let ctx = Context::new(act()?).with_command_id(p.command_id);
+            match p.team {
+                Some(team) => game_state.join_team(&ctx, p.game_id, team)?,
+                None => game_state.join_game(&ctx, p.game_id)?,
+            }
+            Value::Null
+        }
+        "join_game_with_bot" => {
+            let p: GameCommand = params(raw)?;
+            game_state.join_game_with_bot(&Context::new(act()?).with_command_id(p.command_id), p.game_id)?;
+            Value::Null
+        }
+        "reveal_cards" => {
+            let p: GameCommand = params(raw)?;
+            let revealed = game_state.contribute_reveal(&Context::new(act()?).with_command_id(p.command_id), p.game_id)?;
+            let summary = game_state.game_summary(p.game_id);
+            json!({ "revealed": revealed, "settled": summary.is_none(), "game": summary })
+        }
+        // Queries, nothing to save after them
+        "stake_of" => {
+            let p: User = params(raw)?;
+            let stake = game_state.stake_of(&p.user);
+            return Ok(json!({ "available": stake.available, "locked": stake.locked }));
+        }
+        "game_summary" => {
+            let p: GameId = params(raw)?;
+            return Ok(json!(game_state.game_summary(p.game_id).ok_or(GameError::GameNotFound)?));
+        }
+        "spectate" => {
+            let p: GameId = params(raw)?;
+            return Ok(json!(game_state.spectate(p.game_id).ok_or(GameError::GameNotFound)?));
+        }
+        "list_open_games" => {
+            let p: OpenGames = if raw.is_null() { OpenGames::default() } else { params(raw)? };
+            let filter = LobbyFilter { min_bet: p.min_bet, max_bet: p.max_bet };
+            return Ok(json!(game_state.list_open_games(&filter, p.after, p.limit.unwrap_or(50))));
+        }
+        "list_games" => {
+            let p: ListGames = if raw.is_null() { ListGames::default() } else { params(raw)? };
+            return Ok(json!(game_state.list_games(&p.filter, p.cursor, p.limit.unwrap_or(50))));
+        }
+        "list_balances" => {
+            let p: ListBalances = if raw.is_null() { ListBalances::default() } else { params(raw)? };
+            return Ok(json!(game_state.list_balances(p.cursor.as_deref(), p.limit.unwrap_or(50))));
+        }
+        "admin_overview" => {
+            let p: Overview = if raw.is_null() { Overview::default() } else { params(raw)? };
+            let admin = caller.ok_or(GameError::Unauthenticated)?;
+            return Ok(json!(game_state.admin_overview(&Context::new(admin.clone()), p.top.unwrap_or(10))?));
+        }
+        _ => return Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}.", method))),
+    };
+    *changed = true;
+    Ok(result)
+}
+
+#[test]
+fn test_calls_and_error_codes() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    let mut token = ERC20Token::new(account("Owner"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("Owner"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.mint(&caller("Bob"), 100, 0).unwrap();
+    let (alice, bob) = (account("Alice"), account("Bob"));
+    let unbounded = OperationContext::unbounded();
+    let mut rpc = |caller: Option<&AccountId>, body: Value| handle(&mut game_state, &mut token, caller, &body.to_string(), None, &unbounded);
+
+    let anonymous = rpc(None, json!({ "jsonrpc": "2.0", "method": "deposit", "params": { "amount": 100 }, "id": 1 }));
+    assert!(anonymous.body.unwrap().contains("Missing or unknown credential."));
+    let unapproved = rpc(Some(&alice), json!({ "jsonrpc": "2.0", "method": "deposit", "params": { "amount": 100 }, "id": 1 }));
+    assert!(unapproved.body.unwrap().contains("Insufficient allowance."));
+    rpc(Some(&alice), json!({ "jsonrpc": "2.0", "method": "approve", "params": { "amount": 100 } }));
+    let staked = rpc(Some(&alice), json!({ "jsonrpc": "2.0", "method": "deposit", "params": { "amount": 100 }, "id": 1 }));
+    assert_eq!(staked, Reply { body: Some(json!({ "jsonrpc": "2.0", "result": null, "id": 1 }).to_string()), changed: true });
+
+    // Positional params, and notifications in the batch that get no response
+    let batch = rpc(Some(&alice), json!([
+        { "jsonrpc": "2.0", "method": "start_game", "params": [40], "id": "a" },
+        { "jsonrpc": "2.0", "method": "join_game", "params": { "game_id": 0 }, "id": "b" },
+        { "jsonrpc": "2.0", "method": "stake_of", "params": ["Alice"], "id": "c" },
+        { "jsonrpc": "2.0", "method": "stake_of", "params": ["Alice"] },
+    ]));
+    let responses: Value = serde_json::from_str(&batch.body.unwrap()).unwrap();
+    assert_eq!(responses[0]["result"], 0);
+    assert_eq!(responses[1]["error"], json!({ "code": INVALID, "message": "Cannot join your own game." }));
+    assert_eq!(responses[2]["result"], json!({ "available": 60, "locked": 40 }));
+    assert_eq!(responses.as_array().unwrap().len(), 3);
+    rpc(Some(&bob), json!({ "jsonrpc": "2.0", "method": "approve", "params": [100] }));
+    rpc(Some(&bob), json!({ "jsonrpc": "2.0", "method": "deposit", "params": [100] }));
+
+    let listed = rpc(None, json!({ "jsonrpc": "2.0", "method": "list_balances", "params": { "limit": 1 }, "id": 4 }));
+    let listed: Value = serde_json::from_str(&listed.body.unwrap()).unwrap();
+    assert_eq!(listed["result"], json!({ "items": [["Alice", { "available": 60, "locked": 40 }]], "next": "Alice" }));
+    let listed = rpc(None, json!({ "jsonrpc": "2.0", "method": "list_games", "params": { "player": "Alice", "min_bet": 40 }, "id": 5 }));
+    assert_eq!(serde_json::from_str::<Value>(&listed.body.unwrap()).unwrap()["result"]["items"][0]["game_id"], 0);
+
+    let missing = rpc(None, json!({ "jsonrpc": "2.0", "method": "game_summary", "params": { "game_id": 7 }, "id": 2 }));
+    assert!(missing.body.unwrap().contains(&format!("\"code\":{}", NOT_FOUND)));
+    assert!(!missing.changed);
+    let unknown = rpc(Some(&bob), json!({ "jsonrpc": "2.0", "method": "mint", "id": 3 }));
+    assert!(unknown.body.unwrap().contains(&METHOD_NOT_FOUND.to_string()));
+    let garbage = handle(&mut game_state, &mut token, None, "{", None, &unbounded);
+    assert!(garbage.body.unwrap().contains(&PARSE_ERROR.to_string()));
+
+    // Out of time, nothing is done
+    let expired = OperationContext::new(crate::cancel::CancellationToken::new(), std::time::Duration::ZERO);
+    let late = json!({ "jsonrpc": "2.0", "method": "withdraw", "params": [10], "id": 6 });
+    let late = handle(&mut game_state, &mut token, Some(&alice), &late.to_string(), None, &expired);
+    assert_eq!(late.body.unwrap(), json!({ "jsonrpc": "2.0", "error": { "code": UNAVAILABLE, "message": "Operation timed out." }, "id": 6 }).to_string());
+    assert!(!late.changed);
+    assert_eq!(token.get_balance("Bob"), 0);
+    game_state.check_vault(&token).unwrap();
+}
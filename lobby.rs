@@ -0,0 +1,97 @@
+use serde::{Serialize, Deserialize};
+
+use crate::seats;
+use crate::teams;
+
+// What a player browsing the lobby sees of an open game, also the view of any unsettled game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GameSummary {
+    pub game_id: u64,
+    pub creator: String,
+    pub opponent: Option<String>,
+    pub bet: u64,
+    pub pot: u64,
+    pub created_at: u64,
+    pub expires_at: u64,
+    #[serde(default = "seats::heads_up", skip_serializing_if = "seats::is_heads_up")]
+    pub seats: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub players: Vec<String>, // Everyone seated in a game of more than two seats, opponent stays None there
+    #[serde(default, skip_serializing_if = "teams::is_solo")]
+    pub teams: bool,
+}
+
+impl GameSummary {
+    // Every seat is taken, the game can be revealed
+    pub fn is_full(&self) -> bool {
+        if seats::is_heads_up(&self.seats) { self.opponent.is_some() } else { self.players.len() >= self.seats as usize }
+    }
+
+    pub fn is_seated(&self, player: &str) -> bool {
+        self.creator == player || self.opponent.as_deref() == Some(player) || self.players.iter().any(|seated| seated == player)
+    }
+}
+
+// Bet range of the listing, both ends inclusive and optional
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LobbyFilter {
+    pub min_bet: Option<u64>,
+    pub max_bet: Option<u64>,
+}
+
+impl LobbyFilter {
+    pub fn matches(&self, summary: &GameSummary) -> bool {
+        self.min_bet.is_none_or(|min| summary.bet >= min) && self.max_bet.is_none_or(|max| summary.bet <= max)
+    }
+}
+
+// What list_games keeps of the unsettled games, every one of them by default
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameFilter {
+    #[serde(flatten)]
+    pub bets: LobbyFilter,
+    pub player: Option<String>, // Seated in the game
+    pub open: Option<bool>, // Still waiting for players, or every seat taken
+}
+
+impl GameFilter {
+    pub fn matches(&self, summary: &GameSummary) -> bool {
+        self.bets.matches(summary)
+            && self.player.as_ref().is_none_or(|player| summary.is_seated(player))
+            && self.open.is_none_or(|open| summary.is_full() != open)
+    }
+}
+
+// One page of `summaries` sorted by game id, starting after the id `after` (the last id of the previous page)
+pub fn page(summaries: impl IntoIterator<Item = GameSummary>, filter: &LobbyFilter, after: Option<u64>, limit: usize) -> Vec<GameSummary> {
+    let mut page: Vec<GameSummary> = summaries
+        .into_iter()
+        .filter(|summary| after.is_none_or(|after| summary.game_id > after) && filter.matches(summary))
+        .collect();
+    page.sort_by_key(|summary| summary.game_id);
+    page.truncate(limit);
+    page
+}
+
+#[test]
+fn test_filter_and_paginate() {
+    let summary = |game_id, bet| GameSummary {
+        game_id,
+        creator: "Alice".to_string(),
+        opponent: None,
+        bet,
+        pot: bet,
+        created_at: 0,
+        expires_at: 60,
+        seats: seats::HEADS_UP,
+        players: Vec::new(),
+        teams: false,
+    };
+    let open = vec![summary(3, 50), summary(1, 10), summary(2, 30), summary(4, 70)];
+    let filter = LobbyFilter { min_bet: Some(20), max_bet: Some(60) };
+
+    let ids = |page: Vec<GameSummary>| page.iter().map(|s| s.game_id).collect::<Vec<_>>();
+    assert_eq!(ids(page(open.clone(), &filter, None, 10)), vec![2, 3]);
+    assert_eq!(ids(page(open.clone(), &LobbyFilter::default(), None, 2)), vec![1, 2]);
+    assert_eq!(ids(page(open, &LobbyFilter::default(), Some(2), 2)), vec![3, 4]);
+}
@@ -1,543 +1,444 @@
-use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
-use rand::Rng;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Game {
-    creator: String,
-    bet_amount: u64,
-    opponent: Option<String>,
-    creator_card: Option<u8>,
-    opponent_card: Option<u8>,
-    is_settled: bool,
-    start_time: u64,
-    stakes: HashMap<String, u64>, // Added field for stakes
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+#[cfg(feature = "grpc")]
+use std::sync::Arc;
+#[cfg(feature = "grpc")]
+use tokio::sync::RwLock;
+
+#[cfg(feature = "tui")]
+use assessment_rust::dashboard;
+#[cfg(feature = "grpc")]
+use assessment_rust::grpc;
+use assessment_rust::auth::Credentials;
+use assessment_rust::lobby::LobbyFilter;
+#[cfg(feature = "server")]
+use assessment_rust::ratelimit::RateLimiter;
+#[cfg(feature = "server")]
+use assessment_rust::server;
+use assessment_rust::token::{DEFAULT_NAME, DEFAULT_SYMBOL, WEI_PER_ETH};
+use assessment_rust::vault::VAULT;
+use assessment_rust::{AccountId, Context, ERC20Token, GameError, GameState};
+
+// Parts of the game binary, not of the library
+mod repl;
+
+// Every command loads the state file, applies one action and saves it again
+#[derive(Parser, Debug)]
+#[command(name = "game", about = "Stake tokens and play card games against other players.")]
+struct Cli {
+    /// State file, created on the first command that changes something
+    #[arg(long, global = true, default_value = "game_state.json")]
+    state: PathBuf,
+    /// Token export stakes are paid from, created with a new token if it does not exist yet
+    #[arg(long, global = true, default_value = "token.json")]
+    token: PathBuf,
+    #[command(flatten)]
+    new_token: NewToken,
+    /// Bearer credentials the servers take the caller of a request from, see the issue command
+    #[arg(long, global = true, default_value = "credentials.json")]
+    credentials: PathBuf,
+    /// Print results as JSON instead of sentences
+    #[arg(long, global = true)]
+    json: bool,
+    /// Format of the logs written to stderr, RUST_LOG filters them and is info when unset
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    #[command(subcommand)]
+    command: Command,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct GameState {
-    current_game: Option<Game>,
-    stakes: HashMap<String, u64>, // Added field for stakes
-    do_not_use: HashMap<String, bool>, // Added for Denial of Service vulnerability
+// The token created when the token export does not exist yet
+#[derive(Args, Debug)]
+struct NewToken {
+    #[arg(long, global = true, default_value = "OwnerAddress")]
+    owner: AccountId,
+    /// Supply cap of a new token, uncapped when left out
+    #[arg(long, global = true)]
+    max_supply: Option<u64>,
+    /// Name of a new token
+    #[arg(long, global = true, default_value = DEFAULT_NAME)]
+    name: String,
+    /// Symbol amounts of a new token are shown with
+    #[arg(long, global = true, default_value = DEFAULT_SYMBOL)]
+    symbol: String,
+    /// Decimals of a new token, its balances count units of 10^-decimals tokens
+    #[arg(long, global = true, default_value_t = 0)]
+    decimals: u8,
 }
 
-impl GameState {
-    fn new() -> Self {
-        GameState {
-            current_game: None,
-            stakes: HashMap::new(),
-            do_not_use: HashMap::new(), // Initialize for vulnerability
+impl NewToken {
+    fn load(&self, path: &Path) -> Result<ERC20Token, GameError> {
+        if !path.exists() {
+            return Ok(ERC20Token::new(self.owner.clone(), self.name.clone(), self.symbol.clone(), self.decimals, self.max_supply));
         }
+        ERC20Token::import(path).map_err(|e| GameError::Io { path: path.display().to_string(), message: e.to_string() })
     }
+}
 
-    fn initialize(&mut self) {
-        self.current_game = None;
-        self.stakes.clear();
-        self.do_not_use.clear(); // Initialize for vulnerability
-    }
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LogFormat {
+    Text,
+    Json, // One JSON object per line, for log collectors
+}
 
-    fn start_game(&mut self, creator: String, bet: u64) -> Result<(), String> {
-        if self.current_game.is_some() {
-            return Err("Game already started.".to_string());
-        }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Stake tokens of a user, paid into the vault from their token balance
+    Stake { user: AccountId, amount: u64 },
+    /// Start a game and wait for an opponent
+    Start { creator: AccountId, bet: u64 },
+    /// Join an open game
+    Join { game_id: u64, opponent: AccountId },
+    /// Ask for the next round of a joined game the player sits in, played once every player asked
+    Reveal { game_id: u64, player: AccountId },
+    /// Withdraw available tokens from the vault
+    Withdraw { user: AccountId, amount: u64 },
+    /// Show the stake of a user, a game, or the open games when neither is given
+    Status {
+        user: Option<String>,
+        #[arg(long)]
+        game: Option<u64>,
+    },
+    /// Interactive prompt where local players take turns, saving the state file after every action
+    Repl,
+    /// Serve the HTTP API on the state file (server feature)
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Actions each account may take per second, unlimited if not given
+        #[arg(long)]
+        rate_limit: Option<u32>,
+    },
+    /// Serve the gRPC game and token services on the state file (grpc feature)
+    #[cfg(feature = "grpc")]
+    Grpc {
+        #[arg(long, default_value = "127.0.0.1:50051")]
+        addr: String,
+    },
+    /// Issue a credential acting for an account on the servers, its secret is printed only this once
+    Issue { account: AccountId },
+    /// Play through one game and mint some tokens, without touching the state file
+    Demo,
+    /// Follow the event stream of the state file (tui feature)
+    #[cfg(feature = "tui")]
+    Dashboard,
+}
 
-        let user_stake = self.stakes.get(&creator).cloned().unwrap_or(0);
-        if user_stake < bet {
-            return Err("Insufficient stake.".to_string());
-        }
+// What a command prints, a sentence or with --json an object
+#[derive(Debug)]
+struct Report {
+    message: String,
+    json: Value,
+}
 
-        let new_stake = user_stake.checked_sub(bet).ok_or("Overflow error.".to_string())?;
-        self.stakes.insert(creator.clone(), new_stake);
-
-        self.current_game = Some(Game {
-            creator,
-            bet_amount: bet,
-            opponent: None,
-            creator_card: None,
-            opponent_card: None,
-            is_settled: false,
-            start_time: get_current_timestamp(),
-            stakes: self.stakes.clone(),
-        });
-
-        Ok(())
+impl Report {
+    fn new(message: impl Into<String>, json: Value) -> Self {
+        Report { message: message.into(), json }
     }
+}
 
-    fn join_game(&mut self, opponent: String) -> Result<(), String> {
-        if let Some(game) = &mut self.current_game {
-            if game.opponent.is_some() {
-                return Err("Game already joined.".to_string());
-            }
+fn load(path: &Path) -> Result<GameState, GameError> {
+    if path.exists() {
+        GameState::load(path)
+    } else {
+        Ok(GameState::new())
+    }
+}
 
-            if game.creator == opponent {
-                return Err("Cannot join your own game.".to_string());
-            }
-            
-            let user_stake = self.stakes.get(&opponent).cloned().unwrap_or(0);
-            if user_stake < game.bet_amount {
-                return Err("Insufficient stake.".to_string());
+fn run(cli: &Cli) -> Result<Report, GameError> {
+    let mut game_state = load(&cli.state)?;
+    match &cli.command {
+        Command::Issue { account } => {
+            let mut credentials = Credentials::load(&cli.credentials)?;
+            let secret = credentials.issue(account.clone());
+            credentials.save(&cli.credentials)?;
+            let message = format!("Credential for {}: Authorization: Bearer {}", account, secret);
+            Ok(Report::new(message, json!({ "account": account, "secret": secret })))
+        }
+        Command::Demo => {
+            demo();
+            Ok(Report::new("Demo finished.", json!({})))
+        }
+        Command::Repl => {
+            let mut token = cli.new_token.load(&cli.token)?;
+            repl::run(&mut game_state, &mut token, (&cli.state, &cli.token), cli.json)?;
+            Ok(Report::new("Bye.", json!({})))
+        }
+        #[cfg(feature = "server")]
+        Command::Serve { addr, rate_limit } => {
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| GameError::Io { path: addr.clone(), message: e.to_string() })?;
+            let token = cli.new_token.load(&cli.token)?;
+            let mut server = server::Server::new(game_state, token)
+                .saving_to(Some(cli.state.clone()), Some(cli.token.clone()))
+                .with_credentials(Credentials::load(&cli.credentials)?);
+            if let Some(per_sec) = rate_limit {
+                server = server.with_rate_limit(RateLimiter::new(*per_sec));
             }
-
-            let new_stake = user_stake.checked_sub(game.bet_amount).ok_or("Overflow error.".to_string())?;
-            self.stakes.insert(opponent.clone(), new_stake);
-
-            game.opponent = Some(opponent);
-            game.opponent_card = Some(draw_card());
-
-            Ok(())
-        } else {
-            Err("No game to join.".to_string())
+            runtime.block_on(server::serve(addr, server))?;
+            Ok(Report::new("Server stopped.", json!({})))
+        }
+        #[cfg(feature = "grpc")]
+        Command::Grpc { addr } => {
+            let token = cli.new_token.load(&cli.token)?;
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| GameError::Io { path: addr.clone(), message: e.to_string() })?;
+            let service = grpc::Grpc::new(Arc::new(RwLock::new(game_state)), Arc::new(RwLock::new(token)))
+                .saving_to(Some(cli.state.clone()), Some(cli.token.clone()))
+                .with_credentials(Credentials::load(&cli.credentials)?);
+            runtime.block_on(grpc::serve(addr, service))?;
+            Ok(Report::new("Server stopped.", json!({})))
+        }
+        #[cfg(feature = "tui")]
+        Command::Dashboard => {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            game_state.subscribe(move |logged| {
+                let _ = sender.send(logged.clone());
+            });
+            dashboard::run(game_state.events(), receiver, game_state.config().currency.clone())
+                .map_err(|e| GameError::Io { path: "terminal".to_string(), message: e.to_string() })?;
+            Ok(Report::new("Dashboard closed.", json!({})))
+        }
+        command => {
+            let mut token = cli.new_token.load(&cli.token)?;
+            let (report, changed) = execute(&mut game_state, &mut token, command)?;
+            save(&mut game_state, &mut token, changed, (&cli.state, &cli.token))?;
+            Ok(report)
         }
     }
+}
+
+// Saves the state when it changed and the token when it emitted something, which goes to the game's log
+fn save(game_state: &mut GameState, token: &mut ERC20Token, changed: bool, (state, token_path): (&Path, &Path)) -> Result<(), GameError> {
+    let events = token.take_events();
+    if !events.is_empty() {
+        token.export(token_path).map_err(|e| GameError::Io { path: token_path.display().to_string(), message: e.to_string() })?;
+        game_state.log_token_events(events);
+    }
+    if changed {
+        game_state.save(state)?;
+    }
+    Ok(())
+}
 
-            //What is this?
-            // In any case if reentrancy is a concern due to the CEI pattern its better to 
-            //implement a mutex not a hashmap 
-            // The idea is simple: a boolean lock is placed around the susceptible function call. 
-            // The original state of “locked” is false (unlocked). Still, it is set to proper (locked) shortly
-            // before the vulnerable function execution begins and then reset to false (unlocked) when it ends.
-
-
-            // This doesnt work, it can be referred as the Polonius problem 
-            // The current way the borrow checker works, if a lifetime is named, 
-            //then it is deemed to last until the end of the function across all code paths2. 
-            // So even if you have an early return whenever you grab that reference, 
-            //or you drop it across iterations of the loop, it doesn't matter: 
-            // it's still going to be treated as if it's held for the whole function! 
-            // Why this is the case is a much deeper question that I don't have the expertise to answer, but the Polonius update blog post mentioned above goes into some more detail.
-
-            // This is outlined in https://www.lurklurk.org/effective-rust/borrows.html
-
-            fn reveal_cards(&mut self) -> Result<(), String> {
-                if let Some(game) = &mut self.current_game {
-                    if game.is_settled {
-                        return Err("Game already settled.".to_string());
-                    }
-        
-                    if get_current_timestamp() - game.start_time > 600 {
-                        return Err("Game expired.".to_string());
-                    }
-        
-                    let creator_card = draw_card();
-                    game.creator_card = Some(creator_card);
-        
-                    let creator_card = game.creator_card.unwrap();
-                    let opponent_card = game.opponent_card.unwrap();
-        
-                    let bet_amount = game.bet_amount;
-        
-                    let winner = if creator_card > opponent_card {
-                        game.creator.clone()
-                    } else if opponent_card > creator_card {
-                        game.opponent.clone().unwrap()
-                    } else {
-                        // Draw
-                        let new_stake = self.stakes.get(&game.creator).unwrap() + bet_amount;
-                        self.stakes.insert(game.creator.clone(), new_stake);
-                        let new_stake = self.stakes.get(&game.opponent.clone().unwrap()).unwrap() + bet_amount;
-                        self.stakes.insert(game.opponent.clone().unwrap(), new_stake);
-                        game.is_settled = true;
-                        return Ok(()); // Early return to avoid reentrancy
-                    };
-        
-                    // Reentrancy bug introduced here
-                    if let Err(e) = self.reentrant_transfer(&winner, bet_amount * 2) {
-                        return Err(e);
-                    }
-        
-                    game.is_settled = true; // Update state after transfer, vulnerable to reentrancy
-        
-                    Ok(())
-                } else {
-                    Err("No game to reveal.".to_string())
+// Applies one game action, also tells whether the state changed and has to be saved
+fn execute(game_state: &mut GameState, token: &mut ERC20Token, command: &Command) -> Result<(Report, bool), GameError> {
+    let report = match command {
+        // The user runs the command, so the vault is approved for them on the way
+        Command::Stake { user, amount } => {
+            let vault = AccountId::new(VAULT).expect("The vault is a valid account id.");
+            token.approve(&Context::new(user.clone()), vault, *amount)?;
+            game_state.deposit(&Context::new(user.clone()), token, *amount)?;
+            Report::new("Tokens staked successfully.", json!({ "user": user, "staked": amount }))
+        }
+        Command::Start { creator, bet } => {
+            let game_id = game_state.start_game(&Context::new(creator.clone()), *bet)?;
+            Report::new(format!("Game {} started successfully.", game_id), json!({ "game_id": game_id }))
+        }
+        Command::Join { game_id, opponent } => {
+            game_state.join_game(&Context::new(opponent.clone()), *game_id)?;
+            Report::new("Game joined successfully.", json!({ "game_id": game_id, "opponent": opponent }))
+        }
+        Command::Reveal { game_id, player } => {
+            let revealed = game_state.contribute_reveal(&Context::new(player.clone()), *game_id)?;
+            match game_state.game_summary(*game_id) {
+                Some(summary) if !revealed => {
+                    Report::new("Waiting for the other players to reveal.", json!({ "game_id": game_id, "revealed": false, "settled": false, "game": summary }))
                 }
+                Some(summary) => Report::new("Round played, the game goes on.", json!({ "game_id": game_id, "revealed": true, "settled": false, "game": summary })),
+                None => Report::new("Cards revealed, the game is settled.", json!({ "game_id": game_id, "revealed": true, "settled": true })),
             }
-        
-
-    
-    fn reentrant_transfer(&mut self, winner: &String, amount: u64) -> Result<(), String> {
-        if self.do_not_use.contains_key(winner) {
-            return Err("Reentrancy attack detected.".to_string());
         }
+        Command::Withdraw { user, amount } => {
+            game_state.withdraw(&Context::new(user.clone()), token, *amount)?;
+            Report::new("Tokens withdrawn successfully.", json!({ "user": user, "withdrawn": amount }))
+        }
+        Command::Status { user, game } => return Ok((status(game_state, user.as_deref(), *game)?, false)),
+        _ => return Err(GameError::InvalidContent("not a game action".to_string())),
+    };
+    Ok((report, true))
+}
 
-        self.do_not_use.insert(winner.clone(), true); 
-      
-        println!("Transferring {} tokens to {}", amount, winner);
-        self.do_not_use.remove(winner); 
-
-        Ok(())
+fn status(game_state: &GameState, user: Option<&str>, game: Option<u64>) -> Result<Report, GameError> {
+    if let Some(game_id) = game {
+        let summary = game_state.game_summary(game_id).ok_or(GameError::GameNotFound)?;
+        let opponent = summary.opponent.clone().unwrap_or_else(|| "nobody yet".to_string());
+        let message = format!(
+            "Game {}: {} against {}, pot of {}.",
+            game_id,
+            summary.creator,
+            opponent,
+            game_state.format_amount(summary.pot)
+        );
+        return Ok(Report::new(message, json!(summary)));
+    }
+    if let Some(user) = user {
+        let stake = game_state.stake_of(user);
+        let message = format!(
+            "Current stakes for {} are: {} available, {} locked.",
+            user,
+            game_state.format_amount(stake.available.get()),
+            game_state.format_amount(stake.locked.get())
+        );
+        return Ok(Report::new(message, json!({ "user": user, "available": stake.available, "locked": stake.locked })));
     }
+    let open = game_state.list_open_games(&LobbyFilter::default(), None, usize::MAX);
+    let lines: Vec<String> = open
+        .iter()
+        .map(|summary| format!("Game {} by {}, bet {}.", summary.game_id, summary.creator, game_state.format_amount(summary.bet)))
+        .collect();
+    let message = if lines.is_empty() { "No open games.".to_string() } else { lines.join("\n") };
+    Ok(Report::new(message, json!(open)))
+}
 
-    fn stake_tokens(&mut self, user: String, amount: u64) -> Result<(), String> {
-        let current_stake = self.stakes.get(&user).cloned().unwrap_or(0);
-        let new_stake = current_stake.checked_add(amount).ok_or("Overflow error.".to_string())?;
-        self.stakes.insert(user, new_stake);
-        Ok(())
+// Reports go to stdout, logs to stderr so scripts reading the reports do not see them
+fn init_logging(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logs = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => logs.init(),
+        LogFormat::Json => logs.json().init(),
     }
+}
 
-    fn withdraw_stake(&mut self, user: String, amount: u64) -> Result<(), String> {
-        let current_stake = self.stakes.get(&user).cloned().ok_or("User not found.".to_string())?;
-        println!("Current stakes for {} are: {}", user, current_stake);
-        if current_stake < amount {
-            return Err("Insufficient funds.".to_string());
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(cli.log_format);
+    match run(&cli) {
+        Ok(report) if cli.json => println!("{}", report.json),
+        Ok(report) => println!("{}", report.message),
+        Err(e) if cli.json => {
+            println!("{}", json!({ "error": e.to_string() }));
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
         }
-        let new_stake = current_stake.checked_sub(amount).ok_or("Overflow error.".to_string())?;
-        self.stakes.insert(user, new_stake);
-        Ok(())
     }
+    ExitCode::SUCCESS
 }
 
-// Cards are not draw in the same transaction. If this is deployed with current codebase, opponent can draw a card and if creator sees its a high number 
-// it can run initialize function to drop game
-
-// There is no access control in the functions, everybody can call any function at any time, 
-// Example https://github.com/OpenZeppelin/rust-contracts-stylus/blob/main/contracts/src/access/control.rs RBAC on Stylus (Arbitrum)
-
+fn demo() {
+    let account = |name: &str| AccountId::new(name).expect("Demo names are valid account ids.");
+    let caller = |name: &str| Context::new(account(name));
+    let mut token = ERC20Token::new(account("OwnerAddress"), DEFAULT_NAME, DEFAULT_SYMBOL, 0, None);
 
-// Insecure randomness 
-// This function uses uniform distribution
-//Any number generator guaranteeing a uniform distribution is not random. 
-// That said, the more numbers you generate, the more likely it is to resemble a uniform distribution.
-
-// Also you can't use a typical random number generator since you are running inside of a virtual 
-// machine with no access to typical random seed generators like hardware clock or other machine data
-// Instead nstead consider using the provided random seed you can get through a couple of functions exposed 
-// on env like env::random_seed https://github.com/near/near-sdk-rs/blob/master/near-sdk/src/environment/env.rs#L236-L254
-
-// or Verifiable Random Function implementation in the BABE pallet.
+    // Mint tokens
+    match token.mint(&caller("User1"), 100, WEI_PER_ETH / 10) {
+        Ok(()) => info!(user = "User1", amount = 100, "Minted tokens successfully."),
+        Err(e) => warn!(user = "User1", amount = 100, error = %e, "Error minting tokens."),
+    }
 
-fn draw_card() -> u8 {
-    rand::thread_rng().gen_range(1..=13)
-}
+    // Adjust price, only the owner may
+    if let Err(e) = token.adjust_price(&caller("User1"), 0) {
+        warn!(caller = "User1", error = %e, "Error adjusting price.");
+    }
+    token.adjust_price(&caller("OwnerAddress"), WEI_PER_ETH / 500).expect("The demo owner sets the price.");
+    info!(wei_per_token = token.mint_price(), "New mint price set.");
 
-fn get_current_timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs()
-}
+    // Transfer tokens
+    match token.transfer(&caller("User1"), account("User2"), 50) {
+        Ok(()) => info!(from = "User1", to = "User2", amount = 50, "Tokens transferred successfully."),
+        Err(e) => warn!(from = "User1", to = "User2", amount = 50, error = %e, "Error transferring tokens."),
+    }
 
+    // Get balances
+    for user in ["User1", "User2"] {
+        info!(user, balance = %token.format_amount(token.get_balance(user)), "Token balance.");
+    }
 
-fn main() {
     let mut game_state = GameState::new();
 
-    // Example of staking tokens
-    match game_state.stake_tokens("Alice".to_string(), 18446744073709551615) {
-        Ok(()) => println!("Tokens staked successfully."),
-        Err(e) => println!("Error staking tokens: {}", e),
-    }
-
-    match game_state.stake_tokens("Bob".to_string(), 18446744073709551615) {
-        Ok(()) => println!("Tokens staked successfully."),
-        Err(e) => println!("Error staking tokens: {}", e),
+    // Example of staking tokens, paid into the vault
+    for user in ["Alice", "Bob"] {
+        if let Err(e) = token.mint(&caller(user), 100, 100 * token.mint_price()) {
+            warn!(user, amount = 100, error = %e, "Error minting tokens.");
+        }
+        if let Err(e) = token.approve(&caller(user), account(VAULT), u64::MAX) {
+            warn!(user, error = %e, "Error approving the vault.");
+        }
+        match game_state.deposit(&Context::new(account(user)), &mut token, 100) {
+            Ok(()) => info!(user, amount = 100, "Tokens staked successfully."),
+            Err(e) => warn!(user, amount = 100, error = %e, "Error staking tokens."),
+        }
     }
 
     // Start a game with staked tokens
-    match game_state.start_game("Alice".to_string(),18446744073709551615) {
-        Ok(()) => println!("Game started successfully."),
-        Err(e) => println!("Error starting game: {}", e),
-    }
+    let game_id = match game_state.start_game(&Context::new(account("Alice")), 30) {
+        Ok(game_id) => {
+            info!(game_id, creator = "Alice", bet = 30, "Game started successfully.");
+            game_id
+        }
+        Err(e) => {
+            warn!(creator = "Alice", bet = 30, error = %e, "Error starting game.");
+            0
+        }
+    };
 
     // Join the game
-    match game_state.join_game("Bob".to_string()) {
-        Ok(()) => println!("Game joined successfully."),
-        Err(e) => println!("Error joining game: {}", e),
+    match game_state.join_game(&Context::new(account("Bob")), game_id) {
+        Ok(()) => info!(game_id, opponent = "Bob", "Game joined successfully."),
+        Err(e) => warn!(game_id, opponent = "Bob", error = %e, "Error joining game."),
     }
 
-    // Reveal cards
-    match game_state.reveal_cards() {
-        Ok(()) => println!("Cards revealed."),
-        Err(e) => println!("Error revealing cards: {}", e),
+    // Reveal cards, once both players asked for it
+    for user in ["Alice", "Bob"] {
+        match game_state.contribute_reveal(&Context::new(account(user)), game_id) {
+            Ok(true) => info!(game_id, "Cards revealed."),
+            Ok(false) => info!(game_id, user, "Waiting for the other player to reveal."),
+            Err(e) => warn!(game_id, user, error = %e, "Error revealing cards."),
+        }
     }
 
     // Withdraw tokens
-    match game_state.withdraw_stake("Alice".to_string(), 0) {
-        Ok(()) => println!("Tokens withdrawn successfully."),
-        Err(e) => println!("Error withdrawing tokens: {}", e),
-    }
-
-    match game_state.withdraw_stake("Bob".to_string(), 0) {
-        Ok(()) => println!("Tokens withdrawn successfully."),
-        Err(e) => println!("Error withdrawing tokens: {}", e),
+    for user in ["Alice", "Bob"] {
+        match game_state.withdraw(&Context::new(account(user)), &mut token, 0) {
+            Ok(()) => info!(user, amount = 0, "Tokens withdrawn successfully."),
+            Err(e) => warn!(user, amount = 0, error = %e, "Error withdrawing tokens."),
+        }
     }
 }
 
-// Bussiness logic issues functions can be invoked without calling start game, this is a high issue 
-// since all the game logic can be flawed and protocol's crash or DoS can occur
-
-#[test]
-#[should_panic]
-fn test_bussiness_logic_join_game(){
-    let mut game_state2 = GameState::new();
-    
-    //@audit-issue It is possible to call join the game at any stage 
-    let result = game_state2.join_game("Alice".to_string());
-    assert!(result.is_ok(), "Error joining game: {:?}", result.unwrap_err());
-}
-
-
-#[test]
-#[should_panic]
-fn test_bussiness_logic_reveal_cards(){
-    let mut game_state2 = GameState::new();
-    
-    //@audit-issue It is possible to call before start
-    let result = game_state2.reveal_cards();
-    assert!(result.is_ok(), "Error revealing cards: {:?}", result.unwrap_err());
-}
-
-
 #[test]
-#[should_panic]
-fn test_bussiness_logic_withdraw(){
-    let mut game_state2 = GameState::new();
-    
-    //@audit-issue It is possible to call withdraw
-    let result = game_state2.withdraw_stake("Alice".to_string(), 100);
-    assert!(result.is_ok(), "Error witdhrawing tokens: {:?}", result.unwrap_err());
-}
-
-
-
-#[test]
-#[should_panic]
-fn test_bussiness_logic_start(){
-    let mut game_state2 = GameState::new();
-    
-    //@audit-issue after start any function can be called 
-    let status = game_state2.start_game("Alice".to_string(), 0);
-    assert!(status.is_ok(), "Error starting game: {:?}", status.unwrap_err());
-
-
-    let result = game_state2.reveal_cards();
-    assert!(result.is_ok(), "Error revealing cards: {:?}", result.unwrap_err());
-
-}
-
-// It is possible to call the stake function with zero amount. Protocol griefing
-
-#[test]
-fn test_zero_stake(){
-
-    let mut game_state3 = GameState::new();
-
-    // 
-    let stake1 = game_state3.stake_tokens("Alice".to_string(), 0); 
-    assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-    let stake2 = game_state3.stake_tokens("Bob".to_string(), 0 );
-    assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-}
-
-// The game allows a creator to witdraw stake zero amount
-
-#[test]
-fn test_withdraw_zero_amount(){
-
-    let mut game_state3 = GameState::new();
-
-    // Example of staking tokens
-    let stake1 = game_state3.stake_tokens("Alice".to_string(), 10); 
-    assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-    
-
-    let withdraw = game_state3.withdraw_stake("Alice".to_string(), 0);
-    assert!(withdraw.is_ok(), "Error revealing cards: {:?}", withdraw.unwrap_err());
-
-}
-
-// The game allows a creator to place bets of zero amount and play the game completely
-
-#[test]
-fn test_bets_and_amount_with_zero(){
-
-    let mut game_state3 = GameState::new();
-
-    // Example of staking tokens
-    let stake1 = game_state3.stake_tokens("Alice".to_string(), 0); 
-    assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-    let stake2 = game_state3.stake_tokens("Bob".to_string(), 0 );
-    assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-    // Start a game with staked tokens
-    let start1 = game_state3.start_game("Alice".to_string(), 0); 
-    assert!(start1.is_ok(), "Error starting game: {:?}", start1.unwrap_err());
-    // Join the game
-    let join1 = game_state3.join_game("Bob".to_string()); 
-    assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
-    // Reveal cards
-    let reveal = game_state3.reveal_cards(); 
-    assert!(reveal.is_ok(), "Error revealing cards: {:?}", reveal.unwrap_err());
-
-    let withdraw = game_state3.withdraw_stake("Alice".to_string(), 0);
-    assert!(withdraw.is_ok(), "Error revealing cards: {:?}", withdraw.unwrap_err());
-
-}
-
-#[test]
-#[should_panic]
-
-// If the Game is expired or reveal cards is not invoked for any reason the bets are lost and users stake is reduced
-fn test_bets_are_lost(){
-
-   
-    let mut game_state3 = GameState::new();
-
-    // Alice is 100
-    // Bob is 200
-
-    let stake1 = game_state3.stake_tokens("Alice".to_string(), 100); 
-    assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-    let stake2 = game_state3.stake_tokens("Bob".to_string(), 200 );
-    assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-    // Start a game with staked tokens
-
-    let start1 = game_state3.start_game("Alice".to_string(), 10); 
-    assert!(start1.is_ok(), "Error starting game: {:?}", start1.unwrap_err());
-    // Join the game
-    let join1 = game_state3.join_game("Bob".to_string()); 
-    assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
- 
-    // Expiration time - Can use a Mock here for the time elapsed 
-
-    let reveal = game_state3.reveal_cards(); 
-   
-    // Alice is 90 
-    // Bob is 190
-
-    let _result = game_state3.withdraw_stake("Alice".to_string(), 0);
-    let _result = game_state3.withdraw_stake("Bob".to_string(), 0);
-
-    // Just trigger the error in reveal cards
-
-    assert!(!reveal.is_ok(), "Error time expired: {:?}", reveal.unwrap_err());
-
-
-}
-
-// Oponent can DOS a creator game
-#[test]
-#[should_panic]
-fn test_initialize_dos_opponent(){
-
-   
-  
-        let mut game_state3 = GameState::new();
-    
-        let stake1 = game_state3.stake_tokens("Alice".to_string(), 100); 
-        assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-        let stake2 = game_state3.stake_tokens("Bob".to_string(), 200 );
-        assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-        // Start a game with staked tokens
-    
-        let start1 = game_state3.start_game("Alice".to_string(), 10); 
-        assert!(start1.is_ok(), "Error starting game: {:?}", start1.unwrap_err());
-
-        game_state3.initialize();
-        // Join the game
-        let join1 = game_state3.join_game("Bob".to_string()); 
-        assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
-    
-        let reveal = game_state3.reveal_cards();      
-    
-   
-        assert!(reveal.is_ok(), "Error time expired: {:?}", reveal.unwrap_err());
-    
+fn test_commands_share_the_state_file() {
+    let state = std::env::temp_dir().join(format!("cli_state_{}.json", std::process::id()));
+    let token = std::env::temp_dir().join(format!("cli_token_{}.json", std::process::id()));
+    let credentials = std::env::temp_dir().join(format!("cli_credentials_{}.json", std::process::id()));
+    let paths = ["--state", state.to_str().unwrap(), "--token", token.to_str().unwrap(), "--credentials", credentials.to_str().unwrap()];
+    let cli = |args: &[&str]| Cli::parse_from(["game"].iter().chain(&paths).chain(args));
+
+    // Nobody holds tokens of a new token yet
+    assert!(run(&cli(&["stake", "Alice", "100"])).is_err());
+    let mut minted = ERC20Token::new(AccountId::new("OwnerAddress").unwrap(), DEFAULT_NAME, DEFAULT_SYMBOL, 0, None);
+    minted.adjust_price(&Context::new(AccountId::new("OwnerAddress").unwrap()), 0).unwrap();
+    for user in ["Alice", "Bob"] {
+        minted.mint(&Context::new(AccountId::new(user).unwrap()), 100, 0).unwrap();
     }
-
-
-
-    // Anybody can call initialize before revealing cards
-
-    #[test]
-    #[should_panic]
-    fn test_initialize_dos_reveal(){    
-       
-      
-            let mut game_state3 = GameState::new();
-        
-            let stake1 = game_state3.stake_tokens("Alice".to_string(), 100); 
-            assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-            let stake2 = game_state3.stake_tokens("Bob".to_string(), 200 );
-            assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-            // Start a game with staked tokens
-        
-            let start1 = game_state3.start_game("Alice".to_string(), 10); 
-            assert!(start1.is_ok(), "Error starting game: {:?}", start1.unwrap_err());
-    
-           
-            // Join the game
-            let join1 = game_state3.join_game("Bob".to_string()); 
-            assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
-
-
-            game_state3.initialize();
-        
-            let reveal = game_state3.reveal_cards();      
-        
-       
-            assert!(reveal.is_ok(), "Error time expired: {:?}", reveal.unwrap_err());
-        
-        }
-
-    // Issue: While there are specific cases that should trigger an error, (ie. Opponent cannot join game twice)
-    // This is not gracefully handled by the app/protocol and the game state is lost
-
-    #[test]
-    #[should_panic]
-    fn test_join_fails(){
-
-    let mut game_state3 = GameState::new();
-
-    // Example of staking tokens
-    let stake1 = game_state3.stake_tokens("Alice".to_string(), 0); 
-    assert!(stake1.is_ok(), "Error in stake: {:?}", stake1.unwrap_err());
-    let stake2 = game_state3.stake_tokens("Bob".to_string(), 0 );
-    assert!(stake2.is_ok(), "Error in stake: {:?}", stake2.unwrap_err());
-    // Start a game with staked tokens
-    let start1 = game_state3.start_game("Alice".to_string(), 0); 
-    assert!(start1.is_ok(), "Error starting game: {:?}", start1.unwrap_err());
-    // Join the game
-    let join1 = game_state3.join_game("Bob".to_string()); 
-    assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
-    let join1 = game_state3.join_game("Bob".to_string()); 
-    assert!(join1.is_ok(), "Error joining game: {:?}", join1.unwrap_err());
-
-    let reveal = game_state3.reveal_cards(); 
-    assert!(reveal.is_ok(), "Error revealing cards: {:?}", reveal.unwrap_err());
-
-
-
+    minted.export(&token).unwrap();
+
+    run(&cli(&["stake", "Alice", "100"])).unwrap();
+    run(&cli(&["stake", "Bob", "100"])).unwrap();
+    let started = run(&cli(&["--json", "start", "Alice", "40"])).unwrap();
+    let game_id = started.json["game_id"].as_u64().unwrap();
+    run(&cli(&["join", &game_id.to_string(), "Bob"])).unwrap();
+
+    let status = run(&cli(&["status", "Bob"])).unwrap();
+    assert_eq!(status.json, json!({ "user": "Bob", "available": 60, "locked": 40 }));
+    assert_eq!(run(&cli(&["withdraw", "Bob", "61"])).unwrap_err(), GameError::InsufficientFunds);
+    run(&cli(&["withdraw", "Bob", "60"])).unwrap();
+    let paid = ERC20Token::import(&token).unwrap();
+    assert_eq!((paid.get_balance("Bob").get(), paid.get_balance(VAULT).get()), (60, 140));
+    load(&state).unwrap().check_vault(&paid).unwrap();
+
+    let issued = run(&cli(&["--json", "issue", "Bob"])).unwrap();
+    let secret = issued.json["secret"].as_str().unwrap();
+    assert_eq!(Credentials::load(&credentials).unwrap().authenticate(secret), Ok(&AccountId::new("Bob").unwrap()));
+    for path in [&state, &token, &credentials] {
+        std::fs::remove_file(path).unwrap();
     }
-
-// when cards are revealed and either creator or opponent wins, the bet is not added to the winner
-// If the game is not draw, creator and opponent are just losing the bet ammount
-
-
-// Example from main function creator wins
-// Bet is : 18446744073709551615
-
-//Tokens staked successfully.
-//Tokens staked successfully.
-//Game started successfully.
-//Game joined successfully.
-//creator won
-//Cards revealed.
-//Current stakes for Alice are: 0
-//Tokens withdrawn successfully.
-//Current stakes for Bob are: 0
-//Tokens withdrawn successfully.
-
-// When the game is draw both get their bets back
-
-
-//Tokens staked successfully.
-//Tokens staked successfully.
-//Game started successfully.
-//Game joined successfully.
-//Draw
-//Cards revealed.
-//Current stakes for Alice are: 18446744073709551615
-//Tokens withdrawn successfully.
-//Current stakes for Bob are: 18446744073709551615
-//Tokens withdrawn successfully.
+}
@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+
+use crate::account_id::AccountId;
+use crate::error::GameError;
+
+// A player waiting for an opponent. With a band, only opponents rated within `band` points are accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+    pub player: AccountId,
+    pub bet: u64,
+    pub band: Option<u32>,
+    pub queued_at: u64,
+}
+
+impl Ticket {
+    fn accepts(&self, own_rating: i32, other_rating: i32) -> bool {
+        match self.band {
+            Some(band) => own_rating.abs_diff(other_rating) <= band,
+            None => true,
+        }
+    }
+}
+
+// First come, first served: the oldest ticket is paired with the oldest compatible one
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Matchmaking {
+    queue: Vec<Ticket>,
+}
+
+impl Matchmaking {
+    pub fn new() -> Self {
+        Matchmaking::default()
+    }
+
+    pub fn enqueue(&mut self, ticket: Ticket) -> Result<(), GameError> {
+        if self.queue.iter().any(|t| t.player == ticket.player) {
+            return Err(GameError::AlreadyQueued);
+        }
+        self.queue.push(ticket);
+        Ok(())
+    }
+
+    // Puts a ticket taken out by take_match back at its original place
+    pub fn requeue(&mut self, ticket: Ticket) {
+        let index = self.queue.iter().position(|t| t.queued_at > ticket.queued_at).unwrap_or(self.queue.len());
+        self.queue.insert(index, ticket);
+    }
+
+    pub fn remove(&mut self, player: &str) -> Option<Ticket> {
+        let index = self.queue.iter().position(|t| t.player == player)?;
+        Some(self.queue.remove(index))
+    }
+
+    pub fn queued(&self) -> &[Ticket] {
+        &self.queue
+    }
+
+    // Takes the first compatible pair out of the queue, same bet and each inside the other's band
+    pub fn take_match(&mut self, rating: impl Fn(&str) -> i32) -> Option<(Ticket, Ticket)> {
+        for (i, first) in self.queue.iter().enumerate() {
+            let first_rating = rating(&first.player);
+            let found = self.queue[i + 1..].iter().position(|second| {
+                let second_rating = rating(&second.player);
+                first.bet == second.bet
+                    && first.accepts(first_rating, second_rating)
+                    && second.accepts(second_rating, first_rating)
+            });
+            if let Some(offset) = found {
+                let second = self.queue.remove(i + 1 + offset);
+                let first = self.queue.remove(i);
+                return Some((first, second));
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_pairs_same_bet_within_bands() {
+    let ticket = |player: &str, bet, band| Ticket { player: crate::account_id::account(player), bet, band, queued_at: 0 };
+    let rating = |player: &str| match player {
+        "Alice" => 1500,
+        "Bob" => 1200,
+        _ => 1210,
+    };
+
+    let mut matchmaking = Matchmaking::new();
+    matchmaking.enqueue(ticket("Alice", 10, Some(100))).unwrap();
+    matchmaking.enqueue(ticket("Bob", 10, None)).unwrap();
+    matchmaking.enqueue(ticket("Carol", 20, None)).unwrap();
+    assert!(matchmaking.enqueue(ticket("Bob", 5, None)).is_err());
+    // Alice is too far from Bob, Carol wants another bet
+    assert!(matchmaking.take_match(rating).is_none());
+
+    matchmaking.enqueue(ticket("Dave", 10, Some(50))).unwrap();
+    let (first, second) = matchmaking.take_match(rating).unwrap();
+    assert_eq!((first.player.as_str(), second.player.as_str()), ("Bob", "Dave"));
+    assert_eq!(matchmaking.queued().len(), 2);
+    assert!(matchmaking.remove("Alice").is_some());
+}
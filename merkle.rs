@@ -0,0 +1,129 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::balance::Balance;
+use crate::commitments::{from_hex, to_hex};
+use crate::error::GameError;
+use crate::GameState;
+
+type Hash = [u8; 32];
+
+// Root of a tree without leaves
+pub const EMPTY_ROOT: Hash = [0; 32];
+
+// Leaves and inner nodes are hashed with different prefixes, so no leaf can pass for a node. The account is
+// prefixed with its length and the amounts are big endian, as a contract would encode them.
+pub fn leaf_hash(account: &str, balance: &Balance) -> Hash {
+    Sha256::new()
+        .chain_update([0u8])
+        .chain_update((account.len() as u64).to_be_bytes())
+        .chain_update(account)
+        .chain_update(balance.available.get().to_be_bytes())
+        .chain_update(balance.locked.get().to_be_bytes())
+        .finalize()
+        .into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new().chain_update([1u8]).chain_update(left).chain_update(right).finalize().into()
+}
+
+// One level up, a node left without a sibling moves up as it is
+fn parents(level: &[Hash]) -> Vec<Hash> {
+    level.chunks(2).map(|pair| if let [left, right] = pair { node_hash(left, right) } else { pair[0] }).collect()
+}
+
+pub fn root(leaves: &[Hash]) -> Hash {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = parents(&level);
+    }
+    level.first().copied().unwrap_or(EMPTY_ROOT)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Left,
+    Right,
+}
+
+// Sibling of the path at one level, hex encoded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofStep {
+    pub sibling: String,
+    pub position: Position, // Where the sibling goes when hashing the two
+}
+
+// Everything needed to check the stake of one account against a published root, leaf to root
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub account: String,
+    pub balance: Balance,
+    pub path: Vec<ProofStep>,
+}
+
+impl MerkleProof {
+    // Whether the proof leads from the account's balance to `root`, a hex encoded root
+    pub fn verify(&self, root: &str) -> bool {
+        let mut hash = leaf_hash(&self.account, &self.balance);
+        for step in &self.path {
+            let Some(sibling) = from_hex(&step.sibling).and_then(|bytes| Hash::try_from(bytes).ok()) else { return false };
+            hash = match step.position {
+                Position::Left => node_hash(&sibling, &hash),
+                Position::Right => node_hash(&hash, &sibling),
+            };
+        }
+        to_hex(&hash) == root
+    }
+}
+
+pub fn prove(leaves: &[Hash], mut index: usize) -> Vec<ProofStep> {
+    let mut path = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            let position = if sibling < index { Position::Left } else { Position::Right };
+            path.push(ProofStep { sibling: to_hex(&level[sibling]), position });
+        }
+        level = parents(&level);
+        index /= 2;
+    }
+    path
+}
+
+impl GameState {
+    // Leaves of every balance, in account order
+    fn stake_leaves(&self) -> (Vec<&String>, Vec<Hash>) {
+        let mut accounts: Vec<&String> = self.balances.keys().collect();
+        accounts.sort();
+        let leaves = accounts.iter().map(|account| leaf_hash(account, &self.balances[*account])).collect();
+        (accounts, leaves)
+    }
+
+    // Hex encoded root of the tree over every (account, balance) pair, for light clients and bridges that
+    // check single stakes with prove_stake without the whole state
+    pub fn stakes_merkle_root(&self) -> String {
+        to_hex(&root(&self.stake_leaves().1))
+    }
+
+    pub fn prove_stake(&self, account: &str) -> Result<MerkleProof, GameError> {
+        let (accounts, leaves) = self.stake_leaves();
+        let index = accounts.binary_search_by(|probe| probe.as_str().cmp(account)).map_err(|_| GameError::UserNotFound)?;
+        Ok(MerkleProof { account: account.to_string(), balance: self.balances[account], path: prove(&leaves, index) })
+    }
+}
+
+#[test]
+fn test_every_leaf_proves_against_the_root() {
+    let leaves: Vec<Hash> = (0..7u64).map(|i| leaf_hash(&format!("Player{}", i), &Balance::new(i, 1))).collect();
+    let root = to_hex(&root(&leaves));
+    for index in 0..leaves.len() {
+        let proof = MerkleProof { account: format!("Player{}", index), balance: Balance::new(index as u64, 1), path: prove(&leaves, index) };
+        assert!(proof.verify(&root));
+        assert!(!MerkleProof { balance: Balance::new(index as u64, 2), ..proof.clone() }.verify(&root));
+        assert!(!MerkleProof { account: "Mallory".to_string(), ..proof }.verify(&root));
+    }
+    assert_eq!(self::root(&leaves[..1]), leaves[0]);
+    assert_eq!(self::root(&[]), EMPTY_ROOT);
+}
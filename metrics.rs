@@ -0,0 +1,201 @@
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", feature = "near"))]
+use crate::near::Instant;
+#[cfg(all(target_arch = "wasm32", feature = "stylus", not(feature = "near")))]
+use crate::stylus::Instant;
+#[cfg(all(target_arch = "wasm32", not(any(feature = "near", feature = "stylus"))))]
+use web_time::Instant; // std::time panics in the browser, this one reads the clock of the page
+
+use crate::error::ErrorKind;
+use crate::events::{GameEvent, LoggedEvent};
+
+// Upper bounds of the latency buckets in microseconds, the last bucket takes everything above
+pub const LATENCY_BUCKETS_MICROS: [u64; 7] = [10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; LATENCY_BUCKETS_MICROS.len() + 1], // Not cumulative, one count per bucket
+    pub count: u64,
+    pub sum_micros: u64,
+    pub max_micros: u64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, micros: u64) {
+        let bucket = LATENCY_BUCKETS_MICROS.iter().position(|&bound| micros <= bound).unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+}
+
+// An operation that took at least the configured threshold, waiting to be emitted as an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowOperation {
+    pub operation: String,
+    pub micros: u64,
+    pub timestamp: u64,
+    pub trace: String, // Sizes of the state when the operation started, then the backtrace where it ended
+}
+
+// Protocol activity, counted from the event log as it grows and from refusals the transports report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivityCounters {
+    pub events_counted: usize, // Events of the log already counted
+    pub games_started: u64,
+    pub games_settled: u64,
+    pub errors: BTreeMap<String, u64>, // By ErrorKind
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    latencies: BTreeMap<String, LatencyHistogram>,
+    slow: Vec<SlowOperation>,
+    activity: ActivityCounters,
+}
+
+// Latency of every operation by name. Timers record into it when they are dropped, so every early
+// return is measured too. Like the reentrancy guard, the data sits behind an Arc so a running timer
+// does not keep `self` borrowed.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+pub struct OperationTimer {
+    recorded: Arc<Mutex<Recorded>>,
+    operation: &'static str,
+    started: Instant,
+    timestamp: u64,
+    slow_micros: u64,
+    state_sizes: String,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    // Operations running for `slow_micros` or longer are kept with a trace, `state_sizes` goes first in it
+    pub fn start(&self, operation: &'static str, timestamp: u64, slow_micros: u64, state_sizes: String) -> OperationTimer {
+        OperationTimer {
+            recorded: Arc::clone(&self.recorded),
+            operation,
+            started: Instant::now(),
+            timestamp,
+            slow_micros,
+            state_sizes,
+        }
+    }
+
+    pub fn histogram(&self, operation: &str) -> Option<LatencyHistogram> {
+        self.lock().latencies.get(operation).cloned()
+    }
+
+    pub fn take_slow_operations(&self) -> Vec<SlowOperation> {
+        std::mem::take(&mut self.lock().slow)
+    }
+
+    // Counts the events of `log` logged since the last call, `log` is the whole event log every time
+    pub fn count_events(&self, log: &[LoggedEvent]) {
+        let mut recorded = self.lock();
+        let activity = &mut recorded.activity;
+        for logged in log.get(activity.events_counted..).unwrap_or_default() {
+            match logged.event {
+                GameEvent::GameStarted { .. } => activity.games_started += 1,
+                GameEvent::Settled { .. } => activity.games_settled += 1,
+                _ => {}
+            }
+        }
+        activity.events_counted = log.len();
+    }
+
+    pub fn count_error(&self, kind: ErrorKind) {
+        *self.lock().activity.errors.entry(format!("{:?}", kind)).or_default() += 1;
+    }
+
+    // Prometheus text format, bucket counts are cumulative there
+    pub fn export(&self) -> String {
+        let recorded = self.lock();
+        let mut out = String::from("# TYPE operation_latency_micros histogram\n");
+        for (operation, histogram) in &recorded.latencies {
+            let mut cumulative = 0;
+            for (index, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = LATENCY_BUCKETS_MICROS.get(index).map_or("+Inf".to_string(), |bound| bound.to_string());
+                let _ = writeln!(out, "operation_latency_micros_bucket{{operation=\"{}\",le=\"{}\"}} {}", operation, bound, cumulative);
+            }
+            let _ = writeln!(out, "operation_latency_micros_sum{{operation=\"{}\"}} {}", operation, histogram.sum_micros);
+            let _ = writeln!(out, "operation_latency_micros_count{{operation=\"{}\"}} {}", operation, histogram.count);
+        }
+        let activity = &recorded.activity;
+        let _ = writeln!(out, "# TYPE games_started_total counter\ngames_started_total {}", activity.games_started);
+        let _ = writeln!(out, "# TYPE games_settled_total counter\ngames_settled_total {}", activity.games_settled);
+        out.push_str("# TYPE errors_total counter\n");
+        for (kind, count) in &activity.errors {
+            let _ = writeln!(out, "errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+        out
+    }
+
+    // A timer that panicked while recording must not take the metrics down with it
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recorded> {
+        self.recorded.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// A cloned state starts with empty metrics instead of sharing the original's
+impl Clone for Metrics {
+    fn clone(&self) -> Self {
+        Metrics::new()
+    }
+}
+
+impl Drop for OperationTimer {
+    fn drop(&mut self) {
+        let micros = self.started.elapsed().as_micros().min(u64::MAX as u128) as u64;
+        let mut recorded = self.recorded.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        recorded.latencies.entry(self.operation.to_string()).or_default().record(micros);
+        if micros >= self.slow_micros {
+            recorded.slow.push(SlowOperation {
+                operation: self.operation.to_string(),
+                micros,
+                timestamp: self.timestamp,
+                trace: format!("{}\n{}", self.state_sizes, Backtrace::force_capture()),
+            });
+        }
+    }
+}
+
+#[test]
+fn test_histogram_buckets_and_export() {
+    let metrics = Metrics::new();
+    let mut histogram = LatencyHistogram::default();
+    for micros in [5, 10, 11, 20_000_000] {
+        histogram.record(micros);
+    }
+    assert_eq!(histogram.buckets, [2, 1, 0, 0, 0, 0, 0, 1]);
+    assert_eq!((histogram.count, histogram.max_micros), (4, 20_000_000));
+
+    drop(metrics.start("fast", 0, u64::MAX, String::new()));
+    drop(metrics.start("slow", 7, 0, "games: 3".to_string()));
+    assert_eq!(metrics.histogram("fast").unwrap().count, 1);
+    let slow = metrics.take_slow_operations();
+    assert_eq!(slow.len(), 1);
+    assert_eq!((slow[0].operation.as_str(), slow[0].timestamp), ("slow", 7));
+    assert!(slow[0].trace.starts_with("games: 3\n"));
+    assert!(metrics.take_slow_operations().is_empty());
+
+    metrics.count_error(ErrorKind::Conflict);
+    metrics.count_error(ErrorKind::Conflict);
+    let export = metrics.export();
+    assert!(export.contains("errors_total{kind=\"Conflict\"} 2\n"));
+    assert!(export.contains("operation_latency_micros_bucket{operation=\"fast\",le=\"+Inf\"} 1"));
+    assert!(export.contains("operation_latency_micros_count{operation=\"slow\"} 1"));
+}
@@ -0,0 +1,89 @@
+use serde_json::{json, Map, Value};
+
+use crate::audit::AuditLog;
+use crate::error::GameError;
+use crate::events::LoggedEvent;
+
+// Version written into every serialized GameState. Raise it with every change that needs a migration
+// below, and add a fixture of the old format to the tests.
+pub const STATE_VERSION: u32 = 3;
+
+// Version of a serialized state, states saved before the field existed are version 1
+pub fn version_of(state: &Value) -> Result<u32, GameError> {
+    match state.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .map(|version| version as u32)
+            .ok_or(GameError::InvalidContent("version is not a number".to_string())),
+    }
+}
+
+// Upgrades a serialized state of any earlier version to STATE_VERSION, one version at a time.
+// Returns the version it started from.
+pub fn migrate(state: &mut Value) -> Result<u32, GameError> {
+    let from = version_of(state)?;
+    if from > STATE_VERSION {
+        return Err(GameError::UnsupportedVersion(from));
+    }
+    for version in from..STATE_VERSION {
+        match version {
+            1 => v1_to_v2(state)?,
+            2 => v2_to_v3(state)?,
+            _ => unreachable!("every version below STATE_VERSION has a migration"),
+        }
+        state["version"] = json!(version + 1);
+    }
+    Ok(from)
+}
+
+// Version 1 games copied every balance into a `stakes` snapshot and had no pot of their own,
+// and there was no escrow yet: each game's pot is its bet once per seated player.
+fn v1_to_v2(state: &mut Value) -> Result<(), GameError> {
+    let games = state
+        .get_mut("games")
+        .and_then(Value::as_object_mut)
+        .ok_or(GameError::InvalidContent("games is missing".to_string()))?;
+
+    let mut pots = Map::new();
+    for (game_id, game) in games.iter_mut() {
+        let game = game.as_object_mut().ok_or(GameError::InvalidContent(format!("game {} is not an object", game_id)))?;
+        game.remove("stakes");
+        let bet = game.get("bet_amount").and_then(Value::as_u64).unwrap_or(0);
+        let seated = if game.get("opponent").is_some_and(|opponent| !opponent.is_null()) { 2 } else { 1 };
+        let pot = bet.checked_mul(seated).ok_or(GameError::Overflow)?;
+        game.insert("pot".to_string(), json!(pot));
+        if !game.get("is_settled").and_then(Value::as_bool).unwrap_or(false) {
+            pots.insert(game_id.clone(), json!(pot));
+        }
+    }
+    if state.get("escrow").is_none() {
+        state["escrow"] = json!({ "pots": pots });
+    }
+    Ok(())
+}
+
+// Version 2 event logs had no audit chain, it is built over the events as they were saved
+fn v2_to_v3(state: &mut Value) -> Result<(), GameError> {
+    let Some(events) = state.get_mut("events").and_then(Value::as_object_mut) else { return Ok(()) };
+    let entries = events.get("entries").cloned().unwrap_or(json!([]));
+    let entries: Vec<LoggedEvent> = serde_json::from_value(entries).map_err(|e| GameError::InvalidContent(e.to_string()))?;
+    let audit = serde_json::to_value(AuditLog::rebuild(&entries)).map_err(|e| GameError::Serialize(e.to_string()))?;
+    events.insert("audit".to_string(), audit);
+    Ok(())
+}
+
+#[test]
+fn test_versions_in_order() {
+    let mut current = json!({ "version": STATE_VERSION, "games": {} });
+    assert_eq!(migrate(&mut current), Ok(STATE_VERSION));
+
+    let mut newer = json!({ "version": STATE_VERSION + 1 });
+    assert_eq!(migrate(&mut newer), Err(GameError::UnsupportedVersion(STATE_VERSION + 1)));
+
+    let mut old = json!({ "games": { "4": { "bet_amount": 10, "opponent": "Bob", "is_settled": false, "stakes": {} } } });
+    assert_eq!(migrate(&mut old), Ok(1));
+    assert_eq!(old["games"]["4"], json!({ "bet_amount": 10, "opponent": "Bob", "is_settled": false, "pot": 20 }));
+    assert_eq!(old["escrow"]["pots"]["4"], 20);
+    assert_eq!(old["version"], STATE_VERSION);
+}
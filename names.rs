@@ -0,0 +1,255 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::bot::HOUSE_BOT;
+use crate::error::GameError;
+use crate::tx::{restore_entries, save_entries};
+
+pub const MAX_NAME_CHARS: usize = 32;
+
+// Accounts the state plays from itself, no player can take them or a look-alike of them
+const RESERVED: [&str; 1] = [HOUSE_BOT];
+
+// Precomposed Latin-1 letters folded to their base letter, so "é" and "e" + U+0301 end up the same
+fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        c => c,
+    }
+}
+
+// Letters of other scripts that render like Latin ones, and Latin characters that are easy to swap
+fn fold_homoglyph(c: char) -> char {
+    match c {
+        'а' | 'α' => 'a',
+        'в' | 'β' => 'b',
+        'с' | 'ϲ' => 'c',
+        'е' | 'ε' => 'e',
+        'н' | 'η' => 'h',
+        'і' | 'ι' | 'l' | '1' | '|' | '!' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'м' | 'μ' => 'm',
+        'п' => 'n',
+        'о' | 'ο' | '0' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' | '5' | '$' => 's',
+        'т' | 'τ' => 't',
+        'у' | 'γ' => 'y',
+        'х' | 'χ' => 'x',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '7' => 't',
+        c => c,
+    }
+}
+
+// Key two names are compared by. Case, width, accents and look-alike characters are folded away,
+// so "AIice", "Alice" and "Аlice" (Cyrillic A) all share one canonical name.
+pub fn canonical_name(name: &str) -> Result<String, GameError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(GameError::NameEmpty);
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(GameError::NameTooLong);
+    }
+    if name.chars().any(|c| c.is_control() || ('\u{200B}'..='\u{200F}').contains(&c) || c == '\u{FEFF}') {
+        return Err(GameError::NameInvisible);
+    }
+
+    let folded: String = name
+        .chars()
+        // Fullwidth forms of ASCII, e.g. "Ａ"
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            c => c,
+        })
+        // Combining marks of decomposed accents
+        .filter(|c| !('\u{0300}'..='\u{036F}').contains(c))
+        .flat_map(char::to_lowercase)
+        .map(fold_accent)
+        .map(fold_homoglyph)
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-' && *c != '.')
+        .collect();
+
+    if folded.is_empty() {
+        return Err(GameError::NameEmpty);
+    }
+    // Letter pairs that read as a single letter
+    Ok(folded.replace("rn", "m").replace("vv", "w"))
+}
+
+// Where player names come from. Chosen names are checked and registered below, account ids are already
+// unique and authenticated by the host (e.g. a chain address or account) and are taken as they are:
+// folding look-alikes would merge addresses like 0x3a.. and 0xea..
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Identities {
+    #[default]
+    Chosen,
+    Accounts,
+}
+
+// Display names by canonical name. The first player to use a name owns it, later look-alikes are refused.
+// The name of a closed account stays blocked until its quarantine ends, so nobody can pose as the old owner.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Names {
+    owners: BTreeMap<String, String>,
+    #[serde(default)]
+    quarantined: BTreeMap<String, u64>, // Canonical name to the time it can be claimed again
+    #[serde(default)]
+    identities: Identities,
+}
+
+// Canonical names of the accounts that have one
+fn canonical_names(accounts: &[String]) -> Vec<String> {
+    accounts.iter().filter_map(|account| canonical_name(account).ok()).collect()
+}
+
+impl Names {
+    pub fn new() -> Self {
+        Names::default()
+    }
+
+    pub fn identities(&self) -> Identities {
+        self.identities
+    }
+
+    pub fn set_identities(&mut self, identities: Identities) {
+        self.identities = identities;
+    }
+
+    // Refuses what claim would refuse whoever owns the name
+    pub fn check(&self, name: &str) -> Result<(), GameError> {
+        match self.identities {
+            Identities::Chosen => {
+                let canonical = canonical_name(name)?;
+                if RESERVED.iter().any(|reserved| canonical_name(reserved).is_ok_and(|reserved| reserved == canonical)) {
+                    return Err(GameError::NameReserved);
+                }
+                Ok(())
+            }
+            Identities::Accounts if name.is_empty() => Err(GameError::NameEmpty),
+            Identities::Accounts if RESERVED.contains(&name) => Err(GameError::NameReserved),
+            Identities::Accounts => Ok(()),
+        }
+    }
+
+    // Registers `name` on first use, fine again for the exact same name
+    pub fn claim(&mut self, name: &str, now: u64) -> Result<(), GameError> {
+        self.check(name)?;
+        if self.identities == Identities::Accounts {
+            return Ok(());
+        }
+        let canonical = canonical_name(name)?;
+        match self.quarantined.get(&canonical) {
+            Some(&free_at) if now < free_at => return Err(GameError::NameQuarantined),
+            Some(_) => {
+                self.quarantined.remove(&canonical);
+            }
+            None => {}
+        }
+        match self.owners.get(&canonical) {
+            Some(owner) if owner == name => Ok(()),
+            Some(_) => Err(GameError::NameTaken),
+            None => {
+                self.owners.insert(canonical, name.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    // Frees the name of a closed account, nobody can claim it before `free_at`
+    pub fn release(&mut self, name: &str, free_at: u64) -> Result<(), GameError> {
+        if self.identities == Identities::Accounts {
+            return Ok(());
+        }
+        let canonical = canonical_name(name)?;
+        self.owners.remove(&canonical);
+        self.quarantined.insert(canonical, free_at);
+        Ok(())
+    }
+
+    pub fn owner(&self, name: &str) -> Option<&String> {
+        canonical_name(name).ok().and_then(|canonical| self.owners.get(&canonical))
+    }
+
+    pub fn len(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    // The names of `accounts` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, accounts: &[String]) -> Names {
+        let names = canonical_names(accounts);
+        Names {
+            owners: save_entries(&self.owners, &names),
+            quarantined: save_entries(&self.quarantined, &names),
+            identities: self.identities,
+        }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Names, accounts: &[String]) {
+        let names = canonical_names(accounts);
+        restore_entries(&mut self.owners, saved.owners, &names);
+        restore_entries(&mut self.quarantined, saved.quarantined, &names);
+        self.identities = saved.identities;
+    }
+}
+
+#[test]
+fn test_look_alike_names_share_canonical_name() {
+    let alice = canonical_name("Alice").unwrap();
+    assert_eq!(canonical_name("AIice").unwrap(), alice);
+    assert_eq!(canonical_name("\u{0410}lice").unwrap(), alice); // Cyrillic A
+    assert_eq!(canonical_name(" ＡLICE ").unwrap(), alice);
+    assert_eq!(canonical_name("Jos\u{00E9}").unwrap(), canonical_name("Jose\u{0301}").unwrap());
+    assert_eq!(canonical_name("Mo").unwrap(), canonical_name("rno").unwrap());
+    assert_ne!(canonical_name("Bob").unwrap(), alice);
+
+    assert!(canonical_name("  ").is_err());
+    assert!(canonical_name("Al\u{200B}ice").is_err());
+
+    let mut names = Names::new();
+    names.claim("Alice", 0).unwrap();
+    names.claim("Alice", 0).unwrap();
+    assert!(names.claim("AIice", 0).is_err());
+    assert_eq!(names.owner("A1ice"), Some(&"Alice".to_string()));
+    assert_eq!(names.len(), 1);
+
+    names.release("Alice", 100).unwrap();
+    assert_eq!(names.owner("Alice"), None);
+    assert!(names.claim("Alice", 99).is_err());
+    names.claim("AIice", 100).unwrap();
+    assert_eq!(names.owner("Alice"), Some(&"AIice".to_string()));
+
+    assert_eq!(names.claim(HOUSE_BOT, 0), Err(GameError::NameReserved));
+    assert_eq!(names.claim("House_B0t", 0), Err(GameError::NameReserved));
+    names.set_identities(Identities::Accounts);
+    assert_eq!(names.claim(HOUSE_BOT, 0), Err(GameError::NameReserved));
+}
+
+#[test]
+fn test_account_ids_taken_as_they_are() {
+    let first = "0x3a00000000000000000000000000000000000001";
+    let second = "0xea00000000000000000000000000000000000001";
+    let mut names = Names::new();
+    assert!(names.claim(first, 0).is_err());
+
+    names.set_identities(Identities::Accounts);
+    names.claim(first, 0).unwrap();
+    names.claim(second, 0).unwrap();
+    assert!(names.claim("", 0).is_err());
+    names.release(first, 100).unwrap();
+    names.claim(first, 0).unwrap();
+}
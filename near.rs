@@ -0,0 +1,192 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::state::ContractState;
+use near_sdk::{env, near_bindgen, FunctionError, NearToken, Promise};
+use std::io;
+use std::ops::Add;
+use std::time::Duration;
+
+use crate::account_id::AccountId;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::lobby::{GameSummary, LobbyFilter};
+use crate::names::Identities;
+use crate::strict::ParseMode;
+use crate::GameState;
+
+// NEAR contract around the engine (near feature). The caller of every method is the account that called
+// the contract, stakes are attached NEAR and withdrawals are paid back in NEAR.
+
+// One token of the engine is a millionth of a NEAR, so u64 stakes reach far beyond the NEAR supply
+pub const YOCTO_PER_TOKEN: u128 = 1_000_000_000_000_000_000;
+
+// Contracts have no clock but the block's, which does not move during a call: latencies read as zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub fn now() -> Self {
+        Instant(env::block_timestamp())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(env::block_timestamp().saturating_sub(self.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant(self.0.saturating_add(duration.as_nanos().min(u64::MAX as u128) as u64))
+    }
+}
+
+// Every random byte on chain, e.g. the seed of each game, is derived from the seed of the receipt:
+// sha256 of the seed and a counter, so draws within one call differ. Validators know the seed, like
+// every on-chain source of randomness it only keeps players from predicting the cards.
+#[cfg(target_arch = "wasm32")]
+fn random_bytes(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    use sha2::{Digest, Sha256};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static DRAWS: AtomicU64 = AtomicU64::new(0);
+    let seed = env::random_seed();
+    for chunk in buf.chunks_mut(32) {
+        let draw = DRAWS.fetch_add(1, Ordering::Relaxed);
+        let digest = Sha256::new().chain_update(&seed).chain_update(draw.to_le_bytes()).finalize();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+getrandom::register_custom_getrandom!(random_bytes);
+
+impl FunctionError for GameError {
+    fn panic(&self) -> ! {
+        env::panic_str(&self.to_string())
+    }
+}
+
+// Kept in contract storage as the exported JSON state, so the engine types need no borsh layout of
+// their own and stored states go through the same migrations as saved files
+#[near_bindgen]
+pub struct GameContract {
+    state: GameState,
+}
+
+impl Default for GameContract {
+    fn default() -> Self {
+        let mut state = GameState::new();
+        state.set_identities(Identities::Accounts);
+        GameContract { state }
+    }
+}
+
+impl ContractState for GameContract {}
+
+impl BorshSerialize for GameContract {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let json = self.state.export_state().map_err(io::Error::other)?;
+        BorshSerialize::serialize(&json, writer)
+    }
+}
+
+impl BorshDeserialize for GameContract {
+    fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let json = String::deserialize_reader(reader)?;
+        let state = GameState::import_state(&json, ParseMode::Strict).map_err(io::Error::other)?.value;
+        Ok(GameContract { state })
+    }
+}
+
+fn caller() -> AccountId {
+    AccountId::new(env::predecessor_account_id().to_string()).expect("A NEAR account id is a valid account id.")
+}
+
+#[near_bindgen]
+impl GameContract {
+    // Stakes the attached deposit, which has to be a whole number of tokens
+    #[payable]
+    #[handle_result]
+    pub fn stake(&mut self) -> Result<U64, GameError> {
+        let deposit = env::attached_deposit().as_yoctonear();
+        if !deposit.is_multiple_of(YOCTO_PER_TOKEN) {
+            return Err(GameError::InvalidContent("deposit is not a whole number of tokens".to_string()));
+        }
+        let amount = u64::try_from(deposit / YOCTO_PER_TOKEN).map_err(|_| GameError::Overflow)?;
+        self.state.stake_tokens(&Context::new(caller()), amount)?;
+        Ok(U64(amount))
+    }
+
+    #[handle_result]
+    pub fn withdraw(&mut self, amount: U64) -> Result<Promise, GameError> {
+        self.state.withdraw_stake(&Context::new(caller()), amount.0)?;
+        let payout = NearToken::from_yoctonear(amount.0 as u128 * YOCTO_PER_TOKEN);
+        Ok(Promise::new(env::predecessor_account_id()).transfer(payout))
+    }
+
+    #[handle_result]
+    pub fn start_game(&mut self, bet: U64) -> Result<U64, GameError> {
+        Ok(U64(self.state.start_game(&Context::new(caller()), bet.0)?))
+    }
+
+    #[handle_result]
+    pub fn join_game(&mut self, game_id: U64) -> Result<(), GameError> {
+        self.state.join_game(&Context::new(caller()), game_id.0)
+    }
+
+    // Asks for the next round of a game the caller sits in, played once every player asked. True once the
+    // game is settled.
+    #[handle_result]
+    pub fn reveal_cards(&mut self, game_id: U64) -> Result<bool, GameError> {
+        self.state.contribute_reveal(&Context::new(caller()), game_id.0)?;
+        Ok(self.state.game_summary(game_id.0).is_none())
+    }
+
+    // Available and locked tokens of `account_id`
+    pub fn stake_of(&self, account_id: String) -> (U64, U64) {
+        let stake = self.state.stake_of(&account_id);
+        (U64(stake.available.get()), U64(stake.locked.get()))
+    }
+
+    pub fn game(&self, game_id: U64) -> Option<GameSummary> {
+        self.state.game_summary(game_id.0)
+    }
+
+    pub fn open_games(&self, limit: u32) -> Vec<GameSummary> {
+        self.state.list_open_games(&LobbyFilter::default(), None, limit as usize)
+    }
+}
+
+#[test]
+fn test_callers_stake_and_play() {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    let as_account = |index: usize, deposit: u128| {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(index))
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .block_timestamp(1_700_000_000_000_000_000)
+            .build());
+    };
+    let mut contract = GameContract::default();
+    as_account(0, 50 * YOCTO_PER_TOKEN);
+    assert_eq!(contract.stake().unwrap(), U64(50));
+    let game_id = contract.start_game(U64(20)).unwrap();
+
+    as_account(1, 30 * YOCTO_PER_TOKEN + 1);
+    assert!(matches!(contract.stake(), Err(GameError::InvalidContent(_))));
+    as_account(1, 30 * YOCTO_PER_TOKEN);
+    contract.stake().unwrap();
+    contract.join_game(game_id).unwrap();
+    assert_eq!(contract.stake_of(accounts(1).to_string()), (U64(10), U64(20)));
+    assert_eq!(contract.game(game_id).unwrap().opponent, Some(accounts(1).to_string()));
+
+    // What is stored is read back whole
+    let stored = near_sdk::borsh::to_vec(&contract).unwrap();
+    let restored = GameContract::try_from_slice(&stored).unwrap();
+    assert_eq!(restored.stake_of(accounts(0).to_string()), (U64(30), U64(20)));
+}
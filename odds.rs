@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+
+use crate::deck::{Card, DeckSpec, Suit};
+use crate::error::GameError;
+use crate::rules::{GameRules, Outcome};
+
+// Exact outcome of one round over every possible deal, seen from the creator. Each field
+// counts ordered deals, so the probabilities are the counts over `total()`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Odds {
+    pub wins: u128,
+    pub draws: u128,
+    pub losses: u128,
+}
+
+impl Odds {
+    pub fn total(&self) -> u128 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn probability(&self, outcome: Outcome) -> f64 {
+        let count = match outcome {
+            Outcome::CreatorWins => self.wins,
+            Outcome::Draw => self.draws,
+            Outcome::OpponentWins => self.losses,
+        };
+        count as f64 / self.total() as f64
+    }
+}
+
+// Called with the cards left per rank, the hand and the number of ways to draw it
+type HandVisitor<'a> = dyn FnMut(&mut [u32], &[Card], u128) + 'a;
+
+// Visits every ordered hand of `size` cards that can still be drawn from `counts` (cards left per rank),
+// with the number of ways to draw it. Suits never decide a round, so hands are built from ranks only.
+fn for_each_hand(counts: &mut [u32], size: usize, hand: &mut Vec<Card>, ways: u128, visit: &mut HandVisitor) {
+    if hand.len() == size {
+        visit(counts, hand, ways);
+        return;
+    }
+    for index in 0..counts.len() {
+        let left = counts[index];
+        if left == 0 {
+            continue;
+        }
+        counts[index] -= 1;
+        hand.push(Card { rank: index as u8 + 1, suit: Suit::Clubs });
+        for_each_hand(counts, size, hand, ways * left as u128, visit);
+        hand.pop();
+        counts[index] += 1;
+    }
+}
+
+// Deals the opponent's hand first and the creator's from what is left, the order games are dealt in
+pub fn odds(rules: &dyn GameRules, deck: &DeckSpec) -> Result<Odds, GameError> {
+    let size = rules.hand_size();
+    if deck.size() < 2 * size {
+        return Err(GameError::DeckTooSmall);
+    }
+
+    let mut counts = vec![deck.suits as u32; deck.ranks as usize];
+    let mut odds = Odds::default();
+    for_each_hand(&mut counts, size, &mut Vec::new(), 1, &mut |counts, opponent_hand, opponent_ways| {
+        let opponent_hand = opponent_hand.to_vec();
+        for_each_hand(counts, size, &mut Vec::new(), opponent_ways, &mut |_, creator_hand, ways| {
+            match rules.winner(creator_hand, &opponent_hand) {
+                Outcome::CreatorWins => odds.wins += ways,
+                Outcome::Draw => odds.draws += ways,
+                Outcome::OpponentWins => odds.losses += ways,
+            }
+        });
+    });
+    Ok(odds)
+}
+
+#[test]
+fn test_high_card_odds_are_exact() {
+    use crate::rules::HighCard;
+
+    let high_card = odds(&HighCard, &DeckSpec::default()).unwrap();
+    assert_eq!(high_card.total(), 52 * 51);
+    // The opponent's card leaves 3 of the same rank in 51
+    assert_eq!(high_card.draws, 52 * 3);
+    assert_eq!(high_card.wins, high_card.losses);
+    assert!(odds(&HighCard, &DeckSpec { ranks: 1, suits: 1 }).is_err());
+}
+
+#[test]
+fn test_war_odds_match_simulation() {
+    use crate::deck::Deck;
+
+    let rules = crate::rules::War;
+    let exact = odds(&rules, &DeckSpec::default()).unwrap();
+    assert_eq!(exact.total(), 52 * 51 * 50 * 49);
+
+    let deals = 4_000;
+    let mut wins = 0;
+    for seed in 0..deals {
+        let mut bytes = [0u8; 32];
+        bytes[..4].copy_from_slice(&(seed as u32).to_le_bytes());
+        let mut deck = Deck::shuffled(&bytes);
+        let opponent_hand = deck.deal(2).unwrap();
+        let creator_hand = deck.deal(2).unwrap();
+        if rules.winner(&creator_hand, &opponent_hand) == Outcome::CreatorWins {
+            wins += 1;
+        }
+    }
+    let simulated = wins as f64 / deals as f64;
+    assert!((simulated - exact.probability(Outcome::CreatorWins)).abs() < 0.03);
+}
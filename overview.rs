@@ -0,0 +1,64 @@
+use serde::{Serialize, Deserialize};
+
+use crate::balance::Balance;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::roles::Role;
+use crate::GameState;
+
+// What an admin dashboard shows of the whole protocol at one point in time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AdminOverview {
+    pub total_staked: u128, // Available and locked, of every account
+    pub escrowed: u128,
+    pub treasury: u64,
+    pub jackpot: u64,
+    pub active_games: u64, // Unsettled and not expired, open or joined
+    pub expired_games: u64, // Past their expiry, waiting for the tick to call them off
+    pub top_balances: Vec<(String, Balance)>, // Largest stakes first, ties by name
+}
+
+impl GameState {
+    pub fn admin_overview(&self, ctx: &Context, top: usize) -> Result<AdminOverview, GameError> {
+        let _timer = self.time_operation("admin_overview");
+        self.roles.require(ctx.caller(), Role::Admin)?;
+        let now = self.clock.now();
+        let expired_games = self.games.values().filter(|game| !game.is_settled && now.saturating_sub(game.start_time) > game.timeout_secs).count() as u64;
+
+        let mut accounts: Vec<(&String, &Balance)> = self.balances.iter().collect();
+        accounts.sort_unstable_by(|(a, a_balance), (b, b_balance)| b_balance.total().cmp(&a_balance.total()).then_with(|| a.cmp(b)));
+        Ok(AdminOverview {
+            total_staked: self.balances.values().map(|balance| balance.total().get() as u128).sum(),
+            escrowed: self.escrow.total(),
+            treasury: self.treasury.balance(),
+            jackpot: self.jackpot.balance(),
+            active_games: self.games.values().filter(|game| !game.is_settled).count() as u64 - expired_games,
+            expired_games,
+            top_balances: accounts.into_iter().take(top).map(|(account, balance)| (account.clone(), *balance)).collect(),
+        })
+    }
+}
+
+#[test]
+fn test_overview_is_for_admins() {
+    use crate::account_id::account;
+    use crate::clock::{ManualClock, SharedClock};
+    use crate::context::caller;
+
+    let clock = ManualClock::new(1_000);
+    let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+    game_state.init_admin(account("House")).unwrap();
+    for (player, amount) in [("Alice", 100), ("Bob", 300), ("Carol", 100)] {
+        game_state.stake_tokens(&caller(player), amount).unwrap();
+    }
+    let joined = game_state.start_game(&caller("Alice"), 30).unwrap();
+    game_state.join_game(&caller("Bob"), joined).unwrap();
+    clock.advance(game_state.config.game_timeout_secs + 1);
+    game_state.start_game(&caller("Carol"), 10).unwrap();
+
+    assert!(matches!(game_state.admin_overview(&caller("Alice"), 2), Err(GameError::MissingRole { .. })));
+    let overview = game_state.admin_overview(&caller("House"), 2).unwrap();
+    assert_eq!((overview.total_staked, overview.escrowed), (500, 70));
+    assert_eq!((overview.active_games, overview.expired_games), (1, 1));
+    assert_eq!(overview.top_balances, vec![("Bob".to_string(), Balance::new(270, 30)), ("Alice".to_string(), Balance::new(70, 30))]);
+}
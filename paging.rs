@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+
+// One page of a listing in key order. `next` is the cursor of the following page, the key of the last item,
+// and None on the last page. Listings start after the cursor's key, so a cursor stays valid while items
+// come and go and a page never repeats or skips an item that was there all along.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Page<T, C> {
+    pub items: Vec<T>,
+    pub next: Option<C>,
+}
+
+impl<T, C> Page<T, C> {
+    // Takes at most `limit` items (at least one) of `sorted`, items following the cursor in key order
+    pub fn take(sorted: impl IntoIterator<Item = T>, limit: usize, key: impl Fn(&T) -> C) -> Self {
+        let mut sorted = sorted.into_iter();
+        let items: Vec<T> = sorted.by_ref().take(limit.max(1)).collect();
+        let next = match (items.last(), sorted.next()) {
+            (Some(last), Some(_)) => Some(key(last)),
+            _ => None,
+        };
+        Page { items, next }
+    }
+}
+
+#[test]
+fn test_pages_end_with_the_last_item() {
+    let first = Page::take(1..=5, 2, |item| *item);
+    assert_eq!(first, Page { items: vec![1, 2], next: Some(2) });
+    assert_eq!(Page::take(5..=5, 2, |item| *item), Page { items: vec![5], next: None });
+    assert_eq!(Page::take(4..=5, 2, |item| *item).next, None);
+    assert_eq!(Page::take(1..=5, 0, |item| *item).items, vec![1]);
+}
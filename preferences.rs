@@ -0,0 +1,128 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::config::GameConfig;
+use crate::error::GameError;
+use crate::rules::GameMode;
+use crate::seats::HEADS_UP;
+use crate::teams::TEAM_SEATS;
+
+// Parameters of a new game. Anything left as None is taken from the player's preferences,
+// then from the config (bet has no config default and must come from one of the two).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameOptions {
+    pub bet: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub mode: Option<GameMode>,
+    #[serde(default)]
+    pub seats: Option<u32>, // Heads-up unless set
+    #[serde(default)]
+    pub teams: Option<bool>, // Two pairs, on four seats
+}
+
+// Fully resolved parameters a game is created with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedOptions {
+    pub bet: u64,
+    pub timeout_secs: u64,
+    pub mode: GameMode,
+    pub seats: u32,
+    pub teams: bool,
+}
+
+impl GameOptions {
+    pub fn with_bet(bet: u64) -> Self {
+        GameOptions { bet: Some(bet), ..GameOptions::default() }
+    }
+
+    // Whatever is set in `self` wins over `fallback`
+    pub fn or(self, fallback: GameOptions) -> GameOptions {
+        GameOptions {
+            bet: self.bet.or(fallback.bet),
+            timeout_secs: self.timeout_secs.or(fallback.timeout_secs),
+            mode: self.mode.or(fallback.mode),
+            seats: self.seats.or(fallback.seats),
+            teams: self.teams.or(fallback.teams),
+        }
+    }
+
+    // Checks the values that are set against the bounds of the current config
+    pub fn validate(&self, config: &GameConfig) -> Result<(), GameError> {
+        if let Some(bet) = self.bet {
+            config.check_bet(bet)?;
+        }
+        if let Some(timeout) = self.timeout_secs {
+            if timeout < config.min_timeout_secs || timeout > config.max_timeout_secs {
+                return Err(GameError::TimeoutOutOfBounds);
+            }
+        }
+        let teams = self.teams == Some(true);
+        if let Some(seats) = self.seats.or(teams.then_some(TEAM_SEATS)) {
+            let max_seats = config.max_seats.max(HEADS_UP);
+            if !(HEADS_UP..=max_seats).contains(&seats) {
+                return Err(GameError::SeatsOutOfBounds { max_seats });
+            }
+            if teams && seats != TEAM_SEATS {
+                return Err(GameError::TeamSeats { seats: TEAM_SEATS });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resolve(self, config: &GameConfig) -> Result<ResolvedOptions, GameError> {
+        self.validate(config)?;
+        Ok(ResolvedOptions {
+            bet: self.bet.ok_or(GameError::NoBet)?,
+            timeout_secs: self.timeout_secs.unwrap_or(config.game_timeout_secs),
+            mode: self.mode.unwrap_or_default(),
+            seats: self.seats.unwrap_or(if self.teams == Some(true) { TEAM_SEATS } else { HEADS_UP }),
+            teams: self.teams.unwrap_or(false),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Preferences {
+    players: HashMap<String, GameOptions>,
+}
+
+impl Preferences {
+    pub fn new() -> Self {
+        Preferences::default()
+    }
+
+    pub fn get(&self, player: &str) -> GameOptions {
+        self.players.get(player).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, player: String, preferences: GameOptions, config: &GameConfig) -> Result<(), GameError> {
+        preferences.validate(config)?;
+        self.players.insert(player, preferences);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_options_fall_back_to_preferences_then_config() {
+    let config = GameConfig::default();
+    let mut preferences = Preferences::new();
+
+    let invalid = GameOptions { timeout_secs: Some(1), ..GameOptions::default() };
+    assert!(preferences.set("Alice".to_string(), invalid, &config).is_err());
+
+    let preferred = GameOptions { bet: Some(25), timeout_secs: None, mode: Some(GameMode::CaptureTheAce), seats: None, teams: None };
+    preferences.set("Alice".to_string(), preferred, &config).unwrap();
+
+    let resolved = GameOptions::default().or(preferences.get("Alice")).resolve(&config).unwrap();
+    assert_eq!(resolved, ResolvedOptions { bet: 25, timeout_secs: config.game_timeout_secs, mode: GameMode::CaptureTheAce, seats: HEADS_UP, teams: false });
+
+    let resolved = GameOptions::with_bet(5).or(preferences.get("Alice")).resolve(&config).unwrap();
+    assert_eq!(resolved.bet, 5);
+
+    assert!(GameOptions::default().or(preferences.get("Bob")).resolve(&config).is_err());
+    let crowded = GameOptions { seats: Some(config.max_seats + 1), ..GameOptions::with_bet(5) };
+    assert_eq!(crowded.resolve(&config), Err(GameError::SeatsOutOfBounds { max_seats: config.max_seats }));
+    let teams = GameOptions { teams: Some(true), ..GameOptions::with_bet(5) };
+    assert_eq!(teams.resolve(&config).unwrap().seats, TEAM_SEATS);
+    assert_eq!(GameOptions { seats: Some(3), ..teams }.resolve(&config), Err(GameError::TeamSeats { seats: TEAM_SEATS }));
+}
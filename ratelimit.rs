@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::clock::SharedClock;
+use crate::error::GameError;
+
+// Buckets kept before the full ones are dropped, a full bucket is the same as none
+const MAX_BUCKETS: usize = 10_000;
+
+// Tokens left at `updated_at`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bucket {
+    tokens: u32,
+    updated_at: u64,
+}
+
+// Token bucket per account for transports facing untrusted clients. Every action takes a token, each
+// account gets `per_sec` tokens back every second and holds at most that many, so nobody can spam the
+// state faster than the configured rate. Refused actions take nothing.
+pub struct RateLimiter {
+    per_sec: u32,
+    clock: SharedClock,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: u32) -> Self {
+        RateLimiter::with_clock(per_sec, SharedClock::default())
+    }
+
+    pub fn with_clock(per_sec: u32, clock: SharedClock) -> Self {
+        RateLimiter { per_sec, clock, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn per_sec(&self) -> u32 {
+        self.per_sec
+    }
+
+    fn current(&self, bucket: Option<&Bucket>, now: u64) -> u32 {
+        bucket.map_or(self.per_sec, |bucket| {
+            let refilled = now.saturating_sub(bucket.updated_at).saturating_mul(self.per_sec as u64);
+            (bucket.tokens as u64).saturating_add(refilled).min(self.per_sec as u64) as u32
+        })
+    }
+
+    // Takes a token of `account`, RateLimited if it has none left this second
+    pub fn check(&self, account: &str) -> Result<(), GameError> {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let tokens = self.current(buckets.get(account), now);
+        if tokens == 0 {
+            return Err(GameError::RateLimited);
+        }
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(account) {
+            buckets.retain(|_, bucket| self.current(Some(bucket), now) < self.per_sec);
+        }
+        buckets.insert(account.to_string(), Bucket { tokens: tokens - 1, updated_at: now });
+        Ok(())
+    }
+}
+
+#[test]
+fn test_accounts_get_their_rate_back_every_second() {
+    use crate::clock::ManualClock;
+
+    let clock = ManualClock::new(1_000);
+    let limiter = RateLimiter::with_clock(3, SharedClock::new(clock.clone()));
+    for _ in 0..3 {
+        limiter.check("Mallory").unwrap();
+    }
+    assert_eq!(limiter.check("Mallory"), Err(GameError::RateLimited));
+    limiter.check("Alice").unwrap();
+
+    clock.advance(1);
+    for _ in 0..3 {
+        limiter.check("Mallory").unwrap();
+    }
+    assert_eq!(limiter.check("Mallory"), Err(GameError::RateLimited));
+
+    // Idle seconds do not add up past one second's worth
+    clock.advance(10);
+    assert!((0..4).map(|_| limiter.check("Mallory")).eq([Ok(()), Ok(()), Ok(()), Err(GameError::RateLimited)]));
+}
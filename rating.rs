@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::config::RatingConfig;
+use crate::tx::{restore_entries, save_entries};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RatingChange {
+    pub game_id: u64,
+    pub before: i32,
+    pub after: i32,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerRating {
+    pub rating: i32,
+    pub history: Vec<RatingChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Ratings {
+    players: HashMap<String, PlayerRating>,
+}
+
+// Probability of `rating` scoring against `other` in the Elo model
+pub fn expected_score(rating: i32, other: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf((other - rating) as f64 / 400.0))
+}
+
+impl Ratings {
+    pub fn new() -> Self {
+        Ratings::default()
+    }
+
+    // Players nobody has seen yet start at the configured initial rating
+    pub fn rating(&self, player: &str, config: &RatingConfig) -> i32 {
+        self.players.get(player).map(|p| p.rating).unwrap_or(config.initial_rating)
+    }
+
+    pub fn history(&self, player: &str) -> &[RatingChange] {
+        self.players.get(player).map(|p| p.history.as_slice()).unwrap_or(&[])
+    }
+
+    // `winner` is None on a draw, which scores half a point for each side
+    pub fn record_game(
+        &mut self,
+        game_id: u64,
+        creator: &str,
+        opponent: &str,
+        winner: Option<&str>,
+        config: &RatingConfig,
+        timestamp: u64,
+    ) {
+        let creator_rating = self.rating(creator, config);
+        let opponent_rating = self.rating(opponent, config);
+
+        let creator_score = match winner {
+            None => 0.5,
+            Some(winner) if winner == creator => 1.0,
+            Some(_) => 0.0,
+        };
+
+        let k = config.k_factor as f64;
+        let delta = (k * (creator_score - expected_score(creator_rating, opponent_rating))).round() as i32;
+
+        // Zero sum, whatever the creator gains the opponent loses
+        self.apply(creator, game_id, creator_rating, creator_rating + delta, timestamp);
+        self.apply(opponent, game_id, opponent_rating, opponent_rating - delta, timestamp);
+    }
+
+    fn apply(&mut self, player: &str, game_id: u64, before: i32, after: i32, timestamp: u64) {
+        let entry = self.players.entry(player.to_string()).or_insert_with(|| PlayerRating {
+            rating: before,
+            history: Vec::new(),
+        });
+        entry.rating = after;
+        entry.history.push(RatingChange { game_id, before, after, timestamp });
+    }
+
+    // The ratings of `players` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, players: &[String]) -> Ratings {
+        Ratings { players: save_entries(&self.players, players) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Ratings, players: &[String]) {
+        restore_entries(&mut self.players, saved.players, players);
+    }
+}
+
+#[test]
+fn test_elo_update() {
+    let config = RatingConfig { k_factor: 32, initial_rating: 1200 };
+    let mut ratings = Ratings::new();
+
+    // Equal ratings: the winner takes half of K
+    ratings.record_game(0, "Alice", "Bob", Some("Alice"), &config, 10);
+    assert_eq!(ratings.rating("Alice", &config), 1216);
+    assert_eq!(ratings.rating("Bob", &config), 1184);
+
+    // The favourite drawing loses rating to the underdog
+    ratings.record_game(1, "Alice", "Bob", None, &config, 20);
+    assert!(ratings.rating("Alice", &config) < 1216);
+    assert_eq!(ratings.rating("Alice", &config) + ratings.rating("Bob", &config), 2400);
+
+    assert_eq!(ratings.history("Bob").len(), 2);
+    assert_eq!(ratings.history("Bob")[0], RatingChange { game_id: 0, before: 1200, after: 1184, timestamp: 10 });
+    assert!(ratings.history("Carol").is_empty());
+    assert_eq!(ratings.rating("Carol", &config), 1200);
+}
@@ -0,0 +1,176 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::clock::SharedClock;
+use crate::events::{GameEvent, LoggedEvent};
+use crate::lobby::{self, GameSummary, LobbyFilter};
+use crate::seats;
+use crate::stats::{PlayerStats, Stats, StatsMetric};
+use crate::GameState;
+
+// One settled game as a player sees it in their own history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayedGame {
+    pub game_id: u64,
+    pub opponent: String,
+    pub bet: u64,
+    pub winner: Option<String>, // None on a draw
+    pub payout: u64,
+    pub settled_at: u64,
+}
+
+// Views for the heavy queries, kept up to date from the event stream instead of computed from the state.
+// Queries read them under their own lock, so listing games or leaderboards never waits for the write path
+// and the write path only waits for the events it hands over. They lag the state by at most one event.
+pub struct ReadModels {
+    clock: SharedClock,
+    next: u64, // Sequence of the first event not applied yet
+    running: BTreeMap<u64, GameSummary>, // Games started and not settled or called off yet
+    stats: Stats,
+    played: HashMap<String, Vec<PlayedGame>>, // Oldest first
+}
+
+pub type SharedReadModels = Arc<RwLock<ReadModels>>;
+
+impl ReadModels {
+    pub fn new(clock: SharedClock) -> Self {
+        ReadModels { clock, next: 0, running: BTreeMap::new(), stats: Stats::new(), played: HashMap::new() }
+    }
+
+    // Builds the views from the events already logged and keeps them fed with every event from now on
+    pub fn attach(game_state: &mut GameState) -> SharedReadModels {
+        let mut models = ReadModels::new(game_state.clock.clone());
+        for logged in game_state.events() {
+            models.apply(logged);
+        }
+        let shared = Arc::new(RwLock::new(models));
+        let feed = Arc::clone(&shared);
+        game_state.subscribe(move |logged| feed.write().unwrap().apply(logged));
+        shared
+    }
+
+    pub fn apply(&mut self, logged: &LoggedEvent) {
+        if logged.sequence < self.next {
+            return;
+        }
+        self.next = logged.sequence + 1;
+
+        match &logged.event {
+            GameEvent::GameStarted { game_id, creator, bet, expires_at, odds, seats, teams } => {
+                let summary = GameSummary {
+                    game_id: *game_id,
+                    creator: creator.clone(),
+                    opponent: None,
+                    bet: *bet,
+                    pot: odds.creator_stake(*bet).unwrap_or(*bet),
+                    created_at: logged.timestamp,
+                    expires_at: *expires_at,
+                    seats: *seats,
+                    players: if seats::is_heads_up(seats) { Vec::new() } else { vec![creator.clone()] },
+                    teams: *teams,
+                };
+                self.running.insert(*game_id, summary);
+            }
+            GameEvent::GameJoined { game_id, opponent } => {
+                if let Some(summary) = self.running.get_mut(game_id) {
+                    if seats::is_heads_up(&summary.seats) {
+                        summary.opponent = Some(opponent.clone());
+                    } else {
+                        summary.players.push(opponent.clone());
+                    }
+                    summary.pot = summary.pot.saturating_add(summary.bet);
+                }
+            }
+            GameEvent::Settled { game_id, winner, payout } => {
+                let Some(summary) = self.running.remove(game_id) else { return };
+                let Some(opponent) = summary.opponent else { return };
+                let stakes = [summary.pot - summary.bet, summary.bet];
+                self.stats.record_game(&summary.creator, &opponent, winner.as_deref(), stakes);
+                for (player, against) in [(&summary.creator, &opponent), (&opponent, &summary.creator)] {
+                    self.played.entry(player.clone()).or_default().push(PlayedGame {
+                        game_id: *game_id,
+                        opponent: against.clone(),
+                        bet: summary.bet,
+                        winner: winner.clone(),
+                        payout: *payout,
+                        settled_at: logged.timestamp,
+                    });
+                }
+            }
+            // Comes before Settled, which then finds nothing running
+            GameEvent::PotShared { game_id, winners, payout } => {
+                let Some(summary) = self.running.remove(game_id) else { return };
+                self.stats.record_seats(&summary.players, winners, summary.bet, *payout);
+                let winner = if let [winner] = winners.as_slice() { Some(winner.clone()) } else { None };
+                for (seat, player) in summary.players.iter().enumerate() {
+                    // Against the player seated after them, as the history names the creator's opponent
+                    let against = &summary.players[(seat + 1) % summary.players.len()];
+                    self.played.entry(player.clone()).or_default().push(PlayedGame {
+                        game_id: *game_id,
+                        opponent: against.clone(),
+                        bet: summary.bet,
+                        winner: winner.clone(),
+                        payout: if winners.contains(player) { *payout } else { 0 },
+                        settled_at: logged.timestamp,
+                    });
+                }
+            }
+            GameEvent::Expired { game_id, .. } | GameEvent::PlayerSlashed { game_id: Some(game_id), .. } => {
+                self.running.remove(game_id);
+            }
+            _ => {}
+        }
+    }
+
+    // Same page as GameState::list_open_games
+    pub fn open_games(&self, filter: &LobbyFilter, after: Option<u64>, limit: usize) -> Vec<GameSummary> {
+        let now = self.clock.now();
+        let open = self.running.values().filter(|summary| !summary.is_full() && now <= summary.expires_at).cloned();
+        lobby::page(open, filter, after, limit)
+    }
+
+    pub fn leaderboard(&self, limit: usize, metric: StatsMetric) -> Vec<(String, PlayerStats)> {
+        self.stats.leaderboard(limit, metric)
+    }
+
+    // Settled games of `player`, newest first
+    pub fn player_games(&self, player: &str, limit: usize) -> Vec<PlayedGame> {
+        self.played.get(player).map_or_else(Vec::new, |played| played.iter().rev().take(limit).cloned().collect())
+    }
+}
+
+#[test]
+fn test_views_follow_the_state() {
+    use crate::context::caller;
+    use crate::tx::Scope;
+
+    let mut game_state = GameState::new();
+    for player in ["Alice", "Bob", "Carol"] {
+        game_state.stake_tokens(&caller(player), 100).unwrap();
+    }
+    let first = game_state.start_game(&caller("Alice"), 30).unwrap();
+    game_state.join_game(&caller("Bob"), first).unwrap();
+
+    // Attached halfway, the views catch up on the log first
+    let models = ReadModels::attach(&mut game_state);
+    while game_state.game_summary(first).is_some() {
+        game_state.play_round(first).unwrap();
+    }
+    game_state.start_game(&caller("Carol"), 20).unwrap();
+    let failed = game_state.with_tx(Scope::account("Bob").and_account("Mallory"), |state| {
+        let game_id = state.start_game(&caller("Bob"), 10)?;
+        state.join_game(&caller("Mallory"), game_id)
+    });
+    assert!(failed.is_err());
+
+    let models = models.read().unwrap();
+    let filter = LobbyFilter::default();
+    assert_eq!(models.open_games(&filter, None, 10), game_state.list_open_games(&filter, None, 10));
+    assert_eq!(models.leaderboard(10, StatsMetric::NetPnl), game_state.leaderboard(10, StatsMetric::NetPnl));
+    let record = game_state.history().by_id(first).unwrap();
+    let played = models.player_games("Bob", 10);
+    assert_eq!(played.len(), 1);
+    assert_eq!((played[0].opponent.as_str(), &played[0].winner, played[0].settled_at), ("Alice", &record.winner, record.settled_at));
+    assert!(models.player_games("Carol", 10).is_empty());
+}
@@ -0,0 +1,123 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::account_id::AccountId;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::tx::{restore_entries, save_entries};
+use crate::GameState;
+
+// Who brought whom in, and what each referrer was paid for it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Referrals {
+    referrers: BTreeMap<String, String>, // Referee to the referrer who brought them
+    earnings: BTreeMap<String, u64>, // Everything each referrer was paid, claimed with their stake
+}
+
+impl Referrals {
+    pub fn new() -> Self {
+        Referrals::default()
+    }
+
+    pub fn referrer_of(&self, user: &str) -> Option<&str> {
+        self.referrers.get(user).map(String::as_str)
+    }
+
+    pub fn referees(&self, referrer: &str) -> Vec<&str> {
+        self.referrers.iter().filter(|(_, by)| *by == referrer).map(|(user, _)| user.as_str()).collect()
+    }
+
+    pub fn earnings(&self, referrer: &str) -> u64 {
+        self.earnings.get(referrer).copied().unwrap_or(0)
+    }
+
+    pub fn register(&mut self, user: String, referrer: String) -> Result<(), GameError> {
+        if user == referrer {
+            return Err(GameError::SelfReferral);
+        }
+        if self.referrers.contains_key(&user) {
+            return Err(GameError::AlreadyReferred);
+        }
+        self.referrers.insert(user, referrer);
+        Ok(())
+    }
+
+    pub fn record(&mut self, referrer: &str, amount: u64) {
+        let earnings = self.earnings.entry(referrer.to_string()).or_default();
+        *earnings = earnings.saturating_add(amount);
+    }
+
+    // The referrers and earnings of `users` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, users: &[String]) -> Referrals {
+        Referrals { referrers: save_entries(&self.referrers, users), earnings: save_entries(&self.earnings, users) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Referrals, users: &[String]) {
+        restore_entries(&mut self.referrers, saved.referrers, users);
+        restore_entries(&mut self.earnings, saved.earnings, users);
+    }
+}
+
+impl GameState {
+    // Links the calling new user to the player who brought them in. Only users who never staked or played
+    // can be referred, and only once.
+    pub fn register_referral(&mut self, ctx: &Context, referrer: AccountId) -> Result<(), GameError> {
+        let _timer = self.start_operation("register_referral");
+        self.use_nonce(ctx)?;
+        let (user, referrer) = (ctx.caller().to_string(), referrer.into_string());
+        self.require_not_frozen(&user)?;
+        self.require_not_frozen(&referrer)?;
+        if self.balances.contains_key(&user) || !self.history.by_player(&user).is_empty() || !self.closed_accounts(&user).is_empty() {
+            return Err(GameError::NotNewUser);
+        }
+        self.referrals.register(user.clone(), referrer.clone())?;
+        self.events.emit(self.clock.now(), GameEvent::ReferralRegistered { user, referrer });
+        Ok(())
+    }
+
+    pub fn referrer_of(&self, user: &str) -> Option<&str> {
+        self.referrals.referrer_of(user)
+    }
+
+    pub fn referees(&self, referrer: &str) -> Vec<&str> {
+        self.referrals.referees(referrer)
+    }
+
+    // Everything `referrer` was paid for the games of the players they referred
+    pub fn referral_earnings(&self, referrer: &str) -> u64 {
+        self.referrals.earnings(referrer)
+    }
+
+    // Each player of a decided game generated half of its `fee`. The referrer of a player is credited
+    // `referral_bps` of that half, out of the treasury the fee was just collected into.
+    pub(crate) fn pay_referrals(&mut self, game_id: u64, players: &[String], fee: u64, referral_bps: u32) -> Result<(), GameError> {
+        let share = fee / players.len().max(1) as u64;
+        let cut = (share as u128 * referral_bps.min(10_000) as u128 / 10_000) as u64;
+        if cut == 0 {
+            return Ok(());
+        }
+        for referee in players {
+            let Some(referrer) = self.referrals.referrer_of(referee).map(str::to_string) else { continue };
+            self.treasury.withdraw(cut)?;
+            self.credit(&referrer, cut)?;
+            self.referrals.record(&referrer, cut);
+            self.events.emit(self.clock.now(), GameEvent::ReferralPaid { referrer, referee: referee.clone(), game_id, amount: cut });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_players_are_referred_once() {
+    let mut referrals = Referrals::new();
+    referrals.register("Bob".to_string(), "Alice".to_string()).unwrap();
+    referrals.register("Carol".to_string(), "Alice".to_string()).unwrap();
+    assert_eq!(referrals.register("Bob".to_string(), "Carol".to_string()), Err(GameError::AlreadyReferred));
+    assert_eq!(referrals.register("Dave".to_string(), "Dave".to_string()), Err(GameError::SelfReferral));
+
+    referrals.record("Alice", 3);
+    referrals.record("Alice", 2);
+    assert_eq!((referrals.referrer_of("Bob"), referrals.referees("Alice"), referrals.earnings("Alice")), (Some("Alice"), vec!["Bob", "Carol"], 5));
+    assert_eq!(referrals.earnings("Bob"), 0);
+}
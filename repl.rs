@@ -0,0 +1,123 @@
+use clap::{CommandFactory, Parser};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::Path;
+
+use assessment_rust::{ERC20Token, GameError, GameState};
+
+use crate::{execute, save, Command};
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// One line typed at the prompt, the same subcommands as on the command line
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true, name = "")]
+struct Line {
+    #[command(subcommand)]
+    command: Command,
+}
+
+// Completes the command at the start of the line
+struct Commands {
+    names: Vec<String>,
+}
+
+impl Commands {
+    fn new() -> Self {
+        let mut names: Vec<String> = Line::command().get_subcommands().map(|command| command.get_name().to_string()).collect();
+        names.push("quit".to_string());
+        Commands { names }
+    }
+}
+
+impl Completer for Commands {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let typed = &line[..pos];
+        if typed.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| name.starts_with(typed))
+            .map(|name| Pair { display: name.clone(), replacement: format!("{} ", name) })
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for Commands {
+    type Hint = String;
+}
+
+impl Highlighter for Commands {}
+
+impl Validator for Commands {}
+
+impl Helper for Commands {}
+
+// Reads commands until quit or end of input. Players take turns at the same prompt, every action is
+// saved to the state and token files right away so a crash loses nothing.
+pub fn run(game_state: &mut GameState, token: &mut ERC20Token, paths: (&Path, &Path), json: bool) -> Result<(), GameError> {
+    let terminal = |e: ReadlineError| GameError::Io { path: "terminal".to_string(), message: e.to_string() };
+    let mut editor = Editor::new().map_err(terminal)?;
+    editor.set_helper(Some(Commands::new()));
+
+    loop {
+        let line = match editor.readline("game> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(terminal(e)),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            None => continue,
+            Some(&"quit") | Some(&"exit") => return Ok(()),
+            Some(_) => {}
+        }
+        let _ = editor.add_history_entry(line.as_str());
+
+        // Usage errors and --help come back from clap already formatted
+        let command = match Line::try_parse_from(words) {
+            Ok(parsed) => parsed.command,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        match execute(game_state, token, &command) {
+            Ok((report, changed)) => {
+                save(game_state, token, changed, paths)?;
+                if json {
+                    println!("{}", report.json);
+                } else {
+                    println!("{}{}{}", GREEN, report.message, RESET);
+                }
+            }
+            Err(e) if json => println!("{}", serde_json::json!({ "error": e.to_string() })),
+            Err(e) => println!("{}Error: {}{}", RED, e, RESET),
+        }
+    }
+}
+
+#[test]
+fn test_completes_command_names() {
+    let commands = Commands::new();
+    let history = rustyline::history::DefaultHistory::new();
+    let context = Context::new(&history);
+    let (start, candidates) = commands.complete("st", 2, &context).unwrap();
+    let names: Vec<String> = candidates.into_iter().map(|pair| pair.display).collect();
+    assert_eq!((start, names), (0, vec!["stake".to_string(), "start".to_string(), "status".to_string()]));
+    assert!(commands.complete("stake Al", 8, &context).unwrap().1.is_empty());
+
+    let parsed = Line::try_parse_from(["join", "3", "Bob"]).unwrap();
+    assert!(matches!(parsed.command, Command::Join { game_id: 3, .. }));
+}
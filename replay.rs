@@ -0,0 +1,147 @@
+use crate::commitments::{self, CommitmentKind};
+use crate::deck::Deck;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::rules::{self, DrawPolicy, Outcome, Round};
+use crate::GameState;
+
+// A settled game dealt again, equal to its record once the replay passed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub game_id: u64,
+    pub rounds: Vec<Round>,
+    pub winner: Option<String>,
+    pub claimed: bool, // Won by a timeout claim, the rounds stop where the other player stalled
+}
+
+impl GameState {
+    // Deals a settled game again from its revealed seed under the rules it was created with, and checks
+    // it against the history and the event log: the seed matches its commitment, every round has the
+    // same cards and outcome, and the game went to the same player. Any difference is a ReplayMismatch.
+    pub fn replay(&self, game_id: u64) -> Result<Replay, GameError> {
+        let record = self.history.by_id(game_id).ok_or(GameError::GameNotFound)?;
+        let setup = record.setup.ok_or(GameError::NotReplayable(game_id))?;
+        let mismatch = |reason: &str| GameError::ReplayMismatch { game_id, reason: reason.to_string() };
+
+        let seed: [u8; 32] = commitments::from_hex(&record.seed)
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or_else(|| mismatch("the recorded seed is not 32 bytes of hex"))?;
+        let subject = format!("game:{}", game_id);
+        let committed = self
+            .commitments
+            .by_subject(&subject)
+            .into_iter()
+            .any(|commitment| commitment.kind == CommitmentKind::GameSeed && commitment.hash == commitments::hash_secret(&seed));
+        if !committed {
+            return Err(mismatch("the seed does not match its commitment"));
+        }
+
+        // What the log says happened to the game
+        let mut revealed = Vec::new();
+        let (mut claimed_by, mut settled) = (None, None);
+        for logged in self.events.entries().iter().filter(|logged| logged.event.is_about_game(game_id)) {
+            match &logged.event {
+                GameEvent::CardsRevealed { creator_hand, opponent_hand, .. } => revealed.push((creator_hand, opponent_hand)),
+                GameEvent::TimeoutClaimed { winner, .. } => claimed_by = Some(winner.clone()),
+                GameEvent::Settled { winner, .. } => settled = Some(winner.clone()),
+                _ => {}
+            }
+        }
+
+        // Dealt as join_game and reveal_cards deal: the opponent's first hand on join, then per round the
+        // opponent's next hand and the creator's
+        let config = self.config_log.config(setup.config_version).unwrap_or(&self.config);
+        let rules = setup.mode.rules(config);
+        let hand_size = rules.hand_size();
+        let mut deck = Deck::shuffled(&seed);
+        let mut opponent_hand = deck.deal(hand_size).map_err(|_| mismatch("the deck runs out"))?;
+        let mut rounds: Vec<Round> = Vec::new();
+        let outcome = loop {
+            if claimed_by.is_some() && rounds.len() == record.rounds.len() {
+                break None;
+            }
+            if rounds.len() == record.rounds.len() {
+                return Err(mismatch("the series is not decided after the recorded rounds"));
+            }
+            if !rounds.is_empty() {
+                opponent_hand = deck.deal(hand_size).map_err(|_| mismatch("the deck runs out"))?;
+            }
+            let creator_hand = deck.deal(hand_size).map_err(|_| mismatch("the deck runs out"))?;
+            let outcome = rules.winner(&creator_hand, &opponent_hand);
+            rounds.push(Round { creator_hand, opponent_hand: opponent_hand.clone(), outcome });
+
+            match rules::series_outcome(&rounds, setup.rounds_to_win) {
+                None => {}
+                Some(Outcome::Draw) if config.draw_policy == DrawPolicy::Replay && deck.remaining() >= 2 * hand_size + 2 => {}
+                Some(outcome) => break Some(outcome),
+            }
+        };
+
+        let winner = match outcome {
+            None => claimed_by.clone(),
+            Some(Outcome::CreatorWins) => Some(record.creator.clone()),
+            Some(Outcome::OpponentWins) => Some(record.opponent.clone()),
+            Some(Outcome::Draw) => None,
+        };
+
+        if rounds != record.rounds {
+            return Err(mismatch("the recorded rounds were not dealt from the seed"));
+        }
+        let logged_hands = rounds.iter().map(|round| (&round.creator_hand, &round.opponent_hand));
+        if !logged_hands.eq(revealed.iter().copied()) {
+            return Err(mismatch("the revealed cards in the log were not dealt from the seed"));
+        }
+        if winner != record.winner || settled.as_ref() != Some(&winner) {
+            return Err(mismatch("the game went to another player than the cards say"));
+        }
+
+        Ok(Replay { game_id, rounds, winner, claimed: claimed_by.is_some() })
+    }
+}
+
+#[test]
+fn test_replay_deals_the_recorded_game() {
+    use crate::account_id::account;
+    use crate::clock::{ManualClock, SharedClock};
+    use crate::config::GameConfig;
+    use crate::context::caller;
+
+    let clock = ManualClock::new(1_000);
+    let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+    game_state.init_admin(account("House")).unwrap();
+    game_state.apply_config(&caller("House"), GameConfig { rounds_to_win: 2, ..GameConfig::default() }).unwrap();
+    for player in ["Alice", "Bob"] {
+        game_state.stake_tokens(&caller(player), 100).unwrap();
+    }
+
+    let played = game_state.start_game(&caller("Alice"), 10).unwrap();
+    game_state.join_game(&caller("Bob"), played).unwrap();
+    while game_state.game_summary(played).is_some() {
+        game_state.play_round(played).unwrap();
+    }
+    let replay = game_state.replay(played).unwrap();
+    let record = game_state.history().by_id(played).unwrap();
+    assert_eq!((&replay.rounds, &replay.winner, replay.claimed), (&record.rounds, &record.winner, false));
+
+    // Bob never asks for the reveal, Alice claims the game
+    let claimed = game_state.start_game(&caller("Alice"), 10).unwrap();
+    game_state.join_game(&caller("Bob"), claimed).unwrap();
+    game_state.contribute_reveal(&caller("Alice"), claimed).unwrap();
+    clock.advance(10_000);
+    game_state.claim_timeout_win(&caller("Alice"), claimed).unwrap();
+    let replay = game_state.replay(claimed).unwrap();
+    assert_eq!((replay.rounds.len(), replay.winner.as_deref(), replay.claimed), (0, Some("Alice"), true));
+
+    assert_eq!(game_state.replay(99), Err(GameError::GameNotFound));
+
+    // A record changed after the fact no longer replays
+    let tamper = |change: &dyn Fn(&mut serde_json::Value)| {
+        let mut state = serde_json::to_value(&game_state).unwrap();
+        let records = state["history"]["records"].as_array_mut().unwrap();
+        change(records.iter_mut().find(|record| record["game_id"] == played).unwrap());
+        serde_json::from_value::<GameState>(state).unwrap().replay(played)
+    };
+    assert!(matches!(tamper(&|record| record["winner"] = serde_json::json!("Mallory")), Err(GameError::ReplayMismatch { .. })));
+    assert!(matches!(tamper(&|record| record["rounds"].as_array_mut().unwrap().truncate(1)), Err(GameError::ReplayMismatch { .. })));
+    assert_eq!(tamper(&|record| record["setup"] = serde_json::Value::Null), Err(GameError::NotReplayable(played)));
+}
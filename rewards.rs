@@ -0,0 +1,232 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::context::Context;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::roles::Role;
+use crate::tx::{restore_entries, save_entries};
+use crate::GameState;
+
+// Precision of the index, rewards per idle token are kept in units of 1e-18 token
+const INDEX_SCALE: u128 = 1_000_000_000_000_000_000;
+
+// What one player's idle stake earned up to the index they were last settled at
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Staker {
+    idle: u64,
+    index: u128,
+    earned: u64,
+}
+
+// Yield on idle stakes, funded from the treasury. A funding is paid out evenly until the end of its
+// period, every payout raises the index by what one idle token earned, so a player's share is their idle
+// stake times how much the index rose while they held it. Nothing is paid out while nobody is idle.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Rewards {
+    pool: u64, // Funded and not claimed yet, the rounding left over by the index stays here too
+    undistributed: u64, // Part of the pool the index has not paid out yet
+    period_end: u64,
+    updated_at: u64,
+    index: u128,
+    total_idle: u128,
+    stakers: BTreeMap<String, Staker>,
+}
+
+impl Rewards {
+    pub fn new() -> Self {
+        Rewards::default()
+    }
+
+    pub fn pool(&self) -> u64 {
+        self.pool
+    }
+
+    pub fn undistributed(&self) -> u64 {
+        self.undistributed
+    }
+
+    // Idle stake the rewards were last settled with, should match the available balance
+    pub fn idle(&self, user: &str) -> u64 {
+        self.stakers.get(user).map_or(0, |staker| staker.idle)
+    }
+
+    pub fn stakers(&self) -> impl Iterator<Item = &String> {
+        self.stakers.keys()
+    }
+
+    pub fn total_idle(&self) -> u128 {
+        self.total_idle
+    }
+
+    // Part of the undistributed rewards paid out by `now`
+    fn released(&self, now: u64) -> u64 {
+        if self.total_idle == 0 || now <= self.updated_at {
+            return 0;
+        }
+        let remaining = self.period_end.saturating_sub(self.updated_at);
+        if now >= self.period_end || remaining == 0 {
+            return self.undistributed;
+        }
+        (self.undistributed as u128 * (now - self.updated_at) as u128 / remaining as u128) as u64
+    }
+
+    fn index_at(&self, now: u64) -> u128 {
+        match self.released(now) {
+            0 => self.index,
+            released => self.index.saturating_add(released as u128 * INDEX_SCALE / self.total_idle),
+        }
+    }
+
+    fn accrue(&mut self, now: u64) {
+        let released = self.released(now);
+        self.index = self.index_at(now);
+        self.undistributed -= released;
+        self.updated_at = self.updated_at.max(now);
+    }
+
+    // Adds `amount` to what is still to be paid out, all of it evenly over the next `duration_secs`
+    pub fn fund(&mut self, amount: u64, duration_secs: u64, now: u64) -> Result<(), GameError> {
+        self.accrue(now);
+        let pool = self.pool.checked_add(amount).ok_or(GameError::Overflow)?;
+        self.undistributed += amount;
+        self.pool = pool;
+        self.period_end = now.saturating_add(duration_secs);
+        Ok(())
+    }
+
+    pub fn earned(&self, user: &str, now: u64) -> u64 {
+        self.stakers.get(user).map_or(0, |staker| {
+            let accrued = staker.idle as u128 * (self.index_at(now) - staker.index) / INDEX_SCALE;
+            (staker.earned as u128 + accrued).min(self.pool as u128) as u64
+        })
+    }
+
+    // Settles what `user` earned so far, their idle stake earns from now on
+    pub fn update(&mut self, user: &str, idle: u64, now: u64) {
+        let earned = self.earned(user, now);
+        self.accrue(now);
+        self.total_idle = self.total_idle - self.idle(user) as u128 + idle as u128;
+        if idle == 0 && earned == 0 {
+            self.stakers.remove(user);
+        } else {
+            self.stakers.insert(user.to_string(), Staker { idle, index: self.index, earned });
+        }
+    }
+
+    // Takes what `user` earned out of the pool, returns how much
+    pub fn claim(&mut self, user: &str, now: u64) -> u64 {
+        let earned = self.earned(user, now);
+        self.update(user, self.idle(user), now);
+        if let Some(staker) = self.stakers.get_mut(user) {
+            staker.earned = 0;
+            if staker.idle == 0 {
+                self.stakers.remove(user);
+            }
+        }
+        self.pool -= earned;
+        earned
+    }
+
+    // Nobody is idle anymore and nothing is owed, the whole pool is paid out again from now on
+    pub fn reset(&mut self, now: u64) {
+        self.accrue(now);
+        self.stakers.clear();
+        self.total_idle = 0;
+        self.undistributed = self.pool;
+    }
+
+    // The pool and index with the stakes of `users` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, users: &[String]) -> Rewards {
+        Rewards {
+            pool: self.pool,
+            undistributed: self.undistributed,
+            period_end: self.period_end,
+            updated_at: self.updated_at,
+            index: self.index,
+            total_idle: self.total_idle,
+            stakers: save_entries(&self.stakers, users),
+        }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Rewards, users: &[String]) {
+        restore_entries(&mut self.stakers, saved.stakers, users);
+        *self = Rewards { stakers: std::mem::take(&mut self.stakers), ..saved };
+    }
+}
+
+impl GameState {
+    // Moves `amount` of fees from the treasury into the rewards, paid out to idle stakes over `duration_secs`.
+    // What was not paid out of an earlier funding yet is spread over the new period with it.
+    pub fn fund_rewards(&mut self, ctx: &Context, amount: u64, duration_secs: u64) -> Result<(), GameError> {
+        let _timer = self.start_operation("fund_rewards");
+        self.use_nonce(ctx)?;
+        let caller = ctx.caller();
+        self.roles.require(caller, Role::Admin)?;
+        if self.rewards.pool().checked_add(amount).is_none() {
+            return Err(GameError::Overflow);
+        }
+        self.treasury.withdraw(amount)?;
+        self.rewards.fund(amount, duration_secs, self.clock.now())?;
+        self.events.emit(self.clock.now(), GameEvent::RewardsFunded { admin: caller.to_string(), amount, duration_secs });
+        Ok(())
+    }
+
+    // Adds the rewards the caller earned to their available stake, returns how much
+    pub fn claim_rewards(&mut self, ctx: &Context) -> Result<u64, GameError> {
+        let _timer = self.start_operation("claim_rewards");
+        self.use_nonce(ctx)?;
+        let user = ctx.caller().to_string();
+        self.require_not_frozen(&user)?;
+        self.collect_rewards(&user)
+    }
+
+    pub fn pending_rewards(&self, user: &str) -> u64 {
+        self.rewards.earned(user, self.clock.now())
+    }
+
+    pub fn rewards(&self) -> &Rewards {
+        &self.rewards
+    }
+
+    pub(crate) fn collect_rewards(&mut self, user: &str) -> Result<u64, GameError> {
+        let now = self.clock.now();
+        let amount = self.rewards.earned(user, now);
+        if amount == 0 {
+            return Ok(0);
+        }
+        let mut balance = self.stake_of(user);
+        balance.credit(amount)?;
+        self.rewards.claim(user, now);
+        self.balances.insert(user.to_string(), balance);
+        self.sync_rewards(user);
+        self.events.emit(now, GameEvent::RewardsClaimed { user: user.to_string(), amount });
+        Ok(amount)
+    }
+
+    // Called after every change to the available stake of `user`, so it earns with what it holds now
+    pub(crate) fn sync_rewards(&mut self, user: &str) {
+        let idle = self.balances.get(user).map_or(0, |balance| balance.available.get());
+        self.rewards.update(user, idle, self.clock.now());
+    }
+}
+
+#[test]
+fn test_rewards_follow_idle_stakes() {
+    let mut rewards = Rewards::new();
+    rewards.update("Alice", 100, 0);
+    rewards.fund(1_000, 100, 0).unwrap();
+
+    rewards.update("Bob", 300, 50);
+    assert_eq!((rewards.earned("Alice", 50), rewards.undistributed()), (500, 500));
+    assert_eq!((rewards.earned("Alice", 100), rewards.earned("Bob", 100)), (625, 375));
+
+    // Rewards only accrue while someone is idle, what was not paid out waits for the next idle stake
+    rewards.update("Alice", 0, 100);
+    rewards.update("Bob", 0, 100);
+    rewards.fund(400, 100, 100).unwrap();
+    assert_eq!(rewards.claim("Alice", 300), 625);
+    rewards.update("Alice", 10, 300);
+    assert_eq!(rewards.earned("Alice", 301), 400);
+    assert_eq!((rewards.claim("Bob", 301), rewards.claim("Alice", 301), rewards.pool()), (375, 400, 0));
+}
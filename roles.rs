@@ -0,0 +1,68 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Admin,
+}
+
+// Accounts allowed to call privileged operations
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Roles {
+    grants: BTreeMap<String, BTreeSet<Role>>,
+}
+
+impl Roles {
+    pub fn new() -> Self {
+        Roles::default()
+    }
+
+    pub fn has(&self, account: &str, role: Role) -> bool {
+        self.grants.get(account).is_some_and(|roles| roles.contains(&role))
+    }
+
+    pub fn require(&self, account: &str, role: Role) -> Result<(), GameError> {
+        if self.has(account, role) {
+            Ok(())
+        } else {
+            Err(GameError::MissingRole { account: account.to_string(), role })
+        }
+    }
+
+    pub fn members(&self, role: Role) -> Vec<&String> {
+        self.grants.iter().filter(|(_, roles)| roles.contains(&role)).map(|(account, _)| account).collect()
+    }
+
+    pub fn grant(&mut self, account: String, role: Role) {
+        self.grants.entry(account).or_default().insert(role);
+    }
+
+    // The last admin cannot be removed, nobody could grant the role again
+    pub fn revoke(&mut self, account: &str, role: Role) -> Result<(), GameError> {
+        if role == Role::Admin && self.has(account, role) && self.members(Role::Admin).len() == 1 {
+            return Err(GameError::LastAdmin);
+        }
+        if let Some(roles) = self.grants.get_mut(account) {
+            roles.remove(&role);
+            if roles.is_empty() {
+                self.grants.remove(account);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_last_admin_kept() {
+    let mut roles = Roles::new();
+    assert!(roles.require("Alice", Role::Admin).is_err());
+    roles.grant("Alice".to_string(), Role::Admin);
+    roles.grant("Bob".to_string(), Role::Admin);
+    assert!(roles.require("Alice", Role::Admin).is_ok());
+
+    roles.revoke("Bob", Role::Admin).unwrap();
+    assert!(roles.revoke("Alice", Role::Admin).is_err());
+    assert_eq!(roles.members(Role::Admin), vec![&"Alice".to_string()]);
+}
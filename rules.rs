@@ -0,0 +1,249 @@
+use serde::{Serialize, Deserialize};
+
+use crate::config::GameConfig;
+use crate::deck::Card;
+
+// Cards are ranked 1..=13, the Ace is the lowest card unless a mode says otherwise
+pub const ACE: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    CreatorWins,
+    OpponentWins,
+    Draw,
+}
+
+impl Outcome {
+    // The higher value wins
+    pub fn compare<T: Ord>(creator: T, opponent: T) -> Outcome {
+        match creator.cmp(&opponent) {
+            std::cmp::Ordering::Greater => Outcome::CreatorWins,
+            std::cmp::Ordering::Less => Outcome::OpponentWins,
+            std::cmp::Ordering::Equal => Outcome::Draw,
+        }
+    }
+}
+
+// One deal of a best-of-N series
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    pub creator_hand: Vec<Card>,
+    pub opponent_hand: Vec<Card>,
+    pub outcome: Outcome,
+}
+
+// A best-of-N series is decided as soon as a player reaches `rounds_to_win`, or after 2N-1 rounds
+// (drawn rounds count for nobody) by the most rounds won, which can be a draw.
+pub fn series_outcome(rounds: &[Round], rounds_to_win: u32) -> Option<Outcome> {
+    let rounds_to_win = rounds_to_win.max(1) as usize;
+    let creator_wins = rounds.iter().filter(|r| r.outcome == Outcome::CreatorWins).count();
+    let opponent_wins = rounds.iter().filter(|r| r.outcome == Outcome::OpponentWins).count();
+
+    if creator_wins >= rounds_to_win {
+        return Some(Outcome::CreatorWins);
+    }
+    if opponent_wins >= rounds_to_win {
+        return Some(Outcome::OpponentWins);
+    }
+    if rounds.len() >= 2 * rounds_to_win - 1 {
+        return Some(Outcome::compare(creator_wins, opponent_wins));
+    }
+    None
+}
+
+// What happens to the bets when a series ends in a draw
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DrawPolicy {
+    #[default]
+    Refund, // Both bets go back to the players
+    Replay, // More rounds are dealt from the same deck until someone wins, refunded if the deck runs out
+    CarryPotToRematch, // Both bets stay escrowed and are played for again in a new game between the same players
+}
+
+// Extra draw against the house: a card ranked strictly above `house_threshold` wins `payout` from the jackpot pool
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BonusRound {
+    pub house_threshold: u8,
+    pub payout: u64,
+}
+
+impl Default for BonusRound {
+    fn default() -> Self {
+        BonusRound {
+            house_threshold: 7,
+            payout: 50,
+        }
+    }
+}
+
+// Behaviour that changes between game modes. Only `winner` is required, every other hook has a default
+// so modes only override what they need.
+pub trait GameRules {
+    // Cards dealt to each player every round
+    fn hand_size(&self) -> usize {
+        1
+    }
+
+    fn winner(&self, creator_hand: &[Card], opponent_hand: &[Card]) -> Outcome;
+
+    // Bonus round triggered by a player revealing `hand`, None when the mode has no bonus for it
+    fn bonus_round(&self, _hand: &[Card]) -> Option<BonusRound> {
+        None
+    }
+}
+
+fn ranks(hand: &[Card]) -> Vec<u8> {
+    hand.iter().map(|card| card.rank).collect()
+}
+
+// Plain high card, no extras
+pub struct HighCard;
+
+impl GameRules for HighCard {
+    fn winner(&self, creator_hand: &[Card], opponent_hand: &[Card]) -> Outcome {
+        Outcome::compare(ranks(creator_hand), ranks(opponent_hand))
+    }
+}
+
+// High card where revealing an Ace earns a bonus round paid from the jackpot pool
+pub struct CaptureTheAce {
+    pub bonus: BonusRound,
+}
+
+impl GameRules for CaptureTheAce {
+    fn winner(&self, creator_hand: &[Card], opponent_hand: &[Card]) -> Outcome {
+        HighCard.winner(creator_hand, opponent_hand)
+    }
+
+    fn bonus_round(&self, hand: &[Card]) -> Option<BonusRound> {
+        if hand.iter().any(|card| card.rank == ACE) {
+            Some(self.bonus)
+        } else {
+            None
+        }
+    }
+}
+
+// Aces are high, a tied battle card goes to war and the second card decides
+pub struct War;
+
+impl GameRules for War {
+    fn hand_size(&self) -> usize {
+        2
+    }
+
+    fn winner(&self, creator_hand: &[Card], opponent_hand: &[Card]) -> Outcome {
+        let war_ranks = |hand: &[Card]| -> Vec<u8> {
+            hand.iter().map(|card| if card.rank == ACE { 14 } else { card.rank }).collect()
+        };
+        Outcome::compare(war_ranks(creator_hand), war_ranks(opponent_hand))
+    }
+}
+
+// Three cards each, no hitting. Closest to 21 wins, a bust hand scores nothing.
+pub struct BlackjackLite;
+
+impl BlackjackLite {
+    // Faces count 10, one Ace counts 11 when that does not bust the hand
+    pub fn total(hand: &[Card]) -> u32 {
+        let total: u32 = hand.iter().map(|card| card.rank.min(10) as u32).sum();
+        if hand.iter().any(|card| card.rank == ACE) && total + 10 <= 21 {
+            total + 10
+        } else {
+            total
+        }
+    }
+}
+
+impl GameRules for BlackjackLite {
+    fn hand_size(&self) -> usize {
+        3
+    }
+
+    fn winner(&self, creator_hand: &[Card], opponent_hand: &[Card]) -> Outcome {
+        let score = |hand: &[Card]| {
+            let total = BlackjackLite::total(hand);
+            if total > 21 { 0 } else { total }
+        };
+        Outcome::compare(score(creator_hand), score(opponent_hand))
+    }
+}
+
+// Stored in each game so the mode survives serialization, the rules themselves are built on demand
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    HighCard,
+    CaptureTheAce,
+    War,
+    BlackjackLite,
+}
+
+impl GameMode {
+    pub fn rules(&self, config: &GameConfig) -> Box<dyn GameRules> {
+        match self {
+            GameMode::HighCard => Box::new(HighCard),
+            GameMode::CaptureTheAce => Box::new(CaptureTheAce { bonus: config.ace_bonus }),
+            GameMode::War => Box::new(War),
+            GameMode::BlackjackLite => Box::new(BlackjackLite),
+        }
+    }
+}
+
+#[test]
+fn test_series_outcome_best_of_three() {
+    use crate::deck::Suit;
+
+    let round = |creator_rank, opponent_rank| {
+        let creator_hand = vec![Card { rank: creator_rank, suit: Suit::Spades }];
+        let opponent_hand = vec![Card { rank: opponent_rank, suit: Suit::Hearts }];
+        let outcome = HighCard.winner(&creator_hand, &opponent_hand);
+        Round { creator_hand, opponent_hand, outcome }
+    };
+
+    assert_eq!(series_outcome(&[round(5, 5)], 1), Some(Outcome::Draw));
+    assert_eq!(series_outcome(&[round(9, 5)], 2), None);
+    assert_eq!(series_outcome(&[round(9, 5), round(2, 5)], 2), None);
+    assert_eq!(series_outcome(&[round(9, 5), round(2, 5), round(7, 3)], 2), Some(Outcome::CreatorWins));
+    assert_eq!(series_outcome(&[round(2, 5), round(1, 5)], 2), Some(Outcome::OpponentWins));
+    // Two drawn rounds, the only decided one wins the series
+    assert_eq!(series_outcome(&[round(4, 4), round(1, 5), round(6, 6)], 2), Some(Outcome::OpponentWins));
+}
+
+#[test]
+fn test_only_capture_the_ace_opts_in() {
+    use crate::deck::Suit;
+
+    let config = GameConfig::default();
+    let ace = Card { rank: ACE, suit: Suit::Clubs };
+    let king = Card { rank: 13, suit: Suit::Clubs };
+
+    assert!(GameMode::HighCard.rules(&config).bonus_round(&[ace]).is_none());
+
+    let rules = GameMode::CaptureTheAce.rules(&config);
+    assert_eq!(rules.bonus_round(&[ace]), Some(config.ace_bonus));
+    assert!(rules.bonus_round(&[king]).is_none());
+}
+
+#[test]
+fn test_variants_pick_different_winners() {
+    use crate::deck::Suit;
+
+    let card = |rank| Card { rank, suit: Suit::Hearts };
+    let config = GameConfig::default();
+    let rules = |mode: GameMode| mode.rules(&config);
+
+    // The Ace is the lowest card in high card and the highest in war
+    assert_eq!(rules(GameMode::HighCard).winner(&[card(ACE)], &[card(2)]), Outcome::OpponentWins);
+    assert_eq!(rules(GameMode::War).winner(&[card(ACE), card(2)], &[card(13), card(9)]), Outcome::CreatorWins);
+    // A tied battle card is decided by the war card
+    assert_eq!(rules(GameMode::War).winner(&[card(8), card(2)], &[card(8), card(9)]), Outcome::OpponentWins);
+
+    assert_eq!(BlackjackLite::total(&[card(ACE), card(13), card(5)]), 16);
+    assert_eq!(BlackjackLite::total(&[card(ACE), card(4), card(6)]), 21);
+    let blackjack = rules(GameMode::BlackjackLite);
+    assert_eq!(blackjack.hand_size(), 3);
+    // 25 is bust, 12 wins
+    assert_eq!(blackjack.winner(&[card(10), card(12), card(5)], &[card(2), card(4), card(6)]), Outcome::OpponentWins);
+    assert_eq!(blackjack.winner(&[card(10), card(12), card(5)], &[card(9), card(9), card(9)]), Outcome::Draw);
+}
@@ -0,0 +1,177 @@
+use serde::{Serialize, Deserialize};
+
+use crate::accounting::LedgerEntry;
+use crate::commitments;
+use crate::deck::Card;
+use crate::error::GameError;
+use crate::events::GameEvent;
+use crate::history::GameRecord;
+use crate::rules::{GameRules, Outcome};
+use crate::sidebets::Side;
+use crate::teams::{self, Team};
+use crate::tx::Scope;
+use crate::{Game, GameState};
+
+// Seats of a heads-up game, the creator and one opponent. Games with more seats are played in one deal,
+// every seat against every other.
+pub const HEADS_UP: u32 = 2;
+
+pub fn heads_up() -> u32 {
+    HEADS_UP
+}
+
+pub fn is_heads_up(seats: &u32) -> bool {
+    *seats == HEADS_UP
+}
+
+// A player of a game with more than two seats and the hand dealt to them, empty until the reveal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Seat {
+    pub player: String,
+    pub hand: Vec<Card>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>, // Only in team games
+}
+
+// Indexes of the hands no other hand beats under `rules`. Hands that only beat each other in a circle
+// leave nobody unbeaten, every hand shares the pot then.
+pub fn best_hands(rules: &dyn GameRules, hands: &[Vec<Card>]) -> Vec<usize> {
+    let unbeaten: Vec<usize> = (0..hands.len())
+        .filter(|&i| hands.iter().all(|other| rules.winner(&hands[i], other) != Outcome::OpponentWins))
+        .collect();
+    if unbeaten.is_empty() { (0..hands.len()).collect() } else { unbeaten }
+}
+
+// Each winner's share of `pot` and what is left over after sharing it evenly, which goes to the winner
+// seated first
+pub fn share(pot: u64, winners: usize) -> (u64, u64) {
+    let winners = winners.max(1) as u64;
+    (pot / winners, pot % winners)
+}
+
+impl GameState {
+    // Deals every seat one hand in a single round, the best hands share the pot minus the house fee
+    pub(crate) fn reveal_seats(&mut self, game_id: u64) -> Result<(), GameError> {
+        let now = self.clock.now();
+        let game = self.games.get_mut(&game_id).ok_or(GameError::NoGameToReveal)?;
+        if game.is_settled {
+            return Err(GameError::AlreadySettled);
+        }
+        // Left for the tick to call off, which records the expiry
+        if now.saturating_sub(game.start_time) > game.timeout_secs {
+            return Err(GameError::Expired);
+        }
+        if !game.is_full() {
+            return Err(GameError::NoOpponentToReveal);
+        }
+
+        let config = self.config_log.config(game.config_version).unwrap_or(&self.config);
+        let rules = game.mode.rules(config);
+        for seat in &mut game.players {
+            seat.hand = game.deck.deal(rules.hand_size())?;
+        }
+        let winners: Vec<String> = if game.teams {
+            // The team with the higher total shares the pot, both share it on a tie
+            let team = teams::winning_team(&game.players);
+            game.players.iter().filter(|seat| team.is_none() || seat.team == team).map(|seat| seat.player.clone()).collect()
+        } else {
+            let hands: Vec<Vec<Card>> = game.players.iter().map(|seat| seat.hand.clone()).collect();
+            best_hands(rules.as_ref(), &hands).into_iter().map(|index| game.players[index].player.clone()).collect()
+        };
+        self.events.emit(now, GameEvent::SeatsRevealed {
+            game_id,
+            hands: game.players.iter().map(|seat| (seat.player.clone(), seat.hand.clone())).collect(),
+        });
+        game.reveal_requests.clear();
+        game.is_settled = true; // Effects before the interaction
+        self.settle_seats(game_id, winners)
+    }
+
+    // Every stake goes into the pot, the house fee is taken once and the rest is shared by `winners`. All of it
+    // is paid out or nothing.
+    pub(crate) fn settle_seats(&mut self, game_id: u64, winners: Vec<String>) -> Result<(), GameError> {
+        self.with_tx(Scope::game(game_id), |state| {
+            let game = state.games.get(&game_id).ok_or(GameError::NoGameToSettle)?;
+            let players: Vec<String> = game.seated().into_iter().cloned().collect();
+            let bet = game.bet_amount.get();
+            let pot = state.escrow.pot(game_id);
+            let config = state.config_log.config(game.config_version).unwrap_or(&state.config);
+            let referral_bps = config.referral_bps;
+            let fee = config.fee.rake_with(pot, &state.stats.activity(state.clock.now()));
+            let (payout, left_over) = share(pot - fee, winners.len());
+
+            for (index, winner) in winners.iter().enumerate() {
+                let amount = if index == 0 { payout + left_over } else { payout };
+                state.reentrant_transfer(winner, amount)?;
+            }
+            for player in &players {
+                state.balances.entry(player.clone()).or_default().release(bet)?;
+                state.sync_rewards(player);
+            }
+            state.escrow.release(game_id);
+            if fee > 0 {
+                state.treasury.collect(fee);
+                state.events.emit(state.clock.now(), GameEvent::FeeCollected { game_id: Some(game_id), amount: fee });
+                state.pay_referrals(game_id, &players, fee, referral_bps)?;
+            }
+
+            let winner = if let [winner] = winners.as_slice() { Some(winner.clone()) } else { None };
+            state.events.emit(state.clock.now(), GameEvent::PotShared { game_id, winners: winners.clone(), payout });
+            state.events.emit(state.clock.now(), GameEvent::Settled { game_id, winner, payout });
+            let game = state.games.remove(&game_id).ok_or(GameError::NoGameToArchive)?;
+            state.archive_seats(game, winners, fee, payout)?;
+            state.check_escrow();
+            Ok(())
+        })
+    }
+
+    // Like archive_game, games of more than two seats are not rated and cannot be replayed
+    fn archive_seats(&mut self, game: Game, winners: Vec<String>, fee: u64, payout: u64) -> Result<(), GameError> {
+        let now = self.clock.now();
+        self.commitments.reveal(game.commitment_id, &game.seed, now)?;
+
+        let pot = game.pot.get();
+        self.accounting.record(game.period_id, LedgerEntry::Handle(pot));
+        self.accounting.record(game.period_id, LedgerEntry::Payout(pot - fee));
+        if fee > 0 {
+            self.accounting.record(game.period_id, LedgerEntry::Rake(fee));
+        }
+        // Side bets back the creator or the opponent, neither is how a shared pot ends, so they are refunded
+        self.settle_side_bets(game.id, None::<Side>)?;
+        let players: Vec<String> = game.players.iter().map(|seat| seat.player.clone()).collect();
+        self.stats.record_seats(&players, &winners, game.bet_amount.get(), payout);
+        self.stats.record_pot(pot, now);
+
+        let hand = |index: usize| game.players.get(index).map(|seat| seat.hand.clone()).unwrap_or_default();
+        let winner = if let [winner] = winners.as_slice() { Some(winner.clone()) } else { None };
+        self.history.record(GameRecord {
+            game_id: game.id,
+            creator: game.creator.clone(),
+            opponent: players.get(1).cloned().unwrap_or_default(),
+            creator_hand: hand(0),
+            opponent_hand: hand(1),
+            rounds: Vec::new(),
+            shared_by: if winner.is_none() { winners } else { Vec::new() },
+            winner,
+            pot,
+            started_at: game.start_time,
+            settled_at: now,
+            seed: commitments::to_hex(&game.seed),
+            setup: None,
+            seats: game.players,
+        });
+        Ok(())
+    }
+}
+
+#[test]
+fn test_highest_cards_share_the_pot() {
+    use crate::deck::Suit;
+    use crate::rules::HighCard;
+
+    let hand = |rank| vec![Card { rank, suit: Suit::Spades }];
+    assert_eq!(best_hands(&HighCard, &[hand(3), hand(12), hand(7)]), vec![1]);
+    assert_eq!(best_hands(&HighCard, &[hand(12), hand(5), hand(12), hand(2)]), vec![0, 2]);
+    assert_eq!(share(100, 3), (33, 1));
+    assert_eq!(share(100, 1), (100, 0));
+}
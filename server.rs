@@ -0,0 +1,684 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+use crate::account_id::AccountId;
+use crate::auth::Credentials;
+use crate::cancel::{CancellationToken, OperationContext};
+use crate::config::HandlerTimeouts;
+use crate::context::Context;
+use crate::error::{ErrorKind, GameError};
+use crate::events::LoggedEvent;
+use crate::jsonrpc;
+use crate::lobby::LobbyFilter;
+use crate::preferences::GameOptions;
+use crate::ratelimit::RateLimiter;
+use crate::readmodels::{ReadModels, SharedReadModels};
+use crate::stats::StatsMetric;
+use crate::teams::Team;
+use crate::token::ERC20Token;
+use crate::vault::vault;
+use crate::GameState;
+
+// HTTP front of the game engine (server feature). Every request runs against the one shared state,
+// mutations take the write lock and are saved before the response goes out. Listings are answered from
+// the read models and do not take the lock. Stakes are deposited into and withdrawn from the vault of
+// `token`, the only way tokens enter or leave the game. Actions are taken for the account of the bearer
+// credential of the request, nobody can act without one. An action still waiting for the lock past the
+// budget of its endpoint is refused rather than applied late.
+#[derive(Clone)]
+pub struct Server {
+    pub game_state: Arc<RwLock<GameState>>,
+    pub token: Arc<RwLock<ERC20Token>>,
+    read_models: SharedReadModels,
+    channels: Channels,
+    credentials: Arc<Credentials>,
+    timeouts: Arc<HandlerTimeouts>, // From the config the server starts with
+    cancellation: CancellationToken,
+    save_to: Option<PathBuf>, // State file written after every mutation, nothing is saved without one
+    save_token_to: Option<PathBuf>, // Token export written after every deposit, withdrawal or approval
+    limiter: Option<Arc<RateLimiter>>, // Actions of each account are not limited without one
+}
+
+// Live events of each watched game. A channel exists while someone listens to it.
+#[derive(Clone, Default)]
+pub struct Channels {
+    games: Arc<Mutex<HashMap<u64, broadcast::Sender<LoggedEvent>>>>,
+}
+
+impl Channels {
+    pub fn subscribe(&self, game_id: u64) -> broadcast::Receiver<LoggedEvent> {
+        let mut games = self.games.lock().unwrap();
+        games.entry(game_id).or_insert_with(|| broadcast::channel(256).0).subscribe()
+    }
+
+    fn publish(&self, logged: &LoggedEvent) {
+        let mut games = self.games.lock().unwrap();
+        games.retain(|game_id, sender| {
+            if logged.event.is_about_game(*game_id) {
+                let _ = sender.send(logged.clone());
+            }
+            sender.receiver_count() > 0
+        });
+    }
+}
+
+impl Server {
+    pub fn new(mut game_state: GameState, token: ERC20Token) -> Self {
+        let channels = Channels::default();
+        let publisher = channels.clone();
+        game_state.subscribe(move |logged| publisher.publish(logged));
+        let read_models = ReadModels::attach(&mut game_state);
+        let timeouts = Arc::new(game_state.config().handler_timeouts.clone());
+        Server {
+            game_state: Arc::new(RwLock::new(game_state)),
+            token: Arc::new(RwLock::new(token)),
+            read_models,
+            channels,
+            credentials: Arc::new(Credentials::new()),
+            timeouts,
+            cancellation: CancellationToken::new(),
+            save_to: None,
+            save_token_to: None,
+            limiter: None,
+        }
+    }
+
+    pub fn saving_to(mut self, game_state: Option<PathBuf>, token: Option<PathBuf>) -> Self {
+        self.save_to = game_state;
+        self.save_token_to = token;
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    // Refuses actions of an account past the rate of `limiter` with RateLimited
+    pub fn with_rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    pub fn channels(&self) -> &Channels {
+        &self.channels
+    }
+
+    // Cancelled by whoever stops the server, actions still waiting for the state are then refused
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    fn operation(&self, endpoint: &str) -> OperationContext {
+        OperationContext::new(self.cancellation.clone(), self.timeouts.timeout_for(endpoint))
+    }
+
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/approvals", post(approve))
+            .route("/stakes", post(stake))
+            .route("/stakes/:user", get(stake_of))
+            .route("/withdrawals", post(withdraw))
+            .route("/games", get(open_games).post(start_game))
+            .route("/games/:game_id", get(game))
+            .route("/games/:game_id/join", post(join_game))
+            .route("/games/:game_id/bot", post(join_game_with_bot))
+            .route("/games/:game_id/reveal", post(reveal_cards))
+            .route("/games/:game_id/events", get(watch_game))
+            .route("/leaderboard", get(leaderboard))
+            .route("/players/:user/games", get(player_games))
+            .route("/rpc", post(rpc))
+            .route("/metrics", get(metrics))
+            .with_state(self)
+    }
+
+    fn limit(&self, account: &str) -> Result<(), GameError> {
+        self.limiter.as_ref().map_or(Ok(()), |limiter| limiter.check(account))
+    }
+
+    // Applies `action` under the write lock and saves the result, unless `endpoint` ran out of time first
+    async fn mutate<T>(&self, endpoint: &str, action: impl FnOnce(&mut GameState) -> Result<T, GameError>) -> Result<T, ApiError> {
+        let operation = self.operation(endpoint);
+        let mut game_state = self.game_state.write().await;
+        operation.checkpoint().inspect_err(|error| game_state.record_error(error.kind()))?;
+        let result = action(&mut game_state).inspect_err(|error| game_state.record_error(error.kind()))?;
+        if let Some(path) = &self.save_to {
+            game_state.save(path)?;
+        }
+        Ok(result)
+    }
+
+    // Like mutate for actions moving tokens too, the game and the token are changed and saved together.
+    // What the token emitted goes to the game's log.
+    async fn mutate_staked<T>(
+        &self,
+        endpoint: &str,
+        action: impl FnOnce(&mut GameState, &mut ERC20Token) -> Result<T, GameError>,
+    ) -> Result<T, ApiError> {
+        let operation = self.operation(endpoint);
+        let mut game_state = self.game_state.write().await;
+        let mut token = self.token.write().await;
+        operation.checkpoint().inspect_err(|error| game_state.record_error(error.kind()))?;
+        let result = action(&mut game_state, &mut token);
+        game_state.log_token_events(token.take_events());
+        let result = result.inspect_err(|error| game_state.record_error(error.kind()))?;
+        self.save(&mut game_state, &token)?;
+        Ok(result)
+    }
+
+    fn save(&self, game_state: &mut GameState, token: &ERC20Token) -> Result<(), GameError> {
+        if let Some(path) = &self.save_token_to {
+            token.export(path)?;
+        }
+        if let Some(path) = &self.save_to {
+            game_state.save(path)?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn serve(addr: &str, server: Server) -> Result<(), GameError> {
+    let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| GameError::Io { path: addr.to_string(), message: e.to_string() })?;
+    serve_on(listener, server).await
+}
+
+// Serves on a listener bound by the caller, e.g. to port 0 to let the system pick a free one
+pub async fn serve_on(listener: tokio::net::TcpListener, server: Server) -> Result<(), GameError> {
+    let addr = listener.local_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    tracing::info!(addr, rate_limit = server.limiter.as_ref().map(|limiter| limiter.per_sec()), "Serving HTTP.");
+    axum::serve(listener, server.router()).await.map_err(|e| GameError::Io { path: addr, message: e.to_string() })
+}
+
+// Refusals keep their player facing message, the status tells clients what kind of refusal it is
+pub struct ApiError(pub GameError);
+
+impl From<GameError> for ApiError {
+    fn from(error: GameError) -> Self {
+        ApiError(error)
+    }
+}
+
+pub fn status_code(error: &GameError) -> StatusCode {
+    match error {
+        GameError::RateLimited => return StatusCode::TOO_MANY_REQUESTS,
+        GameError::Unauthenticated => return StatusCode::UNAUTHORIZED,
+        _ => {}
+    }
+    match error.kind() {
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::Forbidden => StatusCode::FORBIDDEN,
+        ErrorKind::Conflict => StatusCode::CONFLICT,
+        ErrorKind::Invalid => StatusCode::BAD_REQUEST,
+        ErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (status_code(&self.0), Json(json!({ "error": self.0.to_string() }))).into_response()
+    }
+}
+
+// The account a request acts for, from its `Authorization: Bearer` credential. Bodies name no account.
+pub struct Caller(pub AccountId);
+
+#[axum::async_trait]
+impl FromRequestParts<Server> for Caller {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, server: &Server) -> Result<Self, ApiError> {
+        let header = parts.headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()).unwrap_or_default();
+        let account = server.credentials.authenticate(Credentials::bearer(header)?)?;
+        Ok(Caller(account.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Amount {
+    pub amount: u64,
+    #[serde(default)]
+    pub command_id: Option<Uuid>, // Retries with the same id get the first answer, see GameState::run_once
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StartGame {
+    pub bet: u64,
+    #[serde(default)]
+    pub seats: Option<u32>, // Two when left out
+    #[serde(default)]
+    pub teams: Option<bool>,
+    #[serde(default)]
+    pub command_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JoinGame {
+    #[serde(default)]
+    pub team: Option<Team>, // Only for team games, the team with fewer players when left out
+    #[serde(default)]
+    pub command_id: Option<Uuid>,
+}
+
+// Body of the actions on a game that need nothing but the caller
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GameCommand {
+    #[serde(default)]
+    pub command_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OpenGames {
+    pub min_bet: Option<u64>,
+    pub max_bet: Option<u64>,
+    pub after: Option<u64>, // Last game id of the previous page
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Leaderboard {
+    pub metric: Option<StatsMetric>, // NetPnl by default
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PlayerGames {
+    pub limit: Option<usize>,
+}
+
+// Lets the vault take up to `amount` tokens of the caller, what a deposit moves
+async fn approve(State(server): State<Server>, Caller(caller): Caller, Json(request): Json<Amount>) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    server.mutate_staked("approve", |_, token| Ok(token.approve(&Context::new(caller), vault(), request.amount)?)).await?;
+    Ok(Json(json!({})))
+}
+
+async fn stake(State(server): State<Server>, Caller(caller): Caller, Json(request): Json<Amount>) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    server.mutate_staked("stake", |game_state, token| game_state.deposit(&ctx, token, request.amount)).await?;
+    Ok(Json(json!({})))
+}
+
+async fn withdraw(State(server): State<Server>, Caller(caller): Caller, Json(request): Json<Amount>) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    server.mutate_staked("withdraw", |game_state, token| game_state.withdraw(&ctx, token, request.amount)).await?;
+    Ok(Json(json!({})))
+}
+
+async fn stake_of(State(server): State<Server>, Path(user): Path<String>) -> Json<Value> {
+    let stake = server.game_state.read().await.stake_of(&user);
+    Json(json!({ "user": user, "available": stake.available, "locked": stake.locked }))
+}
+
+async fn start_game(State(server): State<Server>, Caller(caller): Caller, Json(request): Json<StartGame>) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    let options = GameOptions { seats: request.seats, teams: request.teams, ..GameOptions::with_bet(request.bet) };
+    let game_id = server.mutate("start", |game_state| game_state.start_game_with(&ctx, options)).await?;
+    Ok(Json(json!({ "game_id": game_id })))
+}
+
+async fn join_game(
+    State(server): State<Server>,
+    Caller(caller): Caller,
+    Path(game_id): Path<u64>,
+    Json(request): Json<JoinGame>,
+) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    server
+        .mutate("join", |game_state| match request.team {
+            Some(team) => game_state.join_team(&ctx, game_id, team),
+            None => game_state.join_game(&ctx, game_id),
+        })
+        .await?;
+    Ok(Json(json!({ "game_id": game_id })))
+}
+
+async fn join_game_with_bot(
+    State(server): State<Server>,
+    Caller(caller): Caller,
+    Path(game_id): Path<u64>,
+    Json(request): Json<GameCommand>,
+) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    server.mutate("bot", |game_state| game_state.join_game_with_bot(&ctx, game_id)).await?;
+    Ok(Json(json!({ "game_id": game_id })))
+}
+
+// The caller asks for the next round of a game they sit in, played once every player asked. `revealed`
+// tells whether it was, `game` is null in the answer once the game is settled.
+async fn reveal_cards(
+    State(server): State<Server>,
+    Caller(caller): Caller,
+    Path(game_id): Path<u64>,
+    Json(request): Json<GameCommand>,
+) -> Result<Json<Value>, ApiError> {
+    server.limit(&caller)?;
+    let ctx = Context::new(caller).with_command_id(request.command_id);
+    let (revealed, summary) = server
+        .mutate("reveal", |game_state| {
+            let revealed = game_state.contribute_reveal(&ctx, game_id)?;
+            Ok((revealed, game_state.game_summary(game_id)))
+        })
+        .await?;
+    Ok(Json(json!({ "game_id": game_id, "revealed": revealed, "settled": summary.is_none(), "game": summary })))
+}
+
+async fn game(State(server): State<Server>, Path(game_id): Path<u64>) -> Result<Json<Value>, ApiError> {
+    let summary = server.game_state.read().await.game_summary(game_id).ok_or(GameError::GameNotFound)?;
+    Ok(Json(json!(summary)))
+}
+
+async fn open_games(State(server): State<Server>, Query(query): Query<OpenGames>) -> Json<Value> {
+    let filter = LobbyFilter { min_bet: query.min_bet, max_bet: query.max_bet };
+    let page = server.read_models.read().unwrap().open_games(&filter, query.after, query.limit.unwrap_or(50));
+    Json(json!(page))
+}
+
+async fn leaderboard(State(server): State<Server>, Query(query): Query<Leaderboard>) -> Json<Value> {
+    let metric = query.metric.unwrap_or(StatsMetric::NetPnl);
+    let entries = server.read_models.read().unwrap().leaderboard(query.limit.unwrap_or(50), metric);
+    let entries: Vec<Value> = entries.into_iter().map(|(player, stats)| json!({ "player": player, "stats": stats })).collect();
+    Json(json!(entries))
+}
+
+// Settled games of the player, newest first
+async fn player_games(State(server): State<Server>, Path(user): Path<String>, Query(query): Query<PlayerGames>) -> Json<Value> {
+    let played = server.read_models.read().unwrap().player_games(&user, query.limit.unwrap_or(50));
+    Json(json!(played))
+}
+
+// JSON-RPC 2.0 over the same state, see jsonrpc for the methods. Queries need no credential.
+async fn rpc(State(server): State<Server>, caller: Option<Caller>, body: String) -> Result<Response, ApiError> {
+    let operation = server.operation("rpc");
+    let mut game_state = server.game_state.write().await;
+    let mut token = server.token.write().await;
+    let caller = caller.map(|Caller(caller)| caller);
+    let reply = jsonrpc::handle(&mut game_state, &mut token, caller.as_ref(), &body, server.limiter.as_deref(), &operation);
+    game_state.log_token_events(token.take_events());
+    if reply.changed {
+        server.save(&mut game_state, &token)?;
+    }
+    Ok(match reply.body {
+        Some(body) => ([(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+// Prometheus scrape endpoint
+async fn metrics(State(server): State<Server>) -> Response {
+    let export = server.game_state.read().await.export_metrics();
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], export).into_response()
+}
+
+// Players of the game watch with their credential, everyone else is a spectator
+async fn watch_game(State(server): State<Server>, caller: Option<Caller>, Path(game_id): Path<u64>, upgrade: WebSocketUpgrade) -> Response {
+    let seated = match caller {
+        Some(Caller(caller)) => {
+            let game_state = server.game_state.read().await;
+            let in_history = game_state.history().by_id(game_id).is_some_and(|record| record.involves(&caller));
+            in_history || game_state.games.get(&game_id).is_some_and(|game| game.is_seated(&caller))
+        }
+        None => false,
+    };
+    upgrade.on_upgrade(move |socket| forward_events(socket, server, game_id, !seated))
+}
+
+// Sends every event of the game as JSON, first the ones already logged and then the live ones. A client
+// that falls behind the channel is caught up from the log again, so it never misses an event. Spectators
+// get the cards of a game only once it is settled.
+async fn forward_events(mut socket: WebSocket, server: Server, game_id: u64, spectator: bool) {
+    let mut receiver = server.channels.subscribe(game_id);
+    let mut next = 0; // Sequence of the first event the client has not seen
+    loop {
+        let (missed, settled): (Vec<LoggedEvent>, bool) = {
+            let game_state = server.game_state.read().await;
+            let missed = game_state
+                .events()
+                .iter()
+                .filter(|logged| logged.sequence >= next && logged.event.is_about_game(game_id))
+                .cloned()
+                .collect();
+            (missed, game_state.history().by_id(game_id).is_some())
+        };
+        let hide = spectator && !settled;
+        for logged in missed {
+            next = logged.sequence + 1;
+            if send_event(&mut socket, &logged, hide).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(logged) if logged.sequence < next => {}
+                // Cards are only logged while the game runs, live ones are always hidden from spectators
+                Ok(logged) => {
+                    next = logged.sequence + 1;
+                    if send_event(&mut socket, &logged, spectator).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, logged: &LoggedEvent, redact: bool) -> Result<(), axum::Error> {
+    let json = if redact { serde_json::to_string(&logged.redacted()) } else { serde_json::to_string(logged) };
+    let json = json.map_err(axum::Error::new)?;
+    socket.send(Message::Text(json)).await
+}
+
+// Token minting 100 for free to each of `users`, for tests
+#[cfg(test)]
+fn funded(users: &[&str]) -> ERC20Token {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("Owner"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("Owner"), 0).unwrap();
+    for user in users {
+        token.mint(&caller(user), 100, 0).unwrap();
+    }
+    token
+}
+
+#[tokio::test]
+async fn test_play_over_http() {
+    use crate::account_id::account;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let mut credentials = Credentials::new();
+    let secrets: HashMap<&str, String> = ["Alice", "Bob", "Carol"].into_iter().map(|user| (user, credentials.issue(account(user)))).collect();
+    let server = Server::new(GameState::new(), funded(&["Alice", "Bob"])).with_credentials(credentials);
+    let router = server.clone().router();
+    let call = |user: Option<&str>, method: &str, uri: &str, body: Value| {
+        let mut request = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        if let Some(user) = user {
+            request = request.header("authorization", format!("Bearer {}", secrets[user]));
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let router = router.clone();
+        async move {
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<Value>(&bytes).unwrap())
+        }
+    };
+
+    // The body cannot name someone else
+    let (status, body) = call(None, "POST", "/stakes", json!({ "user": "Alice", "amount": 100 })).await;
+    assert_eq!((status, body), (StatusCode::UNAUTHORIZED, json!({ "error": "Missing or unknown credential." })));
+    let (status, body) = call(Some("Alice"), "POST", "/stakes", json!({ "amount": 100 })).await;
+    assert_eq!((status, body), (StatusCode::CONFLICT, json!({ "error": "Insufficient allowance." })));
+    for user in ["Alice", "Bob"] {
+        assert_eq!(call(Some(user), "POST", "/approvals", json!({ "amount": 100 })).await.0, StatusCode::OK);
+        assert_eq!(call(Some(user), "POST", "/stakes", json!({ "amount": 100 })).await.0, StatusCode::OK);
+    }
+    let (_, started) = call(Some("Alice"), "POST", "/games", json!({ "bet": 30 })).await;
+    let game_id = started["game_id"].as_u64().unwrap();
+    let (_, open) = call(None, "GET", "/games?min_bet=10", Value::Null).await;
+    assert_eq!(open[0]["game_id"], game_id);
+
+    let join = format!("/games/{}/join", game_id);
+    assert_eq!(call(Some("Bob"), "POST", &join, json!({})).await.0, StatusCode::OK);
+    let (status, body) = call(Some("Carol"), "POST", &join, json!({})).await;
+    assert_eq!((status, body), (StatusCode::CONFLICT, json!({ "error": "Game already joined." })));
+
+    let (_, bob) = call(None, "GET", "/stakes/Bob", Value::Null).await;
+    assert_eq!(bob, json!({ "user": "Bob", "available": 70, "locked": 30 }));
+    assert_eq!(call(None, "GET", "/games/99", Value::Null).await.0, StatusCode::NOT_FOUND);
+
+    let reveal = format!("/games/{}/reveal", game_id);
+    let (status, body) = call(Some("Carol"), "POST", &reveal, json!({})).await;
+    assert_eq!((status, body), (StatusCode::FORBIDDEN, json!({ "error": "Not a player of this game." })));
+    let (_, waiting) = call(Some("Bob"), "POST", &reveal, json!({})).await;
+    assert_eq!((&waiting["revealed"], &waiting["settled"]), (&json!(false), &json!(false)));
+    while call(Some("Alice"), "POST", &reveal, json!({})).await.1["settled"] == false {
+        call(Some("Bob"), "POST", &reveal, json!({})).await;
+    }
+    let (_, leaders) = call(None, "GET", "/leaderboard?metric=Games&limit=1", Value::Null).await;
+    assert_eq!(leaders[0]["stats"]["total_wagered"], 30);
+    let (_, played) = call(None, "GET", "/players/Bob/games", Value::Null).await;
+    assert_eq!((&played[0]["game_id"], &played[0]["opponent"]), (&json!(game_id), &json!("Alice")));
+
+    assert_eq!(call(Some("Bob"), "POST", "/withdrawals", json!({ "amount": 10 })).await.0, StatusCode::OK);
+    assert_eq!(server.token.read().await.get_balance("Bob").get(), 10);
+    server.game_state.read().await.check_vault(&*server.token.read().await).unwrap();
+}
+
+#[test]
+fn test_channels_carry_only_their_game() {
+    use crate::context::caller;
+
+    let server = Server::new(GameState::new(), funded(&["Alice", "Bob"]));
+    let (mut game_state, mut token) = (server.game_state.try_write().unwrap(), server.token.try_write().unwrap());
+    for user in ["Alice", "Bob"] {
+        token.approve(&crate::context::caller(user), vault(), 100).unwrap();
+        game_state.deposit(&caller(user), &mut token, 100).unwrap();
+    }
+    let first = game_state.start_game(&caller("Alice"), 10).unwrap();
+    let second = game_state.start_game(&caller("Alice"), 20).unwrap();
+
+    let mut watching = server.channels().subscribe(first);
+    game_state.join_game(&caller("Bob"), second).unwrap();
+    game_state.join_game(&caller("Bob"), first).unwrap();
+    let joined = watching.try_recv().unwrap();
+    assert!(matches!(joined.event, crate::events::GameEvent::GameJoined { game_id, .. } if game_id == first));
+    assert!(watching.try_recv().is_err());
+
+    // Nobody listens to a dropped channel, it goes away with the next event
+    drop(watching);
+    game_state.withdraw(&caller("Alice"), &mut token, 1).unwrap();
+    assert!(server.channels().games.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_spam_is_rate_limited() {
+    use crate::clock::{ManualClock, SharedClock};
+
+    let clock = ManualClock::new(1_000);
+    let server = Server::new(GameState::new(), funded(&["Mallory", "Alice"]));
+    let server = server.with_rate_limit(RateLimiter::with_clock(2, SharedClock::new(clock.clone())));
+    for user in ["Mallory", "Alice"] {
+        server.token.write().await.approve(&crate::context::caller(user), vault(), 100).unwrap();
+    }
+    let stake = |user: &str, amount: u64| {
+        let request = Amount { amount, command_id: None };
+        let (server, user) = (server.clone(), AccountId::new(user).unwrap());
+        async move { stake(State(server), Caller(user), Json(request)).await.into_response().status() }
+    };
+
+    // Refused stakes count too
+    assert_eq!(stake("Mallory", 0).await, StatusCode::BAD_REQUEST);
+    assert_eq!(stake("Mallory", 10).await, StatusCode::OK);
+    assert_eq!(stake("Mallory", 10).await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(stake("Alice", 10).await, StatusCode::OK);
+
+    let body = json!({ "jsonrpc": "2.0", "method": "deposit", "params": { "amount": 10 }, "id": 1 });
+    let mallory = Some(Caller(AccountId::new("Mallory").unwrap()));
+    let reply = rpc(State(server.clone()), mallory, body.to_string()).await.ok().unwrap();
+    let reply = axum::body::to_bytes(reply.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&reply).unwrap()["error"]["message"], "Too many requests, slow down.");
+
+    clock.advance(1);
+    assert_eq!(stake("Mallory", 10).await, StatusCode::OK);
+    assert_eq!(server.game_state.read().await.stake_of("Mallory").available.get(), 20);
+}
+
+#[tokio::test]
+async fn test_late_actions_are_refused() {
+    let mut game_state = GameState::new();
+    game_state.config.handler_timeouts.per_endpoint.insert("withdraw".to_string(), 0);
+    let server = Server::new(game_state, funded(&["Alice"]));
+    server.token.write().await.approve(&crate::context::caller("Alice"), vault(), 100).unwrap();
+    let alice = || Caller(AccountId::new("Alice").unwrap());
+    let amount = |amount: u64| Json(Amount { amount, command_id: None });
+    assert_eq!(stake(State(server.clone()), alice(), amount(100)).await.into_response().status(), StatusCode::OK);
+
+    // No time at all to withdraw
+    let late = withdraw(State(server.clone()), alice(), amount(10)).await.into_response();
+    assert_eq!(late.status(), StatusCode::SERVICE_UNAVAILABLE);
+    server.cancellation().cancel();
+    let cancelled = stake(State(server.clone()), alice(), amount(0)).await.into_response();
+    assert_eq!(cancelled.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let bytes = axum::body::to_bytes(cancelled.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&bytes).unwrap(), json!({ "error": "Operation cancelled." }));
+    assert_eq!(server.game_state.read().await.stake_of("Alice").available.get(), 100);
+}
+
+#[tokio::test]
+async fn test_metrics_are_scraped() {
+    use crate::context::caller;
+
+    let server = Server::new(GameState::new(), funded(&["Alice", "Bob"]));
+    let game_id = {
+        let (mut game_state, mut token) = (server.game_state.write().await, server.token.write().await);
+        for user in ["Alice", "Bob"] {
+            token.approve(&crate::context::caller(user), vault(), 100).unwrap();
+            game_state.deposit(&caller(user), &mut token, 100).unwrap();
+        }
+        game_state.start_game(&caller("Alice"), 30).unwrap()
+    };
+    let join = |opponent: &str| {
+        let opponent = Caller(AccountId::new(opponent).unwrap());
+        join_game(State(server.clone()), opponent, Path(game_id), Json(JoinGame::default()))
+    };
+    assert!(join("Bob").await.is_ok());
+    assert!(join("Carol").await.is_err());
+    for (caller, method) in [("Carol", "join_game"), ("Bob", "reveal_cards"), ("Alice", "reveal_cards")] {
+        let body = json!({ "jsonrpc": "2.0", "method": method, "params": { "game_id": game_id }, "id": 1 });
+        let caller = Some(Caller(AccountId::new(caller).unwrap()));
+        rpc(State(server.clone()), caller, body.to_string()).await.ok().unwrap();
+    }
+
+    let response = metrics(State(server)).await;
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; version=0.0.4");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let export = String::from_utf8(bytes.to_vec()).unwrap();
+    for line in ["games_settled_total 1\n", "active_games 0\n", "volume_total 60\n", "errors_total{kind=\"Conflict\"} 2\n"] {
+        assert!(export.contains(line), "{} missing from\n{}", line, export);
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::account_id::AccountId;
+use crate::accounting::PeriodReport;
+use crate::accounts::ClosedAccount;
+use crate::balance::Balance;
+use crate::config::GameConfig;
+use crate::configlog::ConfigVersion;
+use crate::context::Context;
+use crate::deck::DeckSpec;
+use crate::deposits::{ChainAdapter, PayoutAdapter};
+use crate::error::GameError;
+use crate::events::{LoggedEvent, SubscriberId};
+use crate::eventsource::Snapshot;
+use crate::lobby::{GameFilter, GameSummary, LobbyFilter};
+use crate::merkle::MerkleProof;
+use crate::names::Identities;
+use crate::odds::Odds;
+use crate::overview::AdminOverview;
+use crate::paging::Page;
+use crate::preferences::GameOptions;
+use crate::rating::RatingChange;
+use crate::roles::Role;
+use crate::rules::GameMode;
+use crate::sidebets::Side;
+use crate::slashing::Profile;
+use crate::spectate::SpectatorView;
+use crate::stats::{ActivityAverages, PlayerStats, StatsMetric};
+use crate::teams::Team;
+use crate::storage::Store;
+use crate::token::ERC20Token;
+use crate::tournament::Tournament;
+use crate::watchdog::WatchdogAction;
+use crate::GameState;
+
+// Same methods as GameState, each one taking the lock for its own duration
+macro_rules! delegate {
+    ($lock:ident => $(fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            pub fn $name(&self, $($arg: $ty),*) -> $ret {
+                self.$lock().$name($($arg),*)
+            }
+        )*
+    };
+}
+
+// GameState behind one lock, for threads and handlers that share a single game. Clones share the state.
+// Reads run side by side, every mutation has the state to itself, so each call sees the state whole and
+// the escrow checks hold between calls. Methods of GameState returning references return copies here;
+// read and write run several calls under one lock.
+#[derive(Clone, Default)]
+pub struct SharedGameState {
+    inner: Arc<RwLock<GameState>>,
+}
+
+impl From<GameState> for SharedGameState {
+    fn from(game_state: GameState) -> Self {
+        SharedGameState { inner: Arc::new(RwLock::new(game_state)) }
+    }
+}
+
+impl SharedGameState {
+    pub fn new() -> Self {
+        SharedGameState::default()
+    }
+
+    // A panic in another thread leaves the lock poisoned. The state it left is still checked by the next
+    // mutation like any other, so the lock is taken over rather than failing every later call.
+    fn read_lock(&self) -> RwLockReadGuard<'_, GameState> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, GameState> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn read<T>(&self, action: impl FnOnce(&GameState) -> T) -> T {
+        action(&self.read_lock())
+    }
+
+    pub fn write<T>(&self, action: impl FnOnce(&mut GameState) -> T) -> T {
+        action(&mut self.write_lock())
+    }
+
+    delegate! { read_lock =>
+        fn has_role(account: &str, role: Role) -> bool;
+        fn next_nonce(account: &str) -> u64;
+        fn treasury_balance() -> u64;
+        fn fees_collected() -> u64;
+        fn canonical_name(name: &str) -> Result<String, GameError>;
+        fn energy(player: &str) -> u32;
+        fn time_to_next_game(player: &str) -> u64;
+        fn preferences(player: &str) -> GameOptions;
+        fn jackpot_balance() -> u64;
+        fn rating(player: &str) -> i32;
+        fn leaderboard(limit: usize, metric: StatsMetric) -> Vec<(String, PlayerStats)>;
+        fn activity() -> ActivityAverages;
+        fn export_metrics() -> String;
+        fn format_amount(amount: u64) -> String;
+        fn list_open_games(filter: &LobbyFilter, after: Option<u64>, limit: usize) -> Vec<GameSummary>;
+        fn list_games(filter: &GameFilter, cursor: Option<u64>, limit: usize) -> Page<GameSummary, u64>;
+        fn list_balances(cursor: Option<&str>, limit: usize) -> Page<(String, Balance), String>;
+        fn admin_overview(ctx: &Context, top: usize) -> Result<AdminOverview, GameError>;
+        fn game_summary(game_id: u64) -> Option<GameSummary>;
+        fn teams(game_id: u64) -> Option<[Vec<String>; 2]>;
+        fn spectate(game_id: u64) -> Option<SpectatorView>;
+        fn odds(mode: GameMode, deck: &DeckSpec) -> Result<Odds, GameError>;
+        fn insurance_pool() -> u64;
+        fn overdue_games(now: u64) -> Vec<u64>;
+        fn readyz() -> Result<(), GameError>;
+        fn verify_audit_log() -> Result<(), GameError>;
+        fn stakes_merkle_root() -> String;
+        fn take_snapshot() -> Result<Snapshot, GameError>;
+        fn prove_stake(account: &str) -> Result<MerkleProof, GameError>;
+        fn export_state() -> Result<String, GameError>;
+        fn save(path: &Path) -> Result<(), GameError>;
+        fn stake_of(user: &str) -> Balance;
+    }
+
+    delegate! { write_lock =>
+        fn apply_config(ctx: &Context, config: GameConfig) -> Result<u64, GameError>;
+        fn init_admin(admin: AccountId) -> Result<(), GameError>;
+        fn grant_role(ctx: &Context, account: AccountId, role: Role) -> Result<(), GameError>;
+        fn revoke_role(ctx: &Context, account: &str, role: Role) -> Result<(), GameError>;
+        fn withdraw_treasury(ctx: &Context, to: AccountId, amount: u64) -> Result<(), GameError>;
+        fn migrate_names() -> Vec<String>;
+        fn set_preferences(ctx: &Context, preferences: GameOptions) -> Result<(), GameError>;
+        fn fund_jackpot(ctx: &Context, amount: u64) -> Result<(), GameError>;
+        fn initialize(ctx: &Context) -> Result<(), GameError>;
+        fn close_period(ctx: &Context, period_id: u64) -> Result<PeriodReport, GameError>;
+        fn unsubscribe(id: SubscriberId) -> bool;
+        fn start_game(ctx: &Context, bet: u64) -> Result<u64, GameError>;
+        fn start_game_with(ctx: &Context, options: GameOptions) -> Result<u64, GameError>;
+        fn create_tournament(ctx: &Context, buy_in: u64, max_players: usize) -> Result<u64, GameError>;
+        fn join_tournament(ctx: &Context, tournament_id: u64) -> Result<(), GameError>;
+        fn leave_tournament(ctx: &Context, tournament_id: u64) -> Result<(), GameError>;
+        fn start_tournament(ctx: &Context, tournament_id: u64) -> Result<(), GameError>;
+        fn play_tournament_round(ctx: &Context, tournament_id: u64) -> Result<Option<String>, GameError>;
+        fn join_game(ctx: &Context, game_id: u64) -> Result<(), GameError>;
+        fn join_team(ctx: &Context, game_id: u64, team: Team) -> Result<(), GameError>;
+        fn join_game_with_bot(ctx: &Context, game_id: u64) -> Result<(), GameError>;
+        fn contribute_reveal(ctx: &Context, game_id: u64) -> Result<bool, GameError>;
+        fn claim_timeout_win(ctx: &Context, game_id: u64) -> Result<(), GameError>;
+        fn enqueue_match(ctx: &Context, bet: u64, band: Option<u32>) -> Result<(), GameError>;
+        fn leave_matchmaking(ctx: &Context) -> Result<(), GameError>;
+        fn slash_for_cheating(ctx: &Context, offender: AccountId, reason: String) -> Result<u64, GameError>;
+        fn tick() -> Result<usize, GameError>;
+        fn check_watchdog() -> WatchdogAction;
+        fn place_side_bet(ctx: &Context, game_id: u64, side: Side, amount: u64) -> Result<(), GameError>;
+        fn persist(store: &mut dyn Store) -> Result<(), GameError>;
+        fn restore_from(store: &dyn Store) -> Result<(), GameError>;
+        fn deposit(ctx: &Context, token: &mut ERC20Token, amount: u64) -> Result<(), GameError>;
+        fn withdraw(ctx: &Context, token: &mut ERC20Token, amount: u64) -> Result<(), GameError>;
+        fn close_account(ctx: &Context, payout: &dyn PayoutAdapter) -> Result<u64, GameError>;
+        fn record_deposit(ctx: &Context, tx_id: String, user: AccountId, amount: u64) -> Result<(), GameError>;
+        fn sync_deposits(ctx: &Context, adapter: &dyn ChainAdapter) -> Result<(), GameError>;
+    }
+
+    pub fn set_identities(&self, identities: Identities) {
+        self.write_lock().set_identities(identities)
+    }
+
+    pub fn subscribe<F>(&self, callback: F) -> SubscriberId
+    where
+        F: Fn(&LoggedEvent) + Send + Sync + 'static,
+    {
+        self.write_lock().subscribe(callback)
+    }
+
+    pub fn config(&self) -> GameConfig {
+        self.read_lock().config().clone()
+    }
+
+    pub fn config_versions(&self) -> Vec<ConfigVersion> {
+        self.read_lock().config_versions().to_vec()
+    }
+
+    pub fn rating_history(&self, player: &str) -> Vec<RatingChange> {
+        self.read_lock().rating_history(player).to_vec()
+    }
+
+    pub fn player_stats(&self, player: &str) -> Option<PlayerStats> {
+        self.read_lock().player_stats(player).copied()
+    }
+
+    pub fn period_report(&self, period_id: u64) -> Option<PeriodReport> {
+        self.read_lock().period_report(period_id).cloned()
+    }
+
+    pub fn events(&self) -> Vec<LoggedEvent> {
+        self.read_lock().events().to_vec()
+    }
+
+    pub fn tournament(&self, tournament_id: u64) -> Option<Tournament> {
+        self.read_lock().tournament(tournament_id).cloned()
+    }
+
+    pub fn profile(&self, player: &str) -> Option<Profile> {
+        self.read_lock().profile(player).cloned()
+    }
+
+    pub fn closed_accounts(&self, player: &str) -> Vec<ClosedAccount> {
+        self.read_lock().closed_accounts(player).to_vec()
+    }
+}
+
+// Players staking, playing and withdrawing from many threads: no stake is lost or counted twice
+#[test]
+fn test_concurrent_games_keep_stakes_whole() {
+    use std::sync::Mutex;
+
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::vault::vault;
+
+    let shared = SharedGameState::new();
+    let mut token = ERC20Token::new(account("Owner"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("Owner"), 0).unwrap();
+    for player in (0..8).flat_map(|pair| [format!("Creator{}", pair), format!("Opponent{}", pair)]) {
+        token.mint(&caller(&player), 200, 0).unwrap();
+        token.approve(&caller(&player), vault(), 200).unwrap();
+    }
+    let token = Arc::new(Mutex::new(token));
+    let players: Vec<std::thread::JoinHandle<()>> = (0..8)
+        .map(|pair| {
+            let (shared, token) = (shared.clone(), token.clone());
+            std::thread::spawn(move || {
+                let (creator, opponent) = (account(&format!("Creator{}", pair)), account(&format!("Opponent{}", pair)));
+                for _ in 0..20 {
+                    shared.deposit(&Context::new(creator.clone()), &mut token.lock().unwrap(), 10).unwrap();
+                    shared.deposit(&Context::new(opponent.clone()), &mut token.lock().unwrap(), 10).unwrap();
+                    let game_id = shared.start_game(&Context::new(creator.clone()), 10).unwrap();
+                    shared.join_game(&Context::new(opponent.clone()), game_id).unwrap();
+                    while shared.game_summary(game_id).is_some() {
+                        shared.contribute_reveal(&Context::new(creator.clone()), game_id).unwrap();
+                        shared.contribute_reveal(&Context::new(opponent.clone()), game_id).unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for player in players {
+        player.join().unwrap();
+    }
+
+    let staked = 8 * 2 * 20 * 10;
+    let total: u64 = (0..8)
+        .flat_map(|pair| [format!("Creator{}", pair), format!("Opponent{}", pair)])
+        .map(|player| shared.stake_of(&player))
+        .map(|stake| stake.total().get())
+        .sum();
+    assert_eq!(total + shared.fees_collected() + shared.jackpot_balance(), staked);
+    assert!(shared.list_open_games(&LobbyFilter::default(), None, 10).is_empty());
+    shared.read(|game_state| game_state.check_vault(&token.lock().unwrap())).unwrap();
+}
+
+// Readers never see a pot half paid out: under one read lock the stakes, fees and jackpot always add up
+#[test]
+fn test_readers_see_whole_mutations() {
+    use crate::context::caller;
+
+    let shared = SharedGameState::new();
+    shared.write(|game_state| {
+        game_state.stake_tokens(&caller("Alice"), 1_000).unwrap();
+        game_state.stake_tokens(&caller("Bob"), 1_000).unwrap();
+    });
+    let writer = {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            for _ in 0..50 {
+                let game_id = shared.start_game(&caller("Alice"), 5).unwrap();
+                shared.join_game(&caller("Bob"), game_id).unwrap();
+                while shared.game_summary(game_id).is_some() {
+                    shared.contribute_reveal(&caller("Alice"), game_id).unwrap();
+                    shared.contribute_reveal(&caller("Bob"), game_id).unwrap();
+                }
+            }
+        })
+    };
+    for _ in 0..200 {
+        let total = shared.read(|game_state| {
+            let (alice, bob) = (game_state.stake_of("Alice"), game_state.stake_of("Bob"));
+            alice.total().get() + bob.total().get() + game_state.fees_collected() + game_state.jackpot_balance()
+        });
+        assert_eq!(total, 2_000);
+    }
+    writer.join().unwrap();
+
+    // A panic under the lock does not take the state down with it
+    let poisoner = shared.clone();
+    let _ = std::thread::spawn(move || poisoner.write(|_| panic!("poisoned"))).join();
+    assert!(shared.stake_of("Alice").available > 0);
+}
@@ -0,0 +1,117 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::error::GameError;
+use crate::tx::{restore_entries, save_entries};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Creator,
+    Opponent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SideBet {
+    pub backer: String,
+    pub side: Side,
+    pub amount: u64,
+}
+
+// Side bets of spectators by game id. Amounts are already taken from the backers' stakes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SideBets {
+    games: BTreeMap<u64, Vec<SideBet>>,
+}
+
+impl SideBets {
+    pub fn new() -> Self {
+        SideBets::default()
+    }
+
+    pub fn place(&mut self, game_id: u64, backer: String, side: Side, amount: u64) -> Result<(), GameError> {
+        if amount == 0 {
+            return Err(GameError::SideBetNotPositive);
+        }
+        let bets = self.games.entry(game_id).or_default();
+        bets.iter()
+            .filter(|b| b.side == side)
+            .try_fold(amount, |total, b| total.checked_add(b.amount))
+            .ok_or(GameError::Overflow)?;
+        bets.push(SideBet { backer, side, amount });
+        Ok(())
+    }
+
+    pub fn for_game(&self, game_id: u64) -> &[SideBet] {
+        self.games.get(&game_id).map(|bets| bets.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn pool(&self, game_id: u64, side: Side) -> u64 {
+        self.for_game(game_id).iter().filter(|b| b.side == side).map(|b| b.amount).sum()
+    }
+
+    // Every open side bet, of all games
+    pub fn total(&self) -> u128 {
+        self.games.values().flatten().map(|b| b.amount as u128).sum()
+    }
+
+    // Game ids with open side bets
+    pub fn games(&self) -> impl Iterator<Item = u64> + '_ {
+        self.games.keys().copied()
+    }
+
+    // Closes the book of a game and returns (backer, amount) to credit plus the rounding leftover.
+    // Winning backers get their bet back and a pro-rata share of the losing pool. On a draw, or when
+    // nobody backed the winner, every bet is refunded.
+    pub fn settle(&mut self, game_id: u64, winner: Option<Side>) -> (Vec<(String, u64)>, u64) {
+        let bets = self.games.remove(&game_id).unwrap_or_default();
+        let winning_pool: u64 = bets.iter().filter(|b| Some(b.side) == winner).map(|b| b.amount).sum();
+        if winning_pool == 0 {
+            return (bets.into_iter().map(|b| (b.backer, b.amount)).collect(), 0);
+        }
+
+        let losing_pool: u64 = bets.iter().filter(|b| Some(b.side) != winner).map(|b| b.amount).sum();
+        let mut paid_share = 0;
+        let payouts = bets
+            .into_iter()
+            .filter(|b| Some(b.side) == winner)
+            .map(|b| {
+                let share = (losing_pool as u128 * b.amount as u128 / winning_pool as u128) as u64;
+                paid_share += share;
+                (b.backer, b.amount + share)
+            })
+            .collect();
+        (payouts, losing_pool - paid_share)
+    }
+
+    // Refunds every bet of a game that will not be settled
+    pub fn refund(&mut self, game_id: u64) -> Vec<(String, u64)> {
+        self.settle(game_id, None).0
+    }
+
+    // The bets of `games` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, games: &[u64]) -> SideBets {
+        SideBets { games: save_entries(&self.games, games) }
+    }
+
+    pub(crate) fn restore(&mut self, saved: SideBets, games: &[u64]) {
+        restore_entries(&mut self.games, saved.games, games);
+    }
+}
+
+#[test]
+fn test_losing_pool_shared_pro_rata() {
+    let mut side_bets = SideBets::new();
+    side_bets.place(0, "Carol".to_string(), Side::Creator, 10).unwrap();
+    side_bets.place(0, "Dave".to_string(), Side::Creator, 20).unwrap();
+    side_bets.place(0, "Eve".to_string(), Side::Opponent, 10).unwrap();
+    assert!(side_bets.place(0, "Eve".to_string(), Side::Opponent, 0).is_err());
+    assert_eq!(side_bets.pool(0, Side::Creator), 30);
+
+    let (payouts, leftover) = side_bets.settle(0, Some(Side::Creator));
+    assert_eq!(payouts, vec![("Carol".to_string(), 13), ("Dave".to_string(), 26)]);
+    assert_eq!(leftover, 1);
+    assert!(side_bets.for_game(0).is_empty());
+
+    side_bets.place(1, "Eve".to_string(), Side::Opponent, 10).unwrap();
+    assert_eq!(side_bets.settle(1, Some(Side::Creator)), (vec![("Eve".to_string(), 10)], 0));
+}
@@ -0,0 +1,279 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Serialize, Deserialize};
+
+use crate::account_id::AccountId;
+use crate::clock::{ManualClock, SharedClock};
+use crate::config::GameConfig;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::sidebets::Side;
+use crate::GameState;
+
+// How a simulated player plays
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Behavior {
+    // Small bets, every game played through
+    Steady,
+    // Half the stake or more on every bet, and side bets on the games of others
+    Aggressive,
+    // Never asks for the reveal, the opponent has to wait the game out and claim it
+    Staller,
+    // Walks away from half the games it opens, they expire and are swept by the tick
+    Canceller,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub players: Vec<(Behavior, usize)>, // How many players of each behavior
+    pub games: usize,                    // Games opened, the run ends early once nobody can afford a bet
+    pub initial_stake: u64,
+    pub seed: u64,                       // Picks the players and their bets, the cards are dealt from random seeds
+    pub check_every: usize,              // Games between two invariant checks, the last game is always checked
+    pub game: GameConfig,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            players: vec![(Behavior::Steady, 4), (Behavior::Aggressive, 2), (Behavior::Staller, 2), (Behavior::Canceller, 2)],
+            games: 1_000,
+            initial_stake: 1_000,
+            seed: 0,
+            check_every: 100,
+            game: GameConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerOutcome {
+    pub name: String,
+    pub behavior: Behavior,
+    pub balance: u64, // Available and locked at the end of the run
+    pub won: u64,
+    pub lost: u64,
+}
+
+// Final balances of the players of one behavior
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BalanceDistribution {
+    pub behavior: Behavior,
+    pub players: usize,
+    pub min: u64,
+    pub median: u64,
+    pub max: u64,
+    pub mean: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Breach {
+    pub after_game: usize,
+    pub violations: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub games_opened: usize,
+    pub games_settled: usize,  // Played to the end or claimed after a stall
+    pub timeout_claims: usize,
+    pub games_expired: usize,  // Walked away from, left without an opponent or stalled by both players
+    pub players: Vec<PlayerOutcome>,
+    pub distributions: Vec<BalanceDistribution>,
+    pub house_revenue: u64, // Fees collected into the treasury
+    pub jackpot: u64,
+    pub breaches: Vec<Breach>,
+}
+
+struct Player {
+    name: AccountId,
+    behavior: Behavior,
+}
+
+// Runs `config.games` randomized games between the configured players on a state of their own, with a
+// manual clock moved forward as the games need, and reports where the funds ended up
+pub fn run(config: &SimulationConfig) -> Result<SimulationReport, GameError> {
+    let clock = ManualClock::new(1_000);
+    let mut game_state = GameState::with_clock(SharedClock::new(clock.clone()));
+    let house = Context::new(AccountId::new("House")?);
+    game_state.init_admin(house.caller().clone())?;
+    game_state.apply_config(&house, config.game.clone())?;
+
+    let players: Vec<Player> = config
+        .players
+        .iter()
+        .flat_map(|&(behavior, count)| (0..count).map(move |index| (behavior, format!("{:?}{}", behavior, index))))
+        .map(|(behavior, name)| Ok(Player { name: AccountId::new(name)?, behavior }))
+        .collect::<Result<_, GameError>>()?;
+    for player in &players {
+        game_state.stake_tokens(&Context::new(player.name.clone()), config.initial_stake)?;
+    }
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut report = SimulationReport {
+        games_opened: 0,
+        games_settled: 0,
+        timeout_claims: 0,
+        games_expired: 0,
+        players: Vec::new(),
+        distributions: Vec::new(),
+        house_revenue: 0,
+        jackpot: 0,
+        breaches: Vec::new(),
+    };
+    let (timeout, grace) = (config.game.game_timeout_secs, config.game.claim_grace_secs);
+    let min_bet = config.game.min_bet.max(1);
+
+    for round in 0..config.games {
+        clock.advance(rng.gen_range(1..60));
+        let available = |game_state: &GameState, player: &Player| game_state.stake_of(&player.name).available.get();
+
+        let creators: Vec<&Player> = players.iter().filter(|player| available(&game_state, player) >= min_bet).collect();
+        if creators.len() < 2 {
+            break;
+        }
+        let creator = creators[rng.gen_range(0..creators.len())];
+        let stake = available(&game_state, creator).min(config.game.max_bet);
+        let bet = match creator.behavior {
+            Behavior::Aggressive => rng.gen_range((stake / 2).max(min_bet)..=stake),
+            _ => rng.gen_range(min_bet..=(stake / 10).max(min_bet)),
+        };
+        let game_id = match game_state.start_game(&Context::new(creator.name.clone()), bet) {
+            Ok(game_id) => game_id,
+            Err(_) => continue,
+        };
+        report.games_opened += 1;
+
+        let opponents: Vec<&Player> =
+            players.iter().filter(|player| player.name != creator.name && available(&game_state, player) >= bet).collect();
+        let walked_away = creator.behavior == Behavior::Canceller && rng.gen_bool(0.5);
+        let opponent = if walked_away || opponents.is_empty() {
+            None
+        } else {
+            let opponent = opponents[rng.gen_range(0..opponents.len())];
+            game_state.join_game(&Context::new(opponent.name.clone()), game_id).ok().map(|_| opponent)
+        };
+
+        match opponent {
+            None => {
+                clock.advance(timeout + 1);
+            }
+            Some(opponent) => {
+                for backer in players.iter().filter(|player| player.behavior == Behavior::Aggressive) {
+                    let amount = available(&game_state, backer) / 10;
+                    if backer.name != creator.name && backer.name != opponent.name && amount > 0 && rng.gen_bool(0.5) {
+                        let side = if rng.gen_bool(0.5) { Side::Creator } else { Side::Opponent };
+                        let _ = game_state.place_side_bet(&Context::new(backer.name.clone()), game_id, side, amount);
+                    }
+                }
+                play(&mut game_state, &clock, game_id, [creator, opponent], timeout + grace + 1, &mut report);
+            }
+        }
+        report.games_expired += game_state.tick()?;
+
+        if (round + 1) % config.check_every.max(1) == 0 {
+            check(&game_state, report.games_opened, &mut report);
+        }
+    }
+    clock.advance(timeout + grace + 1);
+    report.games_expired += game_state.tick()?;
+    check(&game_state, report.games_opened, &mut report);
+
+    report.players = players
+        .iter()
+        .map(|player| {
+            let stats = game_state.player_stats(&player.name).copied().unwrap_or_default();
+            PlayerOutcome {
+                name: player.name.to_string(),
+                behavior: player.behavior,
+                balance: game_state.stake_of(&player.name).total().get(),
+                won: stats.wins,
+                lost: stats.losses,
+            }
+        })
+        .collect();
+    report.distributions = distributions(&report.players);
+    report.house_revenue = game_state.fees_collected();
+    report.jackpot = game_state.jackpot_balance();
+    Ok(report)
+}
+
+// Reveals until the game is settled. A staller never asks, so its opponent waits out the grace period and
+// claims the game; with two stallers the game is left to the tick.
+fn play(game_state: &mut GameState, clock: &ManualClock, game_id: u64, seated: [&Player; 2], wait: u64, report: &mut SimulationReport) {
+    let honest: Vec<&Player> = seated.into_iter().filter(|player| player.behavior != Behavior::Staller).collect();
+    if honest.len() == 2 {
+        while game_state.game_summary(game_id).is_some() {
+            for player in &honest {
+                if game_state.contribute_reveal(&Context::new(player.name.clone()), game_id).is_err() {
+                    return;
+                }
+            }
+        }
+        report.games_settled += 1;
+        return;
+    }
+
+    for player in &honest {
+        let _ = game_state.contribute_reveal(&Context::new(player.name.clone()), game_id);
+    }
+    clock.advance(wait);
+    if let Some(player) = honest.first() {
+        if game_state.claim_timeout_win(&Context::new(player.name.clone()), game_id).is_ok() {
+            report.games_settled += 1;
+            report.timeout_claims += 1;
+        }
+    }
+}
+
+fn check(game_state: &GameState, after_game: usize, report: &mut SimulationReport) {
+    match game_state.check_invariants() {
+        Ok(()) => {}
+        Err(GameError::InvariantsViolated(violations)) => report.breaches.push(Breach { after_game, violations }),
+        Err(error) => report.breaches.push(Breach { after_game, violations: vec![error.to_string()] }),
+    }
+}
+
+// One distribution per behavior, in the order behaviors first appear among the players
+fn distributions(players: &[PlayerOutcome]) -> Vec<BalanceDistribution> {
+    let mut behaviors: Vec<Behavior> = Vec::new();
+    for player in players {
+        if !behaviors.contains(&player.behavior) {
+            behaviors.push(player.behavior);
+        }
+    }
+    behaviors
+        .into_iter()
+        .map(|behavior| {
+            let mut balances: Vec<u64> =
+                players.iter().filter(|player| player.behavior == behavior).map(|player| player.balance).collect();
+            balances.sort_unstable();
+            BalanceDistribution {
+                behavior,
+                players: balances.len(),
+                min: balances[0],
+                median: balances[balances.len() / 2],
+                max: balances[balances.len() - 1],
+                mean: balances.iter().map(|&balance| balance as f64).sum::<f64>() / balances.len() as f64,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_simulated_economy_conserves_funds() {
+    let config = SimulationConfig { games: 2_000, ..SimulationConfig::default() };
+    let report = run(&config).unwrap();
+    assert!(report.breaches.is_empty(), "{:?}", report.breaches);
+    assert!(report.games_settled > 0 && report.timeout_claims > 0 && report.games_expired > 0);
+
+    // The house takes a fee from decided games, everything else stays with the players
+    let staked = 10 * config.initial_stake;
+    let balances: u64 = report.players.iter().map(|player| player.balance).sum();
+    assert_eq!(balances + report.house_revenue + report.jackpot, staked);
+    assert!(report.house_revenue > 0);
+
+    let stallers = report.distributions.iter().find(|distribution| distribution.behavior == Behavior::Staller).unwrap();
+    assert_eq!(stallers.players, 2);
+    assert!(stallers.min <= stallers.median && stallers.median <= stallers.max);
+}
@@ -0,0 +1,85 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::tx::{restore_entries, save_entries};
+
+// One cheating verdict against a player
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlashRecord {
+    pub game_id: Option<u64>, // Game the escrow was taken from, None when nothing was escrowed
+    pub amount: u64,
+    pub reason: String,
+    pub slashed_at: u64,
+    pub suspended_until: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub slashes: Vec<SlashRecord>,
+    pub suspended_until: u64,
+}
+
+// Verdicts by player, and the insurance pool confiscated escrow goes to
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Slashing {
+    profiles: HashMap<String, Profile>,
+    insurance_pool: u64,
+}
+
+impl Slashing {
+    pub fn new() -> Self {
+        Slashing::default()
+    }
+
+    pub fn profile(&self, player: &str) -> Option<&Profile> {
+        self.profiles.get(player)
+    }
+
+    pub fn is_suspended(&self, player: &str, now: u64) -> bool {
+        self.profiles.get(player).is_some_and(|p| p.suspended_until > now)
+    }
+
+    pub fn insurance_pool(&self) -> u64 {
+        self.insurance_pool
+    }
+
+    // Moves `record.amount` into the insurance pool. Suspensions never get shorter, a second
+    // verdict during a suspension extends it from the new verdict.
+    pub fn slash(&mut self, player: String, record: SlashRecord) {
+        self.insurance_pool = self.insurance_pool.saturating_add(record.amount);
+        let profile = self.profiles.entry(player).or_default();
+        profile.suspended_until = profile.suspended_until.max(record.suspended_until);
+        profile.slashes.push(record);
+    }
+
+    // The pool with the profiles of `players` alone, for a transaction to put back with restore
+    pub(crate) fn save(&self, players: &[String]) -> Slashing {
+        Slashing { profiles: save_entries(&self.profiles, players), insurance_pool: self.insurance_pool }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Slashing, players: &[String]) {
+        restore_entries(&mut self.profiles, saved.profiles, players);
+        self.insurance_pool = saved.insurance_pool;
+    }
+}
+
+#[test]
+fn test_slash_suspends_and_funds_insurance() {
+    let record = |amount, suspended_until| SlashRecord {
+        game_id: None,
+        amount,
+        reason: "Invalid reveal".to_string(),
+        slashed_at: 0,
+        suspended_until,
+    };
+
+    let mut slashing = Slashing::new();
+    slashing.slash("Mallory".to_string(), record(40, 100));
+    slashing.slash("Mallory".to_string(), record(10, 50));
+
+    assert!(slashing.is_suspended("Mallory", 99));
+    assert!(!slashing.is_suspended("Mallory", 100));
+    assert!(!slashing.is_suspended("Alice", 0));
+    assert_eq!(slashing.insurance_pool(), 50);
+    assert_eq!(slashing.profile("Mallory").unwrap().slashes.len(), 2);
+}
@@ -0,0 +1,133 @@
+use serde::{Serialize, Deserialize};
+
+use crate::deck::Card;
+use crate::events::{LoggedEvent, SubscriberId};
+use crate::lobby::GameSummary;
+use crate::rules::{Outcome, Round};
+use crate::seats::Seat;
+use crate::GameState;
+
+// A round as spectators see it, the outcome is known as soon as it is played
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpectatedRound {
+    pub creator_hand: Vec<Option<Card>>, // None for a card still face down
+    pub opponent_hand: Vec<Option<Card>>,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpectatedSeat {
+    pub player: String,
+    pub hand: Vec<Option<Card>>,
+}
+
+// What anyone may see of a game. Cards stay face down until the game settles, a spectator learns who won
+// each round but not with what.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpectatorView {
+    pub game_id: u64,
+    pub game: Option<GameSummary>, // None once settled
+    pub rounds: Vec<SpectatedRound>,
+    pub seats: Vec<SpectatedSeat>, // Games of more than two seats, dealt in one go at the reveal
+    pub settled: bool,
+    pub winner: Option<String>, // None until settled, and on a draw or a shared pot
+    pub shared_by: Vec<String>,
+}
+
+fn hand(cards: &[Card], shown: bool) -> Vec<Option<Card>> {
+    cards.iter().map(|card| shown.then_some(*card)).collect()
+}
+
+fn round(round: &Round, shown: bool) -> SpectatedRound {
+    SpectatedRound { creator_hand: hand(&round.creator_hand, shown), opponent_hand: hand(&round.opponent_hand, shown), outcome: round.outcome }
+}
+
+fn seat(seat: &Seat, shown: bool) -> SpectatedSeat {
+    SpectatedSeat { player: seat.player.clone(), hand: hand(&seat.hand, shown) }
+}
+
+impl GameState {
+    // A running game with its cards face down, or a settled one still in the history with every card shown
+    pub fn spectate(&self, game_id: u64) -> Option<SpectatorView> {
+        let _timer = self.time_operation("spectate");
+        if let Some(game) = self.games.get(&game_id) {
+            return Some(SpectatorView {
+                game_id,
+                game: Some(game.summary()),
+                rounds: game.rounds.iter().map(|played| round(played, false)).collect(),
+                seats: game.players.iter().map(|seated| seat(seated, false)).collect(),
+                settled: false,
+                winner: None,
+                shared_by: Vec::new(),
+            });
+        }
+        let record = self.history.by_id(game_id)?;
+        Some(SpectatorView {
+            game_id,
+            game: None,
+            rounds: record.rounds.iter().map(|played| round(played, true)).collect(),
+            seats: record.seats.iter().map(|seated| seat(seated, true)).collect(),
+            settled: true,
+            winner: record.winner.clone(),
+            shared_by: record.shared_by.clone(),
+        })
+    }
+
+    // Like subscribe, for the events of `game_id` only and with their cards left out. Cards are only
+    // logged while a game runs, so a spectator never sees one in the stream.
+    pub fn subscribe_spectator<F>(&mut self, game_id: u64, callback: F) -> SubscriberId
+    where
+        F: Fn(&LoggedEvent) + Send + Sync + 'static,
+    {
+        self.subscribe(move |logged| {
+            if logged.event.is_about_game(game_id) {
+                callback(&logged.redacted());
+            }
+        })
+    }
+}
+
+#[test]
+fn test_spectators_see_cards_after_settlement() {
+    use std::sync::{Arc, Mutex};
+    use crate::context::caller;
+    use crate::events::GameEvent;
+
+    let mut game_state = GameState::new();
+    game_state.stake_tokens(&caller("Alice"), 100).unwrap();
+    game_state.stake_tokens(&caller("Bob"), 100).unwrap();
+    let game_id = game_state.start_game(&caller("Alice"), 10).unwrap();
+    let other = game_state.start_game(&caller("Bob"), 10).unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let feed = Arc::clone(&seen);
+    game_state.subscribe_spectator(game_id, move |logged| feed.lock().unwrap().push(logged.event.clone()));
+    game_state.join_game(&caller("Bob"), game_id).unwrap();
+    assert_eq!(game_state.spectate(game_id).unwrap().game, game_state.game_summary(game_id));
+
+    game_state.play_round(game_id).unwrap();
+    if let Some(view) = game_state.spectate(game_id).filter(|view| !view.settled) {
+        // A drawn round that did not settle the game
+        assert!(view.rounds.iter().flat_map(|played| played.creator_hand.iter().chain(&played.opponent_hand)).all(Option::is_none));
+    }
+    while game_state.game_summary(game_id).is_some() {
+        game_state.play_round(game_id).unwrap();
+    }
+    let view = game_state.spectate(game_id).unwrap();
+    let record = game_state.history().by_id(game_id).unwrap();
+    assert!(view.settled && view.game.is_none());
+    assert_eq!(view.winner, record.winner);
+    assert_eq!(view.rounds.len(), record.rounds.len());
+    assert_eq!(view.rounds[0].creator_hand, record.rounds[0].creator_hand.iter().copied().map(Some).collect::<Vec<_>>());
+    assert_eq!(game_state.spectate(other).unwrap().rounds, Vec::new());
+    assert!(game_state.spectate(other + 1).is_none());
+
+    let seen = seen.lock().unwrap();
+    assert!(seen.iter().all(|event| event.is_about_game(game_id)));
+    assert!(seen.iter().any(|event| matches!(event, GameEvent::Settled { .. })));
+    for event in seen.iter() {
+        if let GameEvent::CardsRevealed { creator_hand, opponent_hand, .. } = event {
+            assert!(creator_hand.is_empty() && opponent_hand.is_empty());
+        }
+    }
+    assert!(game_state.events().iter().any(|logged| matches!(&logged.event, GameEvent::CardsRevealed { creator_hand, .. } if !creator_hand.is_empty())));
+}
@@ -0,0 +1,136 @@
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::balance::Balance;
+use crate::error::GameError;
+use crate::events::LoggedEvent;
+use crate::storage::{Changes, Store};
+
+// SQLite keeps integers signed, amounts and ids are stored with their bits as they are
+fn to_sql(value: u64) -> i64 {
+    value as i64
+}
+
+fn from_sql(value: i64) -> u64 {
+    value as u64
+}
+
+fn storage_error(error: impl std::fmt::Display) -> GameError {
+    GameError::Storage(error.to_string())
+}
+
+// Store backed by a SQLite database (storage-sqlite feature). Each commit is one transaction.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self, GameError> {
+        Self::with_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    pub fn in_memory() -> Result<Self, GameError> {
+        Self::with_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, GameError> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS balances (user TEXT PRIMARY KEY, available INTEGER NOT NULL, locked INTEGER NOT NULL);
+                 CREATE TABLE IF NOT EXISTS games (id INTEGER PRIMARY KEY, game TEXT NOT NULL);
+                 CREATE TABLE IF NOT EXISTS events (sequence INTEGER PRIMARY KEY, event TEXT NOT NULL);",
+            )
+            .map_err(storage_error)?;
+        Ok(SqliteStore { connection })
+    }
+}
+
+impl Store for SqliteStore {
+    fn commit(&mut self, changes: &Changes) -> Result<(), GameError> {
+        // Dropped without commit on any error, which rolls the whole mutation back
+        let transaction = self.connection.transaction().map_err(storage_error)?;
+        for (user, balance) in &changes.balances {
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO balances (user, available, locked) VALUES (?1, ?2, ?3)",
+                    params![user, to_sql(balance.available.get()), to_sql(balance.locked.get())],
+                )
+                .map_err(storage_error)?;
+        }
+        for user in &changes.removed_balances {
+            transaction.execute("DELETE FROM balances WHERE user = ?1", params![user]).map_err(storage_error)?;
+        }
+        for (game_id, game) in &changes.games {
+            transaction
+                .execute("INSERT OR REPLACE INTO games (id, game) VALUES (?1, ?2)", params![to_sql(*game_id), game.to_string()])
+                .map_err(storage_error)?;
+        }
+        for game_id in &changes.removed_games {
+            transaction.execute("DELETE FROM games WHERE id = ?1", params![to_sql(*game_id)]).map_err(storage_error)?;
+        }
+        for logged in &changes.events {
+            let event = serde_json::to_string(logged).map_err(storage_error)?;
+            transaction
+                .execute("INSERT INTO events (sequence, event) VALUES (?1, ?2)", params![to_sql(logged.sequence), event])
+                .map_err(storage_error)?;
+        }
+        transaction.commit().map_err(storage_error)
+    }
+
+    fn balances(&self) -> Result<BTreeMap<String, Balance>, GameError> {
+        let mut statement = self.connection.prepare("SELECT user, available, locked FROM balances").map_err(storage_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                let balance = Balance::new(from_sql(row.get(1)?), from_sql(row.get(2)?));
+                Ok((row.get::<_, String>(0)?, balance))
+            })
+            .map_err(storage_error)?;
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    fn games(&self) -> Result<BTreeMap<u64, Value>, GameError> {
+        let mut statement = self.connection.prepare("SELECT id, game FROM games").map_err(storage_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((from_sql(row.get(0)?), row.get::<_, String>(1)?)))
+            .map_err(storage_error)?;
+        let mut games = BTreeMap::new();
+        for row in rows {
+            let (game_id, game) = row.map_err(storage_error)?;
+            games.insert(game_id, serde_json::from_str(&game).map_err(storage_error)?);
+        }
+        Ok(games)
+    }
+
+    fn events(&self) -> Result<Vec<LoggedEvent>, GameError> {
+        let mut statement = self.connection.prepare("SELECT event FROM events ORDER BY sequence").map_err(storage_error)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(storage_error)?;
+        let mut events = Vec::new();
+        for row in rows {
+            events.push(serde_json::from_str(&row.map_err(storage_error)?).map_err(storage_error)?);
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn test_commit_is_one_transaction() {
+    use crate::events::GameEvent;
+
+    let mut store = SqliteStore::in_memory().unwrap();
+    let staked = LoggedEvent { sequence: 0, timestamp: 1, event: GameEvent::Staked { user: "Alice".to_string(), amount: u64::MAX } };
+    let changes = Changes {
+        balances: vec![("Alice".to_string(), Balance::new(u64::MAX, 0))],
+        games: vec![(3, serde_json::json!({ "id": 3 }))],
+        events: vec![staked.clone()],
+        ..Changes::default()
+    };
+    store.commit(&changes).unwrap();
+    assert_eq!(store.balances().unwrap()["Alice"].available, u64::MAX);
+    assert_eq!(store.events().unwrap(), vec![staked.clone()]);
+
+    // The event sequence is taken, so nothing of this commit is kept
+    let failing = Changes { removed_games: vec![3], events: vec![staked], ..Changes::default() };
+    assert!(store.commit(&failing).is_err());
+    assert_eq!(store.games().unwrap().len(), 1);
+}
@@ -0,0 +1,202 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::tx::{restore_entries, save_entries};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+    pub total_wagered: u64,
+    pub net_pnl: i64,
+}
+
+impl PlayerStats {
+    pub fn games(&self) -> u64 {
+        self.wins + self.losses + self.draws
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsMetric {
+    Wins,
+    NetPnl,
+    TotalWagered,
+    Games,
+}
+
+// Games weigh less the older they are, a game `ACTIVITY_WINDOW_SECS` old counts for 1/e of a new one
+pub const ACTIVITY_WINDOW_SECS: u64 = 3600;
+
+// Time-weighted averages of recent games, rounded to whole units
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActivityAverages {
+    pub avg_pot: u64,
+    pub games_per_hour: u64,
+}
+
+// Exponentially decayed sums of settled games and their pots
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Activity {
+    weight: f64, // Decayed number of games
+    pot_sum: f64,
+    last_at: Option<u64>,
+}
+
+impl Activity {
+    fn decay(&self, now: u64) -> f64 {
+        match self.last_at {
+            Some(last_at) => (-(now.saturating_sub(last_at) as f64) / ACTIVITY_WINDOW_SECS as f64).exp(),
+            None => 0.0,
+        }
+    }
+
+    pub fn record(&mut self, pot: u64, now: u64) {
+        let keep = self.decay(now);
+        self.weight = self.weight * keep + 1.0;
+        self.pot_sum = self.pot_sum * keep + pot as f64;
+        self.last_at = Some(now.max(self.last_at.unwrap_or(0)));
+    }
+
+    pub fn averages(&self, now: u64) -> ActivityAverages {
+        if self.weight == 0.0 {
+            return ActivityAverages::default();
+        }
+        let per_window = self.weight * self.decay(now);
+        ActivityAverages {
+            avg_pot: (self.pot_sum / self.weight).round() as u64,
+            games_per_hour: (per_window * 3600.0 / ACTIVITY_WINDOW_SECS as f64).round() as u64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Stats {
+    players: HashMap<String, PlayerStats>,
+    #[serde(default)]
+    activity: Activity,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats::default()
+    }
+
+    // Called once per settled game, `winner` is None on a draw. `stakes` are what the creator and the
+    // opponent put in, the same unless the creator offered odds. The winner wins the stake of the other.
+    pub fn record_game(&mut self, creator: &str, opponent: &str, winner: Option<&str>, stakes: [u64; 2]) {
+        let signed = |stake: u64| i64::try_from(stake).unwrap_or(i64::MAX);
+
+        for (player, stake, other) in [(creator, stakes[0], stakes[1]), (opponent, stakes[1], stakes[0])] {
+            let stats = self.players.entry(player.to_string()).or_default();
+            stats.total_wagered = stats.total_wagered.saturating_add(stake);
+            match winner {
+                None => stats.draws += 1,
+                Some(winner) if winner == player => {
+                    stats.wins += 1;
+                    stats.net_pnl = stats.net_pnl.saturating_add(signed(other));
+                }
+                Some(_) => {
+                    stats.losses += 1;
+                    stats.net_pnl = stats.net_pnl.saturating_sub(signed(stake));
+                }
+            }
+        }
+    }
+
+    // Called once per settled game of more than two seats, each of `winners` was paid `payout`
+    pub fn record_seats(&mut self, players: &[String], winners: &[String], bet: u64, payout: u64) {
+        let signed = |amount: u64| i64::try_from(amount).unwrap_or(i64::MAX);
+        for player in players {
+            let stats = self.players.entry(player.clone()).or_default();
+            stats.total_wagered = stats.total_wagered.saturating_add(bet);
+            if winners.contains(player) {
+                stats.wins += 1;
+                stats.net_pnl = stats.net_pnl.saturating_add(signed(payout) - signed(bet));
+            } else {
+                stats.losses += 1;
+                stats.net_pnl = stats.net_pnl.saturating_sub(signed(bet));
+            }
+        }
+    }
+
+    // Called once per settled game with a pot, tournament games are left out
+    pub fn record_pot(&mut self, pot: u64, now: u64) {
+        self.activity.record(pot, now);
+    }
+
+    pub fn activity(&self, now: u64) -> ActivityAverages {
+        self.activity.averages(now)
+    }
+
+    pub fn player(&self, player: &str) -> Option<&PlayerStats> {
+        self.players.get(player)
+    }
+
+    // Best players first by the chosen metric, ties ordered by name so the result is stable
+    pub fn leaderboard(&self, limit: usize, metric: StatsMetric) -> Vec<(String, PlayerStats)> {
+        let mut entries: Vec<(String, PlayerStats)> =
+            self.players.iter().map(|(player, stats)| (player.clone(), *stats)).collect();
+
+        entries.sort_by(|(name_a, a), (name_b, b)| {
+            let ordering = match metric {
+                StatsMetric::Wins => b.wins.cmp(&a.wins),
+                StatsMetric::NetPnl => b.net_pnl.cmp(&a.net_pnl),
+                StatsMetric::TotalWagered => b.total_wagered.cmp(&a.total_wagered),
+                StatsMetric::Games => b.games().cmp(&a.games()),
+            };
+            ordering.then_with(|| name_a.cmp(name_b))
+        });
+        entries.truncate(limit);
+        entries
+    }
+
+    // The stats of `players` and the activity, for a transaction to put back with restore
+    pub(crate) fn save(&self, players: &[String]) -> Stats {
+        Stats { players: save_entries(&self.players, players), activity: self.activity.clone() }
+    }
+
+    pub(crate) fn restore(&mut self, saved: Stats, players: &[String]) {
+        restore_entries(&mut self.players, saved.players, players);
+        self.activity = saved.activity;
+    }
+}
+
+#[test]
+fn test_stats_and_leaderboard() {
+    let mut stats = Stats::new();
+    stats.record_game("Alice", "Bob", Some("Alice"), [10, 10]);
+    stats.record_game("Alice", "Carol", Some("Carol"), [50, 50]);
+    stats.record_game("Bob", "Carol", None, [5, 5]);
+
+    let alice = stats.player("Alice").unwrap();
+    assert_eq!((alice.wins, alice.losses, alice.draws), (1, 1, 0));
+    assert_eq!(alice.net_pnl, -40);
+    assert_eq!(alice.total_wagered, 60);
+
+    let by_pnl = stats.leaderboard(2, StatsMetric::NetPnl);
+    assert_eq!(by_pnl.len(), 2);
+    assert_eq!(by_pnl[0].0, "Carol");
+    assert_eq!(by_pnl[0].1.net_pnl, 50);
+
+    let by_wins = stats.leaderboard(10, StatsMetric::Wins);
+    assert_eq!(by_wins.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["Alice", "Carol", "Bob"]);
+}
+
+#[test]
+fn test_activity_averages_decay() {
+    let mut stats = Stats::new();
+    assert_eq!(stats.activity(0), ActivityAverages::default());
+
+    stats.record_pot(100, 1_000);
+    stats.record_pot(300, 1_000);
+    assert_eq!(stats.activity(1_000), ActivityAverages { avg_pot: 200, games_per_hour: 2 });
+
+    // An hour later the old games weigh 1/e against a new one
+    stats.record_pot(1_000, 1_000 + ACTIVITY_WINDOW_SECS);
+    let averages = stats.activity(1_000 + ACTIVITY_WINDOW_SECS);
+    assert_eq!(averages.avg_pot, ((400.0 / std::f64::consts::E + 1_000.0) / (2.0 / std::f64::consts::E + 1.0)).round() as u64);
+    assert_eq!(averages.games_per_hour, 2);
+    assert_eq!(stats.activity(1_000 + 10 * ACTIVITY_WINDOW_SECS).games_per_hour, 0);
+}
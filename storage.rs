@@ -0,0 +1,111 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::balance::Balance;
+use crate::error::GameError;
+use crate::events::LoggedEvent;
+
+// What changed in the stored part of the state since the last commit. Games are kept as JSON,
+// a store does not need to know their layout.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub balances: Vec<(String, Balance)>,
+    pub removed_balances: Vec<String>,
+    pub games: Vec<(u64, Value)>,
+    pub removed_games: Vec<u64>,
+    pub events: Vec<LoggedEvent>, // Appended to the log since the last commit
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty() && self.removed_balances.is_empty() && self.games.is_empty() && self.removed_games.is_empty() && self.events.is_empty()
+    }
+}
+
+// Durable home of stakes, open games and events, for deployments that outgrow a saved file
+pub trait Store {
+    // Applies every change of one state mutation, all of them or none
+    fn commit(&mut self, changes: &Changes) -> Result<(), GameError>;
+    fn balances(&self) -> Result<BTreeMap<String, Balance>, GameError>;
+    fn games(&self) -> Result<BTreeMap<u64, Value>, GameError>;
+    fn events(&self) -> Result<Vec<LoggedEvent>, GameError>;
+}
+
+// What was last committed to a store, the next changes are computed against it
+#[derive(Debug, Clone, Default)]
+pub struct Committed {
+    balances: BTreeMap<String, Balance>,
+    games: BTreeMap<u64, Value>,
+    events: usize,
+}
+
+impl Committed {
+    pub fn new(balances: &HashMap<String, Balance>, games: BTreeMap<u64, Value>, events: usize) -> Self {
+        Committed { balances: balances.iter().map(|(user, balance)| (user.clone(), *balance)).collect(), games, events }
+    }
+
+    pub fn changes(&self, balances: &HashMap<String, Balance>, games: &BTreeMap<u64, Value>, events: &[LoggedEvent]) -> Changes {
+        let mut changes = Changes {
+            balances: balances
+                .iter()
+                .filter(|(user, balance)| self.balances.get(*user) != Some(*balance))
+                .map(|(user, balance)| (user.clone(), *balance))
+                .collect(),
+            removed_balances: self.balances.keys().filter(|user| !balances.contains_key(*user)).cloned().collect(),
+            games: games
+                .iter()
+                .filter(|(game_id, game)| self.games.get(*game_id) != Some(*game))
+                .map(|(game_id, game)| (*game_id, game.clone()))
+                .collect(),
+            removed_games: self.games.keys().filter(|game_id| !games.contains_key(*game_id)).copied().collect(),
+            events: events.get(self.events..).unwrap_or_default().to_vec(),
+        };
+        changes.balances.sort_by(|a, b| a.0.cmp(&b.0));
+        changes
+    }
+}
+
+// Store kept in memory, for tests and single process runs
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    balances: BTreeMap<String, Balance>,
+    games: BTreeMap<u64, Value>,
+    events: Vec<LoggedEvent>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn commit(&mut self, changes: &Changes) -> Result<(), GameError> {
+        for (user, balance) in &changes.balances {
+            self.balances.insert(user.clone(), *balance);
+        }
+        for user in &changes.removed_balances {
+            self.balances.remove(user);
+        }
+        for (game_id, game) in &changes.games {
+            self.games.insert(*game_id, game.clone());
+        }
+        for game_id in &changes.removed_games {
+            self.games.remove(game_id);
+        }
+        self.events.extend(changes.events.iter().cloned());
+        Ok(())
+    }
+
+    fn balances(&self) -> Result<BTreeMap<String, Balance>, GameError> {
+        Ok(self.balances.clone())
+    }
+
+    fn games(&self) -> Result<BTreeMap<u64, Value>, GameError> {
+        Ok(self.games.clone())
+    }
+
+    fn events(&self) -> Result<Vec<LoggedEvent>, GameError> {
+        Ok(self.events.clone())
+    }
+}
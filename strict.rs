@@ -0,0 +1,99 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::GameError;
+
+// How operator-provided JSON treats fields the target type does not have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    #[default]
+    Strict, // Any unknown field fails the whole parse
+    Lenient, // Unknown fields are ignored and reported as warnings
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed<T> {
+    pub value: T,
+    pub warnings: Vec<String>,
+}
+
+// Same effect as #[serde(deny_unknown_fields)] on every nested type, without fixing the choice in the types:
+// the parsed value is serialized again and every input key that did not survive is unknown.
+pub fn parse_json<T: Serialize + DeserializeOwned>(json: &str, mode: ParseMode) -> Result<Parsed<T>, GameError> {
+    let input: Value = serde_json::from_str(json).map_err(|e| GameError::InvalidJson(e.to_string()))?;
+    parse_value(input, mode)
+}
+
+// Same as parse_json, for input that was already parsed (and possibly migrated)
+pub fn parse_value<T: Serialize + DeserializeOwned>(input: Value, mode: ParseMode) -> Result<Parsed<T>, GameError> {
+    let value: T = serde_json::from_value(input.clone()).map_err(|e| GameError::InvalidContent(e.to_string()))?;
+    let known = serde_json::to_value(&value).map_err(|e| GameError::Serialize(e.to_string()))?;
+
+    let mut unknown = Vec::new();
+    unknown_fields(&input, &known, "", &mut unknown);
+    unknown.sort(); // Input order depends on serde_json's preserve_order, which other crates may turn on
+    if mode == ParseMode::Strict && !unknown.is_empty() {
+        return Err(GameError::UnknownFields(unknown));
+    }
+    let warnings = unknown.into_iter().map(|field| format!("Unknown field {} ignored.", field)).collect();
+    Ok(Parsed { value, warnings })
+}
+
+// JSON text with the keys of every object sorted, whatever order serde_json keeps maps in. Hashes of
+// states, configs and tokens are taken over this, so they stay the same when preserve_order is turned on.
+pub fn canonical_json(value: &Value) -> String {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                Value::Object(entries.into_iter().map(|(key, value)| (key.clone(), sorted(value))).collect())
+            }
+            Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+            other => other.clone(),
+        }
+    }
+    sorted(value).to_string()
+}
+
+fn unknown_fields(input: &Value, known: &Value, path: &str, out: &mut Vec<String>) {
+    match (input, known) {
+        (Value::Object(input), Value::Object(known)) => {
+            for (key, value) in input {
+                let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match known.get(key) {
+                    Some(known) => unknown_fields(value, known, &field, out),
+                    None => out.push(field),
+                }
+            }
+        }
+        (Value::Array(input), Value::Array(known)) => {
+            for (index, (value, known)) in input.iter().zip(known).enumerate() {
+                unknown_fields(value, known, &format!("{}[{}]", path, index), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn test_unknown_fields_by_mode() {
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Limits {
+        min: u64,
+        named: BTreeMap<String, u64>,
+    }
+
+    let json = r#"{"min": 1, "mni": 2, "named": {"a": 1}, "extra": {"x": 1}}"#;
+    let error = parse_json::<Limits>(json, ParseMode::Strict).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown fields: extra, mni.");
+
+    let parsed = parse_json::<Limits>(json, ParseMode::Lenient).unwrap();
+    assert_eq!(parsed.value.min, 1);
+    assert_eq!(parsed.warnings, vec!["Unknown field extra ignored.", "Unknown field mni ignored."]);
+    // Map keys are data, never unknown
+    assert!(parse_json::<Limits>(r#"{"min": 1, "named": {"b": 2}}"#, ParseMode::Strict).is_ok());
+}
@@ -0,0 +1,266 @@
+use alloy_primitives::{Address, B256, U256, U64};
+use alloy_sol_types::{Revert, SolError};
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::{StorageAddress, StorageMap, StorageString, StorageU64};
+
+use crate::account_id::AccountId;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::names::Identities;
+use crate::roles::Role;
+use crate::strict::ParseMode;
+use crate::GameState;
+
+// Arbitrum Stylus contract around the engine (stylus feature). Players are the addresses calling the
+// contract, stakes are pulled from and paid back in the ERC-20 token given to init, one token unit per
+// unit of stake.
+
+sol_interface! {
+    interface IERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    }
+}
+
+// OpenZeppelin AccessControl ids, DEFAULT_ADMIN_ROLE is zero. The engine has no other role yet.
+pub const DEFAULT_ADMIN_ROLE: B256 = B256::ZERO;
+
+fn role(id: B256) -> Result<Role, GameError> {
+    if id == DEFAULT_ADMIN_ROLE {
+        Ok(Role::Admin)
+    } else {
+        Err(GameError::InvalidContent(format!("unknown role {}", id)))
+    }
+}
+
+// Reverts with Error(string), so wallets show the same message as every other front
+fn revert(error: GameError) -> Vec<u8> {
+    Revert::from(error.to_string()).abi_encode()
+}
+
+// Players are named by their checksummed address
+fn account(address: Address) -> AccountId {
+    AccountId::new(address.to_checksum(None)).expect("A checksummed address is a valid account id.")
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn block_timestamp() -> u64 {
+    unsafe { stylus_sdk::hostio::block_timestamp() }
+}
+
+// Contracts have no clock but the block's, which does not move during a call: latencies read as zero
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(u64);
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Self {
+        Instant(block_timestamp())
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(block_timestamp().saturating_sub(self.0))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::Add<std::time::Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: std::time::Duration) -> Instant {
+        Instant(self.0.saturating_add(duration.as_secs()))
+    }
+}
+
+// Arbitrum has no randomness beacon, random bytes are sha256 of the block number and time, the caller and a
+// counter. The sequencer orders and times the transactions and can predict them, so can a caller simulating
+// the call before sending it: this only keeps the cards from being read off the state.
+#[cfg(target_arch = "wasm32")]
+fn random_bytes(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    use sha2::{Digest, Sha256};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use stylus_sdk::hostio;
+
+    static DRAWS: AtomicU64 = AtomicU64::new(0);
+    let mut sender = [0u8; 20];
+    let (number, time) = unsafe {
+        hostio::msg_sender(sender.as_mut_ptr());
+        (hostio::block_number(), hostio::block_timestamp())
+    };
+    for chunk in buf.chunks_mut(32) {
+        let draw = DRAWS.fetch_add(1, Ordering::Relaxed);
+        let digest = Sha256::new()
+            .chain_update(number.to_le_bytes())
+            .chain_update(time.to_le_bytes())
+            .chain_update(sender)
+            .chain_update(draw.to_le_bytes())
+            .finalize();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+getrandom::register_custom_getrandom!(random_bytes);
+
+// Stakes as the engine last left them, readable by wallets and other contracts without the state below
+#[storage]
+pub struct Stake {
+    available: StorageU64,
+    locked: StorageU64,
+}
+
+// The engine is kept as its exported JSON, like the NEAR contract, so stored states go through the same
+// migrations as saved files. Every call reads and writes it whole.
+#[storage]
+#[entrypoint]
+pub struct GameContract {
+    state: StorageString,
+    token: StorageAddress,
+    stakes: StorageMap<Address, Stake>,
+}
+
+impl GameContract {
+    fn read_state(&self) -> Result<GameState, Vec<u8>> {
+        let json = self.state.get_string();
+        if json.is_empty() {
+            let mut state = GameState::new();
+            state.set_identities(Identities::Accounts);
+            return Ok(state);
+        }
+        Ok(GameState::import_state(&json, ParseMode::Strict).map_err(revert)?.value)
+    }
+
+    fn write_state(&mut self, state: &GameState, players: &[Address]) -> Result<(), Vec<u8>> {
+        self.state.set_str(state.export_state().map_err(revert)?);
+        for &player in players {
+            let balance = state.stake_of(&account(player));
+            let mut stake = self.stakes.setter(player);
+            stake.available.set(U64::from(balance.available.get()));
+            stake.locked.set(U64::from(balance.locked.get()));
+        }
+        Ok(())
+    }
+
+    fn token(&self) -> Result<IERC20, Vec<u8>> {
+        match self.token.get() {
+            token if token.is_zero() => Err(revert(GameError::InvalidContent("contract is not initialized".to_string()))),
+            token => Ok(IERC20::new(token)),
+        }
+    }
+}
+
+#[public]
+impl GameContract {
+    // Sets the token and makes the caller the first admin, only once
+    pub fn init(&mut self, token: Address) -> Result<(), Vec<u8>> {
+        if !self.token.get().is_zero() {
+            return Err(revert(GameError::AdminAlreadySet));
+        }
+        if token.is_zero() {
+            return Err(revert(GameError::InvalidContent("token is the zero address".to_string())));
+        }
+        let mut state = self.read_state()?;
+        state.init_admin(account(self.vm().msg_sender())).map_err(revert)?;
+        self.token.set(token);
+        self.write_state(&state, &[])
+    }
+
+    // Pulls `amount` tokens from the caller, who has to approve the contract for them first
+    pub fn stake(&mut self, amount: u64) -> Result<(), Vec<u8>> {
+        let (token, sender, contract) = (self.token()?, self.vm().msg_sender(), self.vm().contract_address());
+        let mut state = self.read_state()?;
+        state.stake_tokens(&Context::new(account(sender)), amount).map_err(revert)?;
+        self.write_state(&state, &[sender])?;
+        if !token.transfer_from(&mut *self, sender, contract, U256::from(amount))? {
+            return Err(revert(GameError::InsufficientFunds));
+        }
+        Ok(())
+    }
+
+    // The stake is taken before the tokens leave, a failed transfer reverts both
+    pub fn withdraw(&mut self, amount: u64) -> Result<(), Vec<u8>> {
+        let (token, sender) = (self.token()?, self.vm().msg_sender());
+        let mut state = self.read_state()?;
+        state.withdraw_stake(&Context::new(account(sender)), amount).map_err(revert)?;
+        self.write_state(&state, &[sender])?;
+        if !token.transfer(&mut *self, sender, U256::from(amount))? {
+            return Err(revert(GameError::PayoutFailed("token refused the transfer".to_string())));
+        }
+        Ok(())
+    }
+
+    pub fn start_game(&mut self, bet: u64) -> Result<u64, Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        let mut state = self.read_state()?;
+        let game_id = state.start_game(&Context::new(account(sender)), bet).map_err(revert)?;
+        self.write_state(&state, &[sender])?;
+        Ok(game_id)
+    }
+
+    pub fn join_game(&mut self, game_id: u64) -> Result<(), Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        let mut state = self.read_state()?;
+        state.join_game(&Context::new(account(sender)), game_id).map_err(revert)?;
+        self.write_state(&state, &[sender])
+    }
+
+    // Asks for the next round of a game the caller sits in, played once every player asked. True once the
+    // game is settled.
+    pub fn reveal_cards(&mut self, game_id: u64) -> Result<bool, Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        let mut state = self.read_state()?;
+        let summary = state.game_summary(game_id).ok_or(GameError::GameNotFound).map_err(revert)?;
+        let players: Vec<Address> =
+            std::iter::once(summary.creator).chain(summary.opponent).filter_map(|player| player.parse().ok()).collect();
+        state.contribute_reveal(&Context::new(account(sender)), game_id).map_err(revert)?;
+        self.write_state(&state, &players)?;
+        Ok(state.game_summary(game_id).is_none())
+    }
+
+    // Available and locked tokens of `player`
+    pub fn stake_of(&self, player: Address) -> (u64, u64) {
+        let stake = self.stakes.getter(player);
+        (stake.available.get().to::<u64>(), stake.locked.get().to::<u64>())
+    }
+
+    pub fn has_role(&self, role_id: B256, player: Address) -> Result<bool, Vec<u8>> {
+        let state = self.read_state()?;
+        Ok(state.has_role(&account(player), role(role_id).map_err(revert)?))
+    }
+
+    pub fn grant_role(&mut self, role_id: B256, player: Address) -> Result<(), Vec<u8>> {
+        let mut state = self.read_state()?;
+        let role = role(role_id).map_err(revert)?;
+        state.grant_role(&Context::new(account(self.vm().msg_sender())), account(player), role).map_err(revert)?;
+        self.write_state(&state, &[])
+    }
+
+    // The last admin cannot be revoked, as in the engine
+    pub fn revoke_role(&mut self, role_id: B256, player: Address) -> Result<(), Vec<u8>> {
+        let mut state = self.read_state()?;
+        let role = role(role_id).map_err(revert)?;
+        state.revoke_role(&Context::new(account(self.vm().msg_sender())), &account(player), role).map_err(revert)?;
+        self.write_state(&state, &[])
+    }
+}
+
+#[test]
+fn test_roles_and_reverts_follow_solidity() {
+    assert_eq!(role(DEFAULT_ADMIN_ROLE).unwrap(), Role::Admin);
+    assert!(role(B256::repeat_byte(1)).is_err());
+
+    let data = revert(GameError::InsufficientFunds);
+    assert_eq!(data[..4], Revert::SELECTOR);
+    assert_eq!(Revert::abi_decode(&data, true).unwrap().reason, "Insufficient funds.");
+
+    // Addresses that fold to the same name stay apart
+    let (first, second) = (Address::repeat_byte(0x3a), Address::repeat_byte(0xea));
+    let mut state = GameState::new();
+    state.set_identities(Identities::Accounts);
+    state.stake_tokens(&Context::new(account(first)), 10).unwrap();
+    state.stake_tokens(&Context::new(account(second)), 20).unwrap();
+    assert_eq!(state.stake_of(&account(first)).available, 10);
+    assert_eq!(account(first).parse::<Address>().unwrap(), first);
+}
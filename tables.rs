@@ -0,0 +1,153 @@
+use serde::{Serialize, Deserialize};
+
+use crate::account_id::AccountId;
+use crate::balance::Balance;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::roles::Role;
+use crate::token::ERC20Token;
+use crate::vault::vault;
+use crate::GameState;
+
+// Games played for a registered token instead of the game's own stakes. Every token has a table of its
+// own, a game state whose stakes, escrow, fees and jackpot are all counted in that token, so a bet is
+// settled in the token it was made in and tokens are never mixed. Game ids are per table. Tables are
+// saved with the state file, persist only writes the game's own stakes, games and events to a store.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Table {
+    pub token: ERC20Token,
+    pub game_state: GameState,
+}
+
+impl GameState {
+    // Opens a table for `token` under its symbol. The table starts with the config and clock of this state,
+    // later config changes are made on the table itself. Roles are always the ones of this state.
+    pub fn register_token(&mut self, ctx: &Context, token: ERC20Token) -> Result<(), GameError> {
+        let _timer = self.start_operation("register_token");
+        self.use_nonce(ctx)?;
+        self.roles.require(ctx.caller(), Role::Admin)?;
+        if self.tables.contains_key(token.symbol()) {
+            return Err(GameError::TokenAlreadyRegistered);
+        }
+        let mut game_state = GameState::with_clock(self.clock.clone());
+        game_state.config = self.config.clone();
+        game_state.config_log = self.config_log.clone();
+        self.tables.insert(token.symbol().to_string(), Table { token, game_state });
+        Ok(())
+    }
+
+    // Symbols of the registered tokens, in order
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+
+    pub fn table(&self, token: &str) -> Option<&Table> {
+        self.tables.get(token)
+    }
+
+    // Every change to a table goes through here: the nonce of a signed call is used on this state and the
+    // table sees the call unsigned, with the roles granted on this state as they are now
+    fn at_table<T>(&mut self, ctx: &Context, token: &str, action: impl FnOnce(&mut Table, &Context) -> Result<T, GameError>) -> Result<T, GameError> {
+        self.use_nonce(ctx)?;
+        let table = self.tables.get_mut(token).ok_or(GameError::TokenNotRegistered)?;
+        table.game_state.roles.clone_from(&self.roles);
+        action(table, &ctx.unsigned())
+    }
+
+    // Lets the table's vault take up to `amount` of the caller's `token`, what deposit_in moves
+    pub fn approve_in(&mut self, ctx: &Context, token: &str, amount: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| Ok(table.token.approve(ctx, vault(), amount)?))
+    }
+
+    // Stakes tokens the user approved the table's vault for, see GameState::deposit
+    pub fn deposit_in(&mut self, ctx: &Context, token: &str, amount: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.deposit(ctx, &mut table.token, amount))
+    }
+
+    pub fn withdraw_from(&mut self, ctx: &Context, token: &str, amount: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.withdraw(ctx, &mut table.token, amount))
+    }
+
+    // Starts a game whose bet is in `token`, paid from the creator's stake at that table
+    pub fn start_game_in(&mut self, ctx: &Context, token: &str, bet: u64) -> Result<u64, GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.start_game(ctx, bet))
+    }
+
+    pub fn join_game_in(&mut self, ctx: &Context, token: &str, game_id: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.join_game(ctx, game_id))
+    }
+
+    // See GameState::contribute_reveal
+    pub fn contribute_reveal_in(&mut self, ctx: &Context, token: &str, game_id: u64) -> Result<bool, GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.contribute_reveal(ctx, game_id))
+    }
+
+    pub fn claim_timeout_win_in(&mut self, ctx: &Context, token: &str, game_id: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.claim_timeout_win(ctx, game_id))
+    }
+
+    // Pays fees the table collected in `token` to the stake of `to` at that table, for admins of this state
+    pub fn withdraw_treasury_in(&mut self, ctx: &Context, token: &str, to: AccountId, amount: u64) -> Result<(), GameError> {
+        self.at_table(ctx, token, |table, ctx| table.game_state.withdraw_treasury(ctx, to, amount))
+    }
+
+    // The stake of `user` in `token`, empty for tokens nobody registered
+    pub fn stake_in(&self, token: &str, user: &str) -> Balance {
+        self.table(token).map(|table| table.game_state.stake_of(user)).unwrap_or_default()
+    }
+}
+
+#[test]
+fn test_games_settle_in_their_token() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    game_state.init_admin(account("House")).unwrap();
+    let token = |symbol: &str| ERC20Token::new(account("House"), symbol, symbol, 0, None);
+    assert!(matches!(game_state.register_token(&caller("Alice"), token("USDC")), Err(GameError::MissingRole { .. })));
+    game_state.register_token(&caller("House"), token("USDC")).unwrap();
+    game_state.register_token(&caller("House"), token("DAI")).unwrap();
+    assert_eq!(game_state.register_token(&caller("House"), token("DAI")), Err(GameError::TokenAlreadyRegistered));
+    assert_eq!(game_state.tokens().collect::<Vec<_>>(), vec!["DAI", "USDC"]);
+
+    // Minted by the token's owner, not through the game
+    let usdc = &mut game_state.tables.get_mut("USDC").unwrap().token;
+    usdc.adjust_price(&caller("House"), 0).unwrap();
+    for player in ["Alice", "Bob"] {
+        usdc.mint(&caller(player), 100, 0).unwrap();
+    }
+    for player in ["Alice", "Bob"] {
+        game_state.approve_in(&caller(player), "USDC", 100).unwrap();
+        game_state.deposit_in(&caller(player), "USDC", 50).unwrap();
+    }
+    game_state.stake_tokens(&caller("Alice"), 500).unwrap();
+
+    // Bets are locked and settled at the USDC table only, the game's own stakes do not move
+    let game_id = game_state.start_game_in(&caller("Alice"), "USDC", 20).unwrap();
+    assert_eq!(game_state.start_game_in(&caller("Alice"), "EUR", 20), Err(GameError::TokenNotRegistered));
+    game_state.join_game_in(&caller("Bob"), "USDC", game_id).unwrap();
+    assert_eq!(game_state.contribute_reveal_in(&caller("Carol"), "USDC", game_id), Err(GameError::NotYourGame));
+    while game_state.stake_in("USDC", "Bob").locked.get() > 0 {
+        game_state.contribute_reveal_in(&caller("Alice"), "USDC", game_id).unwrap();
+        game_state.contribute_reveal_in(&caller("Bob"), "USDC", game_id).unwrap();
+    }
+    game_state.table("USDC").unwrap().game_state.check_invariants().unwrap();
+
+    let stakes = game_state.stake_in("USDC", "Alice").total().get() + game_state.stake_in("USDC", "Bob").total().get();
+    let fees = game_state.table("USDC").unwrap().game_state.treasury_balance();
+    assert_eq!(stakes + fees, 100);
+    assert_eq!((game_state.stake_of("Alice").available.get(), game_state.stake_in("DAI", "Alice").total().get()), (500, 0));
+
+    let withdrawn = game_state.stake_in("USDC", "Alice").available.get();
+    game_state.withdraw_from(&caller("Alice"), "USDC", withdrawn).unwrap();
+    assert_eq!(game_state.table("USDC").unwrap().token.get_balance("Alice").get(), 50 + withdrawn);
+
+    // Roles granted or revoked after the table was opened count there too
+    assert!(matches!(game_state.withdraw_treasury_in(&caller("Mallory"), "USDC", account("Mallory"), fees), Err(GameError::MissingRole { .. })));
+    game_state.grant_role(&caller("House"), account("Carol"), Role::Admin).unwrap();
+    game_state.revoke_role(&caller("House"), "House", Role::Admin).unwrap();
+    assert!(game_state.withdraw_treasury_in(&caller("House"), "USDC", account("House"), fees).is_err());
+    game_state.withdraw_treasury_in(&caller("Carol"), "USDC", account("Carol"), fees).unwrap();
+    assert_eq!(game_state.stake_in("USDC", "Carol").available.get(), fees);
+}
@@ -0,0 +1,92 @@
+use serde::{Serialize, Deserialize};
+
+use crate::context::Context;
+use crate::deck::Card;
+use crate::error::GameError;
+use crate::seats::Seat;
+use crate::GameState;
+
+// Seats of a team game, two pairs. The creator plays for the home team.
+pub const TEAM_SEATS: u32 = 4;
+const TEAM_SIZE: usize = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Team {
+    Home,
+    Away,
+}
+
+pub fn is_solo(teams: &bool) -> bool {
+    !*teams
+}
+
+// Ranks of every card dealt to `team` added up, the ace counts one
+pub fn total(seats: &[Seat], team: Team) -> u32 {
+    seats
+        .iter()
+        .filter(|seat| seat.team == Some(team))
+        .flat_map(|seat| &seat.hand)
+        .map(|card: &Card| card.rank as u32)
+        .sum()
+}
+
+// The team with the higher total, None when both add up to the same
+pub fn winning_team(seats: &[Seat]) -> Option<Team> {
+    let (home, away) = (total(seats, Team::Home), total(seats, Team::Away));
+    match home.cmp(&away) {
+        std::cmp::Ordering::Greater => Some(Team::Home),
+        std::cmp::Ordering::Less => Some(Team::Away),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+// Team a new player sits down in: the one asked for, otherwise the one with fewer players, home first
+pub fn pick(seats: &[Seat], wanted: Option<Team>) -> Result<Team, GameError> {
+    let players = |team: Team| seats.iter().filter(|seat| seat.team == Some(team)).count();
+    let team = wanted.unwrap_or(if players(Team::Away) < players(Team::Home) { Team::Away } else { Team::Home });
+    if players(team) >= TEAM_SIZE {
+        return Err(GameError::TeamFull);
+    }
+    Ok(team)
+}
+
+impl GameState {
+    // Like join_game, in the team of the caller's choice
+    pub fn join_team(&mut self, ctx: &Context, game_id: u64, team: Team) -> Result<(), GameError> {
+        let _timer = self.start_operation("join_team");
+        self.join_seat(ctx, game_id, Some(team), "join_team")
+    }
+
+    // Players of each team, home first. None unless `game_id` is a running team game.
+    pub fn teams(&self, game_id: u64) -> Option<[Vec<String>; 2]> {
+        let _timer = self.time_operation("teams");
+        let game = self.games.get(&game_id).filter(|game| game.teams)?;
+        let members = |team: Team| game.players.iter().filter(|seat| seat.team == Some(team)).map(|seat| seat.player.clone()).collect();
+        Some([members(Team::Home), members(Team::Away)])
+    }
+}
+
+#[test]
+fn test_teams_fill_evenly_and_add_up_their_cards() {
+    use crate::deck::Suit;
+
+    let seat = |player: &str, team, ranks: &[u8]| Seat {
+        player: player.to_string(),
+        hand: ranks.iter().map(|&rank| Card { rank, suit: Suit::Clubs }).collect(),
+        team: Some(team),
+    };
+    let mut seats = vec![seat("Alice", Team::Home, &[])];
+    assert_eq!(pick(&seats, None), Ok(Team::Away));
+    seats.push(seat("Bob", Team::Away, &[]));
+    assert_eq!(pick(&seats, None), Ok(Team::Home));
+    seats.push(seat("Carol", Team::Away, &[]));
+    assert_eq!(pick(&seats, Some(Team::Away)), Err(GameError::TeamFull));
+    assert_eq!(pick(&seats, None), Ok(Team::Home));
+
+    // Totals decide, not the highest card: a king and a two only tie a seven and an eight
+    let seats = [seat("Alice", Team::Home, &[13]), seat("Bob", Team::Away, &[7]), seat("Carol", Team::Home, &[2]), seat("Dave", Team::Away, &[8])];
+    assert_eq!((total(&seats, Team::Home), total(&seats, Team::Away)), (15, 15));
+    assert_eq!(winning_team(&seats), None);
+    let seats = [seat("Alice", Team::Home, &[13]), seat("Bob", Team::Away, &[7]), seat("Carol", Team::Home, &[1]), seat("Dave", Team::Away, &[8])];
+    assert_eq!(winning_team(&seats), Some(Team::Away));
+}
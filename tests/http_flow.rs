@@ -0,0 +1,158 @@
+// Full games against the server mode over a real socket: HTTP for the actions, a websocket for the events
+#![cfg(feature = "server")]
+
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use assessment_rust::auth::Credentials;
+use assessment_rust::server::{self, Server};
+use assessment_rust::vault::VAULT;
+use assessment_rust::{AccountId, Context, ERC20Token, GameState};
+
+const PLAYERS: [&str; 3] = ["Alice", "Bob", "Carol"];
+
+struct Client {
+    http: reqwest::Client,
+    base: String,
+    secrets: Vec<(&'static str, String)>,
+}
+
+impl Client {
+    fn secret(&self, user: &str) -> &str {
+        &self.secrets.iter().find(|(name, _)| *name == user).unwrap().1
+    }
+
+    async fn post(&self, user: Option<&str>, path: &str, body: Value) -> (StatusCode, Value) {
+        let mut request = self.http.post(format!("{}{}", self.base, path)).json(&body);
+        if let Some(user) = user {
+            request = request.bearer_auth(self.secret(user));
+        }
+        let response = request.send().await.unwrap();
+        (response.status(), response.json().await.unwrap_or(Value::Null))
+    }
+
+    async fn get(&self, path: &str) -> Value {
+        self.http.get(format!("{}{}", self.base, path)).send().await.unwrap().json().await.unwrap()
+    }
+}
+
+// Every player minted 100 tokens of a token with a free mint
+async fn start() -> (Server, Client) {
+    let owner = AccountId::new("Owner").unwrap();
+    let mut token = ERC20Token::new(owner.clone(), "Game Token", "GAME", 0, None);
+    token.adjust_price(&Context::new(owner.clone()), 0).unwrap();
+    let mut credentials = Credentials::new();
+    let mut secrets = Vec::new();
+    for user in PLAYERS {
+        token.mint(&Context::new(AccountId::new(user).unwrap()), 100, 0).unwrap();
+        secrets.push((user, credentials.issue(AccountId::new(user).unwrap())));
+    }
+    let server = Server::new(GameState::new(), token).with_credentials(credentials);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server::serve_on(listener, server.clone()));
+    (server, Client { http: reqwest::Client::new(), base, secrets })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_games_over_http_conserve_the_ledger() {
+    let (server, client) = start().await;
+
+    // Refusals
+    let (status, body) = client.post(None, "/stakes", json!({ "user": "Alice", "amount": 10 })).await;
+    assert_eq!((status, body), (StatusCode::UNAUTHORIZED, json!({ "error": "Missing or unknown credential." })));
+    let (status, body) = client.post(Some("Alice"), "/stakes", json!({ "amount": 10 })).await;
+    assert_eq!((status, body), (StatusCode::CONFLICT, json!({ "error": "Insufficient allowance." })));
+    assert_eq!(client.post(Some("Alice"), "/stakes", json!({ "amount": "ten" })).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+
+    for user in PLAYERS {
+        assert_eq!(client.post(Some(user), "/approvals", json!({ "amount": 100 })).await.0, StatusCode::OK);
+        assert_eq!(client.post(Some(user), "/stakes", json!({ "amount": 100 })).await.0, StatusCode::OK);
+    }
+    let (status, body) = client.post(Some("Carol"), "/withdrawals", json!({ "amount": 101 })).await;
+    assert_eq!((status, body), (StatusCode::CONFLICT, json!({ "error": "Insufficient funds." })));
+
+    // Retries with the same command id are answered without acting twice
+    let withdrawal = json!({ "amount": 10, "command_id": "5f0c7c4e-8d1a-4a5e-9a4b-2f1f0f6a1c01" });
+    for _ in 0..2 {
+        assert_eq!(client.post(Some("Carol"), "/withdrawals", withdrawal.clone()).await.0, StatusCode::OK);
+    }
+    assert_eq!(client.get("/stakes/Carol").await["available"], 90);
+    let start = json!({ "bet": 30, "command_id": "5f0c7c4e-8d1a-4a5e-9a4b-2f1f0f6a1c02" });
+    let (_, first) = client.post(Some("Alice"), "/games", start.clone()).await;
+    let (_, retried) = client.post(Some("Alice"), "/games", start).await;
+    assert_eq!(first, retried);
+    let game_id = first["game_id"].as_u64().unwrap();
+    assert_eq!(client.get("/games").await.as_array().unwrap().len(), 1);
+    assert_eq!(client.get("/stakes/Alice").await, json!({ "user": "Alice", "available": 70, "locked": 30 }));
+
+    // A player watches with the cards, a spectator without them until the game is settled
+    let events = format!("{}/games/{}/events", client.base.replacen("http", "ws", 1), game_id);
+    let mut request = events.as_str().into_client_request().unwrap();
+    request.headers_mut().insert("authorization", format!("Bearer {}", client.secret("Alice")).parse().unwrap());
+    let (mut player, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let (mut spectator, _) = tokio_tungstenite::connect_async(events.as_str()).await.unwrap();
+
+    let join = format!("/games/{}/join", game_id);
+    assert_eq!(client.post(Some("Bob"), &join, json!({})).await.0, StatusCode::OK);
+    let (status, body) = client.post(Some("Carol"), &join, json!({})).await;
+    assert_eq!((status, body), (StatusCode::CONFLICT, json!({ "error": "Game already joined." })));
+    let reveal = format!("/games/{}/reveal", game_id);
+    let (status, body) = client.post(Some("Carol"), &reveal, json!({})).await;
+    assert_eq!((status, body), (StatusCode::FORBIDDEN, json!({ "error": "Not a player of this game." })));
+
+    let mut rounds = 0;
+    loop {
+        let (_, waiting) = client.post(Some("Alice"), &reveal, json!({})).await;
+        assert_eq!(waiting["revealed"], false);
+        let (status, played) = client.post(Some("Bob"), &reveal, json!({})).await;
+        assert_eq!((status, &played["revealed"]), (StatusCode::OK, &json!(true)));
+        rounds += 1;
+        if played["settled"] == true {
+            break;
+        }
+    }
+
+    let mut seen = Vec::new();
+    while let Some(message) = player.next().await {
+        let Message::Text(text) = message.unwrap() else { continue };
+        let logged: Value = serde_json::from_str(&text).unwrap();
+        let settled = logged["event"].get("Settled").is_some();
+        seen.push(logged["event"].clone());
+        if settled {
+            break;
+        }
+    }
+    assert!(seen[0].get("GameStarted").is_some());
+    let revealed: Vec<&Value> = seen.iter().filter_map(|event| event.get("CardsRevealed")).collect();
+    assert_eq!(revealed.len(), rounds);
+    assert!(revealed.iter().all(|cards| !cards["creator_hand"].as_array().unwrap().is_empty()));
+    while let Some(message) = spectator.next().await {
+        let Message::Text(text) = message.unwrap() else { continue };
+        let logged: Value = serde_json::from_str(&text).unwrap();
+        if let Some(cards) = logged["event"].get("CardsRevealed") {
+            assert!(cards["creator_hand"].as_array().unwrap().is_empty());
+        }
+        if logged["event"].get("Settled").is_some() {
+            break;
+        }
+    }
+
+    // Everyone takes out what they have left, the tokens add up to what was minted
+    for user in PLAYERS {
+        let stake = client.get(&format!("/stakes/{}", user)).await;
+        assert_eq!(stake["locked"], 0);
+        let amount = stake["available"].as_u64().unwrap();
+        if amount > 0 {
+            assert_eq!(client.post(Some(user), "/withdrawals", json!({ "amount": amount })).await.0, StatusCode::OK);
+        }
+    }
+    let (game_state, token) = (server.game_state.read().await, server.token.read().await);
+    game_state.check_vault(&token).unwrap();
+    let held: u64 = PLAYERS.iter().map(|user| token.get_balance(user).get()).sum::<u64>() + token.get_balance(VAULT).get();
+    assert_eq!(held, 300);
+}
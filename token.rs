@@ -1,81 +1,1256 @@
+use ed25519_dalek::{Signature, VerifyingKey};
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::account_id::{AccountId, AccountIdError};
+use crate::amount::Amount;
+use crate::clock::SharedClock;
+use crate::config::{CurrencyDisplay, SymbolPosition};
+use crate::context::Context;
+use crate::error::ErrorKind;
+use crate::events::TokenEvent;
+use crate::strict::canonical_json;
+use crate::vesting::{Unlock, Vesting};
+
+// Bumped whenever the layout of an exported token changes. Version 1 kept the price as ETH in an f64.
+const EXPORT_SCHEMA_VERSION: u32 = 2;
+
+pub const WEI_PER_ETH: u64 = 1_000_000_000_000_000_000;
+
+// Numbered from 1 in the order they are taken, 0 is the state before any snapshot
+pub type SnapshotId = u64;
+
+// Metadata of tokens saved before they had any, their balances were counted in whole tokens
+pub const DEFAULT_NAME: &str = "Game Token";
+pub const DEFAULT_SYMBOL: &str = "GAME";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct ERC20Token {
-    owner: String,
-    balances: HashMap<String, u64>,
-    mint_price: f64, // Price per token in ETH
+#[serde(try_from = "StoredToken")]
+pub struct ERC20Token {
+    name: String,
+    symbol: String,
+    decimals: u8, // Balances are counted in units of 10^-decimals tokens, only shown amounts are divided
+    instance: u64, // Drawn when the token is created, permits name it so they are good for this token alone
+    owner: Option<AccountId>, // None once renounced, nobody can administer the token anymore
+    pending_owner: Option<AccountId>, // Offered the token by the owner, it is theirs once they accept
+    balances: HashMap<AccountId, Amount>,
+    total_supply: Amount, // Minted and not burned, what the balances add up to
+    max_supply: Option<Amount>, // Mints stop once the supply reaches it, burned tokens can be minted again
+    allowances: HashMap<AccountId, HashMap<AccountId, Amount>>, // Owner, then spender, to what it may still move
+    non_circulating: BTreeSet<AccountId>, // Treasury, escrow and the like, their tokens are not on the market
+    permit_keys: HashMap<AccountId, [u8; 32]>, // ed25519 keys owners sign their permits with
+    nonces: HashMap<AccountId, u64>, // Permits used so far per owner, a signed permit is good for one nonce only
+    snapshot_id: SnapshotId, // The last snapshot taken
+    // Values as of each snapshot, saved when they first change after it. A snapshot missing from a history
+    // means the value did not change since, the next saved one or the current value is what it was then.
+    balance_snapshots: HashMap<AccountId, Vec<(SnapshotId, Amount)>>,
+    supply_snapshots: Vec<(SnapshotId, Amount)>,
+    mint_price_wei: u64, // Price per token
+    paused: bool, // Set by the owner during an incident, no tokens move until it is cleared
+    frozen: BTreeSet<AccountId>, // Flagged for abuse by the owner, their tokens cannot move
+    vesting: Vesting, // Granted by the owner, minted to the holders as it unlocks
+    #[serde(skip)]
+    events: Vec<TokenEvent>, // Emitted and not taken yet, see take_events
+    #[serde(skip)]
+    clock: SharedClock, // Permit deadlines are checked against it
+}
+
+// A token as saved by any version, tokens saved before the price was kept in wei carry `mint_price`, and
+// tokens saved before burning have their supply counted from the balances
+#[derive(Deserialize)]
+struct StoredToken {
+    #[serde(default = "default_name")]
+    name: String,
+    #[serde(default = "default_symbol")]
+    symbol: String,
+    #[serde(default)]
+    decimals: u8,
+    #[serde(default = "new_instance")]
+    instance: u64,
+    owner: Option<AccountId>,
+    #[serde(default)]
+    pending_owner: Option<AccountId>,
+    balances: HashMap<AccountId, Amount>,
+    total_supply: Option<Amount>,
+    #[serde(default)]
+    max_supply: Option<Amount>,
+    #[serde(default)]
+    allowances: HashMap<AccountId, HashMap<AccountId, Amount>>,
+    #[serde(default)]
+    non_circulating: BTreeSet<AccountId>,
+    #[serde(default)]
+    permit_keys: HashMap<AccountId, [u8; 32]>,
+    #[serde(default)]
+    nonces: HashMap<AccountId, u64>,
+    #[serde(default)]
+    snapshot_id: SnapshotId,
+    #[serde(default)]
+    balance_snapshots: HashMap<AccountId, Vec<(SnapshotId, Amount)>>,
+    #[serde(default)]
+    supply_snapshots: Vec<(SnapshotId, Amount)>,
+    mint_price_wei: Option<u64>,
+    mint_price: Option<f64>,
+    #[serde(default)]
+    paused: bool,
+    #[serde(default)]
+    frozen: BTreeSet<AccountId>,
+    #[serde(default)]
+    vesting: Vesting,
+}
+
+impl TryFrom<StoredToken> for ERC20Token {
+    type Error = String;
+
+    fn try_from(stored: StoredToken) -> Result<ERC20Token, String> {
+        let mint_price_wei = match (stored.mint_price_wei, stored.mint_price) {
+            (Some(wei), _) => wei,
+            (None, Some(eth)) => eth_to_wei(eth).ok_or_else(|| format!("mint price of {} ETH is not a price in wei", eth))?,
+            (None, None) => return Err("missing field `mint_price_wei`".to_string()),
+        };
+        let total_supply = match stored.total_supply {
+            Some(total_supply) => total_supply,
+            None => stored
+                .balances
+                .values()
+                .try_fold(Amount::ZERO, |supply, balance| supply.checked_add(*balance))
+                .ok_or("balances add up to more than a supply can hold")?,
+        };
+        Ok(ERC20Token {
+            name: stored.name,
+            symbol: stored.symbol,
+            decimals: stored.decimals,
+            instance: stored.instance,
+            owner: stored.owner,
+            pending_owner: stored.pending_owner,
+            balances: stored.balances,
+            total_supply,
+            max_supply: stored.max_supply,
+            allowances: stored.allowances,
+            non_circulating: stored.non_circulating,
+            permit_keys: stored.permit_keys,
+            nonces: stored.nonces,
+            snapshot_id: stored.snapshot_id,
+            balance_snapshots: stored.balance_snapshots,
+            supply_snapshots: stored.supply_snapshots,
+            mint_price_wei,
+            paused: stored.paused,
+            frozen: stored.frozen,
+            vesting: stored.vesting,
+            events: Vec::new(),
+            clock: SharedClock::default(),
+        })
+    }
+}
+
+fn default_name() -> String {
+    DEFAULT_NAME.to_string()
+}
+
+fn default_symbol() -> String {
+    DEFAULT_SYMBOL.to_string()
+}
+
+// Tokens saved before they had an instance draw one when loaded, permits signed before are refused after
+fn new_instance() -> u64 {
+    rand::random()
+}
+
+// Only the first change after a snapshot is saved, the value before it is the one the snapshot saw
+fn remember(history: &mut Vec<(SnapshotId, Amount)>, snapshot_id: SnapshotId, value: Amount) {
+    if history.last().is_none_or(|(saved, _)| *saved < snapshot_id) {
+        history.push((snapshot_id, value));
+    }
+}
+
+fn value_at(history: &[(SnapshotId, Amount)], snapshot_id: SnapshotId, current: Amount) -> Amount {
+    let index = history.partition_point(|(saved, _)| *saved < snapshot_id);
+    history.get(index).map_or(current, |(_, value)| *value)
+}
+
+// Rounded to the nearest wei, the only place a float is still read
+fn eth_to_wei(eth: f64) -> Option<u64> {
+    let wei = (eth * WEI_PER_ETH as f64).round();
+    if wei.is_finite() && wei >= 0.0 && wei < u64::MAX as f64 { Some(wei as u64) } else { None }
 }
 
 impl ERC20Token {
-    fn new(owner: String) -> Self {
+    pub fn new(owner: AccountId, name: impl Into<String>, symbol: impl Into<String>, decimals: u8, max_supply: Option<u64>) -> Self {
         ERC20Token {
-            owner,
+            name: name.into(),
+            symbol: symbol.into(),
+            decimals,
+            instance: new_instance(),
+            owner: Some(owner),
+            pending_owner: None,
             balances: HashMap::new(),
-            mint_price: 0.001, // Initial price per token in ETH
+            total_supply: Amount::ZERO,
+            max_supply: max_supply.map(Amount::new),
+            allowances: HashMap::new(),
+            non_circulating: BTreeSet::new(),
+            permit_keys: HashMap::new(),
+            nonces: HashMap::new(),
+            snapshot_id: 0,
+            balance_snapshots: HashMap::new(),
+            supply_snapshots: Vec::new(),
+            mint_price_wei: WEI_PER_ETH / 1_000, // Initial price of 0.001 ETH per token
+            paused: false,
+            frozen: BTreeSet::new(),
+            vesting: Vesting::new(),
+            events: Vec::new(),
+            clock: SharedClock::default(),
         }
     }
 
+    // Replaces the system clock, tokens loaded from a file start on the system clock again
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // Mints to the caller, who paid `wei_paid` for the tokens
     // Does not follow CEI pattern 
-    fn mint(&mut self, user: String, amount: u64, eth_paid: f64) -> Result<(), String> {
-        if eth_paid < amount as f64 * self.mint_price {
-            return Err("Insufficient ETH paid.".to_string());
+    pub fn mint(&mut self, ctx: &Context, amount: u64, wei_paid: u64) -> Result<(), TokenError> {
+        let user = ctx.caller().clone();
+        self.require_not_paused()?;
+        self.require_not_frozen(&user)?;
+        if self.quote_mint(amount)? > wei_paid {
+            return Err(TokenError::InsufficientPayment);
+        }
+
+        let remaining = self.remaining_mintable();
+        if let Some(max_supply) = self.max_supply.filter(|_| remaining < amount) {
+            return Err(TokenError::CapExceeded { max_supply: max_supply.get(), remaining: remaining.get() });
+        }
+        self.issue(user, amount, wei_paid)
+    }
+
+    // New tokens for `user`, whatever paid for them
+    fn issue(&mut self, user: AccountId, amount: u64, wei_paid: u64) -> Result<(), TokenError> {
+        // No balance can hold more than the supply, so a supply that fits keeps every balance countable
+        let total_supply = self.total_supply.checked_add(amount).ok_or(TokenError::Overflow)?;
+        self.remember_supply();
+        self.remember_balance(&user);
+        let current_balance = self.balances.entry(user.clone()).or_default();
+        *current_balance = current_balance.checked_add(amount).ok_or(TokenError::Overflow)?;
+        self.total_supply = total_supply;
+        self.events.push(TokenEvent::Mint { to: user.into_string(), amount, wei_paid });
+
+        Ok(())
+    }
+
+    // Destroys tokens of the caller, they leave the supply for good
+    pub fn burn(&mut self, ctx: &Context, amount: u64) -> Result<(), TokenError> {
+        self.destroy(ctx.caller().clone(), amount)
+    }
+
+    fn destroy(&mut self, owner: AccountId, amount: u64) -> Result<(), TokenError> {
+        self.require_not_paused()?;
+        self.require_not_frozen(&owner)?;
+        let balance = self.balances.get(&owner).copied().ok_or(TokenError::SenderNotFound)?;
+        let remaining = balance.checked_sub(amount).ok_or(TokenError::InsufficientBalance)?;
+        let total_supply = self.total_supply.checked_sub(amount).ok_or(TokenError::InsufficientBalance)?;
+        self.remember_supply();
+        self.remember_balance(&owner);
+        self.total_supply = total_supply;
+        self.balances.insert(owner.clone(), remaining);
+        self.events.push(TokenEvent::Transfer { from: owner.into_string(), to: None, amount });
+        Ok(())
+    }
+
+    // Burns on the owner's behalf out of the caller's allowance, like transfer_from
+    pub fn burn_from(&mut self, ctx: &Context, owner: AccountId, amount: u64) -> Result<(), TokenError> {
+        let spender = ctx.caller().clone();
+        let remaining = self.allowance(&owner, &spender).checked_sub(amount).ok_or(TokenError::InsufficientAllowance)?;
+        self.destroy(owner.clone(), amount)?;
+        self.set_allowance(owner, spender, remaining);
+        Ok(())
+    }
+
+    pub fn total_supply(&self) -> Amount {
+        self.total_supply
+    }
+
+    // The supply less what the accounts marked as not circulating hold
+    pub fn circulating_supply(&self) -> Amount {
+        self.non_circulating
+            .iter()
+            .try_fold(self.total_supply, |circulating, account| circulating.checked_sub(self.get_balance(account)))
+            .unwrap_or_default()
+    }
+
+    // Marks an account like the treasury or an escrow as holding tokens off the market, or puts it back
+    pub fn set_circulating(&mut self, ctx: &Context, account: AccountId, circulating: bool) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        if circulating {
+            self.non_circulating.remove(&account);
+        } else {
+            self.non_circulating.insert(account);
+        }
+        Ok(())
+    }
+
+    // Freezes the balances and the supply as they are now, for distributions proportional to holdings at
+    // that point. Later transfers leave what balance_at reports for the snapshot unchanged.
+    pub fn snapshot(&mut self, ctx: &Context) -> Result<SnapshotId, TokenError> {
+        self.require_owner(ctx)?;
+        self.snapshot_id += 1;
+        Ok(self.snapshot_id)
+    }
+
+    pub fn balance_at(&self, user: &str, snapshot_id: SnapshotId) -> Result<Amount, TokenError> {
+        self.require_snapshot(snapshot_id)?;
+        let history = self.balance_snapshots.get(user).map_or(&[][..], Vec::as_slice);
+        Ok(value_at(history, snapshot_id, self.get_balance(user)))
+    }
+
+    pub fn total_supply_at(&self, snapshot_id: SnapshotId) -> Result<Amount, TokenError> {
+        self.require_snapshot(snapshot_id)?;
+        Ok(value_at(&self.supply_snapshots, snapshot_id, self.total_supply))
+    }
+
+    fn require_snapshot(&self, snapshot_id: SnapshotId) -> Result<(), TokenError> {
+        if snapshot_id == 0 || snapshot_id > self.snapshot_id {
+            return Err(TokenError::UnknownSnapshot(snapshot_id));
         }
+        Ok(())
+    }
 
-        let current_balance = self.balances.entry(user.clone()).or_insert(0);
-        *current_balance += amount;
+    // Called before a balance or the supply changes, keeps the value the last snapshot saw
+    fn remember_balance(&mut self, account: &AccountId) {
+        let balance = self.get_balance(account);
+        if self.snapshot_id > 0 {
+            remember(self.balance_snapshots.entry(account.clone()).or_default(), self.snapshot_id, balance);
+        }
+    }
+
+    fn remember_supply(&mut self) {
+        if self.snapshot_id > 0 {
+            remember(&mut self.supply_snapshots, self.snapshot_id, self.total_supply);
+        }
+    }
 
+    // Tokens mint still accepts, bounded by the cap if there is one and by what a supply can count. Vesting
+    // granted and not claimed yet is kept out of it.
+    pub fn remaining_mintable(&self) -> Amount {
+        self.max_supply
+            .unwrap_or(Amount::MAX)
+            .checked_sub(self.total_supply)
+            .and_then(|remaining| remaining.checked_sub(self.vesting.outstanding()))
+            .unwrap_or_default()
+    }
+
+    // Grants `user` tokens minted as `unlock` frees them, within what can still be minted
+    pub fn grant_vesting(&mut self, ctx: &Context, user: AccountId, amount: u64, unlock: Unlock) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        if !unlock.is_valid() {
+            return Err(TokenError::InvalidSchedule);
+        }
+        let remaining = self.remaining_mintable();
+        if remaining < amount {
+            return Err(match self.max_supply {
+                Some(max_supply) => TokenError::CapExceeded { max_supply: max_supply.get(), remaining: remaining.get() },
+                None => TokenError::Overflow,
+            });
+        }
+        self.vesting.grant(user, Amount::new(amount), unlock);
         Ok(())
     }
 
+    // Mints to the caller what unlocked of their grants since the last claim, returns how much
+    pub fn claim_vested(&mut self, ctx: &Context) -> Result<Amount, TokenError> {
+        let user = ctx.caller().clone();
+        self.require_not_paused()?;
+        self.require_not_frozen(&user)?;
+        let now = self.clock.now();
+        let claimable = self.vesting.claimable(&user, now);
+        if claimable.is_zero() {
+            return Ok(claimable);
+        }
+        self.issue(user.clone(), claimable.get(), 0)?;
+        self.vesting.claim(&user, now);
+        Ok(claimable)
+    }
+
+    pub fn vesting(&self) -> &Vesting {
+        &self.vesting
+    }
+
+    // Sends tokens of the caller to `to`
+    pub fn transfer(&mut self, ctx: &Context, to: AccountId, amount: u64) -> Result<(), TokenError> {
+        self.move_tokens(ctx.caller().clone(), to, amount)
+    }
+
     // Does not follow CEI pattern
     // In this case, if this would be a transfer() call, another contract can recursively call the function it could repeatedly drain funds.
 
-    fn transfer(&mut self, from: String, to: String, amount: u64) -> Result<(), String> {
-        let from_balance = self.balances.get_mut(&from).ok_or("Sender not found.".to_string())?;
-        if *from_balance < amount {
-            return Err("Insufficient balance.".to_string());
+    fn move_tokens(&mut self, from: AccountId, to: AccountId, amount: u64) -> Result<(), TokenError> {
+        self.require_not_paused()?;
+        self.require_not_frozen(&from)?;
+        self.require_not_frozen(&to)?;
+        let from_balance = self.balances.get(&from).copied().ok_or(TokenError::SenderNotFound)?;
+        let remaining = from_balance.checked_sub(amount).ok_or(TokenError::InsufficientBalance)?;
+
+        // Checked before anything moves, a transfer to oneself is a no-op
+        if from != to {
+            let to_balance = self.get_balance(&to).checked_add(amount).ok_or(TokenError::Overflow)?;
+            self.remember_balance(&from);
+            self.remember_balance(&to);
+            self.balances.insert(from.clone(), remaining);
+            self.balances.insert(to.clone(), to_balance);
+        }
+        self.events.push(TokenEvent::Transfer { from: from.into_string(), to: Some(to.into_string()), amount });
+
+        Ok(())
+    }
+
+    // Pays every leg out of the caller's tokens or none of them: the balances the legs touch are put back as
+    // they were when one is refused, and the refusal is returned
+    pub fn transfer_batch(&mut self, ctx: &Context, legs: Vec<(AccountId, u64)>) -> Result<(), TokenError> {
+        let from = ctx.caller().clone();
+        let before: HashMap<AccountId, Option<Amount>> = std::iter::once(&from)
+            .chain(legs.iter().map(|(to, _)| to))
+            .map(|account| (account.clone(), self.balances.get(account).copied()))
+            .collect();
+        let emitted = self.events.len();
+
+        for (to, amount) in legs {
+            if let Err(error) = self.move_tokens(from.clone(), to, amount) {
+                for (account, balance) in before {
+                    match balance {
+                        Some(balance) => self.balances.insert(account, balance),
+                        None => self.balances.remove(&account),
+                    };
+                }
+                self.events.truncate(emitted);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    // Lets `spender` move up to `amount` of the caller's tokens with transfer_from, replacing what it was
+    // allowed before. Approving 0 takes the allowance back.
+    pub fn approve(&mut self, ctx: &Context, spender: AccountId, amount: u64) -> Result<(), TokenError> {
+        self.set_allowance(ctx.caller().clone(), spender, Amount::new(amount));
+        Ok(())
+    }
+
+    pub fn allowance(&self, owner: &str, spender: &str) -> Amount {
+        self.allowances.get(owner).and_then(|spenders| spenders.get(spender)).copied().unwrap_or_default()
+    }
+
+    // Relative changes, they do not race with a transfer_from spending the allowance the way a new approve does
+    pub fn increase_allowance(&mut self, ctx: &Context, spender: AccountId, added: u64) -> Result<(), TokenError> {
+        let owner = ctx.caller().clone();
+        let allowance = self.allowance(&owner, &spender).checked_add(added).ok_or(TokenError::Overflow)?;
+        self.set_allowance(owner, spender, allowance);
+        Ok(())
+    }
+
+    pub fn decrease_allowance(&mut self, ctx: &Context, spender: AccountId, subtracted: u64) -> Result<(), TokenError> {
+        let owner = ctx.caller().clone();
+        let allowance = self.allowance(&owner, &spender).checked_sub(subtracted).ok_or(TokenError::InsufficientAllowance)?;
+        self.set_allowance(owner, spender, allowance);
+        Ok(())
+    }
+
+    // Moves tokens of `from` on its behalf out of the caller's allowance, the allowance is only spent once
+    // the transfer went through
+    pub fn transfer_from(&mut self, ctx: &Context, from: AccountId, to: AccountId, amount: u64) -> Result<(), TokenError> {
+        let spender = ctx.caller().clone();
+        self.require_not_frozen(&spender)?;
+        let remaining = self.allowance(&from, &spender).checked_sub(amount).ok_or(TokenError::InsufficientAllowance)?;
+        self.move_tokens(from.clone(), to, amount)?;
+        self.set_allowance(from, spender, remaining);
+        Ok(())
+    }
+
+    // Sets the key the owner signs permits with, replacing any key set before. Only the owner can set it,
+    // whoever holds the key can spend the owner's tokens through permit.
+    pub fn register_permit_key(&mut self, ctx: &Context, owner: AccountId, key: [u8; 32]) -> Result<(), TokenError> {
+        if ctx.caller() != &owner {
+            return Err(TokenError::NotKeyOwner);
+        }
+        VerifyingKey::from_bytes(&key).map_err(|_| TokenError::InvalidPermitKey)?;
+        self.permit_keys.insert(owner, key);
+        Ok(())
+    }
+
+    pub fn nonce(&self, owner: &str) -> u64 {
+        self.nonces.get(owner).copied().unwrap_or_default()
+    }
+
+    // What the owner signs to allow `spender` `amount` until `deadline`, like an EIP-2612 permit. It names the
+    // token by its instance and the owner's next nonce, so a signature cannot be replayed here or on another
+    // token, even one of the same name and symbol.
+    pub fn permit_message(&self, owner: &str, spender: &str, amount: u64, deadline: u64) -> Vec<u8> {
+        let message = serde_json::json!({
+            "type": "Permit",
+            "token": self.name,
+            "symbol": self.symbol,
+            "instance": self.instance,
+            "owner": owner,
+            "spender": spender,
+            "amount": amount,
+            "nonce": self.nonce(owner),
+            "deadline": deadline,
+        });
+        canonical_json(&message).into_bytes()
+    }
+
+    // Approves on behalf of an owner who signed the permit message, so anyone can submit the approval for
+    // them. Like approve it replaces the allowance, and it uses up the owner's nonce.
+    pub fn permit(&mut self, owner: AccountId, spender: AccountId, amount: u64, deadline: u64, signature: &[u8; 64]) -> Result<(), TokenError> {
+        if self.clock.now() > deadline {
+            return Err(TokenError::PermitExpired { deadline });
         }
+        let key = self.permit_keys.get(&owner).ok_or(TokenError::NoPermitKey)?;
+        let key = VerifyingKey::from_bytes(key).map_err(|_| TokenError::InvalidPermitKey)?;
+        let message = self.permit_message(&owner, &spender, amount, deadline);
+        key.verify_strict(&message, &Signature::from_bytes(signature)).map_err(|_| TokenError::InvalidSignature)?;
+
+        *self.nonces.entry(owner.clone()).or_default() += 1;
+        self.set_allowance(owner, spender, Amount::new(amount));
+        Ok(())
+    }
+
+    fn set_allowance(&mut self, owner: AccountId, spender: AccountId, allowance: Amount) {
+        self.events.push(TokenEvent::Approval { owner: owner.to_string(), spender: spender.to_string(), amount: allowance.get() });
+        if allowance.is_zero() {
+            if let Some(spenders) = self.allowances.get_mut(&owner) {
+                spenders.remove(&spender);
+                if spenders.is_empty() {
+                    self.allowances.remove(&owner);
+                }
+            }
+        } else {
+            self.allowances.entry(owner).or_default().insert(spender, allowance);
+        }
+    }
+
+    // Events emitted since the last call, oldest first. Whoever holds the token passes them on, usually to
+    // GameState::log_token_events so they land in the game's log.
+    pub fn take_events(&mut self) -> Vec<TokenEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Circuit breaker for incidents: while paused mints, transfers and burns are refused. Allowances can
+    // still be changed, they move nothing by themselves.
+    pub fn pause(&mut self, ctx: &Context) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        self.paused = true;
+        Ok(())
+    }
 
-        let to_balance = self.balances.entry(to.clone()).or_insert(0);
-        *from_balance -= amount;
-        *to_balance += amount;
+    pub fn unpause(&mut self, ctx: &Context) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        self.paused = false;
+        Ok(())
+    }
 
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Ownership changes hands in two steps: the owner offers it and the new owner accepts, so a mistyped
+    // account cannot leave the token without anyone able to administer it. A later offer replaces the first.
+    pub fn transfer_ownership(&mut self, ctx: &Context, new_owner: AccountId) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        self.pending_owner = Some(new_owner);
         Ok(())
     }
 
-    fn adjust_price(&mut self, new_price: f64) {
-        // Vulnerability: No access control
-        self.mint_price = new_price;
+    pub fn accept_ownership(&mut self, ctx: &Context) -> Result<(), TokenError> {
+        if self.pending_owner.as_ref() != Some(ctx.caller()) {
+            return Err(TokenError::NotPendingOwner);
+        }
+        self.owner = self.pending_owner.take();
+        Ok(())
+    }
+
+    // Leaves the token without an owner for good, the price and the pause stay as they are
+    pub fn renounce_ownership(&mut self, ctx: &Context) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        self.owner = None;
+        self.pending_owner = None;
+        Ok(())
     }
 
-    fn get_balance(&self, user: &String) -> u64 {
-        self.balances.get(user).cloned().unwrap_or(0)
+    pub fn owner(&self) -> Option<&AccountId> {
+        self.owner.as_ref()
+    }
+
+    pub fn pending_owner(&self) -> Option<&AccountId> {
+        self.pending_owner.as_ref()
+    }
+
+    fn require_owner(&self, ctx: &Context) -> Result<(), TokenError> {
+        if self.owner.as_ref() != Some(ctx.caller()) {
+            return Err(TokenError::NotOwner);
+        }
+        Ok(())
+    }
+
+    // Tokens of a frozen account cannot be minted, sent, received, spent by it as a spender or burned
+    pub fn freeze(&mut self, ctx: &Context, account: AccountId) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        if self.frozen.insert(account.clone()) {
+            self.events.push(TokenEvent::Frozen { account: account.into_string() });
+        }
+        Ok(())
+    }
+
+    pub fn unfreeze(&mut self, ctx: &Context, account: AccountId) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        if self.frozen.remove(&account) {
+            self.events.push(TokenEvent::Unfrozen { account: account.into_string() });
+        }
+        Ok(())
+    }
+
+    pub fn frozen_accounts(&self) -> &BTreeSet<AccountId> {
+        &self.frozen
+    }
+
+    fn require_not_frozen(&self, account: &AccountId) -> Result<(), TokenError> {
+        if self.frozen.contains(account) {
+            return Err(TokenError::Frozen(account.to_string()));
+        }
+        Ok(())
+    }
+
+    fn require_not_paused(&self) -> Result<(), TokenError> {
+        if self.paused {
+            return Err(TokenError::Paused);
+        }
+        Ok(())
+    }
+
+    // Wei to pay for `amount` tokens, exact. A quote that does not fit in a u64 is an overflow.
+    pub fn quote_mint(&self, amount: u64) -> Result<Amount, TokenError> {
+        let wei = amount as u128 * self.mint_price_wei as u128;
+        u64::try_from(wei).map(Amount::new).map_err(|_| TokenError::Overflow)
+    }
+
+    pub fn adjust_price(&mut self, ctx: &Context, new_price_wei: u64) -> Result<(), TokenError> {
+        self.require_owner(ctx)?;
+        self.mint_price_wei = new_price_wei;
+        Ok(())
+    }
+
+    // In wei per token
+    pub fn mint_price(&self) -> u64 {
+        self.mint_price_wei
+    }
+
+    pub fn get_balance(&self, user: &str) -> Amount {
+        self.balances.get(user).copied().unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    // How amounts of this token are shown to people, 1500 units of a token with 3 decimals are "1.500 GAME"
+    pub fn currency(&self) -> CurrencyDisplay {
+        CurrencyDisplay { symbol: self.symbol.clone(), decimals: self.decimals, position: SymbolPosition::Suffix }
+    }
+
+    pub fn format_amount(&self, amount: impl Into<Amount>) -> String {
+        self.currency().format(amount.into().get())
+    }
+
+    // Writes the token on its own, independent of any game state using it, so a deployment can be
+    // migrated or audited separately
+    pub fn export(&self, path: &Path) -> Result<(), TokenError> {
+        let export = TokenExport {
+            schema_version: EXPORT_SCHEMA_VERSION,
+            hash: token_hash(self)?,
+            token: self,
+        };
+        let json = serde_json::to_string_pretty(&export).map_err(|e| TokenError::Serialize(e.to_string()))?;
+        fs::write(path, json).map_err(|e| TokenError::io(path, e))
+    }
+
+    // Refuses files of an unknown schema version and files whose content does not match their hash. The
+    // hash covers the token as it was written, so version 1 files are checked before their price is migrated.
+    pub fn import(path: &Path) -> Result<ERC20Token, TokenError> {
+        let json = fs::read_to_string(path).map_err(|e| TokenError::io(path, e))?;
+        let export: TokenExport<serde_json::Value> =
+            serde_json::from_str(&json).map_err(|e| TokenError::InvalidExport(e.to_string()))?;
+        if !(1..=EXPORT_SCHEMA_VERSION).contains(&export.schema_version) {
+            return Err(TokenError::UnsupportedSchema(export.schema_version));
+        }
+        if value_hash(&export.token) != export.hash {
+            return Err(TokenError::HashMismatch);
+        }
+        serde_json::from_value(export.token).map_err(|e| TokenError::InvalidExport(e.to_string()))
     }
 }
 
-fn main() {
-    let owner = "OwnerAddress".to_string();
-    let mut token = ERC20Token::new(owner.clone());
+// Every way a token operation can be refused
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("Insufficient ETH paid.")]
+    InsufficientPayment,
+    #[error("Sender not found.")]
+    SenderNotFound,
+    #[error("Insufficient balance.")]
+    InsufficientBalance,
+    #[error("Insufficient allowance.")]
+    InsufficientAllowance,
+    #[error("Balance would overflow.")]
+    Overflow,
+    #[error("Mint exceeds the supply cap of {max_supply}, {remaining} can still be minted.")]
+    CapExceeded { max_supply: u64, remaining: u64 },
+    #[error("Permit expired at {deadline}.")]
+    PermitExpired { deadline: u64 },
+    #[error("No permit key registered for the owner.")]
+    NoPermitKey,
+    #[error("Permit key is not a valid ed25519 key.")]
+    InvalidPermitKey,
+    #[error("Only the account itself can set its permit key.")]
+    NotKeyOwner,
+    #[error("Permit signature does not match.")]
+    InvalidSignature,
+    #[error("Vesting schedule has no duration or a cliff past its end.")]
+    InvalidSchedule,
+    #[error("Snapshot {0} was not taken.")]
+    UnknownSnapshot(SnapshotId),
+    #[error("Only the owner of the token can do this.")]
+    NotOwner,
+    #[error("Ownership of the token was not offered to this account.")]
+    NotPendingOwner,
+    #[error("Token operations are paused.")]
+    Paused,
+    #[error("Account {0} is frozen.")]
+    Frozen(String),
+    #[error(transparent)]
+    AccountId(#[from] AccountIdError),
+    #[error("Cannot serialize token: {0}.")]
+    Serialize(String),
+    #[error("Cannot access {path}: {message}.")]
+    Io { path: String, message: String },
+    #[error("Invalid token export: {0}.")]
+    InvalidExport(String),
+    #[error("Unsupported schema version {0}.")]
+    UnsupportedSchema(u32),
+    #[error("Integrity hash mismatch.")]
+    HashMismatch,
+}
 
-    // Mint tokens
-    match token.mint("User1".to_string(), 100, 0.1) {
-        Ok(()) => println!("Minted tokens successfully."),
-        Err(e) => println!("Error minting tokens: {}", e),
+impl TokenError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            TokenError::SenderNotFound | TokenError::NoPermitKey | TokenError::UnknownSnapshot(_) => ErrorKind::NotFound,
+            TokenError::NotOwner | TokenError::NotPendingOwner | TokenError::NotKeyOwner | TokenError::InvalidSignature | TokenError::Frozen(_) => {
+                ErrorKind::Forbidden
+            }
+            TokenError::Paused => ErrorKind::Unavailable,
+            TokenError::InsufficientPayment
+            | TokenError::InsufficientBalance
+            | TokenError::InsufficientAllowance
+            | TokenError::CapExceeded { .. }
+            | TokenError::PermitExpired { .. }
+            | TokenError::Overflow => ErrorKind::Conflict,
+            TokenError::AccountId(_) | TokenError::InvalidSchedule | TokenError::InvalidPermitKey | TokenError::InvalidExport(_) | TokenError::UnsupportedSchema(_) | TokenError::HashMismatch => {
+                ErrorKind::Invalid
+            }
+            TokenError::Serialize(_) | TokenError::Io { .. } => ErrorKind::Internal,
+        }
     }
 
-    // Adjust price (vulnerable to any user)
-    token.adjust_price(0.002);
-    println!("New mint price set to: {}", token.mint_price);
+    fn io(path: &Path, error: std::io::Error) -> Self {
+        TokenError::Io { path: path.display().to_string(), message: error.to_string() }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenExport<T> {
+    schema_version: u32,
+    hash: String, // Hex encoded sha256 of `token` as JSON with sorted keys
+    token: T,
+}
+
+fn token_hash(token: &ERC20Token) -> Result<String, TokenError> {
+    let value = serde_json::to_value(token).map_err(|e| TokenError::Serialize(e.to_string()))?;
+    Ok(value_hash(&value))
+}
+
+fn value_hash(value: &serde_json::Value) -> String {
+    let digest = Sha256::digest(canonical_json(value));
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// What every test token has to keep: its supply is exactly what the balances add up to
+#[cfg(test)]
+fn assert_supply_adds_up(token: &ERC20Token) {
+    let balances: u128 = token.balances.values().map(|balance| balance.get() as u128).sum();
+    assert_eq!(balances, token.total_supply.get() as u128);
+}
+
+#[test]
+fn test_export_import_round_trip() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let path = std::env::temp_dir().join(format!("erc20_export_{}.json", std::process::id()));
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.mint(&caller("User1"), 100, WEI_PER_ETH / 10).unwrap();
+    token.transfer(&caller("User1"), account("User2"), 40).unwrap();
+
+    token.export(&path).unwrap();
+    let imported = ERC20Token::import(&path).unwrap();
+    assert_eq!(imported.get_balance("User2"), 40);
+    assert_eq!(imported.owner().map(AccountId::as_str), Some("OwnerAddress"));
+    assert_eq!(imported.instance, token.instance);
+
+    // A balance edited by hand no longer matches the hash
+    let mut tampered: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    tampered["token"]["balances"]["User1"] = 600.into();
+    fs::write(&path, tampered.to_string()).unwrap();
+    assert_eq!(ERC20Token::import(&path).unwrap_err(), TokenError::HashMismatch);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_balances_never_overflow() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), u64::MAX - 1, 0).unwrap();
+    token.mint(&caller("Bob"), 1, 0).unwrap();
+    // The supply is full, no balance can grow past it
+    assert_eq!(token.mint(&caller("Carol"), 1, 0), Err(TokenError::Overflow));
+    assert_eq!(token.get_balance("Carol"), 0);
+    token.transfer(&caller("Bob"), account("Alice"), 1).unwrap();
+    assert_eq!((token.get_balance("Alice").get(), token.total_supply().get()), (u64::MAX, u64::MAX));
+
+    token.transfer(&caller("Alice"), account("Alice"), u64::MAX).unwrap();
+    assert_eq!(token.get_balance("Alice"), u64::MAX);
+    assert_supply_adds_up(&token);
+}
+
+#[test]
+fn test_mint_price_is_exact() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    // 0.1 ETH is not exact in binary, 100 tokens at 0.001 ETH used to be a matter of rounding
+    assert_eq!(token.quote_mint(100), Ok(Amount::new(WEI_PER_ETH / 10)));
+    assert_eq!(token.mint(&caller("Alice"), 100, WEI_PER_ETH / 10 - 1), Err(TokenError::InsufficientPayment));
+    token.mint(&caller("Alice"), 100, WEI_PER_ETH / 10).unwrap();
+    assert_eq!(token.quote_mint(u64::MAX), Err(TokenError::Overflow));
+
+    // A version 1 export kept the price in ETH, it is read back in wei
+    let path = std::env::temp_dir().join(format!("erc20_v1_{}.json", std::process::id()));
+    let old = serde_json::json!({ "owner": "OwnerAddress", "balances": { "Alice": 5 }, "mint_price": 0.002 });
+    let export = serde_json::json!({ "schema_version": 1, "hash": value_hash(&old), "token": old });
+    fs::write(&path, export.to_string()).unwrap();
+    let imported = ERC20Token::import(&path).unwrap();
+    assert_eq!((imported.mint_price(), imported.get_balance("Alice").get()), (2_000_000_000_000_000, 5));
+    fs::remove_file(&path).unwrap();
+    assert!(serde_json::from_value::<ERC20Token>(serde_json::json!({ "owner": "O", "balances": {}, "mint_price": -1.0 })).is_err());
+}
+
+#[test]
+fn test_spenders_move_only_what_they_were_allowed() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.approve(&caller("Alice"), account("Game"), 30).unwrap();
+    assert_eq!(token.allowance("Alice", "Game"), 30);
+    assert_eq!(token.allowance("Game", "Alice"), 0);
 
-    // Transfer tokens
-    match token.transfer("User1".to_string(), "User2".to_string(), 50) {
-        Ok(()) => println!("Tokens transferred successfully."),
-        Err(e) => println!("Error transferring tokens: {}", e),
+    token.transfer_from(&caller("Game"), account("Alice"), account("Escrow"), 20).unwrap();
+    assert_eq!((token.get_balance("Alice").get(), token.get_balance("Escrow").get()), (80, 20));
+    assert_eq!(token.allowance("Alice", "Game"), 10);
+    assert_eq!(
+        token.transfer_from(&caller("Game"), account("Alice"), account("Escrow"), 11),
+        Err(TokenError::InsufficientAllowance)
+    );
+    assert_eq!(token.transfer_from(&caller("Bob"), account("Alice"), account("Bob"), 1), Err(TokenError::InsufficientAllowance));
+
+    // A refused transfer leaves the allowance as it was
+    token.increase_allowance(&caller("Alice"), account("Game"), 500).unwrap();
+    assert_eq!(
+        token.transfer_from(&caller("Game"), account("Alice"), account("Escrow"), 81),
+        Err(TokenError::InsufficientBalance)
+    );
+    assert_eq!(token.allowance("Alice", "Game"), 510);
+    assert_eq!(token.decrease_allowance(&caller("Alice"), account("Game"), 511), Err(TokenError::InsufficientAllowance));
+    token.decrease_allowance(&caller("Alice"), account("Game"), 510).unwrap();
+    assert!(token.allowances.is_empty());
+    token.increase_allowance(&caller("Alice"), account("Game"), u64::MAX).unwrap();
+    assert_eq!(token.increase_allowance(&caller("Alice"), account("Game"), 1), Err(TokenError::Overflow));
+    assert_supply_adds_up(&token);
+}
+
+#[test]
+fn test_burning_shrinks_the_supply() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.mint(&caller("Bob"), 50, 0).unwrap();
+    token.burn(&caller("Alice"), 30).unwrap();
+    assert_eq!((token.get_balance("Alice").get(), token.total_supply().get()), (70, 120));
+    assert_eq!(token.burn(&caller("Bob"), 51), Err(TokenError::InsufficientBalance));
+    assert_eq!(token.burn(&caller("Carol"), 1), Err(TokenError::SenderNotFound));
+
+    token.approve(&caller("Bob"), account("Game"), 20).unwrap();
+    assert_eq!(token.burn_from(&caller("Game"), account("Bob"), 21), Err(TokenError::InsufficientAllowance));
+    token.burn_from(&caller("Game"), account("Bob"), 20).unwrap();
+    assert_eq!((token.get_balance("Bob").get(), token.allowance("Bob", "Game").get(), token.total_supply().get()), (30, 0, 100));
+    assert_supply_adds_up(&token);
+
+    // Tokens saved before the supply was kept count it from their balances
+    let mut saved = serde_json::to_value(&token).unwrap();
+    saved.as_object_mut().unwrap().remove("total_supply");
+    assert_eq!(serde_json::from_value::<ERC20Token>(saved).unwrap().total_supply(), 100);
+}
+
+#[test]
+fn test_mints_stop_at_the_cap() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, Some(100));
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 60, 0).unwrap();
+    assert_eq!(token.remaining_mintable(), 40);
+    assert_eq!(token.mint(&caller("Bob"), 41, 0), Err(TokenError::CapExceeded { max_supply: 100, remaining: 40 }));
+    token.mint(&caller("Bob"), 40, 0).unwrap();
+    assert_eq!(token.mint(&caller("Bob"), 1, 0).unwrap_err().to_string(), "Mint exceeds the supply cap of 100, 0 can still be minted.");
+
+    // Burned tokens make room again
+    token.burn(&caller("Alice"), 10).unwrap();
+    assert_eq!(token.remaining_mintable(), 10);
+    assert_supply_adds_up(&token);
+    assert_eq!(ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None).remaining_mintable(), u64::MAX);
+}
+
+#[test]
+fn test_paused_token_moves_nothing() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.approve(&caller("Alice"), account("Game"), 10).unwrap();
+    assert_eq!(token.pause(&caller("Alice")), Err(TokenError::NotOwner));
+    token.pause(&caller("OwnerAddress")).unwrap();
+
+    assert_eq!(token.mint(&caller("Alice"), 1, 0), Err(TokenError::Paused));
+    assert_eq!(token.transfer(&caller("Alice"), account("Bob"), 1), Err(TokenError::Paused));
+    assert_eq!(token.transfer_from(&caller("Game"), account("Alice"), account("Bob"), 1), Err(TokenError::Paused));
+    assert_eq!(token.burn(&caller("Alice"), 1), Err(TokenError::Paused));
+    assert_eq!((token.get_balance("Alice").get(), token.allowance("Alice", "Game").get()), (100, 10));
+    assert_eq!(TokenError::Paused.kind(), ErrorKind::Unavailable);
+
+    assert_eq!(token.unpause(&caller("Game")), Err(TokenError::NotOwner));
+    token.unpause(&caller("OwnerAddress")).unwrap();
+    token.transfer(&caller("Alice"), account("Bob"), 1).unwrap();
+}
+
+#[test]
+fn test_ownership_changes_hands_in_two_steps() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    assert_eq!(token.adjust_price(&caller("Mallory"), 0), Err(TokenError::NotOwner));
+    assert_eq!(token.transfer_ownership(&caller("Mallory"), account("Mallory")), Err(TokenError::NotOwner));
+
+    // Offered to Bob, the old owner keeps the token until they accept
+    token.transfer_ownership(&caller("OwnerAddress"), account("Bob")).unwrap();
+    assert_eq!(token.accept_ownership(&caller("Mallory")), Err(TokenError::NotPendingOwner));
+    token.adjust_price(&caller("OwnerAddress"), 1).unwrap();
+    token.accept_ownership(&caller("Bob")).unwrap();
+    assert_eq!((token.owner().map(AccountId::as_str), token.pending_owner()), (Some("Bob"), None));
+    assert_eq!(token.adjust_price(&caller("OwnerAddress"), 0), Err(TokenError::NotOwner));
+    token.adjust_price(&caller("Bob"), 2).unwrap();
+
+    // Renounced for good, the token and its saved form have no owner anymore
+    token.transfer_ownership(&caller("Bob"), account("Carol")).unwrap();
+    token.renounce_ownership(&caller("Bob")).unwrap();
+    assert_eq!(token.accept_ownership(&caller("Carol")), Err(TokenError::NotPendingOwner));
+    assert_eq!(token.pause(&caller("Bob")), Err(TokenError::NotOwner));
+    let saved: ERC20Token = serde_json::from_str(&serde_json::to_string(&token).unwrap()).unwrap();
+    assert_eq!((saved.owner(), saved.mint_price()), (None, 2));
+}
+
+#[test]
+fn test_every_token_flow_is_emitted() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.mint(&caller("Alice"), 100, WEI_PER_ETH / 10).unwrap();
+    token.approve(&caller("Alice"), account("Game"), 30).unwrap();
+    token.transfer_from(&caller("Game"), account("Alice"), account("Bob"), 20).unwrap();
+    token.burn(&caller("Bob"), 5).unwrap();
+    // Refused operations emit nothing
+    assert!(token.transfer(&caller("Carol"), account("Bob"), 1).is_err());
+
+    assert_eq!(token.take_events(), vec![
+        TokenEvent::Mint { to: "Alice".to_string(), amount: 100, wei_paid: WEI_PER_ETH / 10 },
+        TokenEvent::Approval { owner: "Alice".to_string(), spender: "Game".to_string(), amount: 30 },
+        TokenEvent::Transfer { from: "Alice".to_string(), to: Some("Bob".to_string()), amount: 20 },
+        TokenEvent::Approval { owner: "Alice".to_string(), spender: "Game".to_string(), amount: 10 },
+        TokenEvent::Transfer { from: "Bob".to_string(), to: None, amount: 5 },
+    ]);
+    assert!(token.take_events().is_empty());
+
+    // Logged by the game they show up among its events
+    let mut game_state = crate::GameState::new();
+    token.transfer(&caller("Alice"), account("Bob"), 1).unwrap();
+    game_state.log_token_events(token.take_events());
+    assert!(matches!(&game_state.events()[0].event, crate::events::GameEvent::Token(TokenEvent::Transfer { amount: 1, .. })));
+}
+
+#[test]
+fn test_circulating_supply_leaves_out_house_accounts() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Treasury"), 500, 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.transfer(&caller("Alice"), account("Escrow"), 40).unwrap();
+    assert_eq!(token.circulating_supply(), 600);
+
+    assert_eq!(token.set_circulating(&caller("Alice"), account("Treasury"), false), Err(TokenError::NotOwner));
+    token.set_circulating(&caller("OwnerAddress"), account("Treasury"), false).unwrap();
+    token.set_circulating(&caller("OwnerAddress"), account("Escrow"), false).unwrap();
+    assert_eq!((token.total_supply().get(), token.circulating_supply().get()), (600, 60));
+
+    // Paid out of the treasury, tokens start circulating
+    token.transfer(&caller("Treasury"), account("Bob"), 200).unwrap();
+    token.set_circulating(&caller("OwnerAddress"), account("Escrow"), true).unwrap();
+    assert_eq!(token.circulating_supply(), 300);
+    assert_supply_adds_up(&token);
+}
+
+#[test]
+fn test_amounts_are_shown_with_the_token_decimals() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Chips", "CHP", 2, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 1_050, 0).unwrap();
+    assert_eq!((token.name(), token.symbol(), token.decimals()), ("Chips", "CHP", 2));
+    assert_eq!(token.format_amount(token.get_balance("Alice")), "10.50 CHP");
+    assert_eq!(token.format_amount(7), "0.07 CHP");
+
+    // Tokens saved before metadata keep counting whole tokens
+    let mut saved = serde_json::to_value(&token).unwrap();
+    for field in ["name", "symbol", "decimals"] {
+        saved.as_object_mut().unwrap().remove(field);
     }
+    let legacy: ERC20Token = serde_json::from_value(saved).unwrap();
+    assert_eq!((legacy.name(), legacy.decimals()), (DEFAULT_NAME, 0));
+    assert_eq!(legacy.format_amount(1_050), "1050 GAME");
+}
+
+#[test]
+fn test_signed_permits_approve_once() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::clock::ManualClock;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let clock = ManualClock::new(1_000);
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None).with_clock(SharedClock::new(clock.clone()));
+    let alice = SigningKey::from_bytes(&[7; 32]);
+    let sign = |token: &ERC20Token, spender: &str, amount: u64, deadline: u64| {
+        alice.sign(&token.permit_message("Alice", spender, amount, deadline)).to_bytes()
+    };
+
+    let signature = sign(&token, "Game", 50, 2_000);
+    assert_eq!(token.permit(account("Alice"), account("Game"), 50, 2_000, &signature), Err(TokenError::NoPermitKey));
+    // Bob cannot set a key of his own for Alice and spend her tokens with it
+    let bob = SigningKey::from_bytes(&[9; 32]);
+    let refused = token.register_permit_key(&Context::new(account("Bob")), account("Alice"), bob.verifying_key().to_bytes());
+    assert_eq!(refused, Err(TokenError::NotKeyOwner));
+    token.register_permit_key(&Context::new(account("Alice")), account("Alice"), alice.verifying_key().to_bytes()).unwrap();
+
+    // Anything changed from what was signed is refused
+    assert_eq!(token.permit(account("Alice"), account("Game"), 51, 2_000, &signature), Err(TokenError::InvalidSignature));
+    assert_eq!(token.permit(account("Alice"), account("Mallory"), 50, 2_000, &signature), Err(TokenError::InvalidSignature));
+    token.permit(account("Alice"), account("Game"), 50, 2_000, &signature).unwrap();
+    assert_eq!((token.allowance("Alice", "Game").get(), token.nonce("Alice")), (50, 1));
+
+    // The nonce moved on, the same signature cannot be replayed
+    token.approve(&caller("Alice"), account("Game"), 0).unwrap();
+    assert_eq!(token.permit(account("Alice"), account("Game"), 50, 2_000, &signature), Err(TokenError::InvalidSignature));
+
+    clock.set(2_001);
+    let late = sign(&token, "Game", 50, 2_000);
+    assert_eq!(token.permit(account("Alice"), account("Game"), 50, 2_000, &late), Err(TokenError::PermitExpired { deadline: 2_000 }));
+    let mut off_curve = [0; 32];
+    off_curve[0] = 2;
+    assert_eq!(token.register_permit_key(&Context::new(account("Bob")), account("Bob"), off_curve), Err(TokenError::InvalidPermitKey));
+}
+
+#[test]
+fn test_permits_are_good_for_one_token_only() {
+    use crate::account_id::account;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let alice = SigningKey::from_bytes(&[7; 32]);
+    let mut tokens = [(); 2].map(|_| ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None));
+    for token in &mut tokens {
+        token.register_permit_key(&Context::new(account("Alice")), account("Alice"), alice.verifying_key().to_bytes()).unwrap();
+    }
+    let [first, second] = &mut tokens;
+    let signature = alice.sign(&first.permit_message("Alice", "Game", 50, u64::MAX)).to_bytes();
+    assert_eq!(second.permit(account("Alice"), account("Game"), 50, u64::MAX, &signature), Err(TokenError::InvalidSignature));
+    first.permit(account("Alice"), account("Game"), 50, u64::MAX, &signature).unwrap();
+    assert_eq!((first.allowance("Alice", "Game").get(), second.allowance("Alice", "Game").get()), (50, 0));
+}
+
+#[test]
+fn test_batches_pay_every_leg_or_none() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("House"), 100, 0).unwrap();
+    token.take_events();
+
+    // Bob's leg is paid before Carol's finds the house short, it is rolled back with the rest
+    let legs = vec![(account("Alice"), 30), (account("Bob"), 50), (account("Carol"), 30)];
+    assert_eq!(token.transfer_batch(&caller("House"), legs), Err(TokenError::InsufficientBalance));
+    assert_eq!((token.get_balance("House").get(), token.get_balance("Bob").get()), (100, 0));
+    assert!(!token.balances.contains_key("Alice") && token.take_events().is_empty());
+
+    token.transfer_batch(&caller("House"), vec![(account("Alice"), 30), (account("Bob"), 50), (account("Alice"), 20)]).unwrap();
+    assert_eq!((token.get_balance("House").get(), token.get_balance("Alice").get(), token.get_balance("Bob").get()), (0, 50, 50));
+    assert_eq!(token.take_events().len(), 3);
+    assert_supply_adds_up(&token);
+}
 
-    // Get balances
-    println!("User1 balance: {}", token.get_balance(&"User1".to_string()));
-    println!("User2 balance: {}", token.get_balance(&"User2".to_string()));
-}
\ No newline at end of file
+#[test]
+fn test_snapshots_keep_past_balances() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    assert_eq!(token.snapshot(&caller("Alice")), Err(TokenError::NotOwner));
+    let first = token.snapshot(&caller("OwnerAddress")).unwrap();
+
+    token.transfer(&caller("Alice"), account("Bob"), 30).unwrap();
+    token.transfer(&caller("Alice"), account("Bob"), 10).unwrap();
+    let second = token.snapshot(&caller("OwnerAddress")).unwrap();
+    token.mint(&caller("Carol"), 50, 0).unwrap();
+    token.burn(&caller("Bob"), 40).unwrap();
+    let third = token.snapshot(&caller("OwnerAddress")).unwrap();
+
+    let balances = |snapshot_id| ["Alice", "Bob", "Carol"].map(|user| token.balance_at(user, snapshot_id).unwrap().get());
+    assert_eq!(balances(first), [100, 0, 0]);
+    assert_eq!(balances(second), [60, 40, 0]);
+    assert_eq!(balances(third), [60, 0, 50]);
+    let supplies = [first, second, third].map(|snapshot_id| token.total_supply_at(snapshot_id).unwrap().get());
+    assert_eq!(supplies, [100, 100, 110]);
+
+    assert_eq!(token.balance_at("Alice", 0), Err(TokenError::UnknownSnapshot(0)));
+    assert_eq!(token.total_supply_at(4), Err(TokenError::UnknownSnapshot(4)));
+
+    // A refused batch leaves the snapshots as they were
+    assert!(token.transfer_batch(&caller("Alice"), vec![(account("Bob"), 60), (account("Carol"), 1)]).is_err());
+    assert_eq!((token.balance_at("Alice", third).unwrap().get(), token.balance_at("Bob", third).unwrap().get()), (60, 0));
+}
+
+#[test]
+fn test_frozen_accounts_cannot_move_tokens() {
+    use crate::account_id::account;
+    use crate::context::caller;
+
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.mint(&caller("Mallory"), 100, 0).unwrap();
+    token.approve(&caller("Alice"), account("Mallory"), 50).unwrap();
+    assert_eq!(token.freeze(&caller("Alice"), account("Mallory")), Err(TokenError::NotOwner));
+    token.freeze(&caller("OwnerAddress"), account("Mallory")).unwrap();
+    token.take_events();
+
+    let frozen = Err(TokenError::Frozen("Mallory".to_string()));
+    assert_eq!(token.transfer(&caller("Mallory"), account("Bob"), 1), frozen);
+    assert_eq!(token.transfer(&caller("Alice"), account("Mallory"), 1), frozen);
+    assert_eq!(token.transfer_from(&caller("Mallory"), account("Alice"), account("Bob"), 1), frozen);
+    assert_eq!(token.mint(&caller("Mallory"), 1, 0), frozen);
+    assert_eq!(token.burn(&caller("Mallory"), 1), frozen);
+    assert_eq!(token.frozen_accounts().iter().map(AccountId::as_str).collect::<Vec<_>>(), vec!["Mallory"]);
+    token.transfer(&caller("Alice"), account("Bob"), 1).unwrap();
+
+    token.unfreeze(&caller("OwnerAddress"), account("Mallory")).unwrap();
+    token.transfer(&caller("Mallory"), account("Bob"), 1).unwrap();
+    assert_eq!(token.take_events()[1], TokenEvent::Unfrozen { account: "Mallory".to_string() });
+}
+
+#[test]
+fn test_vested_tokens_are_minted_as_they_unlock() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::clock::ManualClock;
+
+    let clock = ManualClock::new(1_000);
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, Some(1_000)).with_clock(SharedClock::new(clock.clone()));
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    let linear = Unlock::Linear { start: 1_000, cliff_secs: 100, duration_secs: 400 };
+    assert_eq!(token.grant_vesting(&caller("Alice"), account("Alice"), 400, linear), Err(TokenError::NotOwner));
+    assert_eq!(
+        token.grant_vesting(&caller("OwnerAddress"), account("Alice"), 400, Unlock::Linear { start: 1_000, cliff_secs: 500, duration_secs: 400 }),
+        Err(TokenError::InvalidSchedule)
+    );
+    token.grant_vesting(&caller("OwnerAddress"), account("Alice"), 400, linear).unwrap();
+    token.grant_vesting(&caller("OwnerAddress"), account("Bob"), 500, Unlock::Cliff { at: 2_000 }).unwrap();
+
+    // What is granted is kept from mint, even before it unlocks
+    assert_eq!(token.remaining_mintable().get(), 100);
+    assert_eq!(token.mint(&caller("Carol"), 101, 0), Err(TokenError::CapExceeded { max_supply: 1_000, remaining: 100 }));
+    assert_eq!(
+        token.grant_vesting(&caller("OwnerAddress"), account("Carol"), 101, Unlock::Cliff { at: 2_000 }),
+        Err(TokenError::CapExceeded { max_supply: 1_000, remaining: 100 })
+    );
+
+    assert_eq!(token.claim_vested(&caller("Alice")).unwrap().get(), 0);
+    clock.set(1_200);
+    assert_eq!(token.claim_vested(&caller("Alice")).unwrap().get(), 200);
+    assert_eq!(token.claim_vested(&caller("Alice")).unwrap().get(), 0);
+    assert_eq!(token.claim_vested(&caller("Bob")).unwrap().get(), 0);
+    clock.set(2_000);
+    assert_eq!(token.claim_vested(&caller("Alice")).unwrap().get(), 200);
+    assert_eq!(token.claim_vested(&caller("Bob")).unwrap().get(), 500);
+    assert_eq!((token.get_balance("Alice").get(), token.get_balance("Bob").get()), (400, 500));
+    assert_eq!((token.total_supply().get(), token.remaining_mintable().get()), (900, 100));
+    assert_supply_adds_up(&token);
+}
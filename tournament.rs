@@ -0,0 +1,236 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::GameError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentStatus {
+    Registering,
+    Running,
+    Finished,
+}
+
+// Where a paid registration ended up
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registration {
+    Registered,
+    Waitlisted,
+}
+
+// One pairing of the bracket. A missing second player is a bye, the first player goes through.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BracketMatch {
+    pub players: (String, Option<String>),
+    pub game_id: Option<u64>,
+    pub winner: Option<String>,
+}
+
+// Single elimination: players buy in while registering, every round halves the field
+// and the last one standing takes the prize pool minus the rake.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tournament {
+    pub id: u64,
+    pub buy_in: u64,
+    pub max_players: usize,
+    pub rake_bps: u32, // Basis points of the prize pool kept by the house
+    pub players: Vec<String>,
+    pub waitlist: Vec<String>, // Paid registrations beyond max_players, first in line is promoted first
+    pub prize_pool: u64,
+    pub held: u64, // Buy-ins of the waitlist, not part of the prize pool
+    pub rounds: Vec<Vec<BracketMatch>>,
+    pub status: TournamentStatus,
+    pub winner: Option<String>,
+}
+
+impl Tournament {
+    pub fn new(id: u64, buy_in: u64, max_players: usize, rake_bps: u32) -> Self {
+        Tournament {
+            id,
+            buy_in,
+            max_players,
+            rake_bps,
+            players: Vec::new(),
+            waitlist: Vec::new(),
+            prize_pool: 0,
+            held: 0,
+            rounds: Vec::new(),
+            status: TournamentStatus::Registering,
+            winner: None,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= self.max_players
+    }
+
+    // The buy-in must already be taken from the player. Once full, registrations go to the waitlist
+    // with their buy-in held.
+    pub fn register(&mut self, player: String) -> Result<Registration, GameError> {
+        if self.status != TournamentStatus::Registering {
+            return Err(GameError::TournamentClosed);
+        }
+        if self.players.contains(&player) || self.waitlist.contains(&player) {
+            return Err(GameError::AlreadyRegistered);
+        }
+
+        if self.is_full() {
+            self.held = self.held.checked_add(self.buy_in).ok_or(GameError::Overflow)?;
+            self.waitlist.push(player);
+            return Ok(Registration::Waitlisted);
+        }
+
+        self.prize_pool = self.prize_pool.checked_add(self.buy_in).ok_or(GameError::Overflow)?;
+        self.players.push(player);
+        Ok(Registration::Registered)
+    }
+
+    // Takes a player out before the start, their buy-in has to be refunded by the caller.
+    // A freed seat goes to the first player of the waitlist, who is returned.
+    pub fn withdraw(&mut self, player: &str) -> Result<Option<String>, GameError> {
+        if self.status != TournamentStatus::Registering {
+            return Err(GameError::TournamentAlreadyStarted);
+        }
+
+        if let Some(index) = self.waitlist.iter().position(|p| p == player) {
+            self.waitlist.remove(index);
+            self.held -= self.buy_in;
+            return Ok(None);
+        }
+
+        let index = self.players.iter().position(|p| p == player).ok_or(GameError::NotRegistered)?;
+        self.players.remove(index);
+        if self.waitlist.is_empty() {
+            self.prize_pool -= self.buy_in;
+            return Ok(None);
+        }
+
+        // The promoted buy-in moves from held to the prize pool, which keeps its size
+        let promoted = self.waitlist.remove(0);
+        self.held -= self.buy_in;
+        self.players.push(promoted.clone());
+        Ok(Some(promoted))
+    }
+
+    // Returns the waitlisted players, their held buy-ins have to be refunded by the caller
+    pub fn start(&mut self) -> Result<Vec<String>, GameError> {
+        if self.status != TournamentStatus::Registering {
+            return Err(GameError::TournamentAlreadyStarted);
+        }
+        if self.players.len() < 2 {
+            return Err(GameError::NotEnoughPlayers);
+        }
+
+        self.rounds.push(pair_up(self.players.clone()));
+        self.status = TournamentStatus::Running;
+        self.held = 0;
+        Ok(std::mem::take(&mut self.waitlist))
+    }
+
+    // Matches of the current round still waiting for a game, as (index, player, player)
+    pub fn pending_matches(&self) -> Vec<(usize, String, String)> {
+        if self.status != TournamentStatus::Running {
+            return Vec::new();
+        }
+        let round = match self.rounds.last() {
+            Some(round) => round,
+            None => return Vec::new(),
+        };
+
+        round
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.winner.is_none())
+            .filter_map(|(index, m)| m.players.1.clone().map(|second| (index, m.players.0.clone(), second)))
+            .collect()
+    }
+
+    pub fn record_result(&mut self, index: usize, game_id: u64, winner: String) -> Result<(), GameError> {
+        let round = self.rounds.last_mut().ok_or(GameError::TournamentNotStarted)?;
+        let bracket_match = round.get_mut(index).ok_or(GameError::MatchNotFound)?;
+        if bracket_match.winner.is_some() {
+            return Err(GameError::MatchDecided);
+        }
+
+        bracket_match.game_id = Some(game_id);
+        bracket_match.winner = Some(winner);
+        Ok(())
+    }
+
+    // Moves to the next round once every match of the current one is decided.
+    // Returns the champion when the bracket is complete.
+    pub fn advance(&mut self) -> Option<String> {
+        let round = self.rounds.last()?;
+        if round.iter().any(|m| m.winner.is_none()) {
+            return None;
+        }
+
+        let survivors: Vec<String> = round.iter().filter_map(|m| m.winner.clone()).collect();
+        if survivors.len() == 1 {
+            self.status = TournamentStatus::Finished;
+            self.winner = survivors.into_iter().next();
+            return self.winner.clone();
+        }
+
+        self.rounds.push(pair_up(survivors));
+        None
+    }
+
+    // (prize for the winner, rake for the house)
+    pub fn payout(&self) -> (u64, u64) {
+        let rake = (self.prize_pool as u128 * self.rake_bps as u128 / 10_000) as u64;
+        (self.prize_pool - rake, rake)
+    }
+}
+
+// Byes are decided on the spot so only real matches are left to play
+fn pair_up(players: Vec<String>) -> Vec<BracketMatch> {
+    players
+        .chunks(2)
+        .map(|pair| {
+            let second = pair.get(1).cloned();
+            let winner = if second.is_none() { Some(pair[0].clone()) } else { None };
+            BracketMatch { players: (pair[0].clone(), second), game_id: None, winner }
+        })
+        .collect()
+}
+
+#[test]
+fn test_bracket_with_bye() {
+    let mut tournament = Tournament::new(0, 10, 4, 500);
+    for player in ["Alice", "Bob", "Carol"] {
+        tournament.register(player.to_string()).unwrap();
+    }
+    assert!(tournament.register("Alice".to_string()).is_err());
+    assert_eq!(tournament.start(), Ok(Vec::new()));
+    assert!(tournament.register("Dave".to_string()).is_err());
+
+    // Carol has a bye in the first round
+    assert_eq!(tournament.pending_matches(), vec![(0, "Alice".to_string(), "Bob".to_string())]);
+    tournament.record_result(0, 7, "Bob".to_string()).unwrap();
+    assert_eq!(tournament.advance(), None);
+
+    assert_eq!(tournament.pending_matches(), vec![(0, "Bob".to_string(), "Carol".to_string())]);
+    tournament.record_result(0, 8, "Carol".to_string()).unwrap();
+    assert_eq!(tournament.advance(), Some("Carol".to_string()));
+
+    assert_eq!(tournament.status, TournamentStatus::Finished);
+    assert_eq!(tournament.payout(), (29, 1));
+}
+
+#[test]
+fn test_waitlist_promoted_then_refunded() {
+    let mut tournament = Tournament::new(0, 10, 2, 0);
+    assert_eq!(tournament.register("Alice".to_string()), Ok(Registration::Registered));
+    assert_eq!(tournament.register("Bob".to_string()), Ok(Registration::Registered));
+    assert_eq!(tournament.register("Carol".to_string()), Ok(Registration::Waitlisted));
+    assert_eq!(tournament.register("Dave".to_string()), Ok(Registration::Waitlisted));
+    assert!(tournament.register("Carol".to_string()).is_err());
+    assert_eq!((tournament.prize_pool, tournament.held), (20, 20));
+
+    assert_eq!(tournament.withdraw("Bob"), Ok(Some("Carol".to_string())));
+    assert_eq!(tournament.players, vec!["Alice".to_string(), "Carol".to_string()]);
+    assert_eq!((tournament.prize_pool, tournament.held), (20, 10));
+
+    assert_eq!(tournament.start(), Ok(vec!["Dave".to_string()]));
+    assert_eq!(tournament.held, 0);
+    assert!(tournament.withdraw("Alice").is_err());
+}
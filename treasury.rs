@@ -0,0 +1,136 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::GameError;
+use crate::stats::ActivityAverages;
+
+// Fee applied to pots of at least `min_pot`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeTier {
+    pub min_pot: u64,
+    pub bps: u32, // Basis points of the pot
+}
+
+// Lets recent activity move the fee so the tiers need no retuning when pots or traffic change
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveFees {
+    pub relative_tiers: bool, // Tier `min_pot` is read as a percentage of the average pot, once there is one
+    pub busy_games_per_hour: u64, // 0 never counts as busy
+    pub busy_discount_bps: u32, // Taken off the tier's fee while the game rate is at least `busy_games_per_hour`
+}
+
+// House fee taken from the pot of every decided game. The tier with the highest `min_pot`
+// the pot reaches applies, pots below every tier pay nothing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeePolicy {
+    pub tiers: Vec<FeeTier>,
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveFees>,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        FeePolicy {
+            tiers: vec![FeeTier { min_pot: 0, bps: 200 }],
+            adaptive: None,
+        }
+    }
+}
+
+impl FeePolicy {
+    pub fn rake_for(&self, pot: u64) -> u64 {
+        self.rake_with(pot, &ActivityAverages::default())
+    }
+
+    // Same as rake_for, with the adaptive rules applied to `activity`
+    pub fn rake_with(&self, pot: u64, activity: &ActivityAverages) -> u64 {
+        let threshold = |tier: &FeeTier| match self.adaptive {
+            Some(adaptive) if adaptive.relative_tiers && activity.avg_pot > 0 => {
+                (activity.avg_pot as u128 * tier.min_pot as u128 / 100).min(u64::MAX as u128) as u64
+            }
+            _ => tier.min_pot,
+        };
+        let mut bps = self
+            .tiers
+            .iter()
+            .filter(|tier| threshold(tier) <= pot)
+            .max_by_key(|tier| threshold(tier))
+            .map(|tier| tier.bps.min(10_000))
+            .unwrap_or(0);
+        if let Some(adaptive) = self.adaptive {
+            if adaptive.busy_games_per_hour > 0 && activity.games_per_hour >= adaptive.busy_games_per_hour {
+                bps = bps.saturating_sub(adaptive.busy_discount_bps);
+            }
+        }
+        (pot as u128 * bps as u128 / 10_000) as u64
+    }
+}
+
+// House account the fees are collected into
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Treasury {
+    balance: u64,
+    collected: u64, // Every fee ever collected, withdrawals do not lower it
+}
+
+impl Treasury {
+    pub fn new() -> Self {
+        Treasury::default()
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.balance
+    }
+
+    pub fn collected(&self) -> u64 {
+        self.collected
+    }
+
+    pub fn collect(&mut self, amount: u64) {
+        self.balance = self.balance.saturating_add(amount);
+        self.collected = self.collected.saturating_add(amount);
+    }
+
+    pub fn withdraw(&mut self, amount: u64) -> Result<(), GameError> {
+        if amount > self.balance {
+            return Err(GameError::InsufficientTreasury);
+        }
+        self.balance -= amount;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_fee_tiers_and_withdrawal() {
+    let policy = FeePolicy {
+        tiers: vec![FeeTier { min_pot: 1_000, bps: 100 }, FeeTier { min_pot: 10, bps: 500 }],
+        adaptive: None,
+    };
+    assert_eq!(policy.rake_for(5), 0);
+    assert_eq!(policy.rake_for(100), 5);
+    assert_eq!(policy.rake_for(2_000), 20);
+    assert_eq!(FeePolicy::default().rake_for(50), 1);
+
+    let mut treasury = Treasury::new();
+    treasury.collect(30);
+    treasury.withdraw(20).unwrap();
+    assert!(treasury.withdraw(11).is_err());
+    assert_eq!((treasury.balance(), treasury.collected()), (10, 30));
+}
+
+#[test]
+fn test_adaptive_fees_follow_activity() {
+    let adaptive = AdaptiveFees { relative_tiers: true, busy_games_per_hour: 20, busy_discount_bps: 100 };
+    let policy = FeePolicy {
+        tiers: vec![FeeTier { min_pot: 0, bps: 300 }, FeeTier { min_pot: 200, bps: 500 }],
+        adaptive: Some(adaptive),
+    };
+    let quiet = ActivityAverages { avg_pot: 50, games_per_hour: 2 };
+    let busy = ActivityAverages { avg_pot: 50, games_per_hour: 20 };
+
+    // The upper tier starts at twice the average pot
+    assert_eq!(policy.rake_with(99, &quiet), 2);
+    assert_eq!(policy.rake_with(100, &quiet), 5);
+    assert_eq!(policy.rake_with(100, &busy), 4);
+    // Without history the tiers are plain amounts
+    assert_eq!(policy.rake_for(100), 3);
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::accounting::Accounting;
+use crate::accounts::ClosedAccounts;
+use crate::balance::Balance;
+use crate::commitments::CommitmentRegistry;
+use crate::deposits::Deposits;
+use crate::energy::Energy;
+use crate::error::GameError;
+use crate::escrow::Escrow;
+use crate::jackpot::Jackpot;
+use crate::matchmaking::Matchmaking;
+use crate::names::Names;
+use crate::rating::Ratings;
+use crate::referrals::Referrals;
+use crate::rewards::Rewards;
+use crate::sidebets::SideBets;
+use crate::slashing::Slashing;
+use crate::stats::Stats;
+use crate::tournament::Tournament;
+use crate::treasury::Treasury;
+use crate::{Game, GameState};
+
+// What a step may change besides the house: the accounts named, the games named with their players and
+// backers and the tournaments named with their players, each account with its referrer. Games and
+// tournaments the step creates are always covered, so are the treasury, jackpot, pools, queue, totals and logs.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    accounts: Vec<String>,
+    games: Vec<u64>,
+    tournaments: Vec<u64>,
+}
+
+impl Scope {
+    pub fn account(account: &str) -> Self {
+        Scope::default().and_account(account)
+    }
+
+    pub fn game(game_id: u64) -> Self {
+        Scope { games: vec![game_id], ..Scope::default() }
+    }
+
+    pub fn tournament(tournament_id: u64) -> Self {
+        Scope { tournaments: vec![tournament_id], ..Scope::default() }
+    }
+
+    pub fn and_account(mut self, account: &str) -> Self {
+        self.accounts.push(account.to_string());
+        self
+    }
+
+    pub fn and_game(mut self, game_id: u64) -> Self {
+        self.games.push(game_id);
+        self
+    }
+}
+
+// Maps a transaction saves and puts back entry by entry
+pub(crate) trait Entries<K, V>: Default {
+    fn entry(&self, key: &K) -> Option<&V>;
+    fn put(&mut self, key: K, value: V);
+    fn take(&mut self, key: &K) -> Option<V>;
+}
+
+impl<K: Ord, V> Entries<K, V> for BTreeMap<K, V> {
+    fn entry(&self, key: &K) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.insert(key, value);
+    }
+
+    fn take(&mut self, key: &K) -> Option<V> {
+        self.remove(key)
+    }
+}
+
+impl<K: Hash + Eq, V> Entries<K, V> for HashMap<K, V> {
+    fn entry(&self, key: &K) -> Option<&V> {
+        self.get(key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.insert(key, value);
+    }
+
+    fn take(&mut self, key: &K) -> Option<V> {
+        self.remove(key)
+    }
+}
+
+// The entries of `map` under `keys`, the ones it does not hold are left out
+pub(crate) fn save_entries<'a, K: Clone + 'a, V: Clone, M: Entries<K, V>>(map: &M, keys: impl IntoIterator<Item = &'a K>) -> M {
+    let mut saved = M::default();
+    for key in keys {
+        if let Some(value) = map.entry(key) {
+            saved.put(key.clone(), value.clone());
+        }
+    }
+    saved
+}
+
+// Puts the entries under `keys` back as they are in `saved`, the ones it does not hold are removed
+pub(crate) fn restore_entries<'a, K: Clone + 'a, V, M: Entries<K, V>>(map: &mut M, mut saved: M, keys: impl IntoIterator<Item = &'a K>) {
+    for key in keys {
+        match saved.take(key) {
+            Some(value) => map.put(key.clone(), value),
+            None => {
+                map.take(key);
+            }
+        }
+    }
+}
+
+// The touched part of the state as it was when the transaction started, every other entry stays where it is
+struct Snapshot {
+    accounts: Vec<String>,
+    games: Vec<u64>,
+    game_entries: BTreeMap<u64, Game>,
+    next_game_id: u64,
+    balances: HashMap<String, Balance>,
+    escrow: Escrow,
+    treasury: Treasury,
+    jackpot: Jackpot,
+    side_bets: SideBets,
+    rewards: Rewards,
+    energy: Energy,
+    stats: Stats,
+    ratings: Ratings,
+    names: Names,
+    referrals: Referrals,
+    slashing: Slashing,
+    deposits: Deposits,
+    closed_accounts: ClosedAccounts,
+    matchmaking: Matchmaking,
+    tournaments: Vec<u64>,
+    tournament_entries: BTreeMap<u64, Tournament>,
+    next_tournament_id: u64,
+    accounting: Accounting,
+    commitments: CommitmentRegistry,
+    commitment_count: usize,
+    history_len: usize,
+}
+
+impl GameState {
+    fn snapshot(&self, scope: Scope) -> Snapshot {
+        let mut accounts = scope.accounts;
+        for game_id in &scope.games {
+            if let Some(game) = self.games.get(game_id) {
+                accounts.extend(game.seated().into_iter().cloned());
+            }
+            accounts.extend(self.side_bets.for_game(*game_id).iter().map(|bet| bet.backer.clone()));
+        }
+        for tournament in scope.tournaments.iter().filter_map(|id| self.tournaments.get(id)) {
+            accounts.extend(tournament.players.iter().chain(&tournament.waitlist).cloned());
+        }
+        // A referrer is paid out of the games of their referees
+        let referrers: Vec<String> = accounts.iter().filter_map(|account| self.referrals.referrer_of(account)).map(str::to_string).collect();
+        accounts.extend(referrers);
+        accounts.sort_unstable();
+        accounts.dedup();
+        let games = scope.games;
+        let commitments: Vec<u64> = games.iter().filter_map(|game_id| self.games.get(game_id)).map(|game| game.commitment_id).collect();
+        Snapshot {
+            game_entries: save_entries(&self.games, &games),
+            next_game_id: self.next_game_id,
+            balances: save_entries(&self.balances, &accounts),
+            escrow: self.escrow.save(&games),
+            treasury: self.treasury.clone(),
+            jackpot: self.jackpot.clone(),
+            side_bets: self.side_bets.save(&games),
+            rewards: self.rewards.save(&accounts),
+            energy: self.energy.save(&accounts),
+            stats: self.stats.save(&accounts),
+            ratings: self.ratings.save(&accounts),
+            names: self.names.save(&accounts),
+            referrals: self.referrals.save(&accounts),
+            slashing: self.slashing.save(&accounts),
+            deposits: self.deposits.save(&accounts),
+            closed_accounts: self.closed_accounts.save(&accounts),
+            matchmaking: self.matchmaking.clone(),
+            tournament_entries: save_entries(&self.tournaments, &scope.tournaments),
+            tournaments: scope.tournaments,
+            next_tournament_id: self.next_tournament_id,
+            accounting: self.accounting.save(),
+            commitments: self.commitments.save(&commitments),
+            commitment_count: self.commitments.all().len(),
+            history_len: self.history.len(),
+            accounts,
+            games,
+        }
+    }
+
+    fn roll_back(&mut self, snapshot: Snapshot) {
+        let accounts = &snapshot.accounts;
+        // Games created by the step held nothing before it
+        let mut games = snapshot.games;
+        games.extend(snapshot.next_game_id..self.next_game_id);
+        restore_entries(&mut self.games, snapshot.game_entries, &games);
+        self.next_game_id = snapshot.next_game_id;
+        restore_entries(&mut self.balances, snapshot.balances, accounts);
+        self.escrow.restore(snapshot.escrow, &games);
+        self.treasury = snapshot.treasury;
+        self.jackpot = snapshot.jackpot;
+        self.side_bets.restore(snapshot.side_bets, &games);
+        self.rewards.restore(snapshot.rewards, accounts);
+        self.energy.restore(snapshot.energy, accounts);
+        self.stats.restore(snapshot.stats, accounts);
+        self.ratings.restore(snapshot.ratings, accounts);
+        self.names.restore(snapshot.names, accounts);
+        self.referrals.restore(snapshot.referrals, accounts);
+        self.slashing.restore(snapshot.slashing, accounts);
+        self.deposits.restore(snapshot.deposits, accounts);
+        self.closed_accounts.restore(snapshot.closed_accounts, accounts);
+        self.matchmaking = snapshot.matchmaking;
+        let mut tournaments = snapshot.tournaments;
+        tournaments.extend(snapshot.next_tournament_id..self.next_tournament_id);
+        restore_entries(&mut self.tournaments, snapshot.tournament_entries, &tournaments);
+        self.next_tournament_id = snapshot.next_tournament_id;
+        self.accounting.restore(snapshot.accounting);
+        self.commitments.restore(snapshot.commitments, snapshot.commitment_count);
+        self.history.truncate(snapshot.history_len);
+    }
+
+    // Runs `action` as one step, if it fails everything it could change within `scope` is put back as it
+    // was and the events it emitted are dropped, so a check failing halfway leaves nothing half done.
+    // Subscribers see the events once it succeeds. Used nonces stay used, a refused signed call cannot be
+    // sent again. Roles, config, preferences, frozen accounts, token tables and the command log are not
+    // saved, steps run in a transaction leave them alone.
+    pub fn with_tx<T>(&mut self, scope: Scope, action: impl FnOnce(&mut GameState) -> Result<T, GameError>) -> Result<T, GameError> {
+        let snapshot = self.snapshot(scope);
+        let hold = self.events.hold();
+        match action(self) {
+            Ok(value) => {
+                self.events.release(hold);
+                Ok(value)
+            }
+            Err(error) => {
+                self.roll_back(snapshot);
+                self.events.discard(hold);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_failed_transactions_leave_nothing_behind() {
+    use std::sync::{Arc, Mutex};
+
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    game_state.stake_tokens(&caller("Alice"), 100).unwrap();
+    let seen = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&seen);
+    game_state.subscribe(move |_| *counter.lock().unwrap() += 1);
+    let logged = game_state.events().len();
+
+    let failed = game_state.with_tx(Scope::account("Alice"), |state| {
+        state.stake_tokens(&caller("Alice"), 50)?;
+        let game_id = state.start_game(&caller("Alice"), 30)?;
+        state.with_tx(Scope::game(game_id).and_account("Bob"), |state| state.join_game(&caller("Bob"), game_id))
+    });
+    assert_eq!(failed, Err(GameError::InsufficientStake));
+    assert_eq!(game_state.stake_of("Alice"), Balance::new(100, 0));
+    assert!(game_state.games.is_empty());
+    assert_eq!(game_state.events().len(), logged);
+    assert_eq!(game_state.verify_audit_log(), Ok(()));
+    assert_eq!(*seen.lock().unwrap(), 0);
+
+    let game_id = game_state.with_tx(Scope::account("Alice"), |state| state.start_game(&caller("Alice"), 30)).unwrap();
+    assert_eq!(game_state.stake_of("Alice"), Balance::new(70, 30));
+    assert_eq!(game_state.game_summary(game_id).unwrap().bet, 30);
+    assert_eq!(*seen.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_failed_settlements_are_rolled_back() {
+    use crate::commitments::RevealStatus;
+    use crate::context::caller;
+
+    let mut game_state = GameState::new();
+    game_state.stake_tokens(&caller("Alice"), 100).unwrap();
+    game_state.stake_tokens(&caller("Bob"), 100).unwrap();
+    let game_id = game_state.start_game(&caller("Alice"), 30).unwrap();
+    game_state.join_game(&caller("Bob"), game_id).unwrap();
+    let commitment_id = game_state.games[&game_id].commitment_id;
+    let (totals, jackpot, commitments) = (*game_state.accounting.open_totals(), game_state.jackpot_balance(), game_state.commitments().all().len());
+
+    // Carol's stake claims her name, the game is played to the end, then the step fails
+    let failed = game_state.with_tx(Scope::game(game_id).and_account("Carol"), |state| {
+        state.stake_tokens(&caller("Carol"), 10)?;
+        while state.game_summary(game_id).is_some() {
+            state.contribute_reveal(&caller("Alice"), game_id)?;
+            state.contribute_reveal(&caller("Bob"), game_id)?;
+        }
+        Err::<(), _>(GameError::Cancelled)
+    });
+    assert_eq!(failed, Err(GameError::Cancelled));
+    assert!(game_state.game_summary(game_id).is_some());
+    assert_eq!(game_state.stake_of("Alice"), Balance::new(70, 30));
+    assert_eq!(game_state.stake_of("Bob"), Balance::new(70, 30));
+    assert_eq!(game_state.stake_of("Carol"), Balance::default());
+    assert_eq!(game_state.names.owner("Carol"), None);
+    assert_eq!((game_state.player_stats("Alice"), game_state.rating_history("Bob")), (None, &[][..]));
+    assert!(game_state.history().is_empty());
+    assert_eq!(game_state.commitments().all().len(), commitments);
+    assert_eq!(game_state.commitments().get(commitment_id).unwrap().status, RevealStatus::Pending);
+    assert_eq!((*game_state.accounting.open_totals(), game_state.jackpot_balance()), (totals, jackpot));
+    game_state.check_escrow();
+
+    while game_state.game_summary(game_id).is_some() {
+        game_state.contribute_reveal(&caller("Alice"), game_id).unwrap();
+        game_state.contribute_reveal(&caller("Bob"), game_id).unwrap();
+    }
+    assert_eq!(game_state.history().len(), 1);
+    assert!(game_state.player_stats("Alice").is_some());
+}
+
+#[test]
+fn test_failed_referral_payouts_are_rolled_back() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::GameConfig;
+
+    let mut game_state = GameState::new();
+    game_state.init_admin(account("House")).unwrap();
+    game_state.apply_config(&caller("House"), GameConfig { referral_bps: 1_000, ..GameConfig::default() }).unwrap();
+    game_state.register_referral(&caller("Bob"), account("Alice")).unwrap();
+    game_state.stake_tokens(&caller("Bob"), 10_000).unwrap();
+    game_state.stake_tokens(&caller("Carol"), 10_000).unwrap();
+
+    // Alice is not named, she is in the scope as the referrer of Bob. Draws pay no fee and are played again.
+    loop {
+        let game_id = game_state.start_game(&caller("Bob"), 1_000).unwrap();
+        game_state.join_game(&caller("Carol"), game_id).unwrap();
+        let (treasury, played) = (game_state.treasury_balance(), game_state.history().len());
+        let failed = game_state.with_tx(Scope::game(game_id), |state| {
+            state.play_round(game_id)?;
+            Err::<(), _>(GameError::Cancelled)
+        });
+        assert_eq!(failed, Err(GameError::Cancelled));
+        assert_eq!((game_state.referral_earnings("Alice"), game_state.stake_of("Alice")), (0, Balance::default()));
+        assert_eq!((game_state.treasury_balance(), game_state.history().len()), (treasury, played));
+
+        game_state.play_round(game_id).unwrap();
+        if game_state.history().all().last().unwrap().winner.is_some() {
+            break;
+        }
+    }
+    // A pot of 2000 pays a fee of 40, Bob generated 20 of it and Alice gets a tenth of that
+    assert_eq!((game_state.referral_earnings("Alice"), game_state.stake_of("Alice").available.get()), (2, 2));
+    game_state.check_invariants().unwrap();
+}
@@ -0,0 +1,114 @@
+use crate::account_id::AccountId;
+use crate::context::Context;
+use crate::error::GameError;
+use crate::token::ERC20Token;
+use crate::tx::Scope;
+use crate::GameState;
+
+// Token account holding every staked token. Stakes are only claims on it: deposits move tokens in and
+// withdrawals move them back out, so a stake is always backed by tokens somebody paid for.
+pub const VAULT: &str = "GameVault";
+
+impl GameState {
+    // Stakes `amount` tokens of the caller, who approved the vault to spend them first. The stake is taken before
+    // the tokens move, so a stake the game refuses moves nothing, and it is rolled back if the token refuses
+    // the transfer. A retried command moves no tokens twice.
+    pub fn deposit(&mut self, ctx: &Context, token: &mut ERC20Token, amount: u64) -> Result<(), GameError> {
+        self.run_once(ctx, "deposit", |state| {
+            state.use_nonce(ctx)?;
+            state.with_tx(Scope::account(ctx.caller().as_str()), |state| {
+                state.stake_tokens(&ctx.unsigned(), amount)?;
+                Ok(token.transfer_from(&Context::new(vault()), ctx.caller().clone(), vault(), amount)?)
+            })
+        })
+    }
+
+    // Pays available stake back out of the vault, kept staked if the token refuses the transfer
+    pub fn withdraw(&mut self, ctx: &Context, token: &mut ERC20Token, amount: u64) -> Result<(), GameError> {
+        self.run_once(ctx, "withdraw", |state| {
+            state.use_nonce(ctx)?;
+            state.with_tx(Scope::account(ctx.caller().as_str()), |state| {
+                state.withdraw_stake(&ctx.unsigned(), amount)?;
+                Ok(token.transfer(&Context::new(vault()), ctx.caller().clone(), amount)?)
+            })
+        })
+    }
+
+    // Checks the vault holds exactly what the state holds for players and the house. Less means stake was
+    // credited that nobody paid for, more that tokens reached the vault without a deposit.
+    pub fn check_vault(&self, token: &ERC20Token) -> Result<(), GameError> {
+        let (vaulted, held) = (token.get_balance(VAULT).get() as u128, self.held_funds());
+        if vaulted != held {
+            return Err(GameError::InvariantsViolated(vec![format!("vault holds {} for {} held", vaulted, held)]));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn vault() -> AccountId {
+    AccountId::new(VAULT).expect("The vault is a valid account id.")
+}
+
+#[test]
+fn test_stakes_are_backed_by_the_vault() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::token::TokenError;
+
+    let mut game_state = GameState::new();
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+
+    // Nothing approved, nothing staked
+    let refused = game_state.deposit(&caller("Alice"), &mut token, 60);
+    assert_eq!(refused, Err(GameError::Token(TokenError::InsufficientAllowance)));
+    assert_eq!((game_state.stake_of("Alice").available.get(), token.get_balance("Alice").get()), (0, 100));
+    assert_eq!(game_state.names.owner("Alice"), None);
+    game_state.check_invariants().unwrap();
+
+    // A retried deposit is not paid twice
+    token.approve(&caller("Alice"), vault(), 100).unwrap();
+    let retried = caller("Alice").with_command_id(Some(uuid::Uuid::from_u128(1)));
+    game_state.deposit(&retried, &mut token, 60).unwrap();
+    game_state.deposit(&retried, &mut token, 60).unwrap();
+    assert_eq!((game_state.stake_of("Alice").available.get(), token.get_balance(VAULT).get()), (60, 60));
+
+    // A paused token keeps the stake where it is
+    token.pause(&caller("OwnerAddress")).unwrap();
+    assert_eq!(game_state.withdraw(&caller("Alice"), &mut token, 20), Err(GameError::Token(TokenError::Paused)));
+    assert_eq!(game_state.stake_of("Alice").available.get(), 60);
+    token.unpause(&caller("OwnerAddress")).unwrap();
+
+    game_state.withdraw(&caller("Alice"), &mut token, 20).unwrap();
+    assert_eq!((game_state.stake_of("Alice").available.get(), token.get_balance("Alice").get()), (40, 60));
+    assert_eq!(game_state.withdraw(&caller("Alice"), &mut token, 41), Err(GameError::InsufficientFunds));
+    game_state.check_vault(&token).unwrap();
+    game_state.check_invariants().unwrap();
+}
+
+// Mallory cannot stake without paying, so she cannot withdraw the tokens Alice deposited. A stake credited
+// without tokens, what stake_tokens does on its own, is caught by the vault check.
+#[test]
+fn test_unpaid_stake_cannot_drain_the_vault() {
+    use crate::account_id::account;
+    use crate::context::caller;
+    use crate::token::TokenError;
+
+    let mut game_state = GameState::new();
+    let mut token = ERC20Token::new(account("OwnerAddress"), "Game Token", "GAME", 0, None);
+    token.adjust_price(&caller("OwnerAddress"), 0).unwrap();
+    token.mint(&caller("Alice"), 100, 0).unwrap();
+    token.approve(&caller("Alice"), vault(), 100).unwrap();
+    game_state.deposit(&caller("Alice"), &mut token, 100).unwrap();
+
+    let refused = game_state.deposit(&caller("Mallory"), &mut token, 100);
+    assert_eq!(refused, Err(GameError::Token(TokenError::InsufficientAllowance)));
+    assert_eq!(game_state.withdraw(&caller("Mallory"), &mut token, 100), Err(GameError::UserNotFound));
+    assert_eq!((token.get_balance(VAULT).get(), token.get_balance("Mallory").get()), (100, 0));
+    game_state.check_vault(&token).unwrap();
+
+    let mut minted = game_state.clone();
+    minted.stake_tokens(&caller("Mallory"), 100).unwrap();
+    assert!(matches!(minted.check_vault(&token), Err(GameError::InvariantsViolated(_))));
+}
@@ -0,0 +1,120 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::account_id::AccountId;
+use crate::amount::Amount;
+
+// When the tokens of a grant unlock
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unlock {
+    Cliff { at: u64 }, // All at once
+    Linear { start: u64, cliff_secs: u64, duration_secs: u64 }, // Evenly from start, nothing before the cliff
+}
+
+impl Unlock {
+    // A linear unlock needs a duration and a cliff within it
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Unlock::Cliff { .. } => true,
+            Unlock::Linear { cliff_secs, duration_secs, .. } => duration_secs > 0 && cliff_secs <= duration_secs,
+        }
+    }
+
+    // Part of `total` unlocked at `now`, rounded down until the end of the schedule
+    pub fn unlocked(&self, total: Amount, now: u64) -> Amount {
+        match *self {
+            Unlock::Cliff { at } => if now >= at { total } else { Amount::ZERO },
+            Unlock::Linear { start, cliff_secs, duration_secs } => {
+                let elapsed = now.saturating_sub(start);
+                if elapsed < cliff_secs {
+                    Amount::ZERO
+                } else if elapsed >= duration_secs {
+                    total
+                } else {
+                    Amount::new((total.get() as u128 * elapsed as u128 / duration_secs as u128) as u64)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub total: Amount,
+    pub claimed: Amount,
+    pub unlock: Unlock,
+}
+
+impl Grant {
+    fn claimable(&self, now: u64) -> Amount {
+        self.unlock.unlocked(self.total, now).checked_sub(self.claimed).unwrap_or_default()
+    }
+}
+
+// Allocations granted by the token owner, minted to their holder as they unlock. What is granted and not
+// claimed yet stays reserved under the supply cap, so a grant can always be claimed in full.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Vesting {
+    grants: HashMap<AccountId, Vec<Grant>>,
+}
+
+impl Vesting {
+    pub fn new() -> Self {
+        Vesting::default()
+    }
+
+    pub fn grant(&mut self, user: AccountId, total: Amount, unlock: Unlock) {
+        self.grants.entry(user).or_default().push(Grant { total, claimed: Amount::ZERO, unlock });
+    }
+
+    pub fn grants(&self, user: &str) -> &[Grant] {
+        self.grants.get(user).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn claimable(&self, user: &str, now: u64) -> Amount {
+        self.grants(user).iter().fold(Amount::ZERO, |sum, grant| sum.checked_add(grant.claimable(now)).unwrap_or(Amount::MAX))
+    }
+
+    // Marks everything unlocked for `user` as claimed and returns it, fully claimed grants are dropped
+    pub fn claim(&mut self, user: &str, now: u64) -> Amount {
+        let claimable = self.claimable(user, now);
+        if let Some(grants) = self.grants.get_mut(user) {
+            for grant in grants.iter_mut() {
+                grant.claimed = grant.unlock.unlocked(grant.total, now).max(grant.claimed);
+            }
+            grants.retain(|grant| grant.claimed < grant.total);
+            if grants.is_empty() {
+                self.grants.remove(user);
+            }
+        }
+        claimable
+    }
+
+    // Granted and not claimed yet, over every holder
+    pub fn outstanding(&self) -> Amount {
+        self.grants
+            .values()
+            .flatten()
+            .fold(Amount::ZERO, |sum, grant| sum.checked_add(grant.total.checked_sub(grant.claimed).unwrap_or_default()).unwrap_or(Amount::MAX))
+    }
+}
+
+#[test]
+fn test_grants_unlock_on_schedule() {
+    use crate::account_id::account;
+
+    let linear = Unlock::Linear { start: 1_000, cliff_secs: 100, duration_secs: 400 };
+    assert_eq!(linear.unlocked(Amount::new(400), 1_099), 0);
+    assert_eq!(linear.unlocked(Amount::new(400), 1_100), 100);
+    assert_eq!(linear.unlocked(Amount::new(400), 1_399), 399);
+    assert_eq!(linear.unlocked(Amount::new(400), 9_999), 400);
+    assert!(!Unlock::Linear { start: 0, cliff_secs: 10, duration_secs: 5 }.is_valid());
+
+    let mut vesting = Vesting::new();
+    vesting.grant(account("Team"), Amount::new(400), linear);
+    vesting.grant(account("Team"), Amount::new(50), Unlock::Cliff { at: 1_200 });
+    assert_eq!(vesting.claim("Team", 1_200), 250);
+    assert_eq!((vesting.claim("Team", 1_200).get(), vesting.outstanding().get()), (0, 200));
+    assert_eq!(vesting.claim("Team", 2_000), 200);
+    assert!(vesting.grants("Team").is_empty() && vesting.outstanding().is_zero());
+}
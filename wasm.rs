@@ -0,0 +1,183 @@
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::account_id::AccountId;
+use crate::context::Context;
+use crate::lobby::LobbyFilter;
+use crate::strict::ParseMode;
+use crate::token::ERC20Token;
+use crate::GameState;
+
+// Browser bindings (wasm feature). Amounts and ids are u64, which JavaScript sees as BigInt; structured
+// answers are JSON strings. Randomness needs getrandom with its js feature on wasm32.
+
+// JSON.parse turns integers above 2^53 into wrong numbers, those are written as strings instead
+fn to_js_json(value: Value) -> String {
+    fn safe(value: Value) -> Value {
+        match value {
+            Value::Number(number) if number.as_u64().is_some_and(|n| n > (1 << 53) - 1) => Value::String(number.to_string()),
+            Value::Array(items) => Value::Array(items.into_iter().map(safe).collect()),
+            Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, value)| (key, safe(value))).collect()),
+            other => other,
+        }
+    }
+    safe(value).to_string()
+}
+
+#[wasm_bindgen(js_name = GameState)]
+pub struct WasmGameState {
+    inner: GameState,
+}
+
+#[wasm_bindgen(js_class = GameState)]
+impl WasmGameState {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmGameState {
+        WasmGameState { inner: GameState::new() }
+    }
+
+    // Restores what exportState returned, unknown fields are an error
+    #[wasm_bindgen(js_name = importState)]
+    pub fn import_state(json: &str) -> Result<WasmGameState, JsError> {
+        Ok(WasmGameState { inner: GameState::import_state(json, ParseMode::Strict)?.value })
+    }
+
+    #[wasm_bindgen(js_name = exportState)]
+    pub fn export_state(&self) -> Result<String, JsError> {
+        Ok(self.inner.export_state()?)
+    }
+
+    // Moves `amount` tokens of the user into the vault, which the user approved to spend them first
+    pub fn deposit(&mut self, user: String, token: &mut WasmToken, amount: u64) -> Result<(), JsError> {
+        Ok(self.inner.deposit(&Context::new(AccountId::new(user)?), &mut token.inner, amount)?)
+    }
+
+    pub fn withdraw(&mut self, user: String, token: &mut WasmToken, amount: u64) -> Result<(), JsError> {
+        Ok(self.inner.withdraw(&Context::new(AccountId::new(user)?), &mut token.inner, amount)?)
+    }
+
+    #[wasm_bindgen(js_name = startGame)]
+    pub fn start_game(&mut self, creator: String, bet: u64) -> Result<u64, JsError> {
+        Ok(self.inner.start_game(&Context::new(AccountId::new(creator)?), bet)?)
+    }
+
+    #[wasm_bindgen(js_name = joinGame)]
+    pub fn join_game(&mut self, game_id: u64, opponent: String) -> Result<(), JsError> {
+        Ok(self.inner.join_game(&Context::new(AccountId::new(opponent)?), game_id)?)
+    }
+
+    // `player` asks for the next round of a game they sit in, played once every player asked. True once
+    // the game is settled.
+    #[wasm_bindgen(js_name = revealCards)]
+    pub fn reveal_cards(&mut self, game_id: u64, player: String) -> Result<bool, JsError> {
+        self.inner.contribute_reveal(&Context::new(AccountId::new(player)?), game_id)?;
+        Ok(self.inner.game_summary(game_id).is_none())
+    }
+
+    pub fn available(&self, user: &str) -> u64 {
+        self.inner.stake_of(user).available.get()
+    }
+
+    pub fn locked(&self, user: &str) -> u64 {
+        self.inner.stake_of(user).locked.get()
+    }
+
+    // Summary of an unsettled game as JSON, undefined for settled or unknown games
+    pub fn game(&self, game_id: u64) -> Option<String> {
+        self.inner.game_summary(game_id).map(|summary| to_js_json(serde_json::json!(summary)))
+    }
+
+    #[wasm_bindgen(js_name = openGames)]
+    pub fn open_games(&self, limit: usize) -> String {
+        to_js_json(serde_json::json!(self.inner.list_open_games(&LobbyFilter::default(), None, limit)))
+    }
+
+    // Events with a sequence number from `since` on, as a JSON array
+    pub fn events(&self, since: u64) -> String {
+        let events = self.inner.events().get(since as usize..).unwrap_or_default();
+        to_js_json(serde_json::json!(events))
+    }
+}
+
+impl Default for WasmGameState {
+    fn default() -> Self {
+        WasmGameState::new()
+    }
+}
+
+#[wasm_bindgen(js_name = ERC20Token)]
+pub struct WasmToken {
+    inner: ERC20Token,
+}
+
+#[wasm_bindgen(js_class = ERC20Token)]
+impl WasmToken {
+    #[wasm_bindgen(constructor)]
+    pub fn new(owner: String, name: String, symbol: String, decimals: u8, max_supply: Option<u64>) -> Result<WasmToken, JsError> {
+        Ok(WasmToken { inner: ERC20Token::new(AccountId::new(owner)?, name, symbol, decimals, max_supply) })
+    }
+
+    pub fn name(&self) -> String {
+        self.inner.name().to_string()
+    }
+
+    pub fn symbol(&self) -> String {
+        self.inner.symbol().to_string()
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.inner.decimals()
+    }
+
+    #[wasm_bindgen(js_name = formatAmount)]
+    pub fn format_amount(&self, amount: u64) -> String {
+        self.inner.format_amount(amount)
+    }
+
+    // Mints to `user`, who paid `wei_paid` for the tokens
+    pub fn mint(&mut self, user: String, amount: u64, wei_paid: u64) -> Result<(), JsError> {
+        Ok(self.inner.mint(&Context::new(AccountId::new(user)?), amount, wei_paid)?)
+    }
+
+    // Sends tokens of `user` to `to`
+    pub fn transfer(&mut self, user: String, to: String, amount: u64) -> Result<(), JsError> {
+        Ok(self.inner.transfer(&Context::new(AccountId::new(user)?), AccountId::new(to)?, amount)?)
+    }
+
+    // Lets `spender` move up to `amount` tokens of `user`
+    pub fn approve(&mut self, user: String, spender: String, amount: u64) -> Result<(), JsError> {
+        Ok(self.inner.approve(&Context::new(AccountId::new(user)?), AccountId::new(spender)?, amount)?)
+    }
+
+    #[wasm_bindgen(js_name = balanceOf)]
+    pub fn balance_of(&self, user: String) -> u64 {
+        self.inner.get_balance(&user).get()
+    }
+
+    #[wasm_bindgen(js_name = mintPrice)]
+    pub fn mint_price(&self) -> u64 {
+        self.inner.mint_price()
+    }
+
+    // Events emitted since the last call, as a JSON array
+    #[wasm_bindgen(js_name = takeEvents)]
+    pub fn take_events(&mut self) -> String {
+        to_js_json(serde_json::json!(self.inner.take_events()))
+    }
+}
+
+#[test]
+fn test_large_numbers_become_strings() {
+    let json = to_js_json(serde_json::json!({ "bet": 10, "pot": u64::MAX, "games": [{ "id": 1u64 << 53 }] }));
+    assert_eq!(json, format!("{{\"bet\":10,\"games\":[{{\"id\":\"{}\"}}],\"pot\":\"{}\"}}", 1u64 << 53, u64::MAX));
+
+    let mut game_state = WasmGameState::new();
+    let mut token = WasmToken::new("Owner".to_string(), "Game Token".to_string(), "GAME".to_string(), 0, None).unwrap();
+    token.inner.adjust_price(&Context::new(AccountId::new("Owner").unwrap()), 0).unwrap();
+    token.mint("Alice".to_string(), 50, 0).unwrap();
+    token.approve("Alice".to_string(), crate::vault::VAULT.to_string(), 50).unwrap();
+    game_state.deposit("Alice".to_string(), &mut token, 50).unwrap();
+    let game_id = game_state.start_game("Alice".to_string(), 20).unwrap();
+    assert_eq!(game_state.locked("Alice"), 20);
+    assert!(game_state.game(game_id).unwrap().contains("\"pot\":20"));
+}
@@ -0,0 +1,108 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    pub stall_secs: u64, // Without a successful tick for this long, the driver is stalled
+    pub max_restarts: u32, // Restarts asked for before raising the alert
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig { stall_secs: 120, max_restarts: 3 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    Ready,
+    Stalled { last_success: Option<u64>, backlog: usize }, // backlog is the number of overdue games
+}
+
+// What the host of the tick driver has to do after a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    None,
+    Restart,
+    Alert, // Restarting did not help, raised once until ticks succeed again
+}
+
+// Watches the tick driver. Expiry of abandoned games only happens in ticks, so a driver that stopped
+// leaves bets locked forever.
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog {
+    started_at: u64,
+    last_success: Option<u64>,
+    restarts: u32,
+    alerted: bool,
+}
+
+impl Watchdog {
+    pub fn new(now: u64) -> Self {
+        Watchdog { started_at: now, ..Watchdog::default() }
+    }
+
+    pub fn record_success(&mut self, now: u64) {
+        self.last_success = Some(now);
+        self.restarts = 0;
+        self.alerted = false;
+    }
+
+    pub fn last_success(&self) -> Option<u64> {
+        self.last_success
+    }
+
+    pub fn health(&self, now: u64, backlog: usize, config: &WatchdogConfig) -> Health {
+        let since = self.last_success.unwrap_or(self.started_at);
+        if now.saturating_sub(since) > config.stall_secs {
+            Health::Stalled { last_success: self.last_success, backlog }
+        } else {
+            Health::Ready
+        }
+    }
+
+    pub fn check(&mut self, now: u64, config: &WatchdogConfig) -> WatchdogAction {
+        if self.health(now, 0, config) == Health::Ready {
+            return WatchdogAction::None;
+        }
+        if self.restarts < config.max_restarts {
+            self.restarts += 1;
+            return WatchdogAction::Restart;
+        }
+        if self.alerted {
+            return WatchdogAction::None;
+        }
+        self.alerted = true;
+        WatchdogAction::Alert
+    }
+
+    // Prometheus text format, next to the latency histograms
+    pub fn export(&self, now: u64, backlog: usize, config: &WatchdogConfig) -> String {
+        let ready = self.health(now, backlog, config) == Health::Ready;
+        format!(
+            "# TYPE tick_last_success_timestamp gauge\ntick_last_success_timestamp {}\n\
+             # TYPE tick_backlog gauge\ntick_backlog {}\n\
+             # TYPE tick_ready gauge\ntick_ready {}\n",
+            self.last_success.unwrap_or(0),
+            backlog,
+            ready as u8
+        )
+    }
+}
+
+#[test]
+fn test_restarts_then_alerts_once() {
+    let config = WatchdogConfig { stall_secs: 10, max_restarts: 2 };
+    let mut watchdog = Watchdog::new(100);
+    assert_eq!(watchdog.check(110, &config), WatchdogAction::None);
+    assert_eq!(watchdog.health(111, 4, &config), Health::Stalled { last_success: None, backlog: 4 });
+
+    assert_eq!(watchdog.check(111, &config), WatchdogAction::Restart);
+    assert_eq!(watchdog.check(112, &config), WatchdogAction::Restart);
+    assert_eq!(watchdog.check(113, &config), WatchdogAction::Alert);
+    assert_eq!(watchdog.check(114, &config), WatchdogAction::None);
+
+    watchdog.record_success(120);
+    assert_eq!(watchdog.health(125, 0, &config), Health::Ready);
+    assert_eq!(watchdog.check(131, &config), WatchdogAction::Restart);
+    assert!(watchdog.export(125, 0, &config).contains("tick_last_success_timestamp 120\n"));
+}